@@ -35,6 +35,14 @@ contract V2ArbBot is IFlashLoanRecipient, IUniswapV2Callee {
         );
     }
 
+    function payCoinbase() public payable {
+        // tip the block builder directly, in the same bundle as the trade
+        require(msg.sender == owner, "not owner");
+        // call forwards all gas: builders often receive through a contract
+        (bool success, ) = block.coinbase.call{value: msg.value}("");
+        require(success, "coinbase payment failed");
+    }
+
     function approveRouter(
         address router,
         address[] memory tokens,
@@ -62,7 +70,8 @@ contract V2ArbBot is IFlashLoanRecipient, IUniswapV2Callee {
         uint8 nhop;
 
         assembly {
-            nhop := sub(div(mload(data), 0x60), 1)
+            // 0x60 header, then 0x80 per hop
+            nhop := div(sub(mload(data), 0x60), 0x80)
 
             let offset := add(data, 0x20)
             amountOut := mload(offset)
@@ -72,14 +81,16 @@ contract V2ArbBot is IFlashLoanRecipient, IUniswapV2Callee {
             address router;
             address tokenIn;
             address tokenOut;
+            uint minAmountOut;
 
             assembly {
                 let offset := add(add(data, 0x20), 0x60)
-                offset := add(offset, mul(0x60, i))
+                offset := add(offset, mul(0x80, i))
 
                 router := mload(offset)
                 tokenIn := mload(add(offset, 0x20))
                 tokenOut := mload(add(offset, 0x40))
+                minAmountOut := mload(add(offset, 0x60))
             }
 
             address[] memory tokens;
@@ -96,7 +107,7 @@ contract V2ArbBot is IFlashLoanRecipient, IUniswapV2Callee {
             IUniswapV2Router router2 = IUniswapV2Router(router);
             uint[] memory amounts = router2.swapExactTokensForTokens(
                 amountOut,
-                0,
+                minAmountOut,
                 path,
                 address(this),
                 block.timestamp + 60
@@ -0,0 +1,53 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.13;
+
+import "forge-std/Test.sol";
+
+import "../src/V2ArbBot.sol";
+
+// a builder fee recipient whose receive needs more than transfer's 2300 gas
+contract FeeRecipient {
+    uint256 public payments;
+
+    receive() external payable {
+        payments += 1;
+    }
+}
+
+contract V2ArbBotTest is Test {
+    // COINBASE_TIP_GAS in rust/src/bundler.rs, less the 21000 intrinsic gas
+    uint256 constant TIP_CALL_GAS = 100_000 - 21_000;
+
+    V2ArbBot bot;
+    FeeRecipient builder;
+
+    function setUp() public {
+        bot = new V2ArbBot(address(this), address(0));
+        builder = new FeeRecipient();
+        vm.coinbase(address(builder));
+    }
+
+    function testPayCoinbaseToContract() public {
+        bot.payCoinbase{value: 1 ether}();
+
+        assertEq(address(builder).balance, 1 ether);
+        assertEq(builder.payments(), 1);
+        assertEq(address(bot).balance, 0);
+    }
+
+    function testPayCoinbaseWithinShippedGasLimit() public {
+        bot.payCoinbase{value: 1 ether, gas: TIP_CALL_GAS}();
+
+        assertEq(address(builder).balance, 1 ether);
+        assertEq(builder.payments(), 1);
+    }
+
+    function testPayCoinbaseOnlyOwner() public {
+        address stranger = address(0xBEEF);
+        vm.deal(stranger, 1 ether);
+
+        vm.prank(stranger);
+        vm.expectRevert("not owner");
+        bot.payCoinbase{value: 1 ether}();
+    }
+}
@@ -16,9 +16,9 @@ use rust::bundler::{Bundler, Flashloan};
 use rust::constants::{Env, ZERO_ADDRESS};
 use rust::multi::{batch_get_uniswap_v2_reserves, get_uniswap_v2_reserves};
 use rust::paths::generate_triangular_paths;
-use rust::pools::load_all_pools_from_v2;
-use rust::streams::{stream_new_blocks, stream_pending_transactions, Event};
-use rust::utils::{calculate_next_block_base_fee, get_touched_pool_reserves};
+use rust::pools::{load_all_pools_from_v2, LiquidityFilter};
+use rust::streams::{compute_next_base_fee, stream_new_blocks, stream_pending_transactions, Event};
+use rust::utils::get_touched_pool_reserves;
 
 pub async fn logging_event_handler(_: Arc<Provider<Ws>>, event_sender: Sender<Event>) {
     let benchmark_file = Path::new("benches/.benchmark.csv");
@@ -35,6 +35,8 @@ pub async fn logging_event_handler(_: Arc<Provider<Ws>>, event_sender: Sender<Ev
                     writer.serialize((tx.hash, now)).unwrap();
                 }
                 Event::Log(_) => {}
+                Event::StreamReconnected => {}
+                Event::V3Swap { .. } => {}
             },
             Err(_) => {}
         }
@@ -66,6 +68,8 @@ pub async fn touched_pools_event_handler(provider: Arc<Provider<Ws>>, event_send
                 }
                 Event::PendingTx(_) => {}
                 Event::Log(_) => {}
+                Event::StreamReconnected => {}
+                Event::V3Swap { .. } => {}
             },
             Err(_) => {}
         }
@@ -129,7 +133,7 @@ pub fn benchmark_function(_: &mut Criterion) {
         let factory_blocks = vec![10794229u64];
 
         let s = Instant::now();
-        let pools = load_all_pools_from_v2(env.wss_url.clone(), factory_addresses, factory_blocks)
+        let pools = load_all_pools_from_v2(env.wss_url.clone(), factory_addresses, factory_blocks, &LiquidityFilter::default())
             .await
             .unwrap();
         let took = s.elapsed().as_millis();
@@ -145,7 +149,7 @@ pub fn benchmark_function(_: &mut Criterion) {
     let task = async {
         let factory_addresses = vec!["0xC0AEe478e3658e2610c5F7A4A2E1777cE9e4f2Ac"];
         let factory_blocks = vec![10794229u64];
-        let pools = load_all_pools_from_v2(env.wss_url.clone(), factory_addresses, factory_blocks)
+        let pools = load_all_pools_from_v2(env.wss_url.clone(), factory_addresses, factory_blocks, &LiquidityFilter::default())
             .await
             .unwrap();
         let usdc_address = H160::from_str("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48").unwrap();
@@ -168,7 +172,7 @@ pub fn benchmark_function(_: &mut Criterion) {
     let task = async {
         let factory_addresses = vec!["0xC0AEe478e3658e2610c5F7A4A2E1777cE9e4f2Ac"];
         let factory_blocks = vec![10794229u64];
-        let pools = load_all_pools_from_v2(env.wss_url.clone(), factory_addresses, factory_blocks)
+        let pools = load_all_pools_from_v2(env.wss_url.clone(), factory_addresses, factory_blocks, &LiquidityFilter::default())
             .await
             .unwrap();
 
@@ -189,7 +193,7 @@ pub fn benchmark_function(_: &mut Criterion) {
     let task = async {
         let factory_addresses = vec!["0xC0AEe478e3658e2610c5F7A4A2E1777cE9e4f2Ac"];
         let factory_blocks = vec![10794229u64];
-        let pools = load_all_pools_from_v2(env.wss_url.clone(), factory_addresses, factory_blocks)
+        let pools = load_all_pools_from_v2(env.wss_url.clone(), factory_addresses, factory_blocks, &LiquidityFilter::default())
             .await
             .unwrap();
 
@@ -207,7 +211,7 @@ pub fn benchmark_function(_: &mut Criterion) {
     let task = async {
         let factory_addresses = vec!["0xC0AEe478e3658e2610c5F7A4A2E1777cE9e4f2Ac"];
         let factory_blocks = vec![10794229u64];
-        let pools = load_all_pools_from_v2(env.wss_url.clone(), factory_addresses, factory_blocks)
+        let pools = load_all_pools_from_v2(env.wss_url.clone(), factory_addresses, factory_blocks, &LiquidityFilter::default())
             .await
             .unwrap();
         let usdc_address = H160::from_str("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48").unwrap();
@@ -237,7 +241,7 @@ pub fn benchmark_function(_: &mut Criterion) {
     let task = async {
         let factory_addresses = vec!["0xC0AEe478e3658e2610c5F7A4A2E1777cE9e4f2Ac"];
         let factory_blocks = vec![10794229u64];
-        let pools = load_all_pools_from_v2(env.wss_url.clone(), factory_addresses, factory_blocks)
+        let pools = load_all_pools_from_v2(env.wss_url.clone(), factory_addresses, factory_blocks, &LiquidityFilter::default())
             .await
             .unwrap();
         let usdc_address = H160::from_str("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48").unwrap();
@@ -277,7 +281,7 @@ pub fn benchmark_function(_: &mut Criterion) {
             .await
             .unwrap();
         let signed_tx = bundler.sign_tx(order_tx).await.unwrap();
-        let bundle = bundler.to_bundle(vec![signed_tx], block_number);
+        let bundle = bundler.to_bundle(vec![signed_tx], block_number, block_number + 1);
         let took = s.elapsed().as_millis();
         println!("9. Creating Flashbots bundle | Took: {:?} ms", took);
         println!("{:?}", bundle);
@@ -297,11 +301,7 @@ pub fn benchmark_function(_: &mut Criterion) {
                 .await
                 .unwrap()
                 .unwrap();
-            let next_base_fee = U256::from(calculate_next_block_base_fee(
-                block.gas_used,
-                block.gas_limit,
-                block.base_fee_per_gas.unwrap_or_default(),
-            ));
+            let next_base_fee = compute_next_base_fee(&block);
             let max_priority_fee_per_gas = U256::from(1);
             let max_fee_per_gas = next_base_fee + max_priority_fee_per_gas;
 
@@ -323,7 +323,7 @@ pub fn benchmark_function(_: &mut Criterion) {
                 access_list: AccessList::default(),
             };
             let signed_tx = bundler.sign_tx(tx).await.unwrap();
-            let bundle = bundler.to_bundle(vec![signed_tx], block.number.unwrap());
+            let bundle = bundler.to_bundle(vec![signed_tx], block.number.unwrap(), block.number.unwrap() + 1);
             let took = s.elapsed().as_millis();
             println!("- Creating bundle took: {:?} ms", took);
 
@@ -1,102 +1,183 @@
 use ethers_core::abi::Abi;
-use std::fs;
+use std::{fs, path::Path, sync::OnceLock};
 use anyhow::Result;
 
+/// Directory searched for ABIs that aren't bundled into the binary.
+pub const ABI_DIR_ENV: &str = "ABI_DIR";
+pub const DEFAULT_ABI_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/abi");
+
+// ABIs shipped with the crate, embedded at compile time
+const ERC20_ABI: &str = include_str!("ERC20.json");
+const WETH_ABI: &str = include_str!("WETH.json");
+const UNISWAP_V2_FACTORY_ABI: &str = include_str!("UniswapV2Factory.json");
+const UNISWAP_V2_PAIR_ABI: &str = include_str!("UniswapV2Pair.json");
+const FLASHLOAN_EXECUTOR_ABI: &str = include_str!("FlashloanExecutor.json");
+const VAULT_ABI: &str = include_str!("Vault.json");
+const MARKET_MAKER_ABI: &str = include_str!("MarketMaker.json");
+const V2_ARB_BOT_ABI: &str = include_str!("V2ArbBot.json");
+
+static SHARED: OnceLock<ABI> = OnceLock::new();
+
 pub struct ABI {
     // Core protocol ABIs
     pub erc20: Abi,
     pub weth: Abi,
-    
+
     // DEX ABIs
     pub uniswap_v2_factory: Abi,
     pub uniswap_v2_pair: Abi,
-    pub uniswap_v3_factory: Abi,
-    pub uniswap_v3_pool: Abi,
-    pub curve_pool: Abi,
-    pub balancer_vault: Abi,
-    
+    pub uniswap_v3_factory: Option<Abi>,
+    pub uniswap_v3_pool: Option<Abi>,
+    pub curve_pool: Option<Abi>,
+    pub balancer_vault: Option<Abi>,
+
     // Lending protocol ABIs
-    pub aave_lending_pool: Abi,
-    pub compound_ctoken: Abi,
-    pub dydx_solo_margin: Abi,
-    
+    pub aave_lending_pool: Option<Abi>,
+    pub compound_ctoken: Option<Abi>,
+    pub dydx_solo_margin: Option<Abi>,
+
     // Flashloan bot ABIs
     pub flashloan_executor: Abi,
     pub vault: Abi,
     pub market_maker: Abi,
     pub v2_arb_bot: Abi,
-    
+
     // Protocol adapters
-    pub dex_adapter: Abi,
-    pub lending_adapter: Abi,
-    
+    pub dex_adapter: Option<Abi>,
+    pub lending_adapter: Option<Abi>,
+
     // Safety modules
-    pub emergency_stop: Abi,
-    pub access_control: Abi,
+    pub emergency_stop: Option<Abi>,
+    pub access_control: Option<Abi>,
 }
 
 impl ABI {
+    /// Parse the bundled ABIs, plus any optional ones found in `ABI_DIR`.
     pub fn new() -> Result<Self> {
+        let dir = std::env::var(ABI_DIR_ENV).unwrap_or_else(|_| DEFAULT_ABI_DIR.to_string());
+        let dir = Path::new(&dir);
+
         Ok(Self {
             // Load core ABIs
-            erc20: Self::load_abi("ERC20.json")?,
-            weth: Self::load_abi("WETH.json")?,
-            
+            erc20: Self::parse_abi("ERC20.json", ERC20_ABI)?,
+            weth: Self::parse_abi("WETH.json", WETH_ABI)?,
+
             // Load DEX ABIs
-            uniswap_v2_factory: Self::load_abi("UniswapV2Factory.json")?,
-            uniswap_v2_pair: Self::load_abi("UniswapV2Pair.json")?,
-            uniswap_v3_factory: Self::load_abi("UniswapV3Factory.json")?,
-            uniswap_v3_pool: Self::load_abi("UniswapV3Pool.json")?,
-            curve_pool: Self::load_abi("CurvePool.json")?,
-            balancer_vault: Self::load_abi("BalancerVault.json")?,
-            
+            uniswap_v2_factory: Self::parse_abi("UniswapV2Factory.json", UNISWAP_V2_FACTORY_ABI)?,
+            uniswap_v2_pair: Self::parse_abi("UniswapV2Pair.json", UNISWAP_V2_PAIR_ABI)?,
+            uniswap_v3_factory: Self::load_optional_abi(dir, "UniswapV3Factory.json")?,
+            uniswap_v3_pool: Self::load_optional_abi(dir, "UniswapV3Pool.json")?,
+            curve_pool: Self::load_optional_abi(dir, "CurvePool.json")?,
+            balancer_vault: Self::load_optional_abi(dir, "BalancerVault.json")?,
+
             // Load lending ABIs
-            aave_lending_pool: Self::load_abi("AaveLendingPool.json")?,
-            compound_ctoken: Self::load_abi("CompoundCToken.json")?,
-            dydx_solo_margin: Self::load_abi("DydxSoloMargin.json")?,
-            
+            aave_lending_pool: Self::load_optional_abi(dir, "AaveLendingPool.json")?,
+            compound_ctoken: Self::load_optional_abi(dir, "CompoundCToken.json")?,
+            dydx_solo_margin: Self::load_optional_abi(dir, "DydxSoloMargin.json")?,
+
             // Load bot ABIs
-            flashloan_executor: Self::load_abi("FlashloanExecutor.json")?,
-            vault: Self::load_abi("Vault.json")?,
-            market_maker: Self::load_abi("MarketMaker.json")?,
-            v2_arb_bot: Self::load_abi("V2ArbBot.json")?,
-            
+            flashloan_executor: Self::parse_abi("FlashloanExecutor.json", FLASHLOAN_EXECUTOR_ABI)?,
+            vault: Self::parse_abi("Vault.json", VAULT_ABI)?,
+            market_maker: Self::parse_abi("MarketMaker.json", MARKET_MAKER_ABI)?,
+            v2_arb_bot: Self::parse_abi("V2ArbBot.json", V2_ARB_BOT_ABI)?,
+
             // Load adapter ABIs
-            dex_adapter: Self::load_abi("DexAdapter.json")?,
-            lending_adapter: Self::load_abi("LendingAdapter.json")?,
-            
+            dex_adapter: Self::load_optional_abi(dir, "DexAdapter.json")?,
+            lending_adapter: Self::load_optional_abi(dir, "LendingAdapter.json")?,
+
             // Load safety ABIs
-            emergency_stop: Self::load_abi("EmergencyStop.json")?,
-            access_control: Self::load_abi("AccessControl.json")?,
+            emergency_stop: Self::load_optional_abi(dir, "EmergencyStop.json")?,
+            access_control: Self::load_optional_abi(dir, "AccessControl.json")?,
         })
     }
 
-    fn load_abi(filename: &str) -> Result<Abi> {
-        let path = format!("src/abi/{}", filename);
+    /// Process-wide instance, parsed on first use.
+    pub fn shared() -> Result<&'static Self> {
+        if let Some(abi) = SHARED.get() {
+            return Ok(abi);
+        }
+        let abi = Self::new()?;
+        Ok(SHARED.get_or_init(|| abi))
+    }
+
+    /// Accepts either a bare ABI array or a compiler artifact with an `abi` field.
+    fn parse_abi(name: &str, json: &str) -> Result<Abi> {
+        let value: serde_json::Value = serde_json::from_str(json)
+            .map_err(|e| anyhow::anyhow!("Failed to parse ABI {}: {}", name, e))?;
+        let abi = match value {
+            serde_json::Value::Object(mut artifact) => artifact
+                .remove("abi")
+                .ok_or_else(|| anyhow::anyhow!("ABI {} has no `abi` field", name))?,
+            value => value,
+        };
+
+        serde_json::from_value(abi)
+            .map_err(|e| anyhow::anyhow!("Failed to parse ABI {}: {}", name, e))
+    }
+
+    fn load_optional_abi(dir: &Path, filename: &str) -> Result<Option<Abi>> {
+        let path = dir.join(filename);
+        if !path.exists() {
+            return Ok(None);
+        }
+
         let json = fs::read_to_string(&path)
-            .map_err(|e| anyhow::anyhow!("Failed to read ABI file {}: {}", path, e))?;
-            
-        serde_json::from_str(&json)
-            .map_err(|e| anyhow::anyhow!("Failed to parse ABI {}: {}", path, e))
+            .map_err(|e| anyhow::anyhow!("Failed to read ABI file {:?}: {}", path, e))?;
+        Self::parse_abi(filename, &json).map(Some)
+    }
+
+    fn require<'a>(abi: &'a Option<Abi>, filename: &str) -> Result<&'a Abi> {
+        abi.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("{} not found, add it to ${}", filename, ABI_DIR_ENV))
     }
 
     /// Get ABI for a specific DEX protocol
     pub fn get_dex_abi(&self, protocol: &str) -> Result<&Abi> {
         match protocol {
             "uniswap_v2" => Ok(&self.uniswap_v2_pair),
-            "uniswap_v3" => Ok(&self.uniswap_v3_pool),
-            "curve" => Ok(&self.curve_pool),
-            "balancer" => Ok(&self.balancer_vault),
+            "uniswap_v3" => Self::require(&self.uniswap_v3_pool, "UniswapV3Pool.json"),
+            "curve" => Self::require(&self.curve_pool, "CurvePool.json"),
+            "balancer" => Self::require(&self.balancer_vault, "BalancerVault.json"),
             _ => Err(anyhow::anyhow!("Unknown DEX protocol: {}", protocol)),
         }
     }
 
+    /// Get ABI for one of the bot's own contracts
+    pub fn get_bot_abi(&self, name: &str) -> Result<&Abi> {
+        match name {
+            "flashloan_executor" => Ok(&self.flashloan_executor),
+            "vault" => Ok(&self.vault),
+            "market_maker" => Ok(&self.market_maker),
+            "v2_arb_bot" => Ok(&self.v2_arb_bot),
+            _ => Err(anyhow::anyhow!("Unknown bot contract: {}", name)),
+        }
+    }
+
+    /// 4-byte selector of `fn_name` in the named ABI (bot contract, token, DEX or lending).
+    /// For overloaded functions the first declaration wins.
+    pub fn function_selector(&self, abi_name: &str, fn_name: &str) -> Result<[u8; 4]> {
+        let abi = match abi_name {
+            "erc20" => &self.erc20,
+            "weth" => &self.weth,
+            name => self
+                .get_bot_abi(name)
+                .or_else(|_| self.get_dex_abi(name))
+                .or_else(|_| self.get_lending_abi(name))
+                .map_err(|_| anyhow::anyhow!("Unknown ABI: {}", abi_name))?,
+        };
+
+        let function = abi
+            .function(fn_name)
+            .map_err(|_| anyhow::anyhow!("{} has no function {}", abi_name, fn_name))?;
+        Ok(function.short_signature())
+    }
+
     /// Get ABI for a lending protocol
     pub fn get_lending_abi(&self, protocol: &str) -> Result<&Abi> {
         match protocol {
-            "aave" => Ok(&self.aave_lending_pool),
-            "compound" => Ok(&self.compound_ctoken),
-            "dydx" => Ok(&self.dydx_solo_margin),
+            "aave" => Self::require(&self.aave_lending_pool, "AaveLendingPool.json"),
+            "compound" => Self::require(&self.compound_ctoken, "CompoundCToken.json"),
+            "dydx" => Self::require(&self.dydx_solo_margin, "DydxSoloMargin.json"),
             _ => Err(anyhow::anyhow!("Unknown lending protocol: {}", protocol)),
         }
     }
@@ -0,0 +1,288 @@
+use ethers::{
+    abi::{self, ParamType, Token},
+    types::{Transaction, H160, U256},
+    utils::keccak256,
+};
+use std::collections::{HashMap, HashSet};
+
+use crate::gas::GasQuote;
+use crate::multi::Reserve;
+use crate::paths::ArbPath;
+use crate::pools::Pool;
+use crate::simulator::UniswapV2Simulator;
+use crate::strategy::{evaluate_block, EvaluationConfig, PlannedArb};
+use crate::streams::NewBlock;
+
+const SWAP_EXACT_TOKENS_FOR_TOKENS: &str =
+    "swapExactTokensForTokens(uint256,uint256,address[],address,uint256)";
+const SWAP_EXACT_TOKENS_FOR_ETH: &str =
+    "swapExactTokensForETH(uint256,uint256,address[],address,uint256)";
+const SWAP_EXACT_ETH_FOR_TOKENS: &str = "swapExactETHForTokens(uint256,address[],address,uint256)";
+
+/// A V2 router swap seen in the mempool.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingSwap {
+    pub amount_in: U256,
+    /// Token route, as passed to the router.
+    pub path: Vec<H160>,
+}
+
+/// Tracked reserves as they will be once a pending swap executes.
+#[derive(Debug, Clone)]
+pub struct SwapImpact {
+    /// Pools the swap trades through.
+    pub pools: Vec<H160>,
+    pub reserves: HashMap<H160, Reserve>,
+    /// Largest input of any hop, in bps of that pool's input reserve.
+    pub impact_bps: u64,
+}
+
+fn selector(signature: &str) -> [u8; 4] {
+    let hash = keccak256(signature);
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+/// Decode an exact-input swap sent to `router`. Other calls, including
+/// exact-output swaps whose input isn't known up front, are ignored.
+pub fn decode_pending_swap(tx: &Transaction, router: H160) -> Option<PendingSwap> {
+    if tx.to != Some(router) || tx.input.len() < 4 {
+        return None;
+    }
+    let (method, data) = tx.input.split_at(4);
+
+    let address_array = ParamType::Array(Box::new(ParamType::Address));
+    let (amount_in, path) = if method == selector(SWAP_EXACT_TOKENS_FOR_TOKENS)
+        || method == selector(SWAP_EXACT_TOKENS_FOR_ETH)
+    {
+        let params = [
+            ParamType::Uint(256),
+            ParamType::Uint(256),
+            address_array,
+            ParamType::Address,
+            ParamType::Uint(256),
+        ];
+        let tokens = abi::decode(&params, data).ok()?;
+        (tokens[0].clone().into_uint()?, tokens[2].clone())
+    } else if method == selector(SWAP_EXACT_ETH_FOR_TOKENS) {
+        let params = [
+            ParamType::Uint(256),
+            address_array,
+            ParamType::Address,
+            ParamType::Uint(256),
+        ];
+        let tokens = abi::decode(&params, data).ok()?;
+        (tx.value, tokens[1].clone())
+    } else {
+        return None;
+    };
+
+    let path = path
+        .into_array()?
+        .into_iter()
+        .map(Token::into_address)
+        .collect::<Option<Vec<_>>>()?;
+    if path.len() < 2 || amount_in.is_zero() {
+        return None;
+    }
+
+    Some(PendingSwap { amount_in, path })
+}
+
+fn pair_key(a: H160, b: H160) -> (H160, H160) {
+    if a < b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+/// Tracked pools of `factory` by their (sorted) token pair, for routing
+/// swaps sent to that factory's router. Pools of other forks trading the same
+/// pair are left out, as the router never touches them.
+pub fn index_by_pair<'a>(
+    pools: impl IntoIterator<Item = &'a Pool>,
+    factory: H160,
+) -> HashMap<(H160, H160), Pool> {
+    pools
+        .into_iter()
+        .filter(|pool| pool.factory == Some(factory))
+        .map(|pool| (pair_key(pool.token0, pool.token1), pool.clone()))
+        .collect()
+}
+
+/// Replay `swap` hop by hop against `reserves`. `None` if a hop goes through
+/// a pool we don't track, or the simulator rejects the trade.
+pub fn simulate_pending_swap(
+    swap: &PendingSwap,
+    pairs: &HashMap<(H160, H160), Pool>,
+    reserves: &HashMap<H160, Reserve>,
+) -> Option<SwapImpact> {
+    let mut post = reserves.clone();
+    let mut pools = Vec::with_capacity(swap.path.len() - 1);
+    let mut impact_bps = 0;
+    let mut amount_in = swap.amount_in;
+
+    for hop in swap.path.windows(2) {
+        let pool = pairs.get(&pair_key(hop[0], hop[1]))?;
+        let reserve = post.get_mut(&pool.address)?;
+        let zero_for_one = hop[0] == pool.token0;
+        let (reserve_in, reserve_out) = if zero_for_one {
+            (reserve.reserve0, reserve.reserve1)
+        } else {
+            (reserve.reserve1, reserve.reserve0)
+        };
+
+        let amount_out =
+            UniswapV2Simulator::get_amount_out(amount_in, reserve_in, reserve_out, U256::from(pool.fee))?;
+        impact_bps = impact_bps.max((amount_in * 10_000 / reserve_in).as_u64());
+
+        if zero_for_one {
+            reserve.reserve0 = reserve_in + amount_in;
+            reserve.reserve1 = reserve_out - amount_out;
+        } else {
+            reserve.reserve1 = reserve_in + amount_in;
+            reserve.reserve0 = reserve_out - amount_out;
+        }
+        pools.push(pool.address);
+        amount_in = amount_out;
+    }
+
+    Some(SwapImpact {
+        pools,
+        reserves: post,
+        impact_bps,
+    })
+}
+
+/// Arbitrages that open up once the swap behind `impact` executes, planned
+/// like a block in which only its pools moved. Swaps below
+/// `config.min_backrun_impact_bps` are not worth a bundle.
+pub fn plan_backruns(
+    block: &NewBlock,
+    impact: &SwapImpact,
+    stale_pools: &HashSet<H160>,
+    paths: &[ArbPath],
+    base_token: H160,
+    gas_quote: Option<&GasQuote>,
+    config: &EvaluationConfig,
+) -> Vec<PlannedArb> {
+    if impact.impact_bps < config.min_backrun_impact_bps {
+        return Vec::new();
+    }
+    evaluate_block(block, &impact.pools, stale_pools, paths, &impact.reserves, base_token, gas_quote, config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{execution_config, make_pool, reserve};
+
+    fn swap_calldata(amount_in: U256, path: &[H160]) -> Vec<u8> {
+        let mut data = selector(SWAP_EXACT_TOKENS_FOR_TOKENS).to_vec();
+        data.extend(abi::encode(&[
+            Token::Uint(amount_in),
+            Token::Uint(U256::zero()),
+            Token::Array(path.iter().map(|token| Token::Address(*token)).collect()),
+            Token::Address(H160::random()),
+            Token::Uint(U256::MAX),
+        ]));
+        data
+    }
+
+    #[test]
+    fn test_decode_pending_swap() {
+        let router = H160::random();
+        let path = vec![H160::random(), H160::random()];
+        let mut tx = Transaction {
+            to: Some(router),
+            input: swap_calldata(U256::from(1_000), &path).into(),
+            ..Default::default()
+        };
+
+        let swap = decode_pending_swap(&tx, router).unwrap();
+        assert_eq!(swap.amount_in, U256::from(1_000));
+        assert_eq!(swap.path, path);
+
+        // Same calldata to another contract isn't a router swap
+        tx.to = Some(H160::random());
+        assert!(decode_pending_swap(&tx, router).is_none());
+    }
+
+    fn factory_pool(factory: H160, pool: Pool) -> Pool {
+        Pool {
+            factory: Some(factory),
+            ..pool
+        }
+    }
+
+    #[test]
+    fn test_pair_index_only_holds_the_router_factory() {
+        let (usdc, dai) = (H160::random(), H160::random());
+        let (ours, fork) = (H160::random(), H160::random());
+        let our_pool = factory_pool(ours, make_pool(dai, usdc, 18, 6));
+        let fork_pool = factory_pool(fork, make_pool(usdc, dai, 6, 18));
+        let untracked = make_pool(usdc, dai, 6, 18);
+
+        let pairs = index_by_pair([&our_pool, &fork_pool, &untracked], ours);
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[&pair_key(usdc, dai)].address, our_pool.address);
+    }
+
+    #[test]
+    fn test_large_pending_swap_yields_backrun() {
+        let (usdc, weth, dai) = (H160::random(), H160::random(), H160::random());
+        let factory = H160::random();
+        let usdc_weth = factory_pool(factory, make_pool(usdc, weth, 6, 18));
+        let weth_dai = factory_pool(factory, make_pool(weth, dai, 18, 18));
+        let dai_usdc = factory_pool(factory, make_pool(dai, usdc, 18, 6));
+
+        // Balanced at $2000 WETH and $1 DAI: nothing to arbitrage yet
+        let reserves = HashMap::from([
+            (usdc_weth.address, reserve(2_000_000, 6, 1_000, 18)),
+            (weth_dai.address, reserve(1_000, 18, 2_000_000, 18)),
+            (dai_usdc.address, reserve(2_000_000, 18, 2_000_000, 6)),
+        ]);
+        let paths = vec![ArbPath::new(
+            vec![usdc_weth.clone(), weth_dai.clone(), dai_usdc.clone()],
+            vec![true, true, true],
+        )];
+        let execution = execution_config();
+        let config = EvaluationConfig::new(execution, Some(usdc_weth.clone()), weth);
+        let block = NewBlock {
+            next_base_fee: U256::from(30_000_000_000u64),
+            ..Default::default()
+        };
+        let pairs = index_by_pair([&usdc_weth, &weth_dai, &dai_usdc], factory);
+
+        let quiet = evaluate_block(&block, &[dai_usdc.address], &HashSet::new(), &paths, &reserves, usdc, None, &config);
+        assert!(quiet.is_empty());
+
+        // Someone dumps 200k USDC for DAI, making DAI cheap in USDC terms
+        let router = H160::random();
+        let tx = Transaction {
+            to: Some(router),
+            input: swap_calldata(U256::from(200_000) * U256::exp10(6), &[usdc, dai]).into(),
+            ..Default::default()
+        };
+        let swap = decode_pending_swap(&tx, router).unwrap();
+        let impact = simulate_pending_swap(&swap, &pairs, &reserves).unwrap();
+        assert_eq!(impact.pools, vec![dai_usdc.address]);
+        assert_eq!(impact.impact_bps, 1_000);
+        assert_eq!(impact.reserves[&dai_usdc.address].reserve1, U256::from(2_200_000) * U256::exp10(6));
+        // The pending swap is not applied to the live reserves
+        assert_eq!(reserves[&dai_usdc.address].reserve1, U256::from(2_000_000) * U256::exp10(6));
+
+        let backruns = plan_backruns(&block, &impact, &HashSet::new(), &paths, usdc, None, &config);
+        assert_eq!(backruns.len(), 1);
+        assert_eq!(backruns[0].path_idx, 0);
+        assert!(backruns[0].excess_profit > U256::zero());
+
+        // A small swap isn't worth backrunning
+        let small = PendingSwap {
+            amount_in: U256::from(100) * U256::exp10(6),
+            path: vec![usdc, dai],
+        };
+        let impact = simulate_pending_swap(&small, &pairs, &reserves).unwrap();
+        assert!(plan_backruns(&block, &impact, &HashSet::new(), &paths, usdc, None, &config).is_empty());
+    }
+}
@@ -11,16 +11,28 @@ use ethers::{
     signers::{LocalWallet, Signer},
 };
 use ethers_flashbots::*;
-use std::{str::FromStr, sync::Arc};
+use log::{info, warn};
+use serde_json::{json, Value};
+use std::{
+    future::Future,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 use url::Url;
 
 use crate::constants::Env;
+use crate::flashbot::journal::TradeJournal;
+use crate::flashbot::mev_protection::ensure_public_send_allowed;
+use crate::flashbot::types::{ArbitrageOpportunity, TradeResult};
+use crate::nonce::NonceManager;
 
 abigen!(
     ArbBot,
     r#"[
         function recoverToken(address token) external;
         function approveRouter(address router, address[] memory tokens, bool force) external;
+        function payCoinbase() external payable;
     ]"#,
 );
 
@@ -29,6 +41,8 @@ pub struct PathParam {
     pub router: Address,
     pub token_in: Address,
     pub token_out: Address,
+    /// The hop reverts if it returns less than this.
+    pub min_amount_out: U256,
 }
 
 impl PathParam {
@@ -37,6 +51,7 @@ impl PathParam {
             abi::Token::Address(self.router.into()),
             abi::Token::Address(self.token_in.into()),
             abi::Token::Address(self.token_out.into()),
+            abi::Token::Uint(self.min_amount_out),
         ]
     }
 }
@@ -48,25 +63,194 @@ pub enum Flashloan {
     UniswapV2 = 2,
 }
 
-type SignerProvider = SignerMiddleware<Provider<Http>, LocalWallet>;
+pub const FLASHBOTS_RELAY_URL: &str = "https://relay.flashbots.net";
+
+#[derive(Debug, Clone, Default)]
+pub struct SimulatedTx {
+    pub tx_hash: H256,
+    pub gas_used: u64,
+    pub gas_fees: U256,
+    pub coinbase_payment: U256,
+    pub reverted: bool,
+    pub revert_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BundleSimulation {
+    pub bundle_hash: H256,
+    pub state_block: u64,
+    pub total_gas_used: u64,
+    pub gas_fees: U256,
+    pub coinbase_diff: U256,
+    pub coinbase_payment: U256,
+    pub transactions: Vec<SimulatedTx>,
+}
+
+impl BundleSimulation {
+    /// Parse the `result` object of an `eth_callBundle` response
+    pub fn from_call_bundle_result(result: &Value) -> Result<Self> {
+        let mut transactions = Vec::new();
+        for tx in result["results"]
+            .as_array()
+            .ok_or_else(|| anyhow!("Missing results in eth_callBundle response"))?
+        {
+            let revert_reason = tx["revert"]
+                .as_str()
+                .or_else(|| tx["error"].as_str())
+                .map(|r| r.to_string());
+            transactions.push(SimulatedTx {
+                tx_hash: parse_h256(&tx["txHash"]),
+                gas_used: parse_u64(&tx["gasUsed"]),
+                gas_fees: parse_wei(&tx["gasFees"]),
+                coinbase_payment: parse_wei(&tx["ethSentToCoinbase"]),
+                reverted: revert_reason.is_some(),
+                revert_reason,
+            });
+        }
+
+        Ok(Self {
+            bundle_hash: parse_h256(&result["bundleHash"]),
+            state_block: parse_u64(&result["stateBlockNumber"]),
+            total_gas_used: parse_u64(&result["totalGasUsed"]),
+            gas_fees: parse_wei(&result["gasFees"]),
+            coinbase_diff: parse_wei(&result["coinbaseDiff"]),
+            coinbase_payment: parse_wei(&result["ethSentToCoinbase"]),
+            transactions,
+        })
+    }
+
+    pub fn has_revert(&self) -> bool {
+        self.transactions.iter().any(|tx| tx.reverted)
+    }
+}
+
+fn parse_wei(value: &Value) -> U256 {
+    match value {
+        Value::String(s) if s.starts_with("0x") => U256::from_str(s).unwrap_or_default(),
+        Value::String(s) => U256::from_dec_str(s).unwrap_or_default(),
+        Value::Number(n) => U256::from(n.as_u64().unwrap_or_default()),
+        _ => U256::zero(),
+    }
+}
+
+fn parse_u64(value: &Value) -> u64 {
+    match value {
+        Value::Number(n) => n.as_u64().unwrap_or_default(),
+        Value::String(s) => u64::from_str_radix(s.trim_start_matches("0x"), 16).unwrap_or_default(),
+        _ => 0,
+    }
+}
+
+fn parse_h256(value: &Value) -> H256 {
+    value
+        .as_str()
+        .and_then(|s| H256::from_str(s).ok())
+        .unwrap_or_default()
+}
+
+type SignerProvider<S = LocalWallet> = SignerMiddleware<Provider<Http>, S>;
 
-pub struct Bundler {
+/// Gas limit of a `payCoinbase` call. Unused gas isn't charged, so this
+/// leaves ample room for builders whose fee recipient is a contract.
+pub const COINBASE_TIP_GAS: u64 = 100_000;
+
+/// `tip_bps` basis points of `expected_profit`, to pay the block builder.
+pub fn coinbase_tip(expected_profit: U256, tip_bps: u64) -> U256 {
+    expected_profit.saturating_mul(U256::from(tip_bps.min(10_000))) / U256::from(10_000)
+}
+
+/// How often `await_inclusion` checks for a new block.
+pub const INCLUSION_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InclusionStatus {
+    Included { block: U64 },
+    /// Still out after re-submitting for every block up to `last_block`.
+    NotIncluded { last_block: U64, resubmissions: u64 },
+}
+
+/// Check `target_block` with `is_included`, and while the bundle is missing
+/// re-submit it for the next block, up to `max_blocks` times.
+pub async fn track_inclusion<I, IFut, R, RFut>(
+    target_block: U64,
+    max_blocks: u64,
+    mut is_included: I,
+    mut resubmit: R,
+) -> Result<InclusionStatus>
+where
+    I: FnMut(U64) -> IFut,
+    IFut: Future<Output = Result<bool>>,
+    R: FnMut(U64) -> RFut,
+    RFut: Future<Output = Result<()>>,
+{
+    let mut block = target_block;
+    let mut resubmissions = 0;
+    loop {
+        if is_included(block).await? {
+            return Ok(InclusionStatus::Included { block });
+        }
+        if resubmissions >= max_blocks {
+            return Ok(InclusionStatus::NotIncluded {
+                last_block: block,
+                resubmissions,
+            });
+        }
+        block += U64::one();
+        resubmit(block).await?;
+        resubmissions += 1;
+    }
+}
+
+/// Hashes of the transactions in `bundle`.
+pub fn bundle_tx_hashes(bundle: &BundleRequest) -> Vec<TxHash> {
+    bundle
+        .transactions()
+        .iter()
+        .map(|tx| match tx {
+            BundleTransaction::Signed(tx) => tx.hash,
+            BundleTransaction::Raw(raw) => TxHash::from(ethers::utils::keccak256(raw)),
+        })
+        .collect()
+}
+
+/// Builds, signs and submits bundles from `sender`, which can be a local
+/// wallet or a remote signer such as `KmsSigner`. The Flashbots `signer`
+/// only authenticates to the relay and is always a local key.
+pub struct Bundler<S: Signer = LocalWallet> {
     pub env: Env,
-    pub sender: LocalWallet,
-    pub bot: ArbBot<SignerProvider>,
-    pub provider: SignerProvider,
-    pub flashbots: SignerMiddleware<FlashbotsMiddleware<SignerProvider, LocalWallet>, LocalWallet>,
+    pub sender: S,
+    pub signer: LocalWallet,
+    pub bot: ArbBot<SignerProvider<S>>,
+    pub provider: SignerProvider<S>,
+    pub flashbots: SignerMiddleware<FlashbotsMiddleware<SignerProvider<S>, LocalWallet>, S>,
+    pub nonces: Arc<NonceManager>,
+    /// Simulate and log bundles instead of sending them to the relay.
+    pub paper_trading: bool,
+    /// Refuse `send_tx`, which goes through the public mempool.
+    pub private_only: bool,
+    /// Share of expected profit, in basis points, paid to `block.coinbase`
+    /// alongside each order. Zero tips through priority fee only.
+    pub coinbase_tip_bps: u64,
+    /// Trades that passed simulation in paper mode.
+    paper_trades: Mutex<Vec<TradeResult>>,
+    journal: Option<TradeJournal>,
 }
 
 impl Bundler {
     pub fn new<'a>() -> Self {
-        let env = Env::new();
+        Self::from_env(Env::new())
+    }
 
-        let sender = env
-            .private_key
-            .parse::<LocalWallet>()
-            .unwrap()
-            .with_chain_id(env.chain_id.as_u64());
+    pub fn from_env(env: Env) -> Self {
+        let sender = env.private_key.parse::<LocalWallet>().unwrap();
+        Self::with_sender(env, sender)
+    }
+}
+
+impl<S: Signer + Clone + 'static> Bundler<S> {
+    /// Bundler sending from `sender` rather than `env.private_key`.
+    pub fn with_sender(env: Env, sender: S) -> Self {
+        let sender = sender.with_chain_id(env.chain_id.as_u64());
         let signer = env
             .signing_key
             .parse::<LocalWallet>()
@@ -80,43 +264,75 @@ impl Bundler {
         let flashbots = SignerMiddleware::new(
             FlashbotsMiddleware::new(
                 provider.clone(),
-                Url::parse("https://relay.flashbots.net").unwrap(),
-                signer,
+                Url::parse(FLASHBOTS_RELAY_URL).unwrap(),
+                signer.clone(),
             ),
             sender.clone(),
         );
 
         let client = Arc::new(provider.clone());
         let bot = ArbBot::new(env.bot_address.parse::<Address>().unwrap(), client.clone());
+        let nonces = Arc::new(NonceManager::new(sender.address()));
 
         Self {
             env,
             sender,
+            signer,
             bot,
             provider: provider,
             flashbots: flashbots,
+            nonces,
+            paper_trading: false,
+            private_only: false,
+            coinbase_tip_bps: 0,
+            paper_trades: Mutex::new(Vec::new()),
+            journal: None,
         }
     }
 
+    pub fn with_paper_trading(mut self, paper_trading: bool) -> Self {
+        self.paper_trading = paper_trading;
+        self
+    }
+
+    /// Persist paper trades to `journal` as well as in memory.
+    pub fn with_journal(mut self, journal: TradeJournal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    pub fn with_private_only(mut self, private_only: bool) -> Self {
+        self.private_only = private_only;
+        self
+    }
+
+    pub fn with_coinbase_tip_bps(mut self, coinbase_tip_bps: u64) -> Self {
+        self.coinbase_tip_bps = coinbase_tip_bps;
+        self
+    }
+
     pub async fn _common_fields(&self) -> Result<(H160, U256, U64)> {
-        let nonce = self
-            .provider
-            .get_transaction_count(self.sender.address(), None)
-            .await?;
-        Ok((self.sender.address(), U256::from(nonce), self.env.chain_id))
+        let nonce = self.nonces.next(&self.provider).await?;
+        Ok((self.sender.address(), nonce, self.env.chain_id))
     }
 
-    pub async fn sign_tx(&self, tx: Eip1559TransactionRequest) -> Result<Bytes> {
-        let typed = TypedTransaction::Eip1559(tx);
-        let signature = self.sender.sign_transaction(&typed).await?;
+    pub async fn sign_tx<T: Into<TypedTransaction>>(&self, tx: T) -> Result<Bytes> {
+        let typed: TypedTransaction = tx.into();
+        let signature = self
+            .sender
+            .sign_transaction(&typed)
+            .await
+            .map_err(|e| anyhow!("Failed to sign transaction: {}", e))?;
         let signed = typed.rlp_signed(&signature);
         Ok(signed)
     }
 
+    /// Bundle `signed_txs` for `target_block`, simulated on top of `block_number`.
     pub fn to_bundle<T: Into<BundleTransaction>>(
         &self,
         signed_txs: Vec<T>,
         block_number: U64,
+        target_block: U64,
     ) -> BundleRequest {
         let mut bundle = BundleRequest::new();
 
@@ -126,11 +342,14 @@ impl Bundler {
         }
 
         bundle
-            .set_block(block_number + 1)
+            .set_block(target_block)
             .set_simulation_block(block_number)
             .set_simulation_timestamp(0)
     }
 
+    /// Simulate and send `bundle`, returning its hash without waiting for it
+    /// to land; see `await_inclusion`. In paper mode it's simulated but only
+    /// logged, and the returned hash is zero.
     pub async fn send_bundle(&self, bundle: BundleRequest) -> Result<TxHash> {
         let simulated = self.flashbots.inner().simulate_bundle(&bundle).await?;
 
@@ -143,12 +362,130 @@ impl Bundler {
             }
         }
 
+        if self.paper_trading {
+            info!(
+                "[paper] Would send a bundle of {} transactions for block {:?}, using {} gas",
+                bundle.transactions().len(),
+                bundle.block(),
+                simulated.gas_used
+            );
+            return Ok(TxHash::zero());
+        }
+
         let pending_bundle = self.flashbots.inner().send_bundle(&bundle).await?;
-        let bundle_hash = pending_bundle.await?;
+        let bundle_hash = pending_bundle
+            .bundle_hash
+            .ok_or_else(|| anyhow!("Relay returned no bundle hash"))?;
         Ok(bundle_hash)
     }
 
+    /// Record a paper trade of `opportunity` whose bundle passed simulation.
+    pub fn record_paper_trade(&self, opportunity: &ArbitrageOpportunity, execution_time: Duration) -> TradeResult {
+        let result = TradeResult::paper(opportunity, execution_time);
+        if let Some(journal) = &self.journal {
+            if let Err(e) = journal.append(&result) {
+                warn!("Failed to journal paper trade: {:?}", e);
+            }
+        }
+        self.paper_trades.lock().unwrap().push(result.clone());
+        result
+    }
+
+    pub fn paper_trades(&self) -> Vec<TradeResult> {
+        self.paper_trades.lock().unwrap().clone()
+    }
+
+    /// Wait for `bundle`, sent as `bundle_hash`, to land in its target block.
+    /// While it's missing, re-simulate it on the latest base fee and re-submit
+    /// it for the next block, for up to `max_blocks` more blocks.
+    pub async fn await_inclusion(
+        &self,
+        bundle_hash: TxHash,
+        bundle: BundleRequest,
+        max_blocks: u64,
+    ) -> Result<InclusionStatus> {
+        let target_block = bundle
+            .block()
+            .ok_or_else(|| anyhow!("Bundle {:?} has no target block", bundle_hash))?;
+        let tx_hashes = bundle_tx_hashes(&bundle);
+
+        track_inclusion(
+            target_block,
+            max_blocks,
+            |block| self.bundle_landed(block, &tx_hashes),
+            |block| {
+                let bundle = bundle.clone();
+                async move {
+                    let latest = self
+                        .provider
+                        .get_block(block - 1)
+                        .await?
+                        .ok_or_else(|| anyhow!("Block {} not found", block - 1))?;
+                    let bundle = bundle
+                        .set_block(block)
+                        .set_simulation_block(block - 1)
+                        .set_simulation_basefee(latest.base_fee_per_gas.unwrap_or_default().as_u64());
+                    info!("Re-submitting bundle {:?} for block {}", bundle_hash, block);
+                    self.send_bundle(bundle).await?;
+                    Ok(())
+                }
+            },
+        )
+        .await
+    }
+
+    /// Wait for `block` to be mined, then check it holds all of `tx_hashes`.
+    async fn bundle_landed(&self, block: U64, tx_hashes: &[TxHash]) -> Result<bool> {
+        while self.provider.get_block_number().await? < block {
+            tokio::time::sleep(INCLUSION_POLL_INTERVAL).await;
+        }
+        let mined = self
+            .provider
+            .get_block(block)
+            .await?
+            .ok_or_else(|| anyhow!("Block {} not found", block))?;
+        Ok(tx_hashes.iter().all(|hash| mined.transactions.contains(hash)))
+    }
+
+    /// Dry-run signed transactions against `block` via `eth_callBundle`
+    pub async fn simulate_bundle(&self, signed_txs: Vec<Bytes>, block: u64) -> Result<BundleSimulation> {
+        let txs: Vec<String> = signed_txs.iter().map(|tx| format!("{:?}", tx)).collect();
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "eth_callBundle",
+            "params": [{
+                "txs": txs,
+                "blockNumber": format!("0x{:x}", block + 1),
+                "stateBlockNumber": format!("0x{:x}", block),
+            }],
+        })
+        .to_string();
+
+        let body_hash = format!("0x{}", hex::encode(ethers::utils::keccak256(body.as_bytes())));
+        let signature = self.signer.sign_message(body_hash).await?;
+        let header = format!("{:?}:0x{}", self.signer.address(), signature);
+
+        let response = reqwest::Client::new()
+            .post(FLASHBOTS_RELAY_URL)
+            .header("Content-Type", "application/json")
+            .header("X-Flashbots-Signature", header)
+            .body(body)
+            .send()
+            .await?
+            .json::<Value>()
+            .await?;
+
+        if let Some(e) = response.get("error") {
+            return Err(anyhow!("eth_callBundle error: {}", e));
+        }
+
+        BundleSimulation::from_call_bundle_result(&response["result"])
+    }
+
+    /// Broadcast `tx` through the public mempool. Fails in private-only mode.
     pub async fn send_tx(&self, tx: Eip1559TransactionRequest) -> Result<TxHash> {
+        ensure_public_send_allowed(self.private_only, "a transaction")?;
         let pending_tx = self.provider.send_transaction(tx, None).await?;
         let receipt = pending_tx.await?.ok_or_else(|| anyhow!("Tx dropped"))?;
         Ok(receipt.transaction_hash)
@@ -243,27 +580,79 @@ impl Bundler {
         loan_from: Address,
         max_priority_fee_per_gas: U256,
         max_fee_per_gas: U256,
-    ) -> Result<Eip1559TransactionRequest> {
-        let nhop = paths.len();
+    ) -> Result<TypedTransaction> {
+        let calldata = encode_order_calldata(&paths, amount_in, flashloan, loan_from);
 
-        let mut params = Vec::new();
-        params.extend(vec![
-            abi::Token::Uint(amount_in),
-            abi::Token::Uint(U256::from(flashloan as u64)),
-            abi::Token::Address(loan_from),
-        ]);
+        // Parse before reserving a nonce so a bad address can't leak one
+        let bot_address = Address::from_str(&self.env.bot_address)?;
+        let common = self._common_fields().await?;
+        Ok(Self::order_tx_request(
+            common,
+            bot_address,
+            calldata,
+            max_priority_fee_per_gas,
+            max_fee_per_gas,
+        ))
+    }
 
-        for i in 0..nhop {
-            params.extend(paths[i].make_params());
+    /// A `payCoinbase` call tipping the builder `coinbase_tip_bps` of
+    /// `expected_profit` (in wei), to bundle after the order. `None` when
+    /// there's nothing to tip.
+    pub async fn coinbase_tip_tx(
+        &self,
+        expected_profit: U256,
+        max_priority_fee_per_gas: U256,
+        max_fee_per_gas: U256,
+    ) -> Result<Option<TypedTransaction>> {
+        let tip = coinbase_tip(expected_profit, self.coinbase_tip_bps);
+        if tip.is_zero() {
+            return Ok(None);
         }
+        let calldata = self.bot.encode("payCoinbase", ())?;
 
-        let encoded = abi::encode(&params);
-        let calldata = Bytes::from(encoded);
-
+        let bot_address = Address::from_str(&self.env.bot_address)?;
         let common = self._common_fields().await?;
-        let to = NameOrAddress::Address(H160::from_str(&self.env.bot_address).unwrap());
-        Ok(Eip1559TransactionRequest {
-            to: Some(to),
+        Ok(Some(Self::coinbase_tip_request(
+            common,
+            bot_address,
+            calldata,
+            tip,
+            max_priority_fee_per_gas,
+            max_fee_per_gas,
+        )))
+    }
+
+    pub fn coinbase_tip_request(
+        common: (H160, U256, U64),
+        bot_address: Address,
+        calldata: Bytes,
+        tip: U256,
+        max_priority_fee_per_gas: U256,
+        max_fee_per_gas: U256,
+    ) -> TypedTransaction {
+        TypedTransaction::Eip1559(Eip1559TransactionRequest {
+            to: Some(NameOrAddress::Address(bot_address)),
+            from: Some(common.0),
+            data: Some(calldata),
+            value: Some(tip),
+            chain_id: Some(common.2),
+            max_priority_fee_per_gas: Some(max_priority_fee_per_gas),
+            max_fee_per_gas: Some(max_fee_per_gas),
+            gas: Some(U256::from(COINBASE_TIP_GAS)),
+            nonce: Some(common.1),
+            access_list: AccessList::default(),
+        })
+    }
+
+    pub fn order_tx_request(
+        common: (H160, U256, U64),
+        bot_address: Address,
+        calldata: Bytes,
+        max_priority_fee_per_gas: U256,
+        max_fee_per_gas: U256,
+    ) -> TypedTransaction {
+        TypedTransaction::Eip1559(Eip1559TransactionRequest {
+            to: Some(NameOrAddress::Address(bot_address)),
             from: Some(common.0),
             data: Some(calldata),
             value: Some(U256::zero()),
@@ -277,10 +666,66 @@ impl Bundler {
     }
 }
 
+/// Encode calldata for the V2ArbBot fallback:
+/// amountIn | useLoan | loanPool | (router, tokenIn, tokenOut, minAmountOut) * nhop
+pub fn encode_order_calldata(
+    paths: &[PathParam],
+    amount_in: U256,
+    flashloan: Flashloan,
+    loan_from: Address,
+) -> Bytes {
+    let mut params = vec![
+        abi::Token::Uint(amount_in),
+        abi::Token::Uint(U256::from(flashloan as u64)),
+        abi::Token::Address(loan_from),
+    ];
+
+    for path in paths {
+        params.extend(path.make_params());
+    }
+
+    Bytes::from(abi::encode(&params))
+}
+
+/// Decode calldata produced by `encode_order_calldata`
+pub fn decode_order_calldata(data: &[u8]) -> Result<(U256, U256, Address, Vec<PathParam>)> {
+    if data.len() < 0x60 || (data.len() - 0x60) % 0x80 != 0 {
+        return Err(anyhow!("Invalid order calldata length: {}", data.len()));
+    }
+    let nhop = (data.len() - 0x60) / 0x80;
+
+    let mut types = vec![
+        abi::ParamType::Uint(256),
+        abi::ParamType::Uint(256),
+        abi::ParamType::Address,
+    ];
+    for _ in 0..nhop {
+        types.extend(vec![abi::ParamType::Address; 3]);
+        types.push(abi::ParamType::Uint(256));
+    }
+
+    let tokens = abi::decode(&types, data)?;
+    let amount_in = tokens[0].clone().into_uint().unwrap_or_default();
+    let flashloan = tokens[1].clone().into_uint().unwrap_or_default();
+    let loan_from = tokens[2].clone().into_address().unwrap_or_default();
+
+    let paths = tokens[3..]
+        .chunks(4)
+        .map(|hop| PathParam {
+            router: hop[0].clone().into_address().unwrap_or_default(),
+            token_in: hop[1].clone().into_address().unwrap_or_default(),
+            token_out: hop[2].clone().into_address().unwrap_or_default(),
+            min_amount_out: hop[3].clone().into_uint().unwrap_or_default(),
+        })
+        .collect();
+
+    Ok((amount_in, flashloan, loan_from, paths))
+}
+
 #[cfg(test)]
 mod bundler_tests {
     use super::*;
-    use crate::constants::{GWEI, WEI};
+    use crate::constants::{GWEI, WEI, ZERO_ADDRESS};
 
     #[tokio::test]
     async fn bundler_test() {
@@ -325,6 +770,7 @@ mod bundler_tests {
             router: Address::from_str("0x1b02dA8Cb0d097eB8D57A175b88c7D8b47997506").unwrap(),
             token_in: Address::from_str("0x0d500B1d8E8eF31E21C99d1Db9A6444d3ADf1270").unwrap(),
             token_out: Address::from_str("0xc2132D05D31c914a87C6611C10748AEb04B58e8F").unwrap(),
+            min_amount_out: U256::zero(),
         }];
         let tx = bundler
             .order_tx(
@@ -340,4 +786,266 @@ mod bundler_tests {
         // let tx_hash = bundler.send_tx(tx).await?;
         // println!("{:?}", tx_hash);
     }
+
+    /// Nothing listens on this port, so any relay or node call would fail
+    fn offline_bundler() -> Bundler {
+        Bundler::from_env(Env {
+            https_url: "http://127.0.0.1:1".to_string(),
+            wss_url: "ws://127.0.0.1:1".to_string(),
+            chain_id: U64::from(1),
+            private_key: "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318".to_string(),
+            signing_key: "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318".to_string(),
+            bot_address: format!("{:?}", Address::random()),
+            kms_key_id: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn paper_bundle_is_not_sent() {
+        let bundler = offline_bundler().with_paper_trading(true);
+
+        let bundle = bundler.to_bundle(vec![Bytes::from(vec![0x02, 0x01])], U64::from(100), U64::from(101));
+        assert_eq!(bundler.send_bundle(bundle).await.unwrap(), TxHash::zero());
+    }
+
+    #[tokio::test]
+    async fn included_bundle_is_not_resubmitted() {
+        let resubmitted = std::cell::RefCell::new(Vec::new());
+
+        let status = track_inclusion(
+            U64::from(100),
+            3,
+            |block| async move { Ok(block == U64::from(100)) },
+            |block| {
+                resubmitted.borrow_mut().push(block);
+                async { Ok(()) }
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(status, InclusionStatus::Included { block: U64::from(100) });
+        assert!(resubmitted.borrow().is_empty());
+    }
+
+    #[tokio::test]
+    async fn missed_bundle_resubmits_then_gives_up() {
+        let resubmitted = std::cell::RefCell::new(Vec::new());
+
+        let status = track_inclusion(
+            U64::from(100),
+            2,
+            |_| async { Ok(false) },
+            |block| {
+                resubmitted.borrow_mut().push(block);
+                async { Ok(()) }
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            status,
+            InclusionStatus::NotIncluded { last_block: U64::from(102), resubmissions: 2 }
+        );
+        assert_eq!(*resubmitted.borrow(), vec![U64::from(101), U64::from(102)]);
+
+        // Landing in a re-submitted block counts
+        let status = track_inclusion(
+            U64::from(100),
+            2,
+            |block| async move { Ok(block == U64::from(101)) },
+            |_| async { Ok(()) },
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, InclusionStatus::Included { block: U64::from(101) });
+    }
+
+    #[test]
+    fn bundle_tx_hashes_of_raw_transactions() {
+        let raw = Bytes::from(vec![0x02, 0x01]);
+        let bundle = offline_bundler().to_bundle(vec![raw.clone()], U64::from(100), U64::from(101));
+
+        assert_eq!(bundle_tx_hashes(&bundle), vec![TxHash::from(ethers::utils::keccak256(&raw))]);
+    }
+
+    #[tokio::test]
+    async fn private_only_refuses_public_broadcast() {
+        let bundler = offline_bundler().with_private_only(true);
+
+        let err = bundler.send_tx(Eip1559TransactionRequest::new()).await.unwrap_err();
+        assert!(err.to_string().contains("Private-only"), "{}", err);
+    }
+
+    #[test]
+    fn parse_call_bundle_response() {
+        let response: Value = serde_json::from_str(
+            r#"{
+                "bundleGasPrice": "476190476193",
+                "bundleHash": "0x73b1e258c7a42fd0230b2fd05529c5d4b6fcb66c227783f8bece8aeacdd1db2e",
+                "coinbaseDiff": "20000000000126000",
+                "ethSentToCoinbase": "20000000000000000",
+                "gasFees": "126000",
+                "results": [
+                    {
+                        "coinbaseDiff": "10000000000063000",
+                        "ethSentToCoinbase": "10000000000000000",
+                        "fromAddress": "0x02A727155aeF8609c9f7F2179b2a1f560B39F5A0",
+                        "gasFees": "63000",
+                        "gasPrice": "476190476193",
+                        "gasUsed": 21000,
+                        "toAddress": "0x73625f59CAdc5009Cb458B751b3E7b6b48C06f2C",
+                        "txHash": "0x669b4704a7d993a946cdd6e2f95233f308ce0c4649d2e04944e8299efcaa098a",
+                        "value": "0x"
+                    },
+                    {
+                        "coinbaseDiff": "10000000000063000",
+                        "ethSentToCoinbase": "10000000000000000",
+                        "fromAddress": "0x02A727155aeF8609c9f7F2179b2a1f560B39F5A0",
+                        "gasFees": "63000",
+                        "gasPrice": "476190476193",
+                        "gasUsed": 21000,
+                        "toAddress": "0x73625f59CAdc5009Cb458B751b3E7b6b48C06f2C",
+                        "txHash": "0xa839ee83465657cac01adc1d50d96c1b586ed498120a84a64749c0034b4f19fa",
+                        "error": "execution reverted",
+                        "revert": "not owner"
+                    }
+                ],
+                "stateBlockNumber": 5221585,
+                "totalGasUsed": 42000
+            }"#,
+        )
+        .unwrap();
+
+        let sim = BundleSimulation::from_call_bundle_result(&response).unwrap();
+        assert_eq!(sim.state_block, 5221585);
+        assert_eq!(sim.total_gas_used, 42000);
+        assert_eq!(sim.gas_fees, U256::from(126000));
+        assert_eq!(sim.coinbase_payment, U256::from(20000000000000000u64));
+        assert_eq!(sim.transactions.len(), 2);
+
+        assert_eq!(sim.transactions[0].gas_used, 21000);
+        assert!(!sim.transactions[0].reverted);
+        assert!(sim.transactions[1].reverted);
+        assert_eq!(sim.transactions[1].revert_reason.as_deref(), Some("not owner"));
+        assert!(sim.has_revert());
+    }
+
+    #[test]
+    fn order_calldata_round_trip() {
+        let paths = vec![
+            PathParam {
+                router: Address::random(),
+                token_in: Address::random(),
+                token_out: Address::random(),
+                min_amount_out: U256::from(2_000_000_000u64),
+            },
+            PathParam {
+                router: Address::random(),
+                token_in: Address::random(),
+                token_out: Address::random(),
+                min_amount_out: U256::from(995) * *WEI,
+            },
+        ];
+        let loan_from = Address::random();
+        let amount_in = U256::from(1) * *WEI;
+
+        let calldata = encode_order_calldata(&paths, amount_in, Flashloan::Balancer, loan_from);
+        assert_eq!(calldata.len(), 0x60 + 0x80 * 2);
+
+        let (decoded_in, decoded_loan, decoded_from, decoded_paths) =
+            decode_order_calldata(&calldata).unwrap();
+        assert_eq!(decoded_in, amount_in);
+        assert_eq!(decoded_loan, U256::from(Flashloan::Balancer as u64));
+        assert_eq!(decoded_from, loan_from);
+        assert_eq!(decoded_paths.len(), 2);
+        for (a, b) in paths.iter().zip(decoded_paths.iter()) {
+            assert_eq!(a.router, b.router);
+            assert_eq!(a.token_in, b.token_in);
+            assert_eq!(a.token_out, b.token_out);
+            assert_eq!(a.min_amount_out, b.min_amount_out);
+        }
+    }
+
+    #[test]
+    fn order_tx_sets_eip1559_fields() {
+        let bot = Address::random();
+        let sender = Address::random();
+        let calldata = encode_order_calldata(&[], U256::from(100), Flashloan::NotUsed, *ZERO_ADDRESS);
+
+        let tx = Bundler::order_tx_request(
+            (sender, U256::from(7), U64::from(1)),
+            bot,
+            calldata.clone(),
+            U256::from(2) * *GWEI,
+            U256::from(30) * *GWEI,
+        );
+
+        match tx {
+            TypedTransaction::Eip1559(req) => {
+                assert_eq!(req.to, Some(NameOrAddress::Address(bot)));
+                assert_eq!(req.from, Some(sender));
+                assert_eq!(req.nonce, Some(U256::from(7)));
+                assert_eq!(req.data, Some(calldata));
+                assert_eq!(req.max_priority_fee_per_gas, Some(U256::from(2) * *GWEI));
+                assert_eq!(req.max_fee_per_gas, Some(U256::from(30) * *GWEI));
+            }
+            _ => panic!("expected an EIP-1559 transaction"),
+        }
+    }
+
+    #[test]
+    fn coinbase_tip_is_a_fraction_of_profit() {
+        let profit = U256::from(3) * *WEI;
+
+        assert_eq!(coinbase_tip(profit, 0), U256::zero());
+        assert_eq!(coinbase_tip(profit, 2_500), U256::from(75) * *WEI / 100);
+        assert_eq!(coinbase_tip(profit, 10_000), profit);
+        // Never more than the whole profit
+        assert_eq!(coinbase_tip(profit, 20_000), profit);
+        assert_eq!(coinbase_tip(U256::from(9_999), 1), U256::zero());
+    }
+
+    #[tokio::test]
+    async fn coinbase_tip_is_encoded_in_the_bundle() {
+        let bundler = offline_bundler().with_coinbase_tip_bps(1_000);
+        let bot = Address::from_str(&bundler.env.bot_address).unwrap();
+        let sender = bundler.sender.address();
+        let calldata = bundler.bot.encode("payCoinbase", ()).unwrap();
+        let tip = coinbase_tip(U256::from(2) * *WEI, bundler.coinbase_tip_bps);
+
+        let order = Bundler::order_tx_request(
+            (sender, U256::from(7), U64::from(1)),
+            bot,
+            encode_order_calldata(&[], U256::from(100), Flashloan::NotUsed, *ZERO_ADDRESS),
+            U256::from(2) * *GWEI,
+            U256::from(30) * *GWEI,
+        );
+        let tip_tx = Bundler::coinbase_tip_request(
+            (sender, U256::from(8), U64::from(1)),
+            bot,
+            calldata.clone(),
+            tip,
+            U256::from(2) * *GWEI,
+            U256::from(30) * *GWEI,
+        );
+        let signed = vec![
+            bundler.sign_tx(order).await.unwrap(),
+            bundler.sign_tx(tip_tx).await.unwrap(),
+        ];
+        let bundle = bundler.to_bundle(signed, U64::from(100), U64::from(101));
+
+        assert_eq!(bundle.transactions().len(), 2);
+        let raw = match &bundle.transactions()[1] {
+            BundleTransaction::Raw(raw) => raw.clone(),
+            _ => panic!("expected a raw transaction"),
+        };
+        let decoded: Transaction = ethers::utils::rlp::decode(&raw).unwrap();
+        assert_eq!(decoded.value, U256::from(2) * *WEI / 10);
+        assert_eq!(decoded.to, Some(bot));
+        assert_eq!(decoded.input, calldata);
+        assert_eq!(decoded.nonce, U256::from(8));
+        assert_eq!(decoded.from, sender);
+    }
 }
@@ -1,9 +1,59 @@
 use anyhow::{Result, anyhow};
-use ethers::types::{Address, U256};
-use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use ethers::{
+    providers::Middleware,
+    signers::{coins_bip39::English, LocalWallet, MnemonicBuilder, Signer},
+    types::{Address, U256},
+    utils::id,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr},
+    path::{Path, PathBuf},
+    time::Duration,
+};
 use validator::{Validate, ValidationError};
 
+use crate::constants::{get_blacklist_tokens, GWEI};
+use crate::flashbot::types::{
+    default_priority_fee_ladder, ExecutionConfig, PriorityFeeStep, RiskConfig, WithdrawalConfig,
+    DEFAULT_MAX_BUNDLES_PER_BLOCK, DEFAULT_MAX_OPPORTUNITY_AGE_BLOCKS,
+    DEFAULT_MIN_PROFIT_MULTIPLIER,
+};
+use crate::kms::{ConfiguredSigner, KmsConfig};
+use crate::pools::MEDIUM_LIQUIDITY_THRESHOLD;
+use crate::utils::LogFormat;
+
+/// Env var holding the path of the bot config file (JSON or TOML).
+pub const CONFIG_PATH_ENV: &str = "CONFIG_PATH";
+/// Env var holding the path of the runtime config file (JSON or TOML).
+pub const RUNTIME_CONFIG_PATH_ENV: &str = "RUNTIME_CONFIG_PATH";
+/// `BOT_RPC_URL` overrides `rpc_url`, and so on for every `BotConfig` field.
+pub const BOT_ENV_PREFIX: &str = "BOT_";
+pub const RUNTIME_ENV_PREFIX: &str = "RUNTIME_";
+/// Env var holding the password of `keystore_path`, unless `keystore_password_env` names another.
+pub const KEYSTORE_PASSWORD_ENV: &str = "KEYSTORE_PASSWORD";
+/// First account of the standard Ethereum derivation path.
+pub const DEFAULT_DERIVATION_PATH: &str = "m/44'/60'/0'/0/0";
+
+/// Risk and execution settings that `BotConfig` doesn't expose.
+pub const DEFAULT_MAX_PRICE_IMPACT_BPS: u16 = 100;
+pub const DEFAULT_BLOCK_DELAY: u8 = 1;
+pub const DEFAULT_MAX_EXECUTION_TIME: Duration = Duration::from_secs(12);
+
+/// Functions the deployed executor must expose.
+pub const EXECUTOR_FUNCTIONS: &[&str] = &[
+    "executeFlashloan(address,uint256,address[],bytes)",
+    "emergencyStop()",
+];
+/// Functions the deployed vault must expose.
+pub const VAULT_FUNCTIONS: &[&str] = &[
+    "deposit(address,uint256)",
+    "withdraw(address,uint256,address)",
+    "getBalance(address)",
+];
+
 #[derive(Debug, Serialize, Deserialize, Validate)]
 pub struct BotConfig {
     // Network configuration
@@ -12,9 +62,24 @@ pub struct BotConfig {
     #[validate(custom = "validate_chain_id")]
     pub chain_id: u64,
     
-    // Wallet configuration
+    // Wallet configuration: exactly one of a raw key, an encrypted JSON
+    // keystore or a BIP-39 mnemonic
+    #[serde(default)]
     #[validate(custom = "validate_private_key")]
-    pub private_key: String,
+    pub private_key: Option<String>,
+    #[serde(default)]
+    pub keystore_path: Option<String>,
+    #[serde(default = "default_keystore_password_env")]
+    pub keystore_password_env: String,
+    #[serde(default)]
+    pub mnemonic: Option<String>,
+    #[serde(default = "default_derivation_path")]
+    pub derivation_path: String,
+    /// More funded wallets to rotate submissions across, besides `private_key`.
+    /// Each must be an executor operator, or startup fails.
+    #[serde(default)]
+    #[validate(custom = "validate_private_keys")]
+    pub additional_private_keys: Vec<String>,
     
     // Contract addresses
     #[validate(custom = "validate_address")]
@@ -23,7 +88,7 @@ pub struct BotConfig {
     pub vault_address: Address,
     
     // Risk parameters
-    #[validate(range(min = 1, max = 1000000))]
+    #[validate(custom = "validate_max_position_size")]
     pub max_position_size: U256,
     #[validate(range(min = 1, max = 10))]
     pub max_leverage: u8,
@@ -39,8 +104,32 @@ pub struct BotConfig {
     pub priority_fee: u64,
     #[validate(range(min = 1, max = 5))]
     pub max_hops: u8,
+    #[serde(default = "default_min_profit_multiplier")]
+    #[validate(range(min = 1, max = 100))]
+    pub min_profit_multiplier: u32,
+    #[serde(default = "default_priority_fee_ladder")]
+    pub priority_fee_ladder: Vec<PriorityFeeStep>,
+    #[serde(default = "default_max_bundles_per_block")]
+    #[validate(range(min = 1, max = 20))]
+    pub max_bundles_per_block: u8,
+    /// Blocks after discovery past which an opportunity is dropped unexecuted.
+    #[serde(default = "default_max_opportunity_age_blocks")]
+    #[validate(range(min = 1, max = 100))]
+    pub max_opportunity_age_blocks: u64,
+    /// Basis points of expected profit paid straight to the block builder.
+    #[serde(default)]
+    #[validate(range(max = 10000))]
+    pub coinbase_tip_bps: u64,
+    /// Up to this many blocks are randomly added to an arb bundle's target.
+    #[serde(default)]
+    #[validate(range(max = 5))]
+    pub max_jitter_blocks: u64,
     
     // MEV protection
+    /// Fail instead of falling back to the public mempool. Admin calls to
+    /// the executor and vault, like the emergency stop, are exempt.
+    #[serde(default)]
+    pub private_only: bool,
     pub flashbots_enabled: bool,
     #[validate(custom = "validate_rpc_url")]
     pub flashbots_rpc: Option<String>,
@@ -54,25 +143,123 @@ pub struct BotConfig {
     pub min_spread_bps: u16,
     #[validate(range(min = 1, max = 100))]
     pub rebalance_threshold: u8,
+
+    // Profit withdrawal, off unless configured
+    #[serde(default)]
+    pub profit_withdrawal: Option<WithdrawalConfig>,
+
+    /// KMS key owning the executor and vault. When set it signs withdrawals
+    /// and emergency stops instead of the trading wallet.
+    #[serde(default)]
+    pub admin_kms: Option<KmsConfig>,
 }
 
 impl BotConfig {
+    /// Load from the file at `CONFIG_PATH`, overlay `BOT_*` env vars and validate.
+    pub fn load() -> Result<Self> {
+        let path = std::env::var(CONFIG_PATH_ENV).ok();
+        Self::load_from(path.as_deref().map(Path::new), std::env::vars())
+    }
+
+    pub fn load_from(
+        path: Option<&Path>,
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self> {
+        let config: Self = load_layered(path, BOT_ENV_PREFIX, env)?;
+        config.validate_all()?;
+        Ok(config)
+    }
+
     pub fn validate_all(&self) -> Result<()> {
         // Run validator derive validations
         if let Err(e) = self.validate() {
-            return Err(anyhow!("Configuration validation failed: {:?}", e));
+            return Err(anyhow!("Configuration validation failed: {}", e));
         }
         
         // Additional complex validations
-        self.validate_contract_compatibility()?;
+        let wallet_sources = [&self.private_key, &self.keystore_path, &self.mnemonic]
+            .iter()
+            .filter(|source| source.is_some())
+            .count();
+        if wallet_sources != 1 {
+            return Err(anyhow!(
+                "Set exactly one of private_key, keystore_path or mnemonic, found {}",
+                wallet_sources
+            ));
+        }
         self.validate_token_configurations()?;
-        self.validate_network_settings()?;
+        if let Some(withdrawal) = &self.profit_withdrawal {
+            if withdrawal.treasury.is_zero() {
+                return Err(anyhow!("Profit withdrawal treasury can't be the zero address"));
+            }
+            if withdrawal.interval_secs == 0 {
+                return Err(anyhow!("Profit withdrawal interval must be positive"));
+            }
+        }
+        validate_priority_fee_ladder(&self.priority_fee_ladder)?;
         
         Ok(())
     }
 
-    fn validate_contract_compatibility(&self) -> Result<()> {
-        // Check if contracts are deployed and compatible
+    /// The trading wallet, from whichever of the raw key, keystore or mnemonic is set.
+    /// The keystore password is read from the `keystore_password_env` env var.
+    pub fn wallet(&self) -> Result<LocalWallet> {
+        if let Some(path) = &self.keystore_path {
+            let password = std::env::var(&self.keystore_password_env).map_err(|_| {
+                anyhow!("Keystore password env var {} is not set", self.keystore_password_env)
+            })?;
+            return wallet_from_keystore(path, &password);
+        }
+        if let Some(phrase) = &self.mnemonic {
+            return wallet_from_mnemonic(phrase, &self.derivation_path);
+        }
+        match &self.private_key {
+            Some(key) => key
+                .parse::<LocalWallet>()
+                .map_err(|e| anyhow!("Invalid private key: {}", e)),
+            None => Err(anyhow!("No private_key, keystore_path or mnemonic configured")),
+        }
+    }
+
+    /// Signer for executor and vault admin calls: the `admin_kms` key if
+    /// configured, otherwise the trading wallet.
+    pub async fn admin_signer(&self) -> Result<ConfiguredSigner> {
+        let signer = match &self.admin_kms {
+            Some(kms) => ConfiguredSigner::Kms(kms.signer(self.chain_id).await?),
+            None => ConfiguredSigner::Local(self.wallet()?),
+        };
+        Ok(signer.with_chain_id(self.chain_id))
+    }
+
+    /// Checks that need the chain: run once a provider for `rpc_url` is available.
+    pub async fn validate_onchain<M: Middleware>(&self, provider: &M) -> Result<()> {
+        self.validate_network_settings(provider).await?;
+        self.validate_contract_compatibility(provider).await?;
+        Ok(())
+    }
+
+    /// Check that the executor and vault are deployed and expose the functions the bot calls.
+    pub async fn validate_contract_compatibility<M: Middleware>(&self, provider: &M) -> Result<()> {
+        let contracts = [
+            ("executor", self.executor_address, EXECUTOR_FUNCTIONS),
+            ("vault", self.vault_address, VAULT_FUNCTIONS),
+        ];
+
+        for (name, address, functions) in contracts {
+            let code = provider
+                .get_code(address, None)
+                .await
+                .map_err(|e| anyhow!("Failed to fetch {} code at {:?}: {}", name, address, e))?;
+            if code.is_empty() {
+                return Err(anyhow!("No contract deployed at {} address {:?}", name, address));
+            }
+
+            for signature in functions {
+                if !bytecode_has_selector(&code, id(signature)) {
+                    return Err(anyhow!("{} at {:?} does not expose {}", name, address, signature));
+                }
+            }
+        }
         Ok(())
     }
 
@@ -81,12 +268,97 @@ impl BotConfig {
         Ok(())
     }
 
-    fn validate_network_settings(&self) -> Result<()> {
-        // Validate network-specific configurations
+    /// Check that the RPC serves the configured chain.
+    pub async fn validate_network_settings<M: Middleware>(&self, provider: &M) -> Result<()> {
+        let chain_id = provider
+            .get_chainid()
+            .await
+            .map_err(|e| anyhow!("Failed to fetch chain id from {}: {}", self.rpc_url, e))?;
+        if chain_id != U256::from(self.chain_id) {
+            return Err(anyhow!(
+                "RPC chain id {} does not match configured chain_id {}",
+                chain_id,
+                self.chain_id
+            ));
+        }
         Ok(())
     }
 }
 
+impl From<&BotConfig> for RiskConfig {
+    fn from(config: &BotConfig) -> Self {
+        Self {
+            max_position_size: config.max_position_size,
+            max_leverage: config.max_leverage,
+            stop_loss_pct: config.stop_loss_pct,
+            max_drawdown: config.max_drawdown,
+            min_pool_liquidity: MEDIUM_LIQUIDITY_THRESHOLD,
+            max_price_impact_bps: DEFAULT_MAX_PRICE_IMPACT_BPS,
+            blacklisted_tokens: get_blacklist_tokens(),
+            min_profit_threshold: U256::zero(),
+        }
+    }
+}
+
+fn default_min_profit_multiplier() -> u32 {
+    DEFAULT_MIN_PROFIT_MULTIPLIER
+}
+
+fn default_max_bundles_per_block() -> u8 {
+    DEFAULT_MAX_BUNDLES_PER_BLOCK
+}
+
+fn default_max_opportunity_age_blocks() -> u64 {
+    DEFAULT_MAX_OPPORTUNITY_AGE_BLOCKS
+}
+
+/// Steps must be ordered by strictly increasing profit ratio, and a more
+/// profitable step may never bid a lower multiplier.
+pub fn validate_priority_fee_ladder(ladder: &[PriorityFeeStep]) -> Result<()> {
+    if ladder.is_empty() {
+        return Err(anyhow!("priority_fee_ladder must have at least one step"));
+    }
+    if ladder.iter().any(|step| step.profit_ratio == 0 || step.multiplier == 0) {
+        return Err(anyhow!("priority_fee_ladder ratios and multipliers must be positive"));
+    }
+    for pair in ladder.windows(2) {
+        if pair[1].profit_ratio <= pair[0].profit_ratio || pair[1].multiplier < pair[0].multiplier {
+            return Err(anyhow!(
+                "priority_fee_ladder is not monotonic: {:?} follows {:?}",
+                pair[1],
+                pair[0]
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// `max_gas_price` and `priority_fee` are configured in gwei and converted to wei.
+impl From<&BotConfig> for ExecutionConfig {
+    fn from(config: &BotConfig) -> Self {
+        Self {
+            max_gas_price: U256::from(config.max_gas_price) * *GWEI,
+            priority_fee: U256::from(config.priority_fee) * *GWEI,
+            max_hops: config.max_hops,
+            block_delay: DEFAULT_BLOCK_DELAY,
+            max_execution_time: DEFAULT_MAX_EXECUTION_TIME,
+            min_profit_threshold: U256::zero(),
+            min_profit_multiplier: config.min_profit_multiplier,
+            priority_fee_ladder: config.priority_fee_ladder.clone(),
+            max_bundles_per_block: config.max_bundles_per_block as usize,
+            max_opportunity_age_blocks: config.max_opportunity_age_blocks,
+            private_only: config.private_only,
+            coinbase_tip_bps: config.coinbase_tip_bps,
+            max_jitter_blocks: config.max_jitter_blocks,
+        }
+    }
+}
+
+/// Solidity dispatchers push each external function selector with PUSH4 (0x63).
+pub fn bytecode_has_selector(code: &[u8], selector: [u8; 4]) -> bool {
+    code.windows(5).any(|window| window[0] == 0x63 && window[1..] == selector)
+}
+
 // Custom validators
 fn validate_rpc_url(url: &str) -> Result<(), ValidationError> {
     if !url.starts_with("http://") && !url.starts_with("https://") && !url.starts_with("ws://") {
@@ -115,6 +387,30 @@ fn validate_chain_id(chain_id: &u64) -> Result<(), ValidationError> {
     }
 }
 
+fn default_keystore_password_env() -> String {
+    KEYSTORE_PASSWORD_ENV.to_string()
+}
+
+fn default_derivation_path() -> String {
+    DEFAULT_DERIVATION_PATH.to_string()
+}
+
+/// Decrypt an encrypted JSON (V3) keystore.
+pub fn wallet_from_keystore(path: impl AsRef<Path>, password: &str) -> Result<LocalWallet> {
+    let path = path.as_ref();
+    LocalWallet::decrypt_keystore(path, password)
+        .map_err(|e| anyhow!("Failed to decrypt keystore {}: {}", path.display(), e))
+}
+
+/// Derive the account at `derivation_path` from a BIP-39 mnemonic.
+pub fn wallet_from_mnemonic(phrase: &str, derivation_path: &str) -> Result<LocalWallet> {
+    MnemonicBuilder::<English>::default()
+        .phrase(phrase)
+        .derivation_path(derivation_path)?
+        .build()
+        .map_err(|e| anyhow!("Failed to derive wallet from mnemonic: {}", e))
+}
+
 fn validate_private_key(key: &str) -> Result<(), ValidationError> {
     if !key.starts_with("0x") || key.len() != 66 {
         return Err(ValidationError::new("invalid_private_key"));
@@ -122,6 +418,30 @@ fn validate_private_key(key: &str) -> Result<(), ValidationError> {
     Ok(())
 }
 
+fn validate_private_keys(keys: &[String]) -> Result<(), ValidationError> {
+    keys.iter().try_for_each(|key| validate_private_key(key))
+}
+
+/// `validator`'s `range` only understands primitive numbers, so U256 bounds are
+/// given as decimal strings and checked here.
+pub fn validate_u256_range(value: &U256, min: &str, max: &str) -> Result<(), ValidationError> {
+    let min = U256::from_dec_str(min).map_err(|_| ValidationError::new("invalid_range_bound"))?;
+    let max = U256::from_dec_str(max).map_err(|_| ValidationError::new("invalid_range_bound"))?;
+
+    if *value < min || *value > max {
+        let mut error = ValidationError::new("range");
+        error.add_param("min".into(), &min.to_string());
+        error.add_param("max".into(), &max.to_string());
+        error.add_param("value".into(), &value.to_string());
+        return Err(error);
+    }
+    Ok(())
+}
+
+fn validate_max_position_size(value: &U256) -> Result<(), ValidationError> {
+    validate_u256_range(value, "1", "1000000")
+}
+
 fn validate_address(address: &Address) -> Result<(), ValidationError> {
     if address == &Address::zero() {
         return Err(ValidationError::new("zero_address"));
@@ -130,24 +450,558 @@ fn validate_address(address: &Address) -> Result<(), ValidationError> {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct RuntimeConfig {
     pub max_memory_mb: u64,
+    pub max_block_staleness_secs: u64,
+    pub min_connected_nodes: u32,
     pub health_check_interval: Duration,
     pub metrics_port: u16,
+    /// Address the metrics server listens on; loopback unless scraped remotely.
+    pub metrics_bind_addr: IpAddr,
+    /// When set, `/metrics` requires `Authorization: Bearer <token>`.
+    pub metrics_auth_token: Option<String>,
     pub log_level: String,
+    pub log_format: LogFormat,
     pub retry_attempts: u32,
     pub backoff_base_ms: u64,
+    /// How long to wait for in-flight executions after a shutdown signal.
+    pub shutdown_grace_period: Duration,
+    /// Opportunities on disjoint pools executed at the same time.
+    pub max_concurrent_executions: usize,
+    /// Run the whole pipeline but log trades instead of broadcasting them.
+    pub paper_trading: bool,
+    /// Keep only the deepest pool per token pair across V2 forks. Off for
+    /// cross-fork arbitrage, which needs every pool of a pair.
+    pub dedup_pools_by_pair: bool,
+    /// Pools worth less than this many USD are dropped when loading; 0 keeps all.
+    pub min_pool_liquidity_usd: f64,
+    /// USD prices pool liquidity is valued at, besides the chain's stablecoin
+    /// at $1. Pools with no priced token count as worthless.
+    pub pool_prices_usd: HashMap<Address, f64>,
+    /// CSV file every executed trade is appended to; no journal when unset.
+    pub trade_journal_path: Option<PathBuf>,
 }
 
 impl Default for RuntimeConfig {
     fn default() -> Self {
         Self {
             max_memory_mb: 1024,
+            max_block_staleness_secs: 120,
+            min_connected_nodes: 1,
             health_check_interval: Duration::from_secs(60),
             metrics_port: 9090,
+            metrics_bind_addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            metrics_auth_token: None,
             log_level: "info".to_string(),
+            log_format: LogFormat::Plain,
             retry_attempts: 3,
             backoff_base_ms: 1000,
+            shutdown_grace_period: Duration::from_secs(30),
+            max_concurrent_executions: 4,
+            paper_trading: false,
+            dedup_pools_by_pair: false,
+            min_pool_liquidity_usd: 0.0,
+            pool_prices_usd: HashMap::new(),
+            trade_journal_path: None,
+        }
+    }
+}
+
+impl RuntimeConfig {
+    /// Load from the file at `RUNTIME_CONFIG_PATH` and `RUNTIME_*` env vars, falling back
+    /// to the defaults for anything left unset.
+    pub fn load() -> Result<Self> {
+        let path = std::env::var(RUNTIME_CONFIG_PATH_ENV).ok();
+        Self::load_from(path.as_deref().map(Path::new), std::env::vars())
+    }
+
+    pub fn load_from(
+        path: Option<&Path>,
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self> {
+        load_layered(path, RUNTIME_ENV_PREFIX, env)
+    }
+}
+
+/// Deserialize `T` from an optional JSON/TOML file, with `{prefix}{FIELD}` env vars taking
+/// precedence over file values. Env values start out as strings, so keys and addresses
+/// are never coerced; one is parsed as JSON only when its field rejects a string.
+fn load_layered<T: DeserializeOwned>(
+    path: Option<&Path>,
+    prefix: &str,
+    env: impl IntoIterator<Item = (String, String)>,
+) -> Result<T> {
+    let mut fields = match path {
+        Some(path) => read_config_file(path)?,
+        None => Map::new(),
+    };
+
+    let mut overrides = Vec::new();
+    for (key, value) in env {
+        if let Some(field) = key.strip_prefix(prefix) {
+            let field = field.to_lowercase();
+            fields.insert(field.clone(), Value::String(value.clone()));
+            overrides.push((field, value));
+        }
+    }
+
+    loop {
+        let error = match serde_json::from_value(Value::Object(fields.clone())) {
+            Ok(config) => return Ok(config),
+            Err(e) => e,
+        };
+
+        // serde reports the rejected value, e.g. `invalid type: string "5", expected u8`
+        let mut retyped = false;
+        for (field, value) in &overrides {
+            let rejected = format!("invalid type: string {:?}", value);
+            if fields.get(field) == Some(&Value::String(value.clone()))
+                && error.to_string().contains(&rejected)
+            {
+                if let Ok(parsed) = serde_json::from_str(value) {
+                    fields.insert(field.clone(), parsed);
+                    retyped = true;
+                }
+            }
+        }
+        if !retyped {
+            return Err(anyhow!("Invalid configuration: {}", error));
+        }
+    }
+}
+
+fn read_config_file(path: &Path) -> Result<Map<String, Value>> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read config {:?}: {}", path, e))?;
+
+    let value: Value = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => toml::from_str(&contents)
+            .map_err(|e| anyhow!("Failed to parse config {:?}: {}", path, e))?,
+        _ => serde_json::from_str(&contents)
+            .map_err(|e| anyhow!("Failed to parse config {:?}: {}", path, e))?,
+    };
+
+    match value {
+        Value::Object(fields) => Ok(fields),
+        _ => Err(anyhow!("Config {:?} must be a table of settings", path)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::{
+        providers::{MockProvider, Provider},
+        signers::Signer,
+        types::Bytes,
+    };
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    fn sample_config() -> Value {
+        json!({
+            "rpc_url": "https://eth.llamarpc.com",
+            "chain_id": 1,
+            "private_key": format!("0x{}", "11".repeat(32)),
+            "executor_address": format!("{:?}", Address::from_low_u64_be(1)),
+            "vault_address": format!("{:?}", Address::from_low_u64_be(2)),
+            "max_position_size": "0x3e8",
+            "max_leverage": 2,
+            "stop_loss_pct": 5,
+            "max_drawdown": 10,
+            "max_gas_price": 100,
+            "priority_fee": 2,
+            "max_hops": 3,
+            "flashbots_enabled": true,
+            "flashbots_rpc": "https://relay.flashbots.net",
+            "eden_enabled": false,
+            "eden_rpc": null,
+            "market_making_enabled": false,
+            "min_spread_bps": 10,
+            "rebalance_threshold": 5
+        })
+    }
+
+    fn write_config(value: &Value) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("bot-config-{}.json", Address::random()));
+        std::fs::write(&path, value.to_string()).unwrap();
+        path
+    }
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_keystore_decrypts_to_the_encrypted_key() {
+        let dir = std::env::temp_dir().join(format!("keystore-{}", Address::random()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (wallet, name) =
+            LocalWallet::new_keystore(&dir, &mut rand::thread_rng(), "hunter2", None).unwrap();
+
+        let decrypted = wallet_from_keystore(dir.join(&name), "hunter2").unwrap();
+        let wrong_password = wallet_from_keystore(dir.join(&name), "hunter3");
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(decrypted.address(), wallet.address());
+        assert!(wrong_password.is_err());
+    }
+
+    #[test]
+    fn test_mnemonic_derives_known_address() {
+        let phrase = "test test test test test test test test test test test junk";
+
+        let first = wallet_from_mnemonic(phrase, DEFAULT_DERIVATION_PATH).unwrap();
+        let second = wallet_from_mnemonic(phrase, "m/44'/60'/0'/0/1").unwrap();
+
+        assert_eq!(
+            first.address(),
+            "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266".parse::<Address>().unwrap()
+        );
+        assert_eq!(
+            second.address(),
+            "0x70997970C51812dc3A010C7d01b50e0d17dc79C8".parse::<Address>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_config_wallet_from_mnemonic() {
+        let mut config = sample_config();
+        config["private_key"] = Value::Null;
+        config["mnemonic"] = json!("test test test test test test test test test test test junk");
+        let path = write_config(&config);
+        let config = BotConfig::load_from(Some(&path), env(&[])).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(
+            config.wallet().unwrap().address(),
+            "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266".parse::<Address>().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_admin_signer_defaults_to_trading_wallet() {
+        let config = sample_bot_config();
+        assert!(config.admin_kms.is_none());
+
+        let signer = config.admin_signer().await.unwrap();
+        assert!(matches!(signer, ConfiguredSigner::Local(_)));
+        assert_eq!(signer.address(), config.wallet().unwrap().address());
+        assert_eq!(signer.chain_id(), config.chain_id);
+    }
+
+    #[test]
+    fn test_two_wallet_sources_are_rejected() {
+        let mut config = sample_config();
+        config["mnemonic"] = json!("test test test test test test test test test test test junk");
+        let path = write_config(&config);
+        let err = BotConfig::load_from(Some(&path), env(&[])).unwrap_err();
+        std::fs::remove_file(&path).ok();
+
+        assert!(err.to_string().contains("exactly one"), "{}", err);
+    }
+
+    #[test]
+    fn test_load_from_file() {
+        let path = write_config(&sample_config());
+        let config = BotConfig::load_from(Some(&path), env(&[])).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(config.chain_id, 1);
+        assert_eq!(config.max_hops, 3);
+        assert_eq!(config.max_position_size, U256::from(1000));
+    }
+
+    #[test]
+    fn test_env_overrides_file_value() {
+        let path = write_config(&sample_config());
+        let config = BotConfig::load_from(
+            Some(&path),
+            env(&[
+                ("BOT_RPC_URL", "https://arb1.arbitrum.io/rpc"),
+                ("BOT_CHAIN_ID", "42161"),
+                ("UNRELATED_CHAIN_ID", "5"),
+            ]),
+        )
+        .unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(config.rpc_url, "https://arb1.arbitrum.io/rpc");
+        assert_eq!(config.chain_id, 42161);
+    }
+
+    #[test]
+    fn test_numeric_looking_env_strings_stay_strings() {
+        let path = write_config(&sample_config());
+        let config = BotConfig::load_from(
+            Some(&path),
+            env(&[
+                ("BOT_KEYSTORE_PASSWORD_ENV", "1234"),
+                ("BOT_MAX_HOPS", "4"),
+                ("BOT_EDEN_ENABLED", "true"),
+            ]),
+        )
+        .unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(config.keystore_password_env, "1234");
+        assert_eq!(config.max_hops, 4);
+        assert!(config.eden_enabled);
+    }
+
+    #[test]
+    fn test_invalid_chain_id_is_reported() {
+        let path = write_config(&sample_config());
+        let err = BotConfig::load_from(Some(&path), env(&[("BOT_CHAIN_ID", "56")])).unwrap_err();
+        std::fs::remove_file(&path).ok();
+
+        let message = err.to_string();
+        assert!(message.contains("chain_id"), "{}", message);
+        assert!(message.contains("unsupported_chain"), "{}", message);
+    }
+
+    fn sample_bot_config() -> BotConfig {
+        serde_json::from_value(sample_config()).unwrap()
+    }
+
+    /// Minimal dispatcher-like bytecode exposing the given functions.
+    fn bytecode_with(functions: &[&str]) -> Bytes {
+        let mut code = vec![0x60, 0x80, 0x60, 0x40, 0x52];
+        for signature in functions {
+            code.push(0x63);
+            code.extend_from_slice(&id(signature));
+            code.extend_from_slice(&[0x14, 0x61, 0x00, 0x00, 0x57]);
         }
+        code.into()
+    }
+
+    #[tokio::test]
+    async fn test_deployed_contracts_pass() {
+        let (provider, mock) = Provider::mocked();
+        // Mock responses are served last-in first-out: executor first, then vault
+        mock.push(bytecode_with(VAULT_FUNCTIONS)).unwrap();
+        mock.push(bytecode_with(EXECUTOR_FUNCTIONS)).unwrap();
+
+        assert!(sample_bot_config()
+            .validate_contract_compatibility(&provider)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_zero_code_executor_fails() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(Bytes::default()).unwrap();
+
+        let err = sample_bot_config()
+            .validate_contract_compatibility(&provider)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("No contract deployed at executor"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_executor_missing_function_fails() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(bytecode_with(&["emergencyStop()"])).unwrap();
+
+        let err = sample_bot_config()
+            .validate_contract_compatibility(&provider)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("executeFlashloan"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_network_chain_id_mismatch_fails() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(U256::from(137)).unwrap();
+        assert!(sample_bot_config().validate_network_settings(&provider).await.is_err());
+
+        mock.push(U256::from(1)).unwrap();
+        assert!(sample_bot_config().validate_network_settings(&provider).await.is_ok());
+    }
+
+    #[test]
+    fn test_risk_config_mapping() {
+        let config = sample_bot_config();
+        let risk = RiskConfig::from(&config);
+
+        assert_eq!(risk.max_position_size, U256::from(1000));
+        assert_eq!(risk.max_leverage, 2);
+        assert_eq!(risk.stop_loss_pct, 5);
+        assert_eq!(risk.max_drawdown, 10);
+        assert_eq!(risk.blacklisted_tokens, get_blacklist_tokens());
+    }
+
+    #[test]
+    fn test_execution_config_mapping() {
+        let config = sample_bot_config();
+        let execution = ExecutionConfig::from(&config);
+
+        assert_eq!(execution.max_gas_price, U256::from(100) * *GWEI);
+        assert_eq!(execution.priority_fee, U256::from(2) * *GWEI);
+        assert_eq!(execution.max_hops, 3);
+        assert_eq!(execution.block_delay, DEFAULT_BLOCK_DELAY);
+        assert_eq!(execution.max_opportunity_age_blocks, DEFAULT_MAX_OPPORTUNITY_AGE_BLOCKS);
+        assert_eq!(execution.max_jitter_blocks, 0);
+
+        let mut raw = sample_config();
+        raw["max_opportunity_age_blocks"] = json!(3);
+        raw["max_jitter_blocks"] = json!(2);
+        let config: BotConfig = serde_json::from_value(raw).unwrap();
+        assert_eq!(ExecutionConfig::from(&config).max_opportunity_age_blocks, 3);
+        assert_eq!(ExecutionConfig::from(&config).max_jitter_blocks, 2);
+    }
+
+    #[test]
+    fn test_u256_range_bounds() {
+        assert!(validate_u256_range(&U256::from(1), "1", "1000000").is_ok());
+        assert!(validate_u256_range(&U256::from(1_000_000), "1", "1000000").is_ok());
+        assert!(validate_u256_range(&U256::zero(), "1", "1000000").is_err());
+        assert!(validate_u256_range(&U256::from(1_000_001), "1", "1000000").is_err());
+        assert!(validate_u256_range(&U256::from(1), "one", "1000000").is_err());
+    }
+
+    #[test]
+    fn test_over_max_position_size_is_rejected() {
+        let mut value = sample_config();
+        value["max_position_size"] = json!(format!("{:#x}", U256::from(1_000_001)));
+        let path = write_config(&value);
+        let err = BotConfig::load_from(Some(&path), env(&[])).unwrap_err();
+        std::fs::remove_file(&path).ok();
+
+        assert!(err.to_string().contains("max_position_size"), "{}", err);
+    }
+
+    #[test]
+    fn test_in_range_position_size_passes() {
+        let mut value = sample_config();
+        value["max_position_size"] = json!(format!("{:#x}", U256::from(500_000)));
+        let path = write_config(&value);
+        let config = BotConfig::load_from(Some(&path), env(&[])).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(config.max_position_size, U256::from(500_000));
+    }
+
+    #[test]
+    fn test_runtime_config_defaults_and_overrides() {
+        let config = RuntimeConfig::load_from(None, env(&[("RUNTIME_METRICS_PORT", "9100")])).unwrap();
+
+        assert_eq!(config.metrics_port, 9100);
+        assert_eq!(config.retry_attempts, RuntimeConfig::default().retry_attempts);
+        assert_eq!(config.metrics_bind_addr, IpAddr::V4(Ipv4Addr::LOCALHOST));
+        assert_eq!(config.metrics_auth_token, None);
+        assert!(!config.paper_trading);
+
+        let config = RuntimeConfig::load_from(None, env(&[("RUNTIME_PAPER_TRADING", "true")])).unwrap();
+        assert!(config.paper_trading);
+        assert!(!config.dedup_pools_by_pair);
+    }
+
+    #[test]
+    fn test_runtime_config_pool_liquidity_filter() {
+        let weth = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2";
+        let prices = format!(r#"{{"{}": 2000.0}}"#, weth);
+        let config = RuntimeConfig::load_from(
+            None,
+            env(&[
+                ("RUNTIME_MIN_POOL_LIQUIDITY_USD", "50000"),
+                ("RUNTIME_POOL_PRICES_USD", prices.as_str()),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(config.min_pool_liquidity_usd, 50_000.0);
+        assert_eq!(config.pool_prices_usd.get(&weth.parse::<Address>().unwrap()), Some(&2000.0));
+    }
+
+    #[test]
+    fn test_runtime_config_metrics_bind_and_token() {
+        let config = RuntimeConfig::load_from(
+            None,
+            env(&[
+                ("RUNTIME_METRICS_BIND_ADDR", "0.0.0.0"),
+                ("RUNTIME_METRICS_AUTH_TOKEN", "s3cret"),
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(config.metrics_bind_addr, IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+        assert_eq!(config.metrics_auth_token.as_deref(), Some("s3cret"));
+    }
+
+    #[test]
+    fn test_runtime_config_log_format() {
+        let config = RuntimeConfig::load_from(None, env(&[("RUNTIME_LOG_FORMAT", "json")])).unwrap();
+        assert_eq!(config.log_format, LogFormat::Json);
+        assert_eq!(RuntimeConfig::default().log_format, LogFormat::Plain);
+    }
+
+    #[test]
+    fn test_min_profit_threshold_is_configurable() {
+        let mut value = sample_config();
+        value["min_profit_multiplier"] = json!(3);
+        let path = write_config(&value);
+        let config = BotConfig::load_from(Some(&path), env(&[])).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        let execution = ExecutionConfig::from(&config);
+        let gas_cost = U256::from(1_000);
+        assert!(execution.should_submit(U256::from(3_001), gas_cost));
+        assert!(!execution.should_submit(U256::from(3_000), gas_cost));
+    }
+
+    #[test]
+    fn test_priority_fee_ladder() {
+        let mut value = sample_config();
+        value["priority_fee_ladder"] = json!([
+            { "profit_ratio": 1, "multiplier": 2 },
+            { "profit_ratio": 2, "multiplier": 4 },
+            { "profit_ratio": 5, "multiplier": 6 }
+        ]);
+        let path = write_config(&value);
+        let config = BotConfig::load_from(Some(&path), env(&[])).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        let execution = ExecutionConfig::from(&config);
+        let threshold = U256::from(100);
+        assert_eq!(execution.priority_multiplier(U256::from(101), threshold), U256::from(2));
+        assert_eq!(execution.priority_multiplier(U256::from(200), threshold), U256::from(2));
+        assert_eq!(execution.priority_multiplier(U256::from(201), threshold), U256::from(4));
+        assert_eq!(execution.priority_multiplier(U256::from(501), threshold), U256::from(6));
+    }
+
+    #[test]
+    fn test_default_ladder_matches_previous_behaviour() {
+        let execution = ExecutionConfig::from(&sample_bot_config());
+        let threshold = execution.min_profit_for(U256::from(1_000));
+
+        assert_eq!(threshold, U256::from(2_000));
+        assert_eq!(execution.priority_multiplier(U256::from(2_001), threshold), U256::from(2));
+        assert_eq!(execution.priority_multiplier(U256::from(6_001), threshold), U256::from(3));
+    }
+
+    #[test]
+    fn test_non_monotonic_ladder_is_rejected() {
+        let step = |profit_ratio, multiplier| PriorityFeeStep { profit_ratio, multiplier };
+
+        assert!(validate_priority_fee_ladder(&default_priority_fee_ladder()).is_ok());
+        assert!(validate_priority_fee_ladder(&[]).is_err());
+        assert!(validate_priority_fee_ladder(&[step(1, 3), step(3, 2)]).is_err());
+        assert!(validate_priority_fee_ladder(&[step(3, 2), step(1, 3)]).is_err());
+        assert!(validate_priority_fee_ladder(&[step(1, 0)]).is_err());
+
+        let mut value = sample_config();
+        value["priority_fee_ladder"] = json!([
+            { "profit_ratio": 1, "multiplier": 3 },
+            { "profit_ratio": 2, "multiplier": 2 }
+        ]);
+        let path = write_config(&value);
+        let err = BotConfig::load_from(Some(&path), env(&[])).unwrap_err();
+        std::fs::remove_file(&path).ok();
+
+        assert!(err.to_string().contains("not monotonic"), "{}", err);
     }
 }
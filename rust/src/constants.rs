@@ -1,8 +1,15 @@
+use anyhow::{anyhow, Result};
 use ethers::{
+    contract::MULTICALL_ADDRESS,
     prelude::Lazy,
     types::{Address, H160, U256, U64},
 };
-use std::str::FromStr;
+use log::{info, warn};
+use std::{collections::HashSet, fs, path::Path, str::FromStr};
+
+use crate::security::{SecurityManager, TokenValidation};
+
+pub mod chain;
 
 pub static WEI: Lazy<U256> = Lazy::new(|| U256::from(10).pow(U256::from(18)));
 pub static GWEI: Lazy<U256> = Lazy::new(|| U256::from(10).pow(U256::from(9)));
@@ -10,6 +17,16 @@ pub static GWEI: Lazy<U256> = Lazy::new(|| U256::from(10).pow(U256::from(9)));
 pub static ZERO_ADDRESS: Lazy<Address> =
     Lazy::new(|| Address::from_str("0x0000000000000000000000000000000000000000").unwrap());
 
+/// Multicall3 deployment on `chain_id`. Every supported chain has the
+/// canonical deterministic deployment; chains that don't belong here with
+/// their own address.
+pub fn multicall3_address(chain_id: u64) -> Option<Address> {
+    match chain_id {
+        1 | 10 | 137 | 8453 | 42161 => Some(MULTICALL_ADDRESS),
+        _ => None,
+    }
+}
+
 pub fn get_env(key: &str) -> String {
     std::env::var(key).unwrap()
 }
@@ -22,6 +39,8 @@ pub struct Env {
     pub private_key: String,
     pub signing_key: String,
     pub bot_address: String,
+    /// AWS KMS key to send from instead of `private_key`, if set.
+    pub kms_key_id: Option<String>,
 }
 
 impl Env {
@@ -33,6 +52,7 @@ impl Env {
             private_key: get_env("PRIVATE_KEY"),
             signing_key: get_env("SIGNING_KEY"),
             bot_address: get_env("BOT_ADDRESS"),
+            kms_key_id: std::env::var("KMS_KEY_ID").ok(),
         }
     }
 }
@@ -44,6 +64,98 @@ pub fn get_blacklist_tokens() -> Vec<H160> {
         .collect()
 }
 
+/// Where user-supplied and discovered blacklist entries are kept (JSON array of addresses).
+pub const BLACKLIST_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/.blacklist.json");
+
+/// The static blacklist merged with the addresses stored at `path`.
+/// A missing or unreadable file only yields the static list.
+pub fn load_blacklist(path: impl AsRef<Path>) -> HashSet<Address> {
+    let path = path.as_ref();
+    let mut blacklist: HashSet<Address> = get_blacklist_tokens().into_iter().collect();
+    if !path.exists() {
+        return blacklist;
+    }
+
+    let entries = fs::read_to_string(path)
+        .map_err(|e| anyhow!("{}", e))
+        .and_then(|json| serde_json::from_str::<Vec<Address>>(&json).map_err(|e| anyhow!("{}", e)));
+    match entries {
+        Ok(entries) => blacklist.extend(entries),
+        Err(e) => warn!("Ignoring blacklist file {:?}: {}", path, e),
+    }
+    blacklist
+}
+
+pub fn persist_blacklist(path: impl AsRef<Path>, blacklist: &HashSet<Address>) -> Result<()> {
+    let path = path.as_ref();
+    let mut entries: Vec<&Address> = blacklist.iter().collect();
+    entries.sort();
+
+    fs::write(path, serde_json::to_string_pretty(&entries)?)
+        .map_err(|e| anyhow!("Failed to write blacklist {:?}: {}", path, e))
+}
+
+/// Honeypot heuristics: invalid tokens and tokens with transfer fees or restrictions.
+pub fn is_blacklist_candidate(validation: &TokenValidation) -> bool {
+    !validation.is_valid || validation.has_transfer_fee || validation.has_transfer_restrictions
+}
+
+/// Add every flagged token to `blacklist`, returning the newly added ones.
+pub fn record_honeypots(
+    blacklist: &mut HashSet<Address>,
+    validations: impl IntoIterator<Item = (Address, TokenValidation)>,
+) -> Vec<Address> {
+    validations
+        .into_iter()
+        .filter(|(token, validation)| !blacklist.contains(token) && is_blacklist_candidate(validation))
+        .map(|(token, _)| {
+            blacklist.insert(token);
+            token
+        })
+        .collect()
+}
+
+/// Blacklist `token` at `path` if a transfer or sell probe flagged it.
+/// Returns whether it was newly added.
+pub fn record_transfer_flag(path: impl AsRef<Path>, token: Address, validation: &TokenValidation) -> Result<bool> {
+    if !validation.is_compromised() {
+        return Ok(false);
+    }
+    let mut blacklist = load_blacklist(&path);
+    if record_honeypots(&mut blacklist, [(token, validation.clone())]).is_empty() {
+        return Ok(false);
+    }
+    info!("Blacklisted {:?}: {}", token, validation.reason);
+    persist_blacklist(path, &blacklist)?;
+    Ok(true)
+}
+
+/// Validate `tokens` not yet blacklisted and persist any newly discovered honeypots to `path`.
+pub async fn augment_blacklist(
+    security: &SecurityManager,
+    tokens: impl IntoIterator<Item = Address>,
+    blacklist: &mut HashSet<Address>,
+    path: impl AsRef<Path>,
+) -> Result<Vec<Address>> {
+    let mut validations = Vec::new();
+    for token in tokens {
+        if blacklist.contains(&token) {
+            continue;
+        }
+        match security.validate_token(token).await {
+            Ok(validation) => validations.push((token, validation)),
+            Err(e) => warn!("Could not validate {:?}: {}", token, e),
+        }
+    }
+
+    let added = record_honeypots(blacklist, validations);
+    if !added.is_empty() {
+        info!("Blacklisted {} new tokens: {:?}", added.len(), added);
+        persist_blacklist(path, blacklist)?;
+    }
+    Ok(added)
+}
+
 // Use later for broadcasting to multiple builders
 // static BUILDER_URLS: &[&str] = &[
 //     "https://builder0x69.io",
@@ -60,3 +172,91 @@ pub fn get_blacklist_tokens() -> Vec<H160> {
 //     "https://rpc.lightspeedbuilder.info",
 //     "https://rpc.nfactorial.xyz",
 // ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validation(is_valid: bool, has_transfer_fee: bool) -> TokenValidation {
+        TokenValidation {
+            is_valid,
+            has_transfer_fee,
+            ..Default::default()
+        }
+    }
+
+    fn temp_path() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("blacklist-{:?}.json", Address::random()))
+    }
+
+    #[test]
+    fn test_multicall3_address_per_chain() {
+        let canonical: Address = "0xcA11bde05977b3631167028862bE2a173976CA11".parse().unwrap();
+        for chain_id in [1, 137, 42161, 10, 8453] {
+            assert_eq!(multicall3_address(chain_id), Some(canonical));
+        }
+        assert_eq!(multicall3_address(56), None);
+    }
+
+    #[test]
+    fn test_transfer_fee_token_is_blacklisted() {
+        let mut blacklist = load_blacklist(temp_path());
+        let fee_token = Address::random();
+        let clean_token = Address::random();
+
+        let added = record_honeypots(
+            &mut blacklist,
+            vec![(fee_token, validation(true, true)), (clean_token, validation(true, false))],
+        );
+
+        assert_eq!(added, vec![fee_token]);
+        assert!(blacklist.contains(&fee_token));
+        assert!(!blacklist.contains(&clean_token));
+    }
+
+    #[test]
+    fn test_transfer_flag_is_persisted() {
+        let path = temp_path();
+        let (fee_token, clean_token) = (Address::random(), Address::random());
+
+        assert!(!record_transfer_flag(&path, clean_token, &validation(false, false)).unwrap());
+        assert!(!path.exists());
+        assert!(record_transfer_flag(&path, fee_token, &validation(false, true)).unwrap());
+        // Already on file
+        assert!(!record_transfer_flag(&path, fee_token, &validation(false, true)).unwrap());
+
+        let blacklist = load_blacklist(&path);
+        fs::remove_file(&path).ok();
+        assert!(blacklist.contains(&fee_token));
+        assert!(!blacklist.contains(&clean_token));
+    }
+
+    #[test]
+    fn test_blacklist_file_round_trip() {
+        let path = temp_path();
+        let discovered = Address::random();
+
+        let mut blacklist = load_blacklist(&path);
+        assert_eq!(blacklist.len(), get_blacklist_tokens().len());
+
+        blacklist.insert(discovered);
+        persist_blacklist(&path, &blacklist).unwrap();
+
+        let reloaded = load_blacklist(&path);
+        fs::remove_file(&path).ok();
+
+        assert!(reloaded.contains(&discovered));
+        assert!(get_blacklist_tokens().iter().all(|token| reloaded.contains(token)));
+    }
+
+    #[test]
+    fn test_malformed_blacklist_file_falls_back_to_static() {
+        let path = temp_path();
+        fs::write(&path, "not json").unwrap();
+
+        let blacklist = load_blacklist(&path);
+        fs::remove_file(&path).ok();
+
+        assert_eq!(blacklist.len(), get_blacklist_tokens().len());
+    }
+}
@@ -0,0 +1,144 @@
+use ethers::types::Address;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+
+/// A Uniswap V2 compatible deployment: pools are synced from `factory` starting at `from_block`.
+#[derive(Debug, Clone)]
+pub struct V2Deployment {
+    pub factory: Address,
+    pub router: Address,
+    pub from_block: u64,
+}
+
+/// Addresses and token parameters the strategy needs for a given chain.
+#[derive(Debug, Clone)]
+pub struct ChainContext {
+    pub chain_id: u64,
+    pub name: &'static str,
+    pub wrapped_native: Address,
+    pub wrapped_native_decimals: u8,
+    pub stablecoin: Address,
+    pub stablecoin_decimals: u8,
+    pub v2_deployments: Vec<V2Deployment>,
+}
+
+lazy_static! {
+    pub static ref CHAIN_CONTEXTS: HashMap<u64, ChainContext> = {
+        let mut m = HashMap::new();
+
+        // Ethereum Mainnet (ChainID: 1), V2 through SushiSwap
+        m.insert(1, ChainContext {
+            chain_id: 1,
+            name: "ethereum",
+            wrapped_native: "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2".parse().unwrap(),
+            wrapped_native_decimals: 18,
+            stablecoin: "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48".parse().unwrap(),
+            stablecoin_decimals: 6,
+            v2_deployments: vec![V2Deployment {
+                factory: "0xC0AEe478e3658e2610c5F7A4A2E1777cE9e4f2Ac".parse().unwrap(),
+                router: "0xd9e1cE17f2641f24aE83637ab66a2cca9C378B9F".parse().unwrap(),
+                from_block: 10794229,
+            }],
+        });
+
+        // Polygon (ChainID: 137), V2 through QuickSwap
+        m.insert(137, ChainContext {
+            chain_id: 137,
+            name: "polygon",
+            wrapped_native: "0x0d500B1d8E8eF31E21C99d1Db9A6444d3ADf1270".parse().unwrap(),
+            wrapped_native_decimals: 18,
+            stablecoin: "0x3c499c542cEF5E3811e1192ce70d8cC03d5c3359".parse().unwrap(),
+            stablecoin_decimals: 6,
+            v2_deployments: vec![V2Deployment {
+                factory: "0x5757371414417b8C6CAad45bAeF941aBc7d3Ab32".parse().unwrap(),
+                router: "0xa5E0829CaCEd8fFDD4De3c43696c57F7D7A678ff".parse().unwrap(),
+                from_block: 4931780,
+            }],
+        });
+
+        // Arbitrum (ChainID: 42161), V2 through SushiSwap
+        m.insert(42161, ChainContext {
+            chain_id: 42161,
+            name: "arbitrum",
+            wrapped_native: "0x82aF49447D8a07e3bd95BD0d56f35241523fBab1".parse().unwrap(),
+            wrapped_native_decimals: 18,
+            stablecoin: "0xaf88d065e77c8cC2239327C5EDb3A432268e5831".parse().unwrap(),
+            stablecoin_decimals: 6,
+            v2_deployments: vec![V2Deployment {
+                factory: "0xc35DADB65012eC5796536bD9864eD8773aBc74C4".parse().unwrap(),
+                router: "0x1b02dA8Cb0d097eB8D57A175b88c7D8b47997506".parse().unwrap(),
+                from_block: 70,
+            }],
+        });
+
+        // Optimism (ChainID: 10), no canonical V2 deployment
+        m.insert(10, ChainContext {
+            chain_id: 10,
+            name: "optimism",
+            wrapped_native: "0x4200000000000000000000000000000000000006".parse().unwrap(),
+            wrapped_native_decimals: 18,
+            stablecoin: "0x0b2C639c533813f4Aa9D7837CAf62653d097Ff85".parse().unwrap(),
+            stablecoin_decimals: 6,
+            v2_deployments: vec![],
+        });
+
+        // Base (ChainID: 8453), V2 through Uniswap
+        m.insert(8453, ChainContext {
+            chain_id: 8453,
+            name: "base",
+            wrapped_native: "0x4200000000000000000000000000000000000006".parse().unwrap(),
+            wrapped_native_decimals: 18,
+            stablecoin: "0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913".parse().unwrap(),
+            stablecoin_decimals: 6,
+            v2_deployments: vec![V2Deployment {
+                factory: "0x8909Dc15e40173Ff4699343b6eB8132c65e18eC6".parse().unwrap(),
+                router: "0x4752ba5DBc23f44D87826276BF6Fd6b1C372aD24".parse().unwrap(),
+                from_block: 6601915,
+            }],
+        });
+
+        m
+    };
+}
+
+impl ChainContext {
+    pub fn for_chain(chain_id: u64) -> Option<&'static ChainContext> {
+        CHAIN_CONTEXTS.get(&chain_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(s: &str) -> Address {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_ethereum_context() {
+        let ctx = ChainContext::for_chain(1).unwrap();
+        assert_eq!(ctx.wrapped_native, address("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"));
+        assert_eq!(ctx.stablecoin, address("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"));
+        assert_eq!(ctx.stablecoin_decimals, 6);
+    }
+
+    #[test]
+    fn test_polygon_context() {
+        let ctx = ChainContext::for_chain(137).unwrap();
+        assert_eq!(ctx.wrapped_native, address("0x0d500B1d8E8eF31E21C99d1Db9A6444d3ADf1270"));
+        assert_eq!(ctx.stablecoin, address("0x3c499c542cEF5E3811e1192ce70d8cC03d5c3359"));
+    }
+
+    #[test]
+    fn test_base_context() {
+        let ctx = ChainContext::for_chain(8453).unwrap();
+        assert_eq!(ctx.wrapped_native, address("0x4200000000000000000000000000000000000006"));
+        assert_eq!(ctx.stablecoin, address("0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913"));
+    }
+
+    #[test]
+    fn test_unknown_chain() {
+        assert!(ChainContext::for_chain(56).is_none());
+    }
+}
@@ -1,11 +1,14 @@
 use anyhow::{anyhow, Result};
-use ethers::types::{Address, U256, H256};
-use std::collections::HashMap;
+use ethers::providers::Middleware;
+use ethers::types::{Address, Bytes, U256, H256};
 use std::sync::Arc;
 use log::{info, warn, error};
+use crate::protocols::flashloan_provider::FlashLoanProvider;
 use crate::security::{SecurityManager, SecurityConfig};
 
-#[derive(Debug, Clone)]
+const BPS_DENOMINATOR: u64 = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum FlashloanProvider {
     Balancer,
     UniswapV2,
@@ -33,30 +36,52 @@ pub struct FlashloanParams {
 }
 
 pub struct FlashloanManager {
-    providers: HashMap<FlashloanProvider, Address>,
-    fee_multipliers: HashMap<FlashloanProvider, U256>,
+    providers: Vec<Box<dyn FlashLoanProvider>>,
     security: Arc<SecurityManager>,
 }
 
 impl FlashloanManager {
     pub fn new() -> Self {
-        let mut providers = HashMap::new();
-        let mut fee_multipliers = HashMap::new();
-        
-        // Initialize with known providers and their fees
-        providers.insert(FlashloanProvider::AAVE, Address::zero());
-        fee_multipliers.insert(FlashloanProvider::AAVE, U256::from(9).checked_div(U256::from(10000)).unwrap());
-        
+        Self::with_providers(Vec::new())
+    }
+
+    pub fn with_providers(providers: Vec<Box<dyn FlashLoanProvider>>) -> Self {
         let security = Arc::new(SecurityManager::new(SecurityConfig::default()));
-        
+
         Self {
             providers,
-            fee_multipliers,
             security,
         }
     }
 
-    pub async fn execute_flashloan(&self, params: FlashloanParams) -> Result<U256> {
+    pub fn add_provider(&mut self, provider: Box<dyn FlashLoanProvider>) {
+        self.providers.push(provider);
+    }
+
+    /// Replace the provider of the same kind, e.g. with refreshed liquidity.
+    pub fn set_provider(&mut self, provider: Box<dyn FlashLoanProvider>) {
+        self.providers.retain(|p| p.kind() != provider.kind());
+        self.providers.push(provider);
+    }
+
+    /// Cheapest provider that can lend `amount` of `token`.
+    pub fn best_provider(&self, token: Address, amount: U256) -> Option<&dyn FlashLoanProvider> {
+        self.providers
+            .iter()
+            .filter(|p| p.max_available(token) >= amount)
+            .min_by_key(|p| p.fee_bps())
+            .map(|p| p.as_ref())
+    }
+
+    fn provider(&self, kind: FlashloanProvider) -> Result<&dyn FlashLoanProvider> {
+        self.providers
+            .iter()
+            .find(|p| p.kind() == kind)
+            .map(|p| p.as_ref())
+            .ok_or_else(|| anyhow!("Unsupported flashloan provider"))
+    }
+
+    pub async fn execute_flashloan<M: Middleware>(&self, client: &M, params: FlashloanParams) -> Result<H256> {
         info!("Executing flashloan: {:?}", params);
         
         // Validate parameters
@@ -70,19 +95,22 @@ impl FlashloanManager {
             return Err(anyhow!("Flashloan not profitable after fees"));
         }
         
-        // Execute based on provider
-        let result = match params.provider {
-            FlashloanProvider::AAVE => self.execute_aave_flashloan(params).await,
-            FlashloanProvider::Balancer => self.execute_balancer_flashloan(params).await,
-            _ => Err(anyhow!("Provider not implemented")),
-        };
-        
-        // Record transaction if successful
-        if let Ok(tx_hash) = result {
-            self.security.record_transaction(tx_hash).await;
-        }
-        
-        result.map(|tx_hash| U256::from(0)) // Return U256 instead of H256
+        let provider = self.provider(params.provider)?;
+        let tx = provider.build_flashloan(
+            params.token,
+            params.amount,
+            params.callback,
+            Bytes::from(params.data.clone()),
+        )?;
+
+        let tx_hash = client
+            .send_transaction(tx, None)
+            .await
+            .map_err(|e| anyhow!("Failed to send {:?} flashloan: {}", params.provider, e))?
+            .tx_hash();
+
+        self.security.record_transaction(tx_hash).await;
+        Ok(tx_hash)
     }
     
     async fn validate_params(&self, params: &FlashloanParams) -> Result<()> {
@@ -91,16 +119,16 @@ impl FlashloanManager {
             return Err(anyhow!("Flashloan amount cannot be zero"));
         }
         
-        if !self.providers.contains_key(&params.provider) {
-            return Err(anyhow!("Unsupported flashloan provider"));
+        let provider = self.provider(params.provider)?;
+        if provider.max_available(params.token) < params.amount {
+            return Err(anyhow!("Not enough {:?} liquidity for flashloan", params.provider));
         }
         
         // Security checks
-        let provider_address = self.providers.get(&params.provider).unwrap();
         if !self.security.check_transaction_safety(
             H256::zero(), // Will be set later
             params.callback,
-            *provider_address,
+            provider.lender(),
             params.amount,
             params.gas_price,
         ).await? {
@@ -111,12 +139,11 @@ impl FlashloanManager {
     }
     
     fn calculate_fee(&self, params: &FlashloanParams) -> Result<U256> {
-        let fee_multiplier = self.fee_multipliers
-            .get(&params.provider)
-            .ok_or_else(|| anyhow!("Fee not found for provider"))?;
+        let fee_bps = self.provider(params.provider)?.fee_bps();
             
         params.amount
-            .checked_mul(*fee_multiplier)
+            .checked_mul(U256::from(fee_bps))
+            .map(|fee| fee / U256::from(BPS_DENOMINATOR))
             .ok_or_else(|| anyhow!("Fee calculation overflow"))
     }
     
@@ -129,21 +156,21 @@ impl FlashloanManager {
             
         amount > total_cost
     }
-    
-    async fn execute_aave_flashloan(&self, params: FlashloanParams) -> Result<H256> {
-        // Implement AAVE flashloan logic
-        todo!("Implement AAVE flashloan")
-    }
-    
-    async fn execute_balancer_flashloan(&self, params: FlashloanParams) -> Result<H256> {
-        // Implement Balancer flashloan logic
-        todo!("Implement Balancer flashloan")
-    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::protocols::flashloan_provider::{AaveFlashLoanProvider, BalancerFlashLoanProvider};
+
+    fn manager_with(aave_liquidity: u64, balancer_balance: u64, token: Address) -> FlashloanManager {
+        let mut aave = AaveFlashLoanProvider::new(Address::random());
+        aave.update_liquidity(token, U256::from(aave_liquidity));
+        let mut balancer = BalancerFlashLoanProvider::new(Address::random());
+        balancer.update_balance(token, U256::from(balancer_balance));
+
+        FlashloanManager::with_providers(vec![Box::new(aave), Box::new(balancer)])
+    }
     
     #[tokio::test]
     async fn test_flashloan_validation() {
@@ -164,7 +191,8 @@ mod tests {
     
     #[tokio::test]
     async fn test_fee_calculation() {
-        let manager = FlashloanManager::new();
+        let mut manager = FlashloanManager::new();
+        manager.add_provider(Box::new(AaveFlashLoanProvider::new(Address::random())));
         
         let params = FlashloanParams {
             provider: FlashloanProvider::AAVE,
@@ -177,5 +205,43 @@ mod tests {
         
         let fee = manager.calculate_fee(&params).unwrap();
         assert!(fee > U256::zero());
+        assert_eq!(fee, U256::from(500)); // 5 bps
+    }
+
+    #[test]
+    fn test_best_provider_prefers_cheapest_with_liquidity() {
+        let token = Address::random();
+        let manager = manager_with(1_000_000, 1_000_000, token);
+
+        // Balancer charges nothing, so it wins while it can fill the loan
+        let best = manager.best_provider(token, U256::from(500_000)).unwrap();
+        assert_eq!(best.kind(), FlashloanProvider::Balancer);
+        assert_eq!(best.fee_bps(), 0);
+    }
+
+    #[test]
+    fn test_set_provider_replaces_same_kind() {
+        let token = Address::random();
+        let mut manager = manager_with(100_000, 0, token);
+
+        let mut refreshed = AaveFlashLoanProvider::new(Address::random());
+        refreshed.update_liquidity(token, U256::from(1_000_000));
+        manager.set_provider(Box::new(refreshed));
+
+        let best = manager.best_provider(token, U256::from(500_000)).unwrap();
+        assert_eq!(best.kind(), FlashloanProvider::AAVE);
+        assert_eq!(manager.providers.len(), 2);
+    }
+
+    #[test]
+    fn test_best_provider_falls_back_when_liquidity_short() {
+        let token = Address::random();
+        let manager = manager_with(1_000_000, 100_000, token);
+
+        let best = manager.best_provider(token, U256::from(500_000)).unwrap();
+        assert_eq!(best.kind(), FlashloanProvider::AAVE);
+
+        assert!(manager.best_provider(token, U256::from(2_000_000)).is_none());
+        assert!(manager.best_provider(Address::random(), U256::one()).is_none());
     }
 }
@@ -0,0 +1,63 @@
+//! Pools, reserves and configs shared by the unit tests.
+
+use ethers::types::{H160, U256};
+use std::time::Duration;
+
+use crate::flashbot::types::{default_priority_fee_ladder, ArbitrageOpportunity, ExecutionConfig};
+use crate::multi::Reserve;
+use crate::pools::{DexVariant, Pool};
+
+/// A 0.3% Uniswap V2 pool at a random address, without reserves.
+pub fn make_pool(token0: H160, token1: H160, decimals0: u8, decimals1: u8) -> Pool {
+    Pool {
+        address: H160::random(),
+        version: DexVariant::UniswapV2,
+        token0,
+        token1,
+        decimals0,
+        decimals1,
+        fee: 300,
+        reserve0: U256::zero(),
+        reserve1: U256::zero(),
+        sqrt_price_x96: None,
+        liquidity: None,
+        tick: None,
+        factory: None,
+    }
+}
+
+/// Reserves given in whole tokens.
+pub fn reserve(reserve0: u128, decimals0: u8, reserve1: u128, decimals1: u8) -> Reserve {
+    Reserve {
+        reserve0: U256::from(reserve0) * U256::exp10(decimals0 as usize),
+        reserve1: U256::from(reserve1) * U256::exp10(decimals1 as usize),
+    }
+}
+
+/// An opportunity with no path or pools, found at block 0.
+pub fn opportunity(expected_profit: u64, gas_cost: u64) -> ArbitrageOpportunity {
+    ArbitrageOpportunity {
+        expected_profit: U256::from(expected_profit),
+        gas_cost: U256::from(gas_cost),
+        ..Default::default()
+    }
+}
+
+/// No profit floor beyond twice the gas cost, up to three bundles a block.
+pub fn execution_config() -> ExecutionConfig {
+    ExecutionConfig {
+        max_gas_price: U256::zero(),
+        priority_fee: U256::zero(),
+        max_hops: 3,
+        block_delay: 1,
+        max_execution_time: Duration::from_secs(12),
+        min_profit_threshold: U256::zero(),
+        min_profit_multiplier: 2,
+        priority_fee_ladder: default_priority_fee_ladder(),
+        max_bundles_per_block: 3,
+        max_opportunity_age_blocks: 1,
+        private_only: false,
+        coinbase_tip_bps: 0,
+        max_jitter_blocks: 0,
+    }
+}
@@ -3,13 +3,20 @@ use ethers::{
     types::{Address, U256, Transaction},
     providers::{Provider, Http},
     middleware::SignerMiddleware,
-    signers::LocalWallet,
+    signers::{LocalWallet, Signer},
 };
-use std::{sync::Arc, collections::HashMap};
+use std::{sync::Arc, collections::{HashMap, HashSet}, time::Instant};
 use tokio::sync::RwLock;
+use log::{info, warn};
+use crate::flashbot::journal::TradeJournal;
 use crate::flashbot::types::*;
 use crate::dex::{DexPool, DexManager};
+use crate::monitoring::ErrorRecovery;
 use crate::security::SecurityManager;
+use crate::simulator::{CurvePoolState, CurveSimulator};
+
+/// Largest Curve round trip searched, in whole tokens.
+const CURVE_ARB_MAX_TOKENS: u64 = 1_000_000;
 
 pub struct ArbitrageManager {
     dex_manager: Arc<DexManager>,
@@ -18,6 +25,10 @@ pub struct ArbitrageManager {
     risk_config: Arc<RwLock<RiskConfig>>,
     execution_config: Arc<RwLock<ExecutionConfig>>,
     analytics: Arc<RwLock<Analytics>>,
+    curve_states: Arc<RwLock<HashMap<Address, CurvePoolState>>>,
+    positions: Arc<PositionTracker>,
+    journal: Option<TradeJournal>,
+    paper_trading: bool,
 }
 
 impl ArbitrageManager {
@@ -34,11 +45,36 @@ impl ArbitrageManager {
             risk_config: Arc::new(RwLock::new(risk_config)),
             execution_config: Arc::new(RwLock::new(execution_config)),
             analytics: Arc::new(RwLock::new(Analytics::default())),
+            curve_states: Arc::new(RwLock::new(HashMap::new())),
+            positions: Arc::new(PositionTracker::new()),
+            journal: None,
+            paper_trading: false,
         }
     }
 
-    /// Find arbitrage opportunities across DEXes
-    pub async fn find_opportunities(&self, token: Address) -> Result<Vec<ArbitrageOpportunity>> {
+    /// Persist every recorded trade to `journal` as well as in-memory analytics.
+    pub fn with_journal(mut self, journal: TradeJournal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// In paper mode trades are built and recorded as filled, but never sent.
+    pub fn with_paper_trading(mut self, paper_trading: bool) -> Self {
+        self.paper_trading = paper_trading;
+        self
+    }
+
+    /// Record the latest coins, balances, A and fee of a Curve pool.
+    pub async fn update_curve_state(&self, pool: Address, state: CurvePoolState) {
+        self.curve_states.write().await.insert(pool, state);
+    }
+
+    /// Find arbitrage opportunities across DEXes as of `block_number`
+    pub async fn find_opportunities(
+        &self,
+        token: Address,
+        block_number: u64,
+    ) -> Result<Vec<ArbitrageOpportunity>> {
         // Get all relevant pools
         let pools = self.dex_manager.get_pools_for_token(token).await?;
         
@@ -46,13 +82,13 @@ impl ArbitrageManager {
         let mut opportunities = Vec::new();
         
         // Check V2 style pools
-        self.find_v2_opportunities(&pools, &mut opportunities).await?;
+        self.find_v2_opportunities(&pools, block_number, &mut opportunities).await?;
         
         // Check V3 pools
-        self.find_v3_opportunities(&pools, &mut opportunities).await?;
+        self.find_v3_opportunities(&pools, block_number, &mut opportunities).await?;
         
         // Check Curve pools
-        self.find_curve_opportunities(&pools, &mut opportunities).await?;
+        self.find_curve_opportunities(&pools, block_number, &mut opportunities).await?;
         
         // Filter and validate opportunities
         let valid_ops = self.validate_opportunities(opportunities).await?;
@@ -64,6 +100,7 @@ impl ArbitrageManager {
     async fn find_v2_opportunities(
         &self,
         pools: &[DexPool],
+        block_number: u64,
         opportunities: &mut Vec<ArbitrageOpportunity>
     ) -> Result<()> {
         let v2_pools: Vec<_> = pools.iter()
@@ -92,6 +129,7 @@ impl ArbitrageManager {
                             execution_time_ms: 1000, // Estimated 1s execution
                             pools: vec![pool1.clone(), pool2.clone()],
                             profit_token: pool1.token0,
+                            discovered_at_block: block_number,
                         });
                     }
                 }
@@ -105,6 +143,7 @@ impl ArbitrageManager {
     async fn find_v3_opportunities(
         &self,
         pools: &[DexPool],
+        block_number: u64,
         opportunities: &mut Vec<ArbitrageOpportunity>
     ) -> Result<()> {
         let v3_pools: Vec<_> = pools.iter()
@@ -133,6 +172,7 @@ impl ArbitrageManager {
                             execution_time_ms: 1000,
                             pools: vec![pool1.clone(), pool2.clone()],
                             profit_token: pool1.token0,
+                            discovered_at_block: block_number,
                         });
                     }
                 }
@@ -142,20 +182,133 @@ impl ArbitrageManager {
         Ok(())
     }
 
-    /// Execute arbitrage opportunity
+    /// Find Curve-vs-Uniswap V2 mispricings on pairs both pools hold
+    async fn find_curve_opportunities(
+        &self,
+        pools: &[DexPool],
+        block_number: u64,
+        opportunities: &mut Vec<ArbitrageOpportunity>
+    ) -> Result<()> {
+        let curve_pools: Vec<_> = pools.iter()
+            .filter(|p| matches!(p.protocol, DexProtocol::Curve))
+            .collect();
+        let v2_pools: Vec<_> = pools.iter()
+            .filter(|p| matches!(p.protocol, DexProtocol::UniswapV2))
+            .collect();
+        let curve_states = self.curve_states.read().await;
+
+        for curve_pool in &curve_pools {
+            // Pools without a recorded state can't be simulated offline
+            let state = match curve_states.get(&curve_pool.address) {
+                Some(state) => state,
+                None => continue,
+            };
+
+            for v2_pool in &v2_pools {
+                let i = match state.coins.iter().position(|c| *c == v2_pool.token0) {
+                    Some(i) => i,
+                    None => continue,
+                };
+                let j = match state.coins.iter().position(|c| *c == v2_pool.token1) {
+                    Some(j) => j,
+                    None => continue,
+                };
+
+                let found = CurveSimulator::arbitrage_against_v2(
+                    state,
+                    i,
+                    j,
+                    v2_pool.reserves,
+                    U256::from(v2_pool.fee),
+                    CURVE_ARB_MAX_TOKENS,
+                );
+
+                if let Some((amount, profit)) = found {
+                    if self.is_profitable(profit).await? {
+                        opportunities.push(ArbitrageOpportunity {
+                            path: vec![v2_pool.token0, v2_pool.token1],
+                            expected_profit: profit,
+                            required_flash_amount: amount,
+                            risk_score: self.calculate_risk_score(curve_pool, v2_pool).await?,
+                            gas_cost: self.estimate_gas_cost(curve_pool, v2_pool).await?,
+                            execution_time_ms: 1000,
+                            pools: vec![(*curve_pool).clone(), (*v2_pool).clone()],
+                            profit_token: v2_pool.token0,
+                            discovered_at_block: block_number,
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Drop opportunities whose path touches a token blacklisted by either
+    /// the risk config or the security manager
+    async fn validate_opportunities(
+        &self,
+        mut opportunities: Vec<ArbitrageOpportunity>,
+    ) -> Result<Vec<ArbitrageOpportunity>> {
+        let blacklist: HashSet<Address> = self
+            .risk_config
+            .read()
+            .await
+            .blacklisted_tokens
+            .iter()
+            .chain(self.security_manager.blacklisted_tokens())
+            .copied()
+            .collect();
+
+        opportunities.retain(|op| {
+            let blacklisted = op.routes_through_any(&blacklist);
+            if blacklisted {
+                info!("Dropping {:?}, which routes through a blacklisted token", op.path);
+            }
+            !blacklisted
+        });
+        Ok(opportunities)
+    }
+
+    /// Whether `opportunity` is too old to act on at `current_block`
+    pub async fn is_expired(&self, opportunity: &ArbitrageOpportunity, current_block: u64) -> bool {
+        let max_age = self.execution_config.read().await.max_opportunity_age_blocks;
+        opportunity.is_expired(current_block, max_age)
+    }
+
+    /// Execute arbitrage opportunity from `wallet`, at `nonce` from its own
+    /// nonce sequence. An error means nothing was sent.
     pub async fn execute_arbitrage(
         &self,
         opportunity: &ArbitrageOpportunity,
         wallet: LocalWallet,
+        nonce: U256,
+        recovery: &ErrorRecovery,
     ) -> Result<TradeResult> {
-        // Final validation before execution
-        self.validate_execution(opportunity).await?;
+        let started = Instant::now();
+
+        // Final validation before execution; the position stays open until the trade is done
+        let _position = self.validate_execution(opportunity).await?;
         
         // Prepare flash loan
         let flash_params = self.prepare_flash_loan(opportunity).await?;
         
         // Build transaction
-        let tx = self.build_arbitrage_transaction(opportunity, flash_params).await?;
+        let mut tx = self.build_arbitrage_transaction(opportunity, flash_params).await?;
+        tx.set_from(wallet.address());
+        tx.set_nonce(nonce);
+        // A retry after a nonce or underpricing error goes out with the fix
+        recovery.apply_to(&mut tx).await;
+
+        if self.paper_trading {
+            info!(
+                "[paper] Would execute {:?} for {} expected profit",
+                opportunity.path, opportunity.expected_profit
+            );
+            let result = TradeResult::paper(opportunity, started.elapsed());
+            self.record_trade_result(opportunity, &result).await?;
+            return Ok(result);
+        }
         
         // Execute with MEV protection
         let result = self.execute_with_protection(tx, wallet).await;
@@ -187,8 +340,32 @@ impl ArbitrageManager {
         Ok(score)
     }
 
-    /// Validate if opportunity is still profitable
-    async fn validate_execution(&self, op: &ArbitrageOpportunity) -> Result<()> {
+    /// Validate if opportunity is still profitable, and open its position
+    async fn validate_execution(&self, op: &ArbitrageOpportunity) -> Result<PositionGuard> {
+        let (max_position_size, max_price_impact_bps) = {
+            let risk = self.risk_config.read().await;
+            (risk.max_position_size, risk.max_price_impact_bps)
+        };
+
+        // Reject trades that move the pools too far. The impact model only
+        // fits constant-product pools; paths through Curve, V3 or Balancer are
+        // re-priced by their simulators in `simulate_arbitrage` below instead.
+        if op.pools.iter().all(|pool| pool.protocol.is_constant_product()) {
+            let impact = op
+                .price_impact_bps()
+                .ok_or_else(|| anyhow!("Can't compute the price impact along {:?}", op.path))?;
+            if impact > U256::from(max_price_impact_bps) {
+                return Err(anyhow!(
+                    "Price impact of {}bps exceeds {}bps",
+                    impact, max_price_impact_bps
+                ));
+            }
+        }
+
+        // Check the flash-borrowed token's exposure stays within limits
+        let token = *op.path.first().ok_or_else(|| anyhow!("Opportunity has an empty path"))?;
+        let position = self.positions.open(token, op.required_flash_amount, max_position_size)?;
+
         // Check if pools still have sufficient liquidity
         for pool in &op.pools {
             let current_liquidity = self.dex_manager.get_pool_liquidity(&pool.address).await?;
@@ -210,7 +387,7 @@ impl ArbitrageManager {
             return Err(anyhow!("Gas price too high"));
         }
         
-        Ok(())
+        Ok(position)
     }
 
     /// Record trade result and update analytics
@@ -219,39 +396,15 @@ impl ArbitrageManager {
         opportunity: &ArbitrageOpportunity,
         result: &TradeResult,
     ) -> Result<()> {
-        let mut analytics = self.analytics.write().await;
-        
-        // Update metrics
-        if result.success {
-            analytics.successful_trades += 1;
-            analytics.total_profit = analytics.total_profit.saturating_add(result.actual_profit);
-        } else {
-            analytics.failed_trades += 1;
-            if let Some(ref error) = result.error {
-                analytics.errors.push(error.clone());
+        self.analytics.write().await.record(result);
+
+        // The trade already happened, so a journal failure shouldn't fail it
+        if let Some(ref journal) = self.journal {
+            if let Err(e) = journal.append(result) {
+                warn!("Failed to journal trade: {:?}", e);
             }
         }
         
-        // Update averages
-        analytics.avg_profit_per_trade = analytics.total_profit
-            .checked_div(U256::from(analytics.successful_trades))
-            .unwrap_or_default();
-            
-        analytics.avg_execution_time = Duration::from_micros(
-            (analytics.avg_execution_time.as_micros() as u64 + result.execution_time.as_micros() as u64) / 2
-        );
-        
-        // Update gas stats
-        analytics.gas_spent = analytics.gas_spent.saturating_add(result.gas_used);
-        
-        // Add to history
-        analytics.trade_history.push(result.clone());
-        
-        // Trim history if too long
-        if analytics.trade_history.len() > 1000 {
-            analytics.trade_history.remove(0);
-        }
-        
         Ok(())
     }
 }
@@ -1,14 +1,81 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use ethers::{
     types::{Address, U256, Bytes},
     contract::{Contract, ContractFactory},
     providers::{Provider, Http},
     middleware::SignerMiddleware,
-    signers::LocalWallet,
+    signers::{LocalWallet, Signer},
 };
+use log::{info, warn};
 use std::sync::Arc;
 
-pub struct ContractManager {
+use crate::abi::ABI;
+use crate::flashbot::mev_protection::ensure_public_send_allowed;
+use crate::flashbot::types::WithdrawalConfig;
+use crate::monitoring::EmergencyStop;
+
+/// Vault balance reads and withdrawals, as the profit sweeper needs them.
+#[async_trait::async_trait]
+pub trait ProfitVault: Send + Sync {
+    async fn get_balance(&self, token: Address) -> Result<U256>;
+    async fn withdraw(&self, token: Address, amount: U256, recipient: Address) -> Result<()>;
+}
+
+#[async_trait::async_trait]
+impl<S: Signer + 'static> ProfitVault for ContractManager<S> {
+    async fn get_balance(&self, token: Address) -> Result<U256> {
+        ContractManager::get_balance(self, token).await
+    }
+
+    async fn withdraw(&self, token: Address, amount: U256, recipient: Address) -> Result<()> {
+        ContractManager::withdraw(self, token, amount, recipient).await
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: Signer + 'static> EmergencyStop for ContractManager<S> {
+    async fn emergency_stop(&self) -> Result<()> {
+        ContractManager::emergency_stop(self).await
+    }
+}
+
+/// Withdraw the excess over its threshold of every configured token to the
+/// treasury, returning what was withdrawn. The threshold stays in the vault
+/// as working capital. A token that fails is logged and retried next round.
+pub async fn withdraw_profits<V: ProfitVault + ?Sized>(
+    vault: &V,
+    config: &WithdrawalConfig,
+) -> Vec<(Address, U256)> {
+    let mut withdrawn = Vec::new();
+    for (token, threshold) in &config.thresholds {
+        let balance = match vault.get_balance(*token).await {
+            Ok(balance) => balance,
+            Err(e) => {
+                warn!("Failed to read vault balance of {:?}: {}", token, e);
+                continue;
+            }
+        };
+        if balance <= *threshold {
+            continue;
+        }
+        let excess = balance - *threshold;
+        match vault.withdraw(*token, excess, config.treasury).await {
+            Ok(()) => {
+                info!("Withdrew {} of {:?} to {:?}", excess, token, config.treasury);
+                withdrawn.push((*token, excess));
+            }
+            Err(e) => warn!("Failed to withdraw {:?}: {}", token, e),
+        }
+    }
+    withdrawn
+}
+
+/// Executor and vault calls, signed by `S`: a local wallet or e.g. a
+/// `KmsSigner` holding the owner key. In private-only mode flashloans are
+/// refused; admin calls (emergency stop, withdrawals, fee, operator and
+/// adapter changes) still go out directly, since they carry no trade to
+/// front-run and an emergency stop mustn't wait on a relay.
+pub struct ContractManager<S: Signer = LocalWallet> {
     // Core contracts
     pub executor: Address,
     pub vault: Address,
@@ -22,19 +89,28 @@ pub struct ContractManager {
     pub access_control: Address,
     
     // Contract interfaces
-    executor_contract: Contract<Provider<Http>>,
-    vault_contract: Contract<Provider<Http>>,
+    executor_contract: Contract<SignerMiddleware<Provider<Http>, S>>,
+    vault_contract: Contract<SignerMiddleware<Provider<Http>, S>>,
+
+    // Log state-changing calls instead of sending them
+    paper_trading: bool,
+    // Refuse to send trades through the public mempool
+    private_only: bool,
 }
 
-impl ContractManager {
+impl<S: Signer + 'static> ContractManager<S> {
     pub async fn new(
         provider: Arc<Provider<Http>>,
+        signer: S,
         executor: Address,
         vault: Address,
     ) -> Result<Self> {
+        let client = Arc::new(SignerMiddleware::new(provider.as_ref().clone(), signer));
+
         // Load contract ABIs
-        let executor_contract = Contract::new(executor, EXECUTOR_ABI.parse()?, provider.clone());
-        let vault_contract = Contract::new(vault, VAULT_ABI.parse()?, provider.clone());
+        let abi = ABI::shared()?;
+        let executor_contract = Contract::new(executor, abi.flashloan_executor.clone(), client.clone());
+        let vault_contract = Contract::new(vault, abi.vault.clone(), client);
         
         Ok(Self {
             executor,
@@ -45,9 +121,29 @@ impl ContractManager {
             access_control: Address::zero(),
             executor_contract,
             vault_contract,
+            paper_trading: false,
+            private_only: false,
         })
     }
 
+    pub fn with_paper_trading(mut self, paper_trading: bool) -> Self {
+        self.paper_trading = paper_trading;
+        self
+    }
+
+    pub fn with_private_only(mut self, private_only: bool) -> Self {
+        self.private_only = private_only;
+        self
+    }
+
+    /// In paper mode, log `action` and report that it shouldn't be sent.
+    fn skip_in_paper_mode(&self, action: &str) -> bool {
+        if self.paper_trading {
+            info!("[paper] Would {}", action);
+        }
+        self.paper_trading
+    }
+
     /// Execute flashloan arbitrage
     pub async fn execute_flashloan(
         &self,
@@ -56,6 +152,10 @@ impl ContractManager {
         pools: Vec<Address>,
         data: Bytes,
     ) -> Result<()> {
+        if self.skip_in_paper_mode(&format!("flashloan {} of {:?} through {:?}", amount, token, pools)) {
+            return Ok(());
+        }
+        ensure_public_send_allowed(self.private_only, "a flashloan")?;
         self.executor_contract
             .method("executeFlashloan", (token, amount, pools, data))?
             .send()
@@ -70,6 +170,10 @@ impl ContractManager {
         protocol: &str,
         implementation: Address,
     ) -> Result<Address> {
+        if self.paper_trading {
+            return Err(anyhow!("Can't deploy the {} adapter in paper mode", protocol));
+        }
+
         // Deploy adapter proxy
         let adapter = self.deploy_proxy(implementation).await?;
         
@@ -87,6 +191,9 @@ impl ContractManager {
 
     /// Emergency stop all operations
     pub async fn emergency_stop(&self) -> Result<()> {
+        if self.skip_in_paper_mode("trigger the emergency stop") {
+            return Ok(());
+        }
         self.executor_contract
             .method("emergencyStop", ())?
             .send()
@@ -102,6 +209,9 @@ impl ContractManager {
         amount: U256,
         recipient: Address,
     ) -> Result<()> {
+        if self.skip_in_paper_mode(&format!("withdraw {} of {:?} to {:?}", amount, token, recipient)) {
+            return Ok(());
+        }
         self.vault_contract
             .method("withdraw", (token, amount, recipient))?
             .send()
@@ -112,6 +222,9 @@ impl ContractManager {
 
     /// Update protocol fee
     pub async fn update_fee(&self, new_fee: U256) -> Result<()> {
+        if self.skip_in_paper_mode(&format!("update the fee to {}", new_fee)) {
+            return Ok(());
+        }
         self.executor_contract
             .method("updateFee", new_fee)?
             .send()
@@ -122,6 +235,9 @@ impl ContractManager {
 
     /// Add new operator
     pub async fn add_operator(&self, operator: Address) -> Result<()> {
+        if self.skip_in_paper_mode(&format!("add operator {:?}", operator)) {
+            return Ok(());
+        }
         self.access_control
             .method("addOperator", operator)?
             .send()
@@ -138,6 +254,12 @@ impl ContractManager {
             .await?)
     }
 
+    /// Whether the executor takes calls from `sender`: its owner or an operator
+    pub async fn accepts_sender(&self, sender: Address) -> Result<bool> {
+        let owner: Address = self.executor_contract.method("owner", ())?.call().await?;
+        Ok(owner == sender || self.is_operator(sender).await?)
+    }
+
     /// Get protocol fee
     pub async fn get_fee(&self) -> Result<U256> {
         Ok(self.executor_contract
@@ -154,3 +276,68 @@ impl ContractManager {
             .await?)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tokio::sync::Mutex;
+
+    #[derive(Default)]
+    struct MockVault {
+        balances: HashMap<Address, U256>,
+        withdrawals: Mutex<Vec<(Address, U256, Address)>>,
+    }
+
+    #[async_trait::async_trait]
+    impl ProfitVault for MockVault {
+        async fn get_balance(&self, token: Address) -> Result<U256> {
+            Ok(self.balances.get(&token).copied().unwrap_or_default())
+        }
+
+        async fn withdraw(&self, token: Address, amount: U256, recipient: Address) -> Result<()> {
+            self.withdrawals.lock().await.push((token, amount, recipient));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_withdraws_only_above_threshold() {
+        let (weth, usdc) = (Address::random(), Address::random());
+        let vault = MockVault {
+            balances: HashMap::from([(weth, U256::from(5_000)), (usdc, U256::from(900))]),
+            ..Default::default()
+        };
+        let config = WithdrawalConfig {
+            treasury: Address::random(),
+            interval_secs: 60,
+            thresholds: HashMap::from([(weth, U256::from(1_000)), (usdc, U256::from(1_000))]),
+        };
+
+        let withdrawn = withdraw_profits(&vault, &config).await;
+
+        // Only the excess leaves; the threshold stays behind
+        assert_eq!(withdrawn, vec![(weth, U256::from(4_000))]);
+        assert_eq!(
+            *vault.withdrawals.lock().await,
+            vec![(weth, U256::from(4_000), config.treasury)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unconfigured_tokens_stay_in_vault() {
+        let token = Address::random();
+        let vault = MockVault {
+            balances: HashMap::from([(token, U256::MAX)]),
+            ..Default::default()
+        };
+        let config = WithdrawalConfig {
+            treasury: Address::random(),
+            interval_secs: 60,
+            thresholds: HashMap::new(),
+        };
+
+        assert!(withdraw_profits(&vault, &config).await.is_empty());
+        assert!(vault.withdrawals.lock().await.is_empty());
+    }
+}
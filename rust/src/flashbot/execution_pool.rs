@@ -0,0 +1,139 @@
+use ethers::types::Address;
+use std::collections::{BTreeSet, HashMap};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard, Semaphore};
+
+/// Bounded set of workers for executing opportunities. Opportunities on
+/// disjoint pools run concurrently; those sharing a pool run one at a time,
+/// since the first to land moves the reserves the others were priced on.
+#[derive(Clone)]
+pub struct ExecutionPool {
+    workers: Arc<Semaphore>,
+    pool_locks: Arc<Mutex<HashMap<Address, Arc<AsyncMutex<()>>>>>,
+}
+
+impl ExecutionPool {
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            workers: Arc::new(Semaphore::new(max_concurrent.max(1))),
+            pool_locks: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Run `task` once every pool it touches is free and a worker is available.
+    pub async fn run<F: Future>(self, pools: Vec<Address>, task: F) -> F::Output {
+        // Locks are always taken in address order, so two tasks can't each
+        // hold a pool the other is waiting for
+        let pools: BTreeSet<Address> = pools.into_iter().collect();
+        let locks: Vec<_> = {
+            let mut pool_locks = self.pool_locks.lock().unwrap();
+            pools
+                .iter()
+                .map(|pool| pool_locks.entry(*pool).or_default().clone())
+                .collect()
+        };
+
+        let mut held = HeldPools {
+            pool_locks: self.pool_locks.clone(),
+            pools,
+            guards: Vec::with_capacity(locks.len()),
+        };
+        for lock in locks {
+            held.guards.push(lock.lock_owned().await);
+        }
+        let _worker = self.workers.acquire().await.expect("execution pool closed");
+
+        task.await
+    }
+
+    #[cfg(test)]
+    fn tracked_pools(&self) -> usize {
+        self.pool_locks.lock().unwrap().len()
+    }
+}
+
+/// Pool locks held by one task. On drop, including when the task is
+/// cancelled, the locks nobody else holds or waits on are forgotten so the
+/// map only tracks pools in use.
+struct HeldPools {
+    pool_locks: Arc<Mutex<HashMap<Address, Arc<AsyncMutex<()>>>>>,
+    pools: BTreeSet<Address>,
+    guards: Vec<OwnedMutexGuard<()>>,
+}
+
+impl Drop for HeldPools {
+    fn drop(&mut self) {
+        self.guards.clear();
+        // Other tasks only clone a lock under the map mutex, so a count of
+        // one here means the map holds the last reference
+        let mut pool_locks = self.pool_locks.lock().unwrap();
+        for pool in &self.pools {
+            if pool_locks.get(pool).map_or(false, |lock| Arc::strong_count(lock) == 1) {
+                pool_locks.remove(pool);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use tokio::task::JoinSet;
+
+    /// Runs one task per pool set and returns the most tasks seen running at once.
+    async fn peak_concurrency(pool: ExecutionPool, pool_sets: Vec<Vec<Address>>) -> usize {
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        let mut set = JoinSet::new();
+        for pools in pool_sets {
+            let (running, peak) = (running.clone(), peak.clone());
+            set.spawn(pool.clone().run(pools, async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+            }));
+        }
+        while set.join_next().await.is_some() {}
+
+        peak.load(Ordering::SeqCst)
+    }
+
+    #[tokio::test]
+    async fn test_disjoint_opportunities_run_concurrently() {
+        let (a, b, c, d) = (Address::random(), Address::random(), Address::random(), Address::random());
+        let peak = peak_concurrency(ExecutionPool::new(4), vec![vec![a, b], vec![c, d]]).await;
+        assert_eq!(peak, 2);
+    }
+
+    #[tokio::test]
+    async fn test_overlapping_opportunities_serialize() {
+        let (a, b, c) = (Address::random(), Address::random(), Address::random());
+        let peak = peak_concurrency(ExecutionPool::new(4), vec![vec![a, b], vec![c, b]]).await;
+        assert_eq!(peak, 1);
+    }
+
+    #[tokio::test]
+    async fn test_finished_pools_are_forgotten() {
+        let pool = ExecutionPool::new(4);
+        let (a, b, c) = (Address::random(), Address::random(), Address::random());
+        peak_concurrency(pool.clone(), vec![vec![a, b], vec![c, b]]).await;
+        assert_eq!(pool.tracked_pools(), 0);
+
+        // A cancelled task releases its pools too
+        let pending = pool.clone().run(vec![a], std::future::pending::<()>());
+        assert!(tokio::time::timeout(Duration::from_millis(10), pending).await.is_err());
+        assert_eq!(pool.tracked_pools(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_worker_limit() {
+        let pool_sets = (0..4).map(|_| vec![Address::random()]).collect();
+        let peak = peak_concurrency(ExecutionPool::new(2), pool_sets).await;
+        assert_eq!(peak, 2);
+    }
+}
@@ -0,0 +1,161 @@
+use anyhow::{Result, anyhow};
+use ethers::types::U256;
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::flashbot::types::TradeResult;
+
+const JOURNAL_HEADER: [&str; 7] = [
+    "timestamp",
+    "path",
+    "profit",
+    "gas_used",
+    "execution_time_ms",
+    "success",
+    "error",
+];
+
+/// Append-only CSV ledger of executed trades, one row per `TradeResult`.
+pub struct TradeJournal {
+    path: PathBuf,
+}
+
+/// Aggregate P&L over a range of journal entries.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JournalSummary {
+    pub trades: u64,
+    pub successful_trades: u64,
+    pub total_profit: U256,
+    pub gas_used: U256,
+}
+
+impl JournalSummary {
+    pub fn win_rate(&self) -> f64 {
+        if self.trades == 0 {
+            return 0.0;
+        }
+        self.successful_trades as f64 / self.trades as f64
+    }
+}
+
+impl TradeJournal {
+    /// Open the journal at `path`, creating it with a header if it doesn't exist.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        if file.metadata()?.len() == 0 {
+            let mut writer = csv::Writer::from_writer(file);
+            writer.write_record(JOURNAL_HEADER)?;
+            writer.flush()?;
+        }
+        Ok(Self { path })
+    }
+
+    pub fn append(&self, result: &TradeResult) -> Result<()> {
+        let file = OpenOptions::new().append(true).open(&self.path)?;
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(file);
+
+        let path = result
+            .opportunity
+            .path
+            .iter()
+            .map(|token| format!("{:?}", token))
+            .collect::<Vec<_>>()
+            .join(">");
+        writer.write_record(&[
+            result.timestamp.to_string(),
+            path,
+            result.actual_profit.to_string(),
+            result.gas_used.to_string(),
+            result.execution_time.as_millis().to_string(),
+            result.success.to_string(),
+            result.error.clone().unwrap_or_default(),
+        ])?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Totals for trades with `from <= timestamp < to`.
+    pub fn summary(&self, from: u64, to: u64) -> Result<JournalSummary> {
+        let mut reader = csv::Reader::from_path(&self.path)?;
+        let mut summary = JournalSummary::default();
+
+        for row in reader.records() {
+            let row = row?;
+            let field = |i: usize| {
+                row.get(i)
+                    .ok_or_else(|| anyhow!("Journal row is missing {}", JOURNAL_HEADER[i]))
+            };
+
+            let timestamp: u64 = field(0)?.parse()?;
+            if timestamp < from || timestamp >= to {
+                continue;
+            }
+
+            summary.trades += 1;
+            summary.gas_used = summary.gas_used.saturating_add(U256::from_dec_str(field(3)?)?);
+            if bool::from_str(field(5)?)? {
+                summary.successful_trades += 1;
+                summary.total_profit = summary
+                    .total_profit
+                    .saturating_add(U256::from_dec_str(field(2)?)?);
+            }
+        }
+
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::opportunity;
+    use ethers::types::Address;
+    use std::time::Duration;
+
+    fn trade(timestamp: u64, profit: u64, success: bool) -> TradeResult {
+        TradeResult {
+            opportunity: opportunity(profit, 100),
+            actual_profit: U256::from(profit),
+            gas_used: U256::from(200_000),
+            execution_time: Duration::from_millis(800),
+            success,
+            error: (!success).then(|| "reverted".to_string()),
+            timestamp,
+            paper: false,
+        }
+    }
+
+    #[test]
+    fn test_journal_summary_over_time_range() {
+        let path = std::env::temp_dir().join(format!("trade-journal-{:?}.csv", Address::random()));
+        let journal = TradeJournal::open(&path).unwrap();
+
+        journal.append(&trade(100, 50, true)).unwrap();
+        journal.append(&trade(200, 70, true)).unwrap();
+        journal.append(&trade(300, 0, false)).unwrap();
+        journal.append(&trade(400, 30, true)).unwrap();
+
+        let summary = journal.summary(0, u64::MAX).unwrap();
+        assert_eq!(summary.trades, 4);
+        assert_eq!(summary.total_profit, U256::from(150));
+        assert_eq!(summary.gas_used, U256::from(800_000));
+        assert_eq!(summary.win_rate(), 0.75);
+
+        // Only the trades at 200 and 300
+        let summary = journal.summary(200, 400).unwrap();
+        assert_eq!(summary.trades, 2);
+        assert_eq!(summary.total_profit, U256::from(70));
+        assert_eq!(summary.win_rate(), 0.5);
+
+        // Reopening keeps existing rows and doesn't repeat the header
+        let journal = TradeJournal::open(&path).unwrap();
+        journal.append(&trade(500, 10, true)).unwrap();
+        assert_eq!(journal.summary(0, u64::MAX).unwrap().trades, 5);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,12 +1,17 @@
 use anyhow::Result;
+use log::info;
 use ethers::{
     types::{Address, U256},
     providers::{Provider, Http},
 };
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 use std::collections::HashMap;
 
+use crate::flashbot::mev_protection::ensure_public_send_allowed;
+use crate::flashbot::types::{ArbitrageOpportunity, TradeResult};
+
 pub struct MarketMaker {
     // Liquidity config
     max_pool_exposure: U256,
@@ -23,6 +28,13 @@ pub struct MarketMaker {
     // Current state
     current_positions: Arc<RwLock<HashMap<Address, U256>>>,
     current_spreads: Arc<RwLock<HashMap<Address, u16>>>,
+
+    // Log and record rebalances instead of trading
+    paper_trading: bool,
+    paper_trades: Arc<RwLock<Vec<TradeResult>>>,
+
+    // Rebalances trade through the public mempool, so refuse them
+    private_only: bool,
 }
 
 impl MarketMaker {
@@ -40,13 +52,31 @@ impl MarketMaker {
             inventory_range: HashMap::new(),
             current_positions: Arc::new(RwLock::new(HashMap::new())),
             current_spreads: Arc::new(RwLock::new(HashMap::new())),
+            paper_trading: false,
+            paper_trades: Arc::new(RwLock::new(Vec::new())),
+            private_only: false,
         }
     }
 
+    pub fn with_paper_trading(mut self, paper_trading: bool) -> Self {
+        self.paper_trading = paper_trading;
+        self
+    }
+
+    pub fn with_private_only(mut self, private_only: bool) -> Self {
+        self.private_only = private_only;
+        self
+    }
+
+    /// Rebalances recorded in paper mode.
+    pub async fn paper_trades(&self) -> Vec<TradeResult> {
+        self.paper_trades.read().await.clone()
+    }
+
     /// Update position for token
     pub async fn update_position(&self, token: Address, amount: U256) -> Result<()> {
-        let mut positions = self.current_positions.write().await;
-        positions.insert(token, amount);
+        // Released before rebalancing, which reads the positions again
+        self.current_positions.write().await.insert(token, amount);
         
         // Check if rebalance needed
         if self.needs_rebalance(token, amount).await? {
@@ -104,6 +134,19 @@ impl MarketMaker {
             .unwrap_or_default();
             
         if let Some(&target) = self.target_inventory.get(&token) {
+            if self.paper_trading {
+                info!("[paper] Would rebalance {:?} from {} to {}", token, current, target);
+                let rebalance = ArbitrageOpportunity {
+                    path: vec![token],
+                    required_flash_amount: if current > target { current - target } else { target - current },
+                    profit_token: token,
+                    ..Default::default()
+                };
+                let result = TradeResult::paper(&rebalance, Duration::ZERO);
+                self.paper_trades.write().await.push(result);
+                return Ok(());
+            }
+            ensure_public_send_allowed(self.private_only, "a rebalance")?;
             if current > target {
                 // Reduce position
                 let amount = current - target;
@@ -166,3 +209,36 @@ impl MarketMaker {
         Ok(impact.min(1.0))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_paper_rebalance_does_not_trade() {
+        let token = Address::random();
+        let mut market_maker = MarketMaker::new(U256::from(1_000_000), 10, 30).with_paper_trading(true);
+        market_maker.target_inventory.insert(token, U256::from(1_000));
+
+        // 50% over target, so a rebalance is due but only logged
+        market_maker.update_position(token, U256::from(1_500)).await.unwrap();
+        assert_eq!(
+            market_maker.current_positions.read().await.get(&token),
+            Some(&U256::from(1_500))
+        );
+
+        let recorded = market_maker.paper_trades().await;
+        assert_eq!(recorded.len(), 1);
+        assert!(recorded[0].paper);
+        assert_eq!(recorded[0].opportunity.required_flash_amount, U256::from(500));
+    }
+    #[tokio::test]
+    async fn test_private_only_refuses_rebalance() {
+        let token = Address::random();
+        let mut market_maker = MarketMaker::new(U256::from(1_000_000), 10, 30).with_private_only(true);
+        market_maker.target_inventory.insert(token, U256::from(1_000));
+
+        let err = market_maker.update_position(token, U256::from(1_500)).await.unwrap_err();
+        assert!(err.to_string().contains("Private-only"), "{}", err);
+    }
+}
@@ -1,34 +1,199 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use ethers::{
-    types::{Address, Transaction, U256, BlockNumber},
+    abi::{decode, ParamType},
+    types::{Address, Log, Transaction, TransactionReceipt, H256, U256, U64, BlockNumber},
     providers::{Provider, Http, Middleware},
+    signers::{LocalWallet, Signer},
+    utils::keccak256,
 };
-use std::sync::Arc;
+use futures::stream::{FuturesUnordered, StreamExt};
+use rand::Rng;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::sync::RwLock;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+
+use crate::streams::V2_SWAP_EVENT;
+
+/// Pending transactions per block above which the mempool counts as congested.
+pub const CONGESTED_PENDING_COUNT: usize = 1000;
+/// How often a relay submission checks whether its transaction was mined.
+pub const INCLUSION_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Fails in private-only mode, where `what` would reach the public mempool.
+pub fn ensure_public_send_allowed(private_only: bool, what: &str) -> Result<()> {
+    if private_only {
+        return Err(anyhow!("Private-only mode: not sending {} to the public mempool", what));
+    }
+    Ok(())
+}
+
+/// What the block delay is judged on.
+#[derive(Debug, Clone, Default)]
+pub struct MempoolConditions {
+    pub pending_count: usize,
+    pub gas_volatile: bool,
+    pub similar_pending: bool,
+    /// A known sandwich bot has a transaction pending.
+    pub sandwich_bot_pending: bool,
+}
+
+/// A V2 pair `Swap`, as read from a block's receipts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolSwap {
+    pub pool: Address,
+    /// Sender of the transaction that swapped
+    pub sender: Address,
+    /// Token0 in, token1 out
+    pub zero_for_one: bool,
+    /// `(transaction index, log index)` within the block
+    pub position: (U64, U256),
+}
+
+/// A private relay or builder endpoint that bundles can be sent to.
+#[async_trait::async_trait]
+pub trait Relay: Send + Sync {
+    /// Identifies the endpoint; relays with the same name are only sent to once.
+    fn name(&self) -> &str;
+
+    /// Submit `tx` for `target_block`, resolving once the relay reports it included.
+    async fn submit(&self, tx: &Transaction, target_block: U64) -> Result<()>;
+}
+
+/// A relay reached over JSON-RPC. Transactions go out with
+/// `eth_sendPrivateTransaction`, valid up to the target block, and carry an
+/// `X-Flashbots-Signature` when there's an auth signer. Inclusion is watched
+/// on `provider`.
+pub struct RpcRelay<M> {
+    name: String,
+    url: String,
+    auth_signer: Option<LocalWallet>,
+    provider: Arc<M>,
+    poll_interval: Duration,
+}
+
+impl<M: Middleware> RpcRelay<M> {
+    pub fn new(name: &str, url: &str, provider: Arc<M>) -> Self {
+        Self {
+            name: name.to_string(),
+            url: url.to_string(),
+            auth_signer: None,
+            provider,
+            poll_interval: INCLUSION_POLL_INTERVAL,
+        }
+    }
+
+    pub fn with_auth_signer(mut self, auth_signer: LocalWallet) -> Self {
+        self.auth_signer = Some(auth_signer);
+        self
+    }
+
+    /// The `eth_sendPrivateTransaction` request for `tx` up to `target_block`.
+    pub fn request_body(tx: &Transaction, target_block: U64) -> String {
+        json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "eth_sendPrivateTransaction",
+            "params": [{
+                "tx": tx.rlp(),
+                "maxBlockNumber": target_block,
+            }],
+        })
+        .to_string()
+    }
+
+    /// Resolve once `hash` is mined, failing once `target_block` passes without it.
+    async fn wait_for_inclusion(&self, hash: H256, target_block: U64) -> Result<()> {
+        loop {
+            let receipt = self
+                .provider
+                .get_transaction_receipt(hash)
+                .await
+                .map_err(|e| anyhow!("Failed to fetch the receipt of {:?}: {}", hash, e))?;
+            if receipt.is_some() {
+                return Ok(());
+            }
+            let block = self
+                .provider
+                .get_block_number()
+                .await
+                .map_err(|e| anyhow!("Failed to fetch the block number: {}", e))?;
+            if block >= target_block {
+                return Err(anyhow!("not included by block {}", target_block));
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<M: Middleware + 'static> Relay for RpcRelay<M> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn submit(&self, tx: &Transaction, target_block: U64) -> Result<()> {
+        let body = Self::request_body(tx, target_block);
+        let mut request = reqwest::Client::new()
+            .post(&self.url)
+            .header("Content-Type", "application/json");
+        if let Some(signer) = &self.auth_signer {
+            let body_hash = format!("0x{}", hex::encode(keccak256(body.as_bytes())));
+            let signature = signer.sign_message(body_hash).await?;
+            request = request.header("X-Flashbots-Signature", format!("{:?}:0x{}", signer.address(), signature));
+        }
+
+        let response = request.body(body).send().await?.json::<Value>().await?;
+        if let Some(e) = response.get("error") {
+            return Err(anyhow!("eth_sendPrivateTransaction error: {}", e));
+        }
+        self.wait_for_inclusion(tx.hash, target_block).await
+    }
+}
+
+/// Removes a transaction from the in-flight set when its fan-out finishes.
+struct InFlightTx<'a> {
+    in_flight: &'a Mutex<HashSet<H256>>,
+    hash: H256,
+}
+
+impl Drop for InFlightTx<'_> {
+    fn drop(&mut self) {
+        self.in_flight.lock().unwrap().remove(&self.hash);
+    }
+}
 
 pub struct MEVProtection {
     // Flashbots RPC endpoint
     flashbots_endpoint: String,
     // Eden network endpoint
     eden_endpoint: Option<String>,
-    // Private transaction relayer
-    private_relayer: Option<Address>,
+    // Private transaction relayer RPC endpoint
+    private_relayer: Option<String>,
     // Maximum tip for priority
     max_tip: U256,
     // Minimum blocks to wait
     min_block_delay: u64,
+    // Random extra blocks, up to this many, so our bundles' timing isn't predictable
+    max_jitter_blocks: u64,
     // Set of known sandwich bots
-    sandwich_bots: HashSet<Address>,
+    sandwich_bots: Mutex<HashSet<Address>>,
     // Pending transaction monitoring
     monitor_mempool: bool,
+    // Never let a transaction reach the public mempool
+    private_only: bool,
+    // Relays fanned out to by `submit_to_all_relays`
+    relays: Vec<Arc<dyn Relay>>,
+    // Transactions currently being fanned out
+    in_flight: Mutex<HashSet<H256>>,
 }
 
 impl MEVProtection {
     pub fn new(
         flashbots_endpoint: String,
         eden_endpoint: Option<String>,
-        private_relayer: Option<Address>,
+        private_relayer: Option<String>,
         max_tip: U256,
     ) -> Self {
         Self {
@@ -37,9 +202,63 @@ impl MEVProtection {
             private_relayer,
             max_tip,
             min_block_delay: 1,
-            sandwich_bots: HashSet::new(),
+            max_jitter_blocks: 0,
+            sandwich_bots: Mutex::new(HashSet::new()),
             monitor_mempool: true,
+            private_only: false,
+            relays: Vec::new(),
+            in_flight: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Add a relay to fan out to, unless one with the same name is already added.
+    pub fn with_relay(mut self, relay: Arc<dyn Relay>) -> Self {
+        if self.relays.iter().all(|existing| existing.name() != relay.name()) {
+            self.relays.push(relay);
         }
+        self
+    }
+
+    pub fn with_min_block_delay(mut self, min_block_delay: u64) -> Self {
+        self.min_block_delay = min_block_delay;
+        self
+    }
+
+    pub fn with_private_only(mut self, private_only: bool) -> Self {
+        self.private_only = private_only;
+        self
+    }
+
+    /// Add a relay for each configured endpoint: Flashbots authenticated by
+    /// `auth_signer`, Eden and the private relayer without. Inclusion is
+    /// watched on `provider`.
+    pub fn with_endpoint_relays<M: Middleware + 'static>(self, provider: Arc<M>, auth_signer: LocalWallet) -> Self {
+        let mut relays = Vec::new();
+        if !self.flashbots_endpoint.is_empty() {
+            relays.push(
+                RpcRelay::new("flashbots", &self.flashbots_endpoint, provider.clone())
+                    .with_auth_signer(auth_signer),
+            );
+        }
+        if let Some(eden) = &self.eden_endpoint {
+            relays.push(RpcRelay::new("eden", eden, provider.clone()));
+        }
+        if let Some(relayer) = &self.private_relayer {
+            relays.push(RpcRelay::new("relayer", relayer, provider.clone()));
+        }
+        relays
+            .into_iter()
+            .fold(self, |protection, relay| protection.with_relay(Arc::new(relay)))
+    }
+
+    /// Whether any private relay has been added.
+    pub fn has_private_relay(&self) -> bool {
+        !self.relays.is_empty()
+    }
+
+    pub fn with_max_jitter_blocks(mut self, max_jitter_blocks: u64) -> Self {
+        self.max_jitter_blocks = max_jitter_blocks;
+        self
     }
 
     /// Check if transaction might be sandwiched
@@ -54,7 +273,7 @@ impl MEVProtection {
         // Look for potential sandwich attacks
         for ptx in pending_txs {
             // Check if from known sandwich bot
-            if self.sandwich_bots.contains(&ptx.from) {
+            if self.is_sandwich_bot(ptx.from) {
                 return Ok(true);
             }
             
@@ -72,26 +291,55 @@ impl MEVProtection {
         Ok(false)
     }
 
-    /// Submit transaction through private channels
-    pub async fn submit_private_tx(&self, tx: Transaction) -> Result<()> {
-        // Try Flashbots first
-        if let Ok(_) = self.submit_to_flashbots(&tx).await {
-            return Ok(());
+    /// Submit `tx` through every private relay for `target_block`, returning
+    /// the one that got it included. Never falls back to the public mempool;
+    /// outside private-only mode that is up to the caller.
+    pub async fn submit_private_tx(&self, tx: Transaction, target_block: U64) -> Result<String> {
+        if !self.has_private_relay() {
+            return Err(if self.private_only {
+                anyhow!("Private-only submission, but no private relay is configured")
+            } else {
+                anyhow!("No private relay is configured")
+            });
         }
+        self.submit_to_all_relays(&tx, target_block).await
+    }
 
-        // Try Eden network as backup
-        if let Some(ref eden) = self.eden_endpoint {
-            if let Ok(_) = self.submit_to_eden(&tx).await {
-                return Ok(());
-            }
+    /// Submit `tx` to every relay at once for `target_block`, returning the
+    /// name of the first to confirm inclusion. The other submissions are
+    /// dropped as soon as one succeeds, and a transaction already being
+    /// fanned out isn't submitted again. Dropping them only stops waiting:
+    /// nothing is cancelled at the relays, which is harmless since every relay
+    /// holds the same signed transaction and its nonce can only land once.
+    pub async fn submit_to_all_relays(&self, tx: &Transaction, target_block: U64) -> Result<String> {
+        if self.relays.is_empty() {
+            return Err(anyhow!("No relays configured"));
         }
-
-        // Fall back to private relayer
-        if let Some(relayer) = self.private_relayer {
-            self.submit_to_relayer(&tx, relayer).await?;
+        if !self.in_flight.lock().unwrap().insert(tx.hash) {
+            return Err(anyhow!("{:?} is already being submitted", tx.hash));
         }
+        let _in_flight = InFlightTx {
+            in_flight: &self.in_flight,
+            hash: tx.hash,
+        };
+
+        let mut submissions: FuturesUnordered<_> = self
+            .relays
+            .iter()
+            .map(|relay| async move { (relay.name(), relay.submit(tx, target_block).await) })
+            .collect();
 
-        Ok(())
+        let mut errors = Vec::new();
+        while let Some((name, result)) = submissions.next().await {
+            match result {
+                Ok(()) => return Ok(name.to_string()),
+                Err(e) => errors.push(format!("{}: {}", name, e)),
+            }
+        }
+        Err(anyhow!(
+            "No relay included {:?} for block {}: {}",
+            tx.hash, target_block, errors.join("; ")
+        ))
     }
 
     /// Monitor mempool for frontrunning attempts
@@ -113,44 +361,452 @@ impl MEVProtection {
 
     /// Calculate optimal block delay to avoid sandwiching
     pub async fn calculate_block_delay(&self, tx: &Transaction) -> Result<u64> {
+        let conditions = MempoolConditions {
+            pending_count: self.get_pending_count().await?,
+            gas_volatile: self.is_gas_volatile().await?,
+            similar_pending: self.has_similar_pending(tx).await?,
+            sandwich_bot_pending: false,
+        };
+        Ok(self.block_delay(&conditions))
+    }
+
+    /// Blocks to wait given the mempool `conditions`, before jitter
+    pub fn block_delay(&self, conditions: &MempoolConditions) -> u64 {
         let mut delay = self.min_block_delay;
         
         // Check mempool congestion
-        let pending_count = self.get_pending_count().await?;
-        if pending_count > 1000 {
+        if conditions.pending_count > CONGESTED_PENDING_COUNT {
             delay += 1;
         }
         
         // Check gas price volatility
-        if self.is_gas_volatile().await? {
+        if conditions.gas_volatile {
             delay += 1;
         }
         
         // Check for similar transactions
-        if self.has_similar_pending(tx).await? {
+        if conditions.similar_pending {
             delay += 2;
         }
+
+        // Check for active sandwich bots
+        if conditions.sandwich_bot_pending {
+            delay += 1;
+        }
         
-        Ok(delay)
+        delay
     }
 
-    /// Update list of known sandwich bots
-    pub async fn update_sandwich_bots(&mut self) -> Result<()> {
-        // Analyze recent blocks for sandwich patterns
-        let recent_blocks = self.get_recent_blocks(1000).await?;
-        
-        for block in recent_blocks {
-            let txs = block.transactions;
-            
-            // Look for sandwich patterns
-            for i in 0..txs.len().saturating_sub(2) {
-                if self.is_sandwich_pattern(&txs[i], &txs[i+1], &txs[i+2]) {
-                    self.sandwich_bots.insert(txs[i].from);
-                    self.sandwich_bots.insert(txs[i+2].from);
+    /// Block to target from `block_number` after `delay` blocks plus jitter.
+    /// A delay of 1 is the next block.
+    pub fn target_block(&self, block_number: U64, delay: u64) -> U64 {
+        let jitter = rand::thread_rng().gen_range(0..=self.max_jitter_blocks);
+        block_number + delay.max(1) + jitter
+    }
+
+    /// Whether `sender` has been seen sandwiching other transactions
+    pub fn is_sandwich_bot(&self, sender: Address) -> bool {
+        self.sandwich_bots.lock().unwrap().contains(&sender)
+    }
+
+    /// Senders seen sandwiching other transactions
+    pub fn sandwich_bots(&self) -> HashSet<Address> {
+        self.sandwich_bots.lock().unwrap().clone()
+    }
+
+    /// Update list of known sandwich bots from the `blocks` blocks up to
+    /// `to_block`, judged on the pair `Swap` logs in their receipts.
+    /// Returns how many sandwiches were found.
+    pub async fn update_sandwich_bots<M: Middleware>(
+        &self,
+        provider: &M,
+        to_block: U64,
+        blocks: u64,
+    ) -> Result<usize> {
+        let to_block = to_block.as_u64();
+        let mut found = 0;
+        for number in to_block.saturating_sub(blocks.saturating_sub(1))..=to_block {
+            let receipts = provider
+                .get_block_receipts(number)
+                .await
+                .map_err(|e| anyhow!("Failed to fetch the receipts of block {}: {}", number, e))?;
+            found += self.record_sandwiches(&swaps_from_receipts(&receipts));
+        }
+        Ok(found)
+    }
+
+    /// Add the senders of every sandwich among `swaps` of one block. Each
+    /// front-run is paired with the sender's next swap back on the same pool;
+    /// any number of other transactions may sit in between.
+    pub fn record_sandwiches(&self, swaps: &[PoolSwap]) -> usize {
+        let mut by_pool: HashMap<Address, Vec<&PoolSwap>> = HashMap::new();
+        for swap in swaps {
+            by_pool.entry(swap.pool).or_default().push(swap);
+        }
+
+        let mut bots = self.sandwich_bots.lock().unwrap();
+        let mut found = 0;
+        for pool_swaps in by_pool.values_mut() {
+            pool_swaps.sort_by_key(|swap| swap.position);
+            for (i, front) in pool_swaps.iter().enumerate() {
+                let rest = &pool_swaps[i + 1..];
+                let back = match rest
+                    .iter()
+                    .position(|swap| swap.sender == front.sender && swap.zero_for_one != front.zero_for_one)
+                {
+                    Some(back) => back,
+                    None => continue,
+                };
+                if rest[..back]
+                    .iter()
+                    .any(|victim| Self::is_sandwich_pattern(front, victim, rest[back]))
+                {
+                    bots.insert(front.sender);
+                    found += 1;
                 }
             }
         }
-        
-        Ok(())
+        found
+    }
+
+    /// `front` and `back` come from the same sender and bracket `victim` from
+    /// someone else on the same pool: `front` swaps in the same direction as
+    /// `victim` and `back` swaps it back.
+    pub fn is_sandwich_pattern(front: &PoolSwap, victim: &PoolSwap, back: &PoolSwap) -> bool {
+        front.pool == victim.pool
+            && front.pool == back.pool
+            && front.sender == back.sender
+            && front.sender != victim.sender
+            && front.position < victim.position
+            && victim.position < back.position
+            && front.zero_for_one == victim.zero_for_one
+            && front.zero_for_one != back.zero_for_one
+    }
+}
+
+/// Every V2 pair swap in `receipts`, attributed to its transaction's sender.
+pub fn swaps_from_receipts(receipts: &[TransactionReceipt]) -> Vec<PoolSwap> {
+    receipts
+        .iter()
+        .flat_map(|receipt| {
+            receipt
+                .logs
+                .iter()
+                .filter_map(move |log| decode_v2_swap(log, receipt.from, receipt.transaction_index))
+        })
+        .collect()
+}
+
+/// Decode a V2 pair `Swap` log. Swaps that pay in both tokens have no single
+/// direction and are skipped.
+pub fn decode_v2_swap(log: &Log, sender: Address, transaction_index: U64) -> Option<PoolSwap> {
+    if log.removed.unwrap_or(false) || log.topics.first() != Some(&H256::from(keccak256(V2_SWAP_EVENT))) {
+        return None;
+    }
+    // amount0In, amount1In, amount0Out, amount1Out
+    let amounts = decode(&[ParamType::Uint(256); 4], &log.data)
+        .ok()?
+        .into_iter()
+        .map(|token| token.into_uint())
+        .collect::<Option<Vec<U256>>>()?;
+    let zero_for_one = match (amounts[0].is_zero(), amounts[1].is_zero()) {
+        (false, true) => true,
+        (true, false) => false,
+        _ => return None,
+    };
+    Some(PoolSwap {
+        pool: log.address,
+        sender,
+        zero_for_one,
+        position: (transaction_index, log.log_index.unwrap_or_default()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::Token;
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    fn protection() -> MEVProtection {
+        MEVProtection::new("https://relay.flashbots.net".to_string(), None, None, U256::zero())
+    }
+
+    /// A swap by `sender` on `pool`, the block's `index`th transaction
+    fn swap(sender: Address, pool: Address, zero_for_one: bool, index: u64) -> PoolSwap {
+        PoolSwap {
+            pool,
+            sender,
+            zero_for_one,
+            position: (U64::from(index), U256::zero()),
+        }
+    }
+
+    struct MockRelay {
+        name: String,
+        delay: Duration,
+        include: bool,
+        submissions: AtomicUsize,
+        finished: AtomicUsize,
+    }
+
+    impl MockRelay {
+        fn new(name: &str, delay_ms: u64, include: bool) -> Arc<Self> {
+            Arc::new(Self {
+                name: name.to_string(),
+                delay: Duration::from_millis(delay_ms),
+                include,
+                submissions: AtomicUsize::new(0),
+                finished: AtomicUsize::new(0),
+            })
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Relay for MockRelay {
+        fn name(&self) -> &str {
+            &self.name
+        }
+
+        async fn submit(&self, _tx: &Transaction, _target_block: U64) -> Result<()> {
+            self.submissions.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            self.finished.fetch_add(1, Ordering::SeqCst);
+            if self.include {
+                Ok(())
+            } else {
+                Err(anyhow!("not included"))
+            }
+        }
+    }
+
+    fn fan_out(relays: &[Arc<MockRelay>]) -> MEVProtection {
+        relays.iter().fold(protection(), |protection, relay| {
+            protection.with_relay(relay.clone())
+        })
+    }
+
+    #[tokio::test]
+    async fn test_fastest_relay_wins() {
+        let fast = MockRelay::new("fast", 5, true);
+        let slow = MockRelay::new("slow", 500, true);
+        let failing = MockRelay::new("failing", 1, false);
+        let protection = fan_out(&[slow.clone(), failing.clone(), fast.clone()]);
+
+        let winner = protection
+            .submit_to_all_relays(&Transaction::default(), U64::from(101))
+            .await
+            .unwrap();
+
+        assert_eq!(winner, "fast");
+        // Every relay got the transaction, and the slow one is no longer awaited
+        for relay in [&fast, &slow, &failing] {
+            assert_eq!(relay.submissions.load(Ordering::SeqCst), 1);
+        }
+        assert_eq!(slow.finished.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_fan_out_fails_when_no_relay_includes() {
+        let protection = fan_out(&[MockRelay::new("a", 1, false), MockRelay::new("b", 2, false)]);
+
+        let err = protection
+            .submit_to_all_relays(&Transaction::default(), U64::from(101))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("a: not included"), "{}", err);
+
+        // The failed submission no longer counts as in flight, so it can be retried
+        assert!(protection.in_flight.lock().unwrap().is_empty());
+        let retry = protection
+            .submit_to_all_relays(&Transaction::default(), U64::from(101))
+            .await
+            .unwrap_err();
+        assert!(!retry.to_string().contains("already being submitted"), "{}", retry);
+    }
+
+    #[tokio::test]
+    async fn test_relays_and_submissions_are_deduplicated() {
+        let relay = MockRelay::new("flashbots", 20, true);
+        let protection = fan_out(&[relay.clone(), MockRelay::new("flashbots", 1, true)]);
+        let tx = Transaction::default();
+
+        let (first, second) = tokio::join!(
+            protection.submit_to_all_relays(&tx, U64::from(101)),
+            protection.submit_to_all_relays(&tx, U64::from(101)),
+        );
+
+        assert!(first.is_ok());
+        assert!(second.unwrap_err().to_string().contains("already being submitted"));
+        assert_eq!(relay.submissions.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_private_only_without_relay_fails() {
+        let protection = MEVProtection::new(String::new(), None, None, U256::zero())
+            .with_private_only(true);
+        assert!(!protection.has_private_relay());
+
+        let err = protection
+            .submit_private_tx(Transaction::default(), U64::from(101))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("no private relay"), "{}", err);
+    }
+
+    #[test]
+    fn test_endpoints_become_relays() {
+        let (provider, _) = Provider::mocked();
+        let protection = MEVProtection::new(
+            "https://relay.flashbots.net".to_string(),
+            Some("https://api.edennetwork.io/v1/rpc".to_string()),
+            None,
+            U256::zero(),
+        )
+        .with_endpoint_relays(Arc::new(provider), LocalWallet::new(&mut rand::thread_rng()));
+
+        let names: Vec<&str> = protection.relays.iter().map(|relay| relay.name()).collect();
+        assert_eq!(names, vec!["flashbots", "eden"]);
+        assert!(protection.has_private_relay());
+    }
+
+    #[test]
+    fn test_private_transaction_request() {
+        let tx = Transaction::default();
+        let body: Value = serde_json::from_str(
+            &RpcRelay::<Provider<ethers::providers::MockProvider>>::request_body(&tx, U64::from(101)),
+        )
+        .unwrap();
+
+        assert_eq!(body["method"], "eth_sendPrivateTransaction");
+        assert_eq!(body["params"][0]["maxBlockNumber"], "0x65");
+        assert_eq!(body["params"][0]["tx"], json!(tx.rlp()));
+    }
+
+    #[tokio::test]
+    async fn test_relay_gives_up_after_target_block() {
+        let (provider, mock) = Provider::mocked();
+        // Served last-in first-out: no receipt, then the target block
+        mock.push(U64::from(101)).unwrap();
+        mock.push::<Option<TransactionReceipt>, _>(None).unwrap();
+        let relay = RpcRelay::new("flashbots", "http://localhost:8545", Arc::new(provider));
+
+        let err = relay.wait_for_inclusion(H256::random(), U64::from(101)).await.unwrap_err();
+        assert!(err.to_string().contains("not included by block 101"), "{}", err);
+    }
+
+    #[test]
+    fn test_detects_sandwich() {
+        let (bot, victim, pool) = (Address::random(), Address::random(), Address::random());
+        let swaps = [
+            swap(bot, pool, true, 3),
+            // Unrelated trades in between, on the same pool and elsewhere
+            swap(Address::random(), Address::random(), false, 4),
+            swap(Address::random(), pool, false, 5),
+            swap(victim, pool, true, 6),
+            swap(Address::random(), Address::random(), true, 7),
+            swap(bot, pool, false, 9),
+        ];
+
+        let protection = protection();
+        assert_eq!(protection.record_sandwiches(&swaps), 1);
+        assert!(protection.is_sandwich_bot(bot));
+        assert!(!protection.is_sandwich_bot(victim));
+    }
+
+    #[test]
+    fn test_ignores_non_sandwiches() {
+        let (bot, victim, pool) = (Address::random(), Address::random(), Address::random());
+
+        // The back-run trades the same direction again
+        let same_direction = [
+            swap(bot, pool, true, 0),
+            swap(victim, pool, true, 1),
+            swap(bot, pool, true, 2),
+        ];
+        // The middle trade is the bot's own
+        let own_trade = [
+            swap(bot, pool, true, 0),
+            swap(bot, pool, true, 1),
+            swap(bot, pool, false, 2),
+        ];
+        // The victim sells while the bot buys
+        let opposite_victim = [
+            swap(bot, pool, true, 0),
+            swap(victim, pool, false, 1),
+            swap(bot, pool, false, 2),
+        ];
+        // The back-run is on another pool
+        let other_pool = [
+            swap(bot, pool, true, 0),
+            swap(victim, pool, true, 1),
+            swap(bot, Address::random(), false, 2),
+        ];
+
+        let protection = protection();
+        for swaps in [same_direction, own_trade, opposite_victim, other_pool] {
+            assert_eq!(protection.record_sandwiches(&swaps), 0);
+        }
+        assert!(protection.sandwich_bots().is_empty());
+    }
+
+    #[test]
+    fn test_decodes_swaps_from_receipts() {
+        let (pool, sender) = (Address::random(), Address::random());
+        let swap_log = |amounts: [u64; 4]| Log {
+            address: pool,
+            topics: vec![H256::from(keccak256(V2_SWAP_EVENT)), H256::random(), H256::random()],
+            data: ethers::abi::encode(&amounts.map(|amount| Token::Uint(U256::from(amount)))).into(),
+            log_index: Some(U256::from(2)),
+            ..Default::default()
+        };
+        let receipt = TransactionReceipt {
+            from: sender,
+            transaction_index: U64::from(7),
+            logs: vec![
+                swap_log([100, 0, 0, 95]),
+                swap_log([0, 100, 95, 0]),
+                // Flash swap paying back both tokens
+                swap_log([100, 100, 95, 0]),
+                Log { address: pool, topics: vec![H256::random()], ..Default::default() },
+            ],
+            ..Default::default()
+        };
+
+        let swaps = swaps_from_receipts(&[receipt]);
+        assert_eq!(
+            swaps,
+            vec![
+                PoolSwap { pool, sender, zero_for_one: true, position: (U64::from(7), U256::from(2)) },
+                PoolSwap { pool, sender, zero_for_one: false, position: (U64::from(7), U256::from(2)) },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_congestion_pushes_target_block_back() {
+        let protection = protection();
+        let quiet = MempoolConditions { pending_count: 10, ..Default::default() };
+        let congested = MempoolConditions { pending_count: 5_000, ..Default::default() };
+
+        let block = U64::from(100);
+        assert_eq!(protection.target_block(block, protection.block_delay(&quiet)), U64::from(101));
+        assert_eq!(protection.target_block(block, protection.block_delay(&congested)), U64::from(102));
+
+        let bot_pending = MempoolConditions { sandwich_bot_pending: true, ..Default::default() };
+        assert_eq!(protection.target_block(block, protection.block_delay(&bot_pending)), U64::from(102));
+    }
+
+    #[test]
+    fn test_jitter_stays_within_bounds() {
+        let protection = protection().with_max_jitter_blocks(2);
+        let block = U64::from(100);
+
+        let targets: HashSet<U64> = (0..200).map(|_| protection.target_block(block, 1)).collect();
+        assert!(targets.iter().all(|target| (101..=103).contains(&target.as_u64())));
+        assert!(targets.len() > 1);
     }
 }
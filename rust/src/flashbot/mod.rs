@@ -0,0 +1,7 @@
+pub mod arbitrage;
+pub mod contracts;
+pub mod execution_pool;
+pub mod journal;
+pub mod market_maker;
+pub mod mev_protection;
+pub mod types;
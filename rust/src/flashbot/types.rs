@@ -1,9 +1,11 @@
+use anyhow::{anyhow, Result};
 use ethers::types::{Address, U256};
 use serde::{Serialize, Deserialize};
-use std::collections::HashMap;
-use std::time::Duration;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ArbitrageOpportunity {
     pub path: Vec<Address>,           // Path of tokens/pools
     pub expected_profit: U256,        // Expected profit in USD
@@ -13,6 +15,51 @@ pub struct ArbitrageOpportunity {
     pub execution_time_ms: u64,      // Expected execution time
     pub pools: Vec<PoolInfo>,        // Pools involved in arbitrage
     pub profit_token: Address,       // Token to receive profit in
+    pub discovered_at_block: u64,    // Block the opportunity was found in
+}
+
+impl ArbitrageOpportunity {
+    /// Whether any token along the path is in `tokens`.
+    pub fn routes_through_any(&self, tokens: &HashSet<Address>) -> bool {
+        self.path.iter().any(|token| tokens.contains(token))
+    }
+
+    /// Total price impact of trading `required_flash_amount` along the path,
+    /// in bps: the sum over hops of `amount_in / reserve_in`. Hop `i` trades
+    /// `path[i]` (wrapping around) through `pools[i]`; each hop's output, by
+    /// constant product and before fees, is the next hop's input. Only
+    /// meaningful when every pool is constant-product. None if a pool doesn't
+    /// hold its input token or has no reserves of it.
+    pub fn price_impact_bps(&self) -> Option<U256> {
+        if self.path.is_empty() {
+            return None;
+        }
+
+        let mut amount_in = self.required_flash_amount;
+        let mut total = U256::zero();
+        for (hop, pool) in self.pools.iter().enumerate() {
+            let token_in = self.path[hop % self.path.len()];
+            let (reserve_in, reserve_out) = if token_in == pool.token0 {
+                pool.reserves
+            } else if token_in == pool.token1 {
+                (pool.reserves.1, pool.reserves.0)
+            } else {
+                return None;
+            };
+            if reserve_in.is_zero() {
+                return None;
+            }
+
+            total = total.saturating_add(amount_in.saturating_mul(U256::from(10_000)) / reserve_in);
+            amount_in = amount_in.saturating_mul(reserve_out) / reserve_in.saturating_add(amount_in);
+        }
+        Some(total)
+    }
+
+    /// More than `max_age` blocks have passed since the opportunity was found.
+    pub fn is_expired(&self, current_block: u64, max_age: u64) -> bool {
+        current_block.saturating_sub(self.discovered_at_block) > max_age
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,6 +82,13 @@ pub enum DexProtocol {
     Custom(Address),
 }
 
+impl DexProtocol {
+    /// Prices by `x * y = k` on its reserves, as `price_impact_bps` assumes.
+    pub fn is_constant_product(&self) -> bool {
+        matches!(self, DexProtocol::UniswapV2)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FlashLoanSource {
     pub protocol: LendingProtocol,
@@ -65,6 +119,62 @@ pub struct RiskConfig {
     pub min_profit_threshold: U256,
 }
 
+/// Open exposure per token, so concurrent trades can't together borrow more
+/// of a token than `RiskConfig::max_position_size`.
+#[derive(Debug, Default)]
+pub struct PositionTracker {
+    open: Mutex<HashMap<Address, U256>>,
+}
+
+/// Exposure opened by `PositionTracker::open`, closed when dropped.
+#[derive(Debug)]
+pub struct PositionGuard {
+    tracker: Arc<PositionTracker>,
+    token: Address,
+    amount: U256,
+}
+
+impl Drop for PositionGuard {
+    fn drop(&mut self) {
+        let mut open = self.tracker.open.lock().unwrap();
+        if let Some(exposure) = open.get_mut(&self.token) {
+            *exposure = exposure.saturating_sub(self.amount);
+            if exposure.is_zero() {
+                open.remove(&self.token);
+            }
+        }
+    }
+}
+
+impl PositionTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn exposure(&self, token: Address) -> U256 {
+        self.open.lock().unwrap().get(&token).copied().unwrap_or_default()
+    }
+
+    /// Open `amount` of exposure to `token`, unless that takes it past `limit`.
+    pub fn open(self: &Arc<Self>, token: Address, amount: U256, limit: U256) -> Result<PositionGuard> {
+        let mut open = self.open.lock().unwrap();
+        let exposure = open.entry(token).or_default();
+        let total = exposure.saturating_add(amount);
+        if total > limit {
+            return Err(anyhow!(
+                "Position in {:?} would reach {}, above the {} limit",
+                token, total, limit
+            ));
+        }
+        *exposure = total;
+        Ok(PositionGuard {
+            tracker: self.clone(),
+            token,
+            amount,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExecutionConfig {
     pub max_gas_price: U256,
@@ -73,6 +183,78 @@ pub struct ExecutionConfig {
     pub block_delay: u8,
     pub max_execution_time: Duration,
     pub min_profit_threshold: U256,
+    /// Excess profit must beat gas cost times this multiplier.
+    pub min_profit_multiplier: u32,
+    pub priority_fee_ladder: Vec<PriorityFeeStep>,
+    /// Bundles submitted per block, each on pools no other bundle touches.
+    pub max_bundles_per_block: usize,
+    /// Opportunities found more than this many blocks ago are not executed.
+    pub max_opportunity_age_blocks: u64,
+    /// Only submit through private relays, never the public mempool.
+    pub private_only: bool,
+    /// Basis points of expected profit tipped to `block.coinbase` per bundle.
+    pub coinbase_tip_bps: u64,
+    /// Most blocks randomly added to an arb bundle's target; 0 targets the
+    /// delayed block exactly.
+    pub max_jitter_blocks: u64,
+}
+
+/// Sweeps vault balances to a treasury once they pass a per-token threshold.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WithdrawalConfig {
+    pub treasury: Address,
+    #[serde(default = "default_withdrawal_interval_secs")]
+    pub interval_secs: u64,
+    /// Vault balance of each token kept as working capital; anything above
+    /// it is withdrawn. Tokens not listed are left in the vault.
+    pub thresholds: HashMap<Address, U256>,
+}
+
+pub const DEFAULT_WITHDRAWAL_INTERVAL_SECS: u64 = 3_600;
+
+pub fn default_withdrawal_interval_secs() -> u64 {
+    DEFAULT_WITHDRAWAL_INTERVAL_SECS
+}
+
+/// Priority fee multiplier applied once excess profit exceeds
+/// `profit_ratio` times the min-profit threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PriorityFeeStep {
+    pub profit_ratio: u32,
+    pub multiplier: u32,
+}
+
+pub const DEFAULT_MIN_PROFIT_MULTIPLIER: u32 = 2;
+pub const DEFAULT_MAX_BUNDLES_PER_BLOCK: u8 = 3;
+pub const DEFAULT_MAX_OPPORTUNITY_AGE_BLOCKS: u64 = 1;
+
+/// 2x base fee for any profitable trade, 3x above three times the threshold.
+pub fn default_priority_fee_ladder() -> Vec<PriorityFeeStep> {
+    vec![
+        PriorityFeeStep { profit_ratio: 1, multiplier: 2 },
+        PriorityFeeStep { profit_ratio: 3, multiplier: 3 },
+    ]
+}
+
+impl ExecutionConfig {
+    /// Excess profit a trade with this gas cost has to beat.
+    pub fn min_profit_for(&self, gas_cost: U256) -> U256 {
+        gas_cost * U256::from(self.min_profit_multiplier)
+    }
+
+    pub fn should_submit(&self, excess_profit: U256, gas_cost: U256) -> bool {
+        excess_profit > self.min_profit_for(gas_cost)
+    }
+
+    /// Multiplier of the highest ladder step the trade clears, 1 if none.
+    pub fn priority_multiplier(&self, excess_profit: U256, threshold: U256) -> U256 {
+        self.priority_fee_ladder
+            .iter()
+            .rev()
+            .find(|step| excess_profit > threshold * U256::from(step.profit_ratio))
+            .map(|step| U256::from(step.multiplier))
+            .unwrap_or_else(U256::one)
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -90,13 +272,101 @@ pub struct Analytics {
     
     // System metrics
     pub avg_execution_time: Duration,
+    /// Trades folded into `avg_execution_time`.
+    pub execution_samples: u64,
     pub gas_spent: U256,
     pub errors: Vec<String>,
+    /// Trades recorded in paper mode; also counted as successful or failed.
+    pub paper_trades: u64,
     
     // Historical data
     pub trade_history: Vec<TradeResult>,
 }
 
+/// Trades kept in `Analytics::trade_history`.
+const MAX_TRADE_HISTORY: usize = 1000;
+
+impl Analytics {
+    /// Fold one executed (or paper) trade into every metric.
+    pub fn record(&mut self, result: &TradeResult) {
+        if result.success {
+            self.successful_trades += 1;
+            self.total_profit = self.total_profit.saturating_add(result.actual_profit);
+        } else {
+            self.failed_trades += 1;
+            if let Some(ref error) = result.error {
+                self.errors.push(error.clone());
+            }
+        }
+        if result.paper {
+            self.paper_trades += 1;
+        }
+
+        self.update_avg_profit();
+        self.record_execution_time(result.execution_time);
+        self.gas_spent = self.gas_spent.saturating_add(result.gas_used);
+
+        self.trade_history.push(result.clone());
+        if self.trade_history.len() > MAX_TRADE_HISTORY {
+            self.trade_history.remove(0);
+        }
+        self.recompute_metrics();
+    }
+
+    /// Fold one trade's duration into `avg_execution_time` as a cumulative mean.
+    pub fn record_execution_time(&mut self, elapsed: Duration) {
+        self.execution_samples += 1;
+        let n = self.execution_samples as u128;
+        let total = self.avg_execution_time.as_nanos() * (n - 1) + elapsed.as_nanos();
+        self.avg_execution_time = Duration::from_nanos((total / n) as u64);
+    }
+
+    /// Mean profit of successful trades, zero before the first one.
+    pub fn update_avg_profit(&mut self) {
+        self.avg_profit_per_trade = if self.successful_trades == 0 {
+            U256::zero()
+        } else {
+            self.total_profit / U256::from(self.successful_trades)
+        };
+    }
+
+    /// Refresh win rate, max drawdown and Sharpe ratio from the trade counts
+    /// and `trade_history`. Drawdown and Sharpe only see the retained history.
+    pub fn recompute_metrics(&mut self) {
+        let trades = self.successful_trades + self.failed_trades;
+        self.win_rate = if trades == 0 {
+            0.0
+        } else {
+            self.successful_trades as f64 / trades as f64
+        };
+
+        let returns: Vec<i128> = self.trade_history.iter().map(TradeResult::pnl).collect();
+
+        let (mut cumulative, mut peak, mut drawdown) = (0i128, 0i128, 0i128);
+        for pnl in &returns {
+            cumulative += pnl;
+            peak = peak.max(cumulative);
+            drawdown = drawdown.max(peak - cumulative);
+        }
+        self.max_drawdown = U256::from(drawdown as u128);
+
+        // Per-trade Sharpe with a risk-free rate of 0
+        self.sharpe_ratio = if returns.len() < 2 {
+            0.0
+        } else {
+            let n = returns.len() as f64;
+            let mean = returns.iter().map(|r| *r as f64).sum::<f64>() / n;
+            let variance = returns
+                .iter()
+                .map(|r| (*r as f64 - mean).powi(2))
+                .sum::<f64>()
+                / (n - 1.0);
+            let stddev = variance.sqrt();
+            if stddev == 0.0 { 0.0 } else { mean / stddev }
+        };
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TradeResult {
     pub opportunity: ArbitrageOpportunity,
@@ -106,4 +376,248 @@ pub struct TradeResult {
     pub success: bool,
     pub error: Option<String>,
     pub timestamp: u64,
+    /// Simulated in paper mode rather than sent.
+    #[serde(default)]
+    pub paper: bool,
+}
+
+impl TradeResult {
+    /// A paper trade of `opportunity`: filled at its expected profit, with
+    /// no gas spent since nothing was sent.
+    pub fn paper(opportunity: &ArbitrageOpportunity, execution_time: Duration) -> Self {
+        Self {
+            opportunity: opportunity.clone(),
+            actual_profit: opportunity.expected_profit,
+            gas_used: U256::zero(),
+            execution_time,
+            success: true,
+            error: None,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            paper: true,
+        }
+    }
+
+    /// Realized P&L: the profit of a successful trade, or minus the gas
+    /// estimate of a failed one.
+    pub fn pnl(&self) -> i128 {
+        // Saturate rather than panic on amounts past i128
+        let clamp = |value: U256| value.min(U256::from(i128::MAX as u128)).as_u128() as i128;
+        if self.success {
+            clamp(self.actual_profit)
+        } else {
+            -clamp(self.opportunity.gas_cost)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::opportunity;
+
+    fn trade(success: bool, profit: u64, gas_cost: u64) -> TradeResult {
+        TradeResult {
+            opportunity: opportunity(profit, gas_cost),
+            actual_profit: if success { U256::from(profit) } else { U256::zero() },
+            gas_used: U256::zero(),
+            execution_time: Duration::ZERO,
+            success,
+            error: None,
+            timestamp: 0,
+            paper: false,
+        }
+    }
+
+    fn analytics_for(trades: Vec<TradeResult>) -> Analytics {
+        let mut analytics = Analytics::default();
+        for trade in trades {
+            if trade.success {
+                analytics.successful_trades += 1;
+            } else {
+                analytics.failed_trades += 1;
+            }
+            analytics.trade_history.push(trade);
+        }
+        analytics.recompute_metrics();
+        analytics
+    }
+
+    #[test]
+    fn test_recompute_metrics_win_rate_and_drawdown() {
+        // P&L: +100, +50 (peak 150), -30, -40 (trough 80), +200
+        let analytics = analytics_for(vec![
+            trade(true, 100, 10),
+            trade(true, 50, 10),
+            trade(false, 0, 30),
+            trade(false, 0, 40),
+            trade(true, 200, 10),
+        ]);
+
+        assert_eq!(analytics.win_rate, 0.6);
+        assert_eq!(analytics.max_drawdown, U256::from(70));
+        assert!(analytics.sharpe_ratio > 0.0);
+    }
+
+    #[test]
+    fn test_pnl_saturates_huge_amounts() {
+        let mut won = trade(true, 0, 0);
+        won.actual_profit = U256::MAX;
+        assert_eq!(won.pnl(), i128::MAX);
+
+        let mut lost = trade(false, 0, 0);
+        lost.opportunity.gas_cost = U256::MAX;
+        assert_eq!(lost.pnl(), -i128::MAX);
+    }
+
+    #[test]
+    fn test_recompute_metrics_sharpe_ratio() {
+        // Returns 10, 20, 30: mean 20, sample stddev 10
+        let analytics = analytics_for(vec![
+            trade(true, 10, 0),
+            trade(true, 20, 0),
+            trade(true, 30, 0),
+        ]);
+        assert!((analytics.sharpe_ratio - 2.0).abs() < 1e-9);
+        assert_eq!(analytics.max_drawdown, U256::zero());
+
+        // Identical returns have no variance to scale by
+        let flat = analytics_for(vec![trade(true, 10, 0), trade(true, 10, 0)]);
+        assert_eq!(flat.sharpe_ratio, 0.0);
+        assert_eq!(Analytics::default().win_rate, 0.0);
+    }
+
+    fn pool(token0: Address, token1: Address, reserve0: u64, reserve1: u64) -> PoolInfo {
+        PoolInfo {
+            address: Address::random(),
+            protocol: DexProtocol::UniswapV2,
+            token0,
+            token1,
+            reserves: (U256::from(reserve0), U256::from(reserve1)),
+            fee: 30,
+            liquidity: U256::zero(),
+        }
+    }
+
+    #[test]
+    fn test_routes_through_blacklisted_token() {
+        let (weth, usdc, scam) = (Address::random(), Address::random(), Address::random());
+        let mut op = trade(true, 10, 0).opportunity;
+        op.path = vec![weth, scam, usdc];
+
+        assert!(op.routes_through_any(&HashSet::from([scam])));
+        assert!(!op.routes_through_any(&HashSet::from([Address::random()])));
+        assert!(!op.routes_through_any(&HashSet::new()));
+    }
+
+    #[test]
+    fn test_only_v2_pools_are_constant_product() {
+        assert!(DexProtocol::UniswapV2.is_constant_product());
+        assert!(!DexProtocol::UniswapV3.is_constant_product());
+        assert!(!DexProtocol::Curve.is_constant_product());
+        assert!(!DexProtocol::Balancer.is_constant_product());
+    }
+
+    #[test]
+    fn test_price_impact_sums_hops() {
+        let (weth, usdc) = (Address::random(), Address::random());
+        let mut op = trade(true, 10, 0).opportunity;
+        op.path = vec![weth, usdc];
+        op.required_flash_amount = U256::from(10);
+
+        // Deep pools: 10 of 100_000 is 1bps, then the 19 out of 200_000 rounds to 0
+        op.pools = vec![pool(weth, usdc, 100_000, 200_000), pool(usdc, weth, 200_000, 100_000)];
+        assert_eq!(op.price_impact_bps(), Some(U256::from(1)));
+
+        // A thin first pool: 10 of 100 is 1000bps, then 18 of 200_000 rounds to 0
+        op.pools = vec![pool(weth, usdc, 100, 200), pool(weth, usdc, 100_000, 200_000)];
+        assert_eq!(op.price_impact_bps(), Some(U256::from(1_000)));
+
+        // The input token isn't in the pool
+        op.pools = vec![pool(usdc, Address::random(), 100, 100)];
+        assert_eq!(op.price_impact_bps(), None);
+    }
+
+    #[test]
+    fn test_position_beyond_limit_is_rejected() {
+        let tracker = Arc::new(PositionTracker::new());
+        let token = Address::random();
+        let limit = U256::from(1_000);
+
+        let first = tracker.open(token, U256::from(600), limit).unwrap();
+        assert!(tracker.open(token, U256::from(500), limit).is_err());
+        assert_eq!(tracker.exposure(token), U256::from(600));
+
+        // Within the limit, and other tokens are tracked separately
+        let _second = tracker.open(token, U256::from(400), limit).unwrap();
+        let _other = tracker.open(Address::random(), U256::from(1_000), limit).unwrap();
+        assert_eq!(tracker.exposure(token), limit);
+
+        drop(first);
+        assert_eq!(tracker.exposure(token), U256::from(400));
+        assert!(tracker.open(token, U256::from(500), limit).is_ok());
+    }
+
+    #[test]
+    fn test_opportunity_expiry() {
+        let mut op = trade(true, 10, 0).opportunity;
+        op.discovered_at_block = 100;
+
+        assert!(!op.is_expired(100, 2));
+        assert!(!op.is_expired(102, 2));
+        assert!(op.is_expired(103, 2));
+        // A lagging block number never expires it
+        assert!(!op.is_expired(90, 2));
+    }
+
+    #[test]
+    fn test_avg_execution_time_is_cumulative_mean() {
+        let mut analytics = Analytics::default();
+        for _ in 0..10 {
+            analytics.record_execution_time(Duration::from_millis(250));
+        }
+        assert_eq!(analytics.avg_execution_time, Duration::from_millis(250));
+        assert_eq!(analytics.execution_samples, 10);
+
+        // The old (avg + new) / 2 would give 625ms here
+        let mut analytics = Analytics::default();
+        for ms in [100, 200, 300, 1000] {
+            analytics.record_execution_time(Duration::from_millis(ms));
+        }
+        assert_eq!(analytics.avg_execution_time, Duration::from_millis(400));
+    }
+
+    #[test]
+    fn test_avg_profit_without_successful_trades() {
+        let mut analytics = Analytics {
+            failed_trades: 3,
+            ..Default::default()
+        };
+        analytics.update_avg_profit();
+        assert_eq!(analytics.avg_profit_per_trade, U256::zero());
+
+        analytics.successful_trades = 4;
+        analytics.total_profit = U256::from(1_000);
+        analytics.update_avg_profit();
+        assert_eq!(analytics.avg_profit_per_trade, U256::from(250));
+    }
+
+    #[test]
+    fn test_paper_trade_updates_analytics() {
+        let mut analytics = Analytics::default();
+        analytics.record(&trade(false, 0, 50));
+
+        let paper = TradeResult::paper(&trade(true, 300, 50).opportunity, Duration::from_millis(4));
+        analytics.record(&paper);
+
+        assert_eq!(analytics.successful_trades, 1);
+        assert_eq!(analytics.failed_trades, 1);
+        assert_eq!(analytics.paper_trades, 1);
+        assert_eq!(analytics.total_profit, U256::from(300));
+        assert_eq!(analytics.gas_spent, U256::zero());
+        assert_eq!(analytics.win_rate, 0.5);
+        assert!(analytics.trade_history[1].paper);
+    }
 }
@@ -0,0 +1,168 @@
+use anyhow::{anyhow, Result};
+use ethers::{
+    providers::Middleware,
+    types::{BlockNumber, FeeHistory, U256},
+};
+use std::sync::Arc;
+
+/// Blocks of `eth_feeHistory` sampled per quote.
+pub const DEFAULT_FEE_HISTORY_BLOCKS: u64 = 10;
+pub const DEFAULT_REWARD_PERCENTILES: [f64; 4] = [25.0, 50.0, 60.0, 75.0];
+pub const DEFAULT_TARGET_PERCENTILE: f64 = 60.0;
+/// Headroom over the next base fee: one full block's worth of EIP-1559 increase.
+pub const DEFAULT_BASE_FEE_BUFFER_BPS: u64 = 1_250;
+
+/// Fees to bid for inclusion in the next block.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GasQuote {
+    /// Next block's base fee plus the buffer.
+    pub base_fee: U256,
+    /// Priority fee paid at the target percentile over the sampled blocks.
+    pub max_priority_fee: U256,
+}
+
+impl GasQuote {
+    /// `(max_fee, max_priority_fee)` with the tip scaled by `multiplier`.
+    pub fn fees(&self, multiplier: U256) -> (U256, U256) {
+        let max_priority_fee = self.max_priority_fee * multiplier;
+        (self.base_fee + max_priority_fee, max_priority_fee)
+    }
+}
+
+/// EIP-1559 fee oracle over `eth_feeHistory`.
+pub struct GasOracle<M> {
+    client: Arc<M>,
+    pub block_count: u64,
+    /// Reward percentiles requested from the node.
+    pub percentiles: Vec<f64>,
+    /// One of `percentiles`: the tip to bid at.
+    pub target_percentile: f64,
+    pub base_fee_buffer_bps: u64,
+}
+
+impl<M: Middleware + 'static> GasOracle<M> {
+    pub fn new(client: Arc<M>) -> Self {
+        Self {
+            client,
+            block_count: DEFAULT_FEE_HISTORY_BLOCKS,
+            percentiles: DEFAULT_REWARD_PERCENTILES.to_vec(),
+            target_percentile: DEFAULT_TARGET_PERCENTILE,
+            base_fee_buffer_bps: DEFAULT_BASE_FEE_BUFFER_BPS,
+        }
+    }
+
+    pub async fn quote(&self) -> Result<GasQuote> {
+        let target = self
+            .percentiles
+            .iter()
+            .position(|p| *p == self.target_percentile)
+            .ok_or_else(|| anyhow!("Target percentile {} is not requested", self.target_percentile))?;
+
+        let history = self
+            .client
+            .fee_history(self.block_count, BlockNumber::Latest, &self.percentiles)
+            .await
+            .map_err(|e| anyhow!("eth_feeHistory failed: {}", e))?;
+
+        let max_priority_fee = *reward_percentiles(&history)
+            .get(target)
+            .ok_or_else(|| anyhow!("eth_feeHistory returned no rewards"))?;
+        // The last entry is the base fee of the block after the newest one
+        let next_base_fee = *history
+            .base_fee_per_gas
+            .last()
+            .ok_or_else(|| anyhow!("eth_feeHistory returned no base fees"))?;
+
+        Ok(GasQuote {
+            base_fee: next_base_fee * (10_000 + self.base_fee_buffer_bps) / 10_000,
+            max_priority_fee,
+        })
+    }
+}
+
+/// `max_fee` raised to still cover the base fee `blocks` blocks later, at
+/// EIP-1559's maximum increase of 12.5% per full block.
+pub fn max_fee_after(max_fee: U256, blocks: u64) -> U256 {
+    (0..blocks).fold(max_fee, |fee, _| fee * 9 / 8)
+}
+
+/// Median over the sampled blocks of each requested reward percentile, in
+/// request order. Blocks without a full reward row are skipped.
+pub fn reward_percentiles(history: &FeeHistory) -> Vec<U256> {
+    let columns = history.reward.iter().map(Vec::len).max().unwrap_or(0);
+    (0..columns)
+        .filter_map(|column| {
+            let mut rewards: Vec<U256> = history
+                .reward
+                .iter()
+                .filter(|row| row.len() == columns)
+                .map(|row| row[column])
+                .collect();
+            rewards.sort();
+            rewards.get(rewards.len() / 2).copied()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::providers::Provider;
+
+    const GWEI: u64 = 1_000_000_000;
+
+    /// Three blocks at 1 gwei base fee, rewards at the 25th and 60th percentile.
+    const FEE_HISTORY: &str = r#"{
+        "oldestBlock": "0x12a05f0",
+        "baseFeePerGas": ["0x3b9aca00", "0x3b9aca00", "0x3b9aca00", "0x3b9aca00"],
+        "gasUsedRatio": [0.5, 0.41, 0.62],
+        "reward": [
+            ["0x3b9aca00", "0x77359400"],
+            ["0x5f5e100", "0xb2d05e00"],
+            ["0x77359400", "0x77359400"]
+        ]
+    }"#;
+
+    #[test]
+    fn test_max_fee_after_covers_full_blocks() {
+        let max_fee = U256::from(64 * GWEI);
+        assert_eq!(max_fee_after(max_fee, 0), max_fee);
+        assert_eq!(max_fee_after(max_fee, 1), U256::from(72 * GWEI));
+        assert_eq!(max_fee_after(max_fee, 2), U256::from(81 * GWEI));
+    }
+
+    #[test]
+    fn test_reward_percentiles_from_fee_history() {
+        let history: FeeHistory = serde_json::from_str(FEE_HISTORY).unwrap();
+        assert_eq!(
+            reward_percentiles(&history),
+            vec![U256::from(GWEI), U256::from(2 * GWEI)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_quote_targets_percentile() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(serde_json::from_str::<FeeHistory>(FEE_HISTORY).unwrap()).unwrap();
+
+        let mut oracle = GasOracle::new(Arc::new(provider));
+        oracle.percentiles = vec![25.0, 60.0];
+        let quote = oracle.quote().await.unwrap();
+
+        assert_eq!(quote.max_priority_fee, U256::from(2 * GWEI));
+        assert_eq!(quote.base_fee, U256::from(1_125_000_000u64));
+        // A 3x ladder step triples the tip, not the base fee
+        assert_eq!(
+            quote.fees(U256::from(3)),
+            (U256::from(7_125_000_000u64), U256::from(6 * GWEI))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_quote_rejects_unrequested_target() {
+        let (provider, _mock) = Provider::mocked();
+        let mut oracle = GasOracle::new(Arc::new(provider));
+        oracle.target_percentile = 90.0;
+        assert!(oracle.quote().await.is_err());
+    }
+}
@@ -0,0 +1,442 @@
+use async_trait::async_trait;
+use aws_sdk_kms::{
+    primitives::Blob,
+    types::{MessageType, SigningAlgorithmSpec},
+};
+use ethers::{
+    signers::{LocalWallet, Signer, WalletError},
+    types::{
+        transaction::{eip2718::TypedTransaction, eip712::Eip712},
+        Address, Signature, H256, U256,
+    },
+    utils::{hash_message, keccak256, to_eip155_v},
+};
+use serde::{Deserialize, Serialize};
+use std::{fmt, sync::Arc};
+
+/// DER `SubjectPublicKeyInfo` prefix of a secp256k1 key; the uncompressed
+/// point (0x04 || x || y) follows it.
+const SECP256K1_SPKI_PREFIX: [u8; 23] = [
+    0x30, 0x56, 0x30, 0x10, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x05,
+    0x2b, 0x81, 0x04, 0x00, 0x0a, 0x03, 0x42, 0x00,
+];
+
+/// Order of the secp256k1 group.
+const SECP256K1_N: [u8; 32] = [
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe,
+    0xba, 0xae, 0xdc, 0xe6, 0xaf, 0x48, 0xa0, 0x3b, 0xbf, 0xd2, 0x5e, 0x8c, 0xd0, 0x36, 0x41, 0x41,
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum KmsSignerError {
+    #[error("KMS request failed: {0}")]
+    Client(String),
+    #[error("Unexpected public key encoding from KMS")]
+    PublicKey,
+    #[error("Malformed DER signature from KMS")]
+    Signature,
+    #[error("KMS signature doesn't recover to {0:?}")]
+    Recovery(Address),
+    #[error("Failed to encode EIP-712 payload: {0}")]
+    Eip712(String),
+    #[error(transparent)]
+    Wallet(#[from] WalletError),
+}
+
+/// The two calls a signer needs from a key custody service, e.g. AWS KMS
+/// `GetPublicKey` and `Sign` with `ECDSA_SHA_256` over a pre-hashed digest.
+#[async_trait]
+pub trait KmsClient: Send + Sync {
+    /// The key's DER-encoded `SubjectPublicKeyInfo`.
+    async fn get_public_key(&self, key_id: &str) -> Result<Vec<u8>, KmsSignerError>;
+
+    /// DER-encoded ECDSA signature of `digest`, which must not be hashed again.
+    async fn sign_digest(&self, key_id: &str, digest: [u8; 32]) -> Result<Vec<u8>, KmsSignerError>;
+}
+
+/// `KmsClient` backed by AWS KMS. The key must be an `ECC_SECG_P256K1`
+/// signing key.
+#[derive(Clone)]
+pub struct AwsKmsClient {
+    client: aws_sdk_kms::Client,
+}
+
+impl AwsKmsClient {
+    pub fn new(client: aws_sdk_kms::Client) -> Self {
+        Self { client }
+    }
+
+    /// Client using the default AWS credential chain, in `region` if given
+    /// and otherwise the environment's region.
+    pub async fn from_env(region: Option<String>) -> Self {
+        let mut loader = aws_config::defaults(aws_config::BehaviorVersion::latest());
+        if let Some(region) = region {
+            loader = loader.region(aws_config::Region::new(region));
+        }
+        Self::new(aws_sdk_kms::Client::new(&loader.load().await))
+    }
+}
+
+#[async_trait]
+impl KmsClient for AwsKmsClient {
+    async fn get_public_key(&self, key_id: &str) -> Result<Vec<u8>, KmsSignerError> {
+        let output = self
+            .client
+            .get_public_key()
+            .key_id(key_id)
+            .send()
+            .await
+            .map_err(|e| KmsSignerError::Client(e.to_string()))?;
+        output
+            .public_key()
+            .map(|key| key.as_ref().to_vec())
+            .ok_or_else(|| KmsSignerError::Client("No public key in response".to_string()))
+    }
+
+    async fn sign_digest(&self, key_id: &str, digest: [u8; 32]) -> Result<Vec<u8>, KmsSignerError> {
+        let output = self
+            .client
+            .sign()
+            .key_id(key_id)
+            .message(Blob::new(digest.to_vec()))
+            .message_type(MessageType::Digest)
+            .signing_algorithm(SigningAlgorithmSpec::EcdsaSha256)
+            .send()
+            .await
+            .map_err(|e| KmsSignerError::Client(e.to_string()))?;
+        output
+            .signature()
+            .map(|signature| signature.as_ref().to_vec())
+            .ok_or_else(|| KmsSignerError::Client("No signature in response".to_string()))
+    }
+}
+
+/// An AWS KMS key to sign with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KmsConfig {
+    /// Key ID, ARN or alias, e.g. `alias/mev-bot`.
+    pub key_id: String,
+    /// Defaults to the region of the AWS environment.
+    #[serde(default)]
+    pub region: Option<String>,
+}
+
+impl KmsConfig {
+    pub async fn signer(&self, chain_id: u64) -> Result<KmsSigner, KmsSignerError> {
+        let client = AwsKmsClient::from_env(self.region.clone()).await;
+        KmsSigner::new(Arc::new(client), self.key_id.clone(), chain_id).await
+    }
+}
+
+/// An ethers `Signer` whose key never leaves the KMS, usable anywhere a
+/// `LocalWallet` is, including `SignerMiddleware`.
+#[derive(Clone)]
+pub struct KmsSigner {
+    client: Arc<dyn KmsClient>,
+    key_id: String,
+    address: Address,
+    chain_id: u64,
+}
+
+impl fmt::Debug for KmsSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KmsSigner")
+            .field("key_id", &self.key_id)
+            .field("address", &self.address)
+            .field("chain_id", &self.chain_id)
+            .finish()
+    }
+}
+
+impl KmsSigner {
+    /// Fetch the key's public key once to derive its address.
+    pub async fn new(
+        client: Arc<dyn KmsClient>,
+        key_id: impl Into<String>,
+        chain_id: u64,
+    ) -> Result<Self, KmsSignerError> {
+        let key_id = key_id.into();
+        let spki = client.get_public_key(&key_id).await?;
+        let address = address_from_spki(&spki)?;
+        Ok(Self {
+            client,
+            key_id,
+            address,
+            chain_id,
+        })
+    }
+
+    /// Sign a 32-byte digest, with `v` of 27 or 28.
+    pub async fn sign_hash(&self, digest: H256) -> Result<Signature, KmsSignerError> {
+        let der = self.client.sign_digest(&self.key_id, digest.0).await?;
+        let (r, s) = parse_der_signature(&der)?;
+
+        // KMS doesn't enforce low-s, which Ethereum requires
+        let n = U256::from_big_endian(&SECP256K1_N);
+        let s = if s > n / 2 { n - s } else { s };
+
+        // Nor does it return the recovery id, so try both
+        [27, 28]
+            .into_iter()
+            .map(|v| Signature { r, s, v })
+            .find(|signature| signature.recover(digest).ok() == Some(self.address))
+            .ok_or(KmsSignerError::Recovery(self.address))
+    }
+}
+
+#[async_trait]
+impl Signer for KmsSigner {
+    type Error = KmsSignerError;
+
+    async fn sign_message<S: Send + Sync + AsRef<[u8]>>(
+        &self,
+        message: S,
+    ) -> Result<Signature, Self::Error> {
+        self.sign_hash(hash_message(message)).await
+    }
+
+    async fn sign_transaction(&self, tx: &TypedTransaction) -> Result<Signature, Self::Error> {
+        let mut tx = tx.clone();
+        if tx.chain_id().is_none() {
+            tx.set_chain_id(self.chain_id);
+        }
+        let chain_id = tx.chain_id().map(|id| id.as_u64()).unwrap_or(self.chain_id);
+
+        let mut signature = self.sign_hash(tx.sighash()).await?;
+        signature.v = to_eip155_v((signature.v - 27) as u8, chain_id);
+        Ok(signature)
+    }
+
+    async fn sign_typed_data<T: Eip712 + Send + Sync>(
+        &self,
+        payload: &T,
+    ) -> Result<Signature, Self::Error> {
+        let digest = payload
+            .encode_eip712()
+            .map_err(|e| KmsSignerError::Eip712(e.to_string()))?;
+        self.sign_hash(H256(digest)).await
+    }
+
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    fn with_chain_id<T: Into<u64>>(mut self, chain_id: T) -> Self {
+        self.chain_id = chain_id.into();
+        self
+    }
+}
+
+/// A local key or a KMS key, whichever is configured, as one `Signer` type.
+#[derive(Debug, Clone)]
+pub enum ConfiguredSigner {
+    Local(LocalWallet),
+    Kms(KmsSigner),
+}
+
+#[async_trait]
+impl Signer for ConfiguredSigner {
+    type Error = KmsSignerError;
+
+    async fn sign_message<S: Send + Sync + AsRef<[u8]>>(
+        &self,
+        message: S,
+    ) -> Result<Signature, Self::Error> {
+        match self {
+            Self::Local(wallet) => Ok(wallet.sign_message(message).await?),
+            Self::Kms(signer) => signer.sign_message(message).await,
+        }
+    }
+
+    async fn sign_transaction(&self, tx: &TypedTransaction) -> Result<Signature, Self::Error> {
+        match self {
+            Self::Local(wallet) => Ok(wallet.sign_transaction(tx).await?),
+            Self::Kms(signer) => signer.sign_transaction(tx).await,
+        }
+    }
+
+    async fn sign_typed_data<T: Eip712 + Send + Sync>(
+        &self,
+        payload: &T,
+    ) -> Result<Signature, Self::Error> {
+        match self {
+            Self::Local(wallet) => Ok(wallet.sign_typed_data(payload).await?),
+            Self::Kms(signer) => signer.sign_typed_data(payload).await,
+        }
+    }
+
+    fn address(&self) -> Address {
+        match self {
+            Self::Local(wallet) => wallet.address(),
+            Self::Kms(signer) => signer.address(),
+        }
+    }
+
+    fn chain_id(&self) -> u64 {
+        match self {
+            Self::Local(wallet) => wallet.chain_id(),
+            Self::Kms(signer) => signer.chain_id(),
+        }
+    }
+
+    fn with_chain_id<T: Into<u64>>(self, chain_id: T) -> Self {
+        match self {
+            Self::Local(wallet) => Self::Local(wallet.with_chain_id(chain_id)),
+            Self::Kms(signer) => Self::Kms(signer.with_chain_id(chain_id)),
+        }
+    }
+}
+
+fn address_from_spki(spki: &[u8]) -> Result<Address, KmsSignerError> {
+    let point = spki
+        .strip_prefix(&SECP256K1_SPKI_PREFIX[..])
+        .filter(|point| point.len() == 65 && point[0] == 0x04)
+        .ok_or(KmsSignerError::PublicKey)?;
+    Ok(Address::from_slice(&keccak256(&point[1..])[12..]))
+}
+
+/// Parse `SEQUENCE { r INTEGER, s INTEGER }`.
+fn parse_der_signature(der: &[u8]) -> Result<(U256, U256), KmsSignerError> {
+    fn integer(input: &[u8]) -> Option<(U256, &[u8])> {
+        let (&tag, rest) = input.split_first()?;
+        let (&len, rest) = rest.split_first()?;
+        let len = len as usize;
+        if tag != 0x02 || rest.len() < len {
+            return None;
+        }
+        let (value, rest) = rest.split_at(len);
+        // A leading zero only keeps the integer positive
+        let value = value.strip_prefix(&[0]).unwrap_or(value);
+        (value.len() <= 32).then(|| (U256::from_big_endian(value), rest))
+    }
+
+    match der {
+        [0x30, len, body @ ..] if *len as usize == body.len() => {
+            let (r, rest) = integer(body).ok_or(KmsSignerError::Signature)?;
+            let (s, rest) = integer(rest).ok_or(KmsSignerError::Signature)?;
+            if !rest.is_empty() {
+                return Err(KmsSignerError::Signature);
+            }
+            Ok((r, s))
+        }
+        _ => Err(KmsSignerError::Signature),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::{
+        core::k256::elliptic_curve::sec1::ToEncodedPoint,
+        types::{Eip1559TransactionRequest, TransactionRequest},
+    };
+
+    /// Signs with an in-memory key but answers like KMS: DER signatures,
+    /// no recovery id and, optionally, high-s values.
+    struct MockKms {
+        wallet: LocalWallet,
+        high_s: bool,
+    }
+
+    fn der_integer(value: U256) -> Vec<u8> {
+        let mut bytes = [0u8; 32];
+        value.to_big_endian(&mut bytes);
+        let start = bytes.iter().position(|b| *b != 0).unwrap_or(31);
+        let mut encoded = bytes[start..].to_vec();
+        if encoded[0] & 0x80 != 0 {
+            encoded.insert(0, 0);
+        }
+        [vec![0x02, encoded.len() as u8], encoded].concat()
+    }
+
+    #[async_trait]
+    impl KmsClient for MockKms {
+        async fn get_public_key(&self, _key_id: &str) -> Result<Vec<u8>, KmsSignerError> {
+            let point = self.wallet.signer().verifying_key().to_encoded_point(false);
+            Ok([&SECP256K1_SPKI_PREFIX[..], point.as_bytes()].concat())
+        }
+
+        async fn sign_digest(&self, _key_id: &str, digest: [u8; 32]) -> Result<Vec<u8>, KmsSignerError> {
+            let signature = self.wallet.sign_hash(H256(digest)).unwrap();
+            let s = if self.high_s {
+                U256::from_big_endian(&SECP256K1_N) - signature.s
+            } else {
+                signature.s
+            };
+            let body = [der_integer(signature.r), der_integer(s)].concat();
+            Ok([vec![0x30, body.len() as u8], body].concat())
+        }
+    }
+
+    async fn signer(high_s: bool) -> (KmsSigner, LocalWallet) {
+        let wallet = LocalWallet::new(&mut rand::thread_rng());
+        let kms = Arc::new(MockKms { wallet: wallet.clone(), high_s });
+        (KmsSigner::new(kms, "alias/mev-bot", 1).await.unwrap(), wallet)
+    }
+
+    #[tokio::test]
+    async fn test_address_matches_kms_key() {
+        let (signer, wallet) = signer(false).await;
+        assert_eq!(signer.address(), wallet.address());
+    }
+
+    #[tokio::test]
+    async fn test_signed_transaction_recovers_to_signer() {
+        let (signer, wallet) = signer(true).await;
+        let tx: TypedTransaction = Eip1559TransactionRequest::new()
+            .to(Address::random())
+            .value(1_000)
+            .nonce(3)
+            .gas(21_000)
+            .into();
+
+        let signature = signer.sign_transaction(&tx).await.unwrap();
+
+        let mut signed = tx.clone();
+        signed.set_chain_id(1u64);
+        assert_eq!(signature.recover(signed.sighash()).unwrap(), signer.address());
+        assert_eq!(signature, wallet.with_chain_id(1u64).sign_transaction(&tx).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_legacy_transaction_gets_eip155_v() {
+        let (signer, _) = signer(false).await;
+        let tx: TypedTransaction = TransactionRequest::new().to(Address::random()).nonce(0).into();
+
+        let signature = signer.with_chain_id(5u64).sign_transaction(&tx).await.unwrap();
+
+        assert!(signature.v == 45 || signature.v == 46);
+    }
+
+    #[tokio::test]
+    async fn test_signed_message_recovers_to_signer() {
+        let (signer, _) = signer(true).await;
+        let signature = signer.sign_message("gm").await.unwrap();
+        assert_eq!(signature.recover("gm").unwrap(), signer.address());
+    }
+
+    #[tokio::test]
+    async fn test_configured_signer_signs_like_the_underlying_key() {
+        let (kms, wallet) = signer(false).await;
+        let tx: TypedTransaction = Eip1559TransactionRequest::new().to(Address::random()).nonce(1).into();
+
+        let local = ConfiguredSigner::Local(wallet.clone().with_chain_id(1u64));
+        let remote = ConfiguredSigner::Kms(kms);
+
+        assert_eq!(local.address(), remote.address());
+        assert_eq!(
+            local.sign_transaction(&tx).await.unwrap(),
+            remote.sign_transaction(&tx).await.unwrap()
+        );
+    }
+
+    #[test]
+    fn test_rejects_malformed_der() {
+        assert!(parse_der_signature(&[0x30, 0x02, 0x02, 0x00]).is_err());
+        assert!(address_from_spki(&[0x04; 65]).is_err());
+    }
+}
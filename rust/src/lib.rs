@@ -1,13 +1,27 @@
 pub mod abi;
+pub mod backrun;
+pub mod backtest;
 pub mod bundler;
+pub mod config;
 pub mod constants;
 pub mod core;        // Contains flashloan functionality
+#[cfg(test)]
+mod fixtures;
+pub mod flashbot;
+pub mod gas;
+pub mod kms;
 pub mod metrics;     // Contains monitoring functionality
+pub mod monitoring;
 pub mod multi;
+pub mod nonce;
 pub mod paths;
 pub mod pools;
+pub mod protocols;
 pub mod routing;     // Contains pathfinding functionality
+pub mod security;
 pub mod simulator;
+pub mod strategies;
 pub mod strategy;
 pub mod streams;
 pub mod utils;
+pub mod wallet_pool;
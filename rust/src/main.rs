@@ -1,31 +1,41 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use ethers::{
     providers::{Provider, Ws},
     types::Address,
-    signers::LocalWallet,
+    signers::Signer,
 };
 use log::{info, error, warn};
-use std::sync::Arc;
-use tokio::sync::broadcast::{self, Sender};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use tokio::sync::broadcast::{self, error::RecvError, Sender};
 use tokio::task::JoinSet;
-use prometheus::default_registry;
 use warp::Filter;
 
 use rust::{
     constants::Env,
     strategy::event_handler,
     streams::{stream_new_blocks, stream_pending_transactions, stream_uniswap_v2_events, Event},
-    utils::setup_logger,
+    utils::{setup_logger_with, u256_to_f64},
+    kms::ConfiguredSigner,
+    wallet_pool::WalletPool,
     flashbot::{
         arbitrage::ArbitrageManager,
         mev_protection::MEVProtection,
-        contracts::ContractManager,
+        contracts::{withdraw_profits, ContractManager},
         market_maker::MarketMaker,
-        types::{RiskConfig, ExecutionConfig},
+        execution_pool::ExecutionPool,
+        journal::TradeJournal,
+        types::{ArbitrageOpportunity, RiskConfig, ExecutionConfig, WithdrawalConfig},
     },
-    security::SecurityManager,
+    security::{SecurityManager, METADATA_CACHE_PATH},
     dex::DexManager,
-    monitoring::{Metrics, HealthChecker, ErrorRecovery},
+    monitoring::{
+        health_route, install_metrics_recorder, metrics_route, path_label, resume_route, AnomalyConfig,
+        AnomalyMonitor, CircuitBreaker, CircuitBreakerConfig, DrawdownMonitor, update_connectivity, ErrorRecovery, HealthChecker, HealthThresholds,
+        Metrics, PnlWindow, Shutdown, DRAWDOWN_EVALUATION_INTERVAL, NODE_PING_INTERVAL,
+    },
     config::{BotConfig, RuntimeConfig},
 };
 
@@ -33,61 +43,123 @@ use rust::{
 async fn main() -> Result<()> {
     // Initialize environment and logging
     dotenv::dotenv().ok();
-    setup_logger()?;
+    let runtime_config = RuntimeConfig::load()?;
+    setup_logger_with(runtime_config.log_format, &runtime_config.log_level)?;
 
     // Load and validate configurations
-    let config = load_config()?;
-    config.validate_all()?;
-    
-    let runtime_config = RuntimeConfig::default();
+    let config = BotConfig::load()?;
 
     // Initialize metrics and monitoring
     let metrics = Arc::new(Metrics::new()?);
-    let health_checker = Arc::new(HealthChecker::new(metrics.clone()));
-    let error_recovery = Arc::new(ErrorRecovery::new(
+    let metrics_recorder = install_metrics_recorder()?;
+    let health_checker = Arc::new(HealthChecker::new(
+        metrics.clone(),
+        HealthThresholds::from(&runtime_config),
+    ));
+    let error_recovery = ErrorRecovery::new(
         metrics.clone(),
         runtime_config.retry_attempts,
         std::time::Duration::from_millis(runtime_config.backoff_base_ms),
-    ));
+    );
 
     // Setup provider and wallet
     let ws = error_recovery
-        .retry_with_backoff(|| Ws::connect(&config.rpc_url))
+        .retry_with_backoff_async(|| Ws::connect(&config.rpc_url))
         .await?;
     let provider = Arc::new(Provider::new(ws));
-    let wallet = LocalWallet::from_bytes(&hex::decode(&config.private_key)?)?;
+    config.validate_onchain(provider.as_ref()).await?;
+    let wallet_pool = Arc::new(WalletPool::from_keys(
+        config.wallet()?,
+        &config.additional_private_keys,
+        config.chain_id,
+    )?);
+    info!("Submitting from {} wallet(s)", wallet_pool.len());
+    let error_recovery = Arc::new(
+        wallet_pool
+            .wallets()
+            .fold(error_recovery, |recovery, wallet| recovery.with_account(provider.clone(), wallet.address())),
+    );
+    let circuit_breaker = Arc::new(CircuitBreaker::new(metrics.clone(), CircuitBreakerConfig::default()));
+    let anomaly_config = AnomalyConfig::from_drawdown(config.max_position_size, config.max_drawdown);
+    // Pausing and the emergency stop judge the same trades
+    let pnl_window = Arc::new(PnlWindow::new(anomaly_config.loss_window));
+    let drawdown_monitor = Arc::new(DrawdownMonitor::new(
+        metrics.clone(),
+        &RiskConfig::from(&config),
+        pnl_window.clone(),
+    ));
 
     // Initialize core components
     let security_manager = Arc::new(SecurityManager::new(provider.clone()));
+    if let Err(e) = security_manager.load_metadata_cache(METADATA_CACHE_PATH).await {
+        warn!("Starting with an empty metadata cache: {}", e);
+    }
     let dex_manager = Arc::new(DexManager::new(provider.clone()));
 
     // Initialize flashbot components with validated config
-    let arbitrage_manager = Arc::new(ArbitrageManager::new(
+    let execution_config = ExecutionConfig::from(&config);
+    let max_tip = execution_config.priority_fee;
+    let private_only = execution_config.private_only;
+    if runtime_config.paper_trading {
+        warn!("Paper trading: nothing will be broadcast");
+    }
+    let mut arbitrage_manager = ArbitrageManager::new(
         dex_manager.clone(),
         security_manager.clone(),
-        config.into(),
-        config.into(),
-    ));
+        RiskConfig::from(&config),
+        execution_config,
+    )
+    .with_paper_trading(runtime_config.paper_trading);
+    if let Some(path) = &runtime_config.trade_journal_path {
+        arbitrage_manager = arbitrage_manager.with_journal(TradeJournal::open(path)?);
+        info!("Journaling trades to {:?}", path);
+    }
+    let arbitrage_manager = Arc::new(arbitrage_manager);
 
     let mev_protection = Arc::new(MEVProtection::new(
-        config.flashbots_rpc.unwrap_or_default(),
-        config.eden_rpc,
+        config.flashbots_rpc.clone().unwrap_or_default(),
+        config.eden_rpc.clone(),
         None,
-        U256::from(config.priority_fee),
-    ));
+        max_tip,
+    )
+    // Flashbots reputation accrues to the trading wallet
+    .with_endpoint_relays(provider.clone(), config.wallet()?)
+    .with_private_only(private_only));
+    if private_only && !mev_protection.has_private_relay() {
+        return Err(anyhow!("private_only is set, but no private relay is configured"));
+    }
 
     let contract_manager = Arc::new(ContractManager::new(
         provider.clone(),
+        config.admin_signer().await?,
         config.executor_address,
         config.vault_address,
-    ).await?);
+    ).await?
+        .with_paper_trading(runtime_config.paper_trading)
+        .with_private_only(private_only));
+    // The executor rejects calls from anyone but its owner and operators
+    for wallet in wallet_pool.wallets() {
+        if !contract_manager.accepts_sender(wallet.address()).await? {
+            return Err(anyhow!(
+                "Executor {:?} doesn't accept calls from {:?}; make it an operator or remove it from the pool",
+                config.executor_address,
+                wallet.address()
+            ));
+        }
+    }
+    let anomaly_monitor = Arc::new(
+        AnomalyMonitor::new(contract_manager.clone(), metrics.clone(), anomaly_config)
+            .with_pnl_window(pnl_window),
+    );
 
     let market_maker = if config.market_making_enabled {
         Some(Arc::new(MarketMaker::new(
             config.max_position_size,
             config.rebalance_threshold,
             config.min_spread_bps,
-        )))
+        )
+        .with_paper_trading(runtime_config.paper_trading)
+        .with_private_only(private_only)))
     } else {
         None
     };
@@ -95,12 +167,16 @@ async fn main() -> Result<()> {
     // Setup event channels
     let (event_sender, _): (Sender<Event>, _) = broadcast::channel(512);
     let mut set = JoinSet::new();
+    let shutdown = Shutdown::new();
 
     // Spawn monitoring tasks
     spawn_monitoring_tasks(
         &mut set,
+        provider.clone(),
         health_checker.clone(),
         metrics.clone(),
+        circuit_breaker.clone(),
+        drawdown_monitor.clone(),
         runtime_config.clone(),
     );
 
@@ -118,12 +194,24 @@ async fn main() -> Result<()> {
         arbitrage_manager.clone(),
         mev_protection.clone(),
         contract_manager.clone(),
-        wallet.clone(),
+        wallet_pool.clone(),
+        provider.clone(),
         event_sender.clone(),
         metrics.clone(),
         error_recovery.clone(),
+        circuit_breaker.clone(),
+        drawdown_monitor.clone(),
+        security_manager.clone(),
+        anomaly_monitor.clone(),
+        shutdown.clone(),
+        runtime_config.max_concurrent_executions,
     );
 
+    // Sweep vault profits to the treasury if configured
+    if let Some(withdrawal) = config.profit_withdrawal.clone() {
+        spawn_profit_withdrawals(&mut set, contract_manager.clone(), withdrawal, shutdown.clone());
+    }
+
     // Spawn market maker if enabled
     if let Some(market_maker) = market_maker {
         spawn_market_maker(
@@ -134,30 +222,62 @@ async fn main() -> Result<()> {
         );
     }
 
-    // Start metrics server
-    let metrics_route = warp::path!("metrics").map(move || {
-        let encoder = prometheus::TextEncoder::new();
-        let mut buffer = Vec::new();
-        encoder.encode(&default_registry().gather(), &mut buffer).unwrap();
-        String::from_utf8(buffer).unwrap()
-    });
+    // Start metrics and health server
+    let metrics_addr = (runtime_config.metrics_bind_addr, runtime_config.metrics_port);
+    if !runtime_config.metrics_bind_addr.is_loopback() && runtime_config.metrics_auth_token.is_none() {
+        warn!("Serving metrics on {:?} without an auth token", metrics_addr);
+    }
+    let mut routes = metrics_route(metrics_recorder, runtime_config.metrics_auth_token.clone())
+        .or(health_route(health_checker.clone()))
+        .unify()
+        .boxed();
+    match &runtime_config.metrics_auth_token {
+        Some(token) => {
+            routes = routes
+                .or(resume_route(circuit_breaker.clone(), drawdown_monitor.clone(), token.clone()))
+                .unify()
+                .boxed();
+        }
+        None => info!("Set metrics_auth_token to enable POST /resume"),
+    }
 
-    tokio::spawn(warp::serve(metrics_route).run(([127, 0, 0, 1], runtime_config.metrics_port)));
+    tokio::spawn(warp::serve(routes).run(metrics_addr));
 
-    // Wait for tasks and handle failures
-    while let Some(res) = set.join_next().await {
-        match res {
-            Ok(_) => continue,
-            Err(e) => {
-                error!("Task error: {}", e);
-                error_recovery.handle_error(e, "Task failure").await;
-                
-                // Check health status
-                if !health_checker.is_healthy().await {
-                    warn!("System unhealthy, attempting recovery...");
-                    // Implement recovery logic
+    // Wait for tasks and handle failures until Ctrl-C
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {
+                info!("Shutdown requested, draining in-flight executions");
+                shutdown.trigger();
+
+                if !shutdown.drain(runtime_config.shutdown_grace_period).await {
+                    warn!(
+                        "{} executions still in flight after {:?}",
+                        shutdown.in_flight(),
+                        runtime_config.shutdown_grace_period
+                    );
                 }
+                if let Err(e) = security_manager.persist_metadata_cache(METADATA_CACHE_PATH).await {
+                    error!("Failed to persist metadata cache: {}", e);
+                }
+
+                set.abort_all();
+                break;
             }
+            res = set.join_next() => match res {
+                None => break,
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => {
+                    error!("Task error: {}", e);
+                    error_recovery.handle_error(e, "Task failure").await;
+                    
+                    // Check health status
+                    if !health_checker.is_healthy().await {
+                        warn!("System unhealthy, attempting recovery...");
+                        // Implement recovery logic
+                    }
+                }
+            },
         }
     }
 
@@ -166,23 +286,56 @@ async fn main() -> Result<()> {
 
 fn spawn_monitoring_tasks(
     set: &mut JoinSet<Result<()>>,
+    provider: Arc<Provider<Ws>>,
     health_checker: Arc<HealthChecker>,
     metrics: Arc<Metrics>,
+    circuit_breaker: Arc<CircuitBreaker>,
+    drawdown_monitor: Arc<DrawdownMonitor>,
     config: RuntimeConfig,
 ) {
     // Health check task
     set.spawn({
         let health_checker = health_checker.clone();
         async move {
+            let mut was_healthy = true;
             loop {
-                if let Err(e) = health_checker.check_health().await {
-                    error!("Health check failed: {}", e);
+                match health_checker.check_health().await {
+                    Ok(report) => {
+                        if !report.healthy {
+                            warn!("Unhealthy: {}", report.failing_checks.join(", "));
+                        }
+                        // Recovering from an unhealthy state closes the breaker early
+                        if report.healthy && !was_healthy {
+                            circuit_breaker.reset();
+                        }
+                        was_healthy = report.healthy;
+                    }
+                    Err(e) => error!("Health check failed: {}", e),
                 }
                 tokio::time::sleep(config.health_check_interval).await;
             }
         }
     });
 
+    // Node connectivity task
+    set.spawn({
+        let metrics = metrics.clone();
+        async move {
+            loop {
+                update_connectivity(provider.as_ref(), &metrics.connected_nodes).await;
+                tokio::time::sleep(NODE_PING_INTERVAL).await;
+            }
+        }
+    });
+
+    // Losses age out of the drawdown window while nothing trades
+    set.spawn(async move {
+        loop {
+            tokio::time::sleep(DRAWDOWN_EVALUATION_INTERVAL).await;
+            drawdown_monitor.evaluate();
+        }
+    });
+
     // Memory monitoring task
     set.spawn({
         let metrics = metrics.clone();
@@ -196,6 +349,26 @@ fn spawn_monitoring_tasks(
     });
 }
 
+fn spawn_profit_withdrawals(
+    set: &mut JoinSet<Result<()>>,
+    contract_manager: Arc<ContractManager<ConfiguredSigner>>,
+    config: WithdrawalConfig,
+    shutdown: Shutdown,
+) {
+    let interval = std::time::Duration::from_secs(config.interval_secs);
+    set.spawn(async move {
+        loop {
+            tokio::select! {
+                _ = shutdown.wait_requested() => break,
+                _ = tokio::time::sleep(interval) => {
+                    withdraw_profits(contract_manager.as_ref(), &config).await;
+                }
+            }
+        }
+        Ok(())
+    });
+}
+
 fn spawn_core_streams(
     set: &mut JoinSet<Result<()>>,
     provider: Arc<Provider<Ws>>,
@@ -208,7 +381,7 @@ fn spawn_core_streams(
         let event_sender = event_sender.clone();
         let error_recovery = error_recovery.clone();
         async move {
-            error_recovery.retry_with_backoff(|| {
+            error_recovery.retry_with_backoff_async(|| {
                 stream_new_blocks(provider.clone(), event_sender.clone())
             }).await
         }
@@ -220,7 +393,7 @@ fn spawn_core_streams(
         let event_sender = event_sender.clone();
         let error_recovery = error_recovery.clone();
         async move {
-            error_recovery.retry_with_backoff(|| {
+            error_recovery.retry_with_backoff_async(|| {
                 stream_pending_transactions(provider.clone(), event_sender.clone())
             }).await
         }
@@ -231,50 +404,134 @@ fn spawn_arbitrage_handler(
     set: &mut JoinSet<Result<()>>,
     arbitrage_manager: Arc<ArbitrageManager>,
     mev_protection: Arc<MEVProtection>,
-    contract_manager: Arc<ContractManager>,
-    wallet: LocalWallet,
+    contract_manager: Arc<ContractManager<ConfiguredSigner>>,
+    wallet_pool: Arc<WalletPool>,
+    provider: Arc<Provider<Ws>>,
     event_sender: Sender<Event>,
     metrics: Arc<Metrics>,
     error_recovery: Arc<ErrorRecovery>,
+    circuit_breaker: Arc<CircuitBreaker>,
+    drawdown_monitor: Arc<DrawdownMonitor>,
+    security_manager: Arc<SecurityManager>,
+    anomaly_monitor: Arc<AnomalyMonitor>,
+    shutdown: Shutdown,
+    max_concurrent_executions: usize,
 ) {
+    // Follows the chain head on its own, since the handler below falls
+    // behind while it executes
+    let latest_block = Arc::new(AtomicU64::new(0));
+    set.spawn({
+        let latest_block = latest_block.clone();
+        let mut rx = event_sender.subscribe();
+        async move {
+            loop {
+                match rx.recv().await {
+                    Ok(Event::Block(block)) => {
+                        latest_block.fetch_max(block.block_number.as_u64(), Ordering::Relaxed);
+                    }
+                    Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                }
+            }
+            Ok(())
+        }
+    });
+
+    // Learns sandwich bots from each new block for the sandwich risk check
+    set.spawn({
+        let (mev_protection, provider) = (mev_protection.clone(), provider.clone());
+        let mut rx = event_sender.subscribe();
+        async move {
+            loop {
+                match rx.recv().await {
+                    Ok(Event::Block(block)) => {
+                        if let Err(e) = mev_protection
+                            .update_sandwich_bots(provider.as_ref(), block.block_number, 1)
+                            .await
+                        {
+                            warn!("Failed to scan block {} for sandwiches: {}", block.block_number, e);
+                        }
+                    }
+                    Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                }
+            }
+            Ok(())
+        }
+    });
+
+    let execution_pool = ExecutionPool::new(max_concurrent_executions);
+    let ctx = ExecutionContext {
+        arbitrage_manager: arbitrage_manager.clone(),
+        mev_protection,
+        wallet_pool: wallet_pool.clone(),
+        provider,
+        metrics: metrics.clone(),
+        error_recovery: error_recovery.clone(),
+        circuit_breaker: circuit_breaker.clone(),
+        drawdown_monitor: drawdown_monitor.clone(),
+        security_manager,
+        anomaly_monitor: anomaly_monitor.clone(),
+        shutdown: shutdown.clone(),
+        latest_block: latest_block.clone(),
+    };
+
     set.spawn({
         async move {
             let mut rx = event_sender.subscribe();
-            while let Ok(event) = rx.recv().await {
+            loop {
+                let event = tokio::select! {
+                    _ = shutdown.wait_requested() => break,
+                    event = rx.recv() => match event {
+                        Ok(event) => event,
+                        Err(_) => break,
+                    },
+                };
+
                 match event {
-                    Event::NewBlock(block) => {
+                    Event::Block(block) => {
                         metrics.last_block_time.set(block.timestamp.as_u64() as f64);
+
+                        if error_recovery.should_pause() {
+                            warn!("Trading paused, skipping block {:?}", block.hash);
+                            continue;
+                        }
                         
                         // Look for arbitrage opportunities
-                        match arbitrage_manager.find_opportunities(block.hash).await {
+                        let block_number = block.block_number.as_u64();
+                        latest_block.fetch_max(block_number, Ordering::Relaxed);
+                        wallet_pool.on_new_block(block.block_number);
+                        match arbitrage_manager.find_opportunities(block.hash, block_number).await {
                             Ok(opportunities) => {
-                                metrics.opportunities_found.inc_by(opportunities.len() as f64);
-                                
+                                let mut executions = JoinSet::new();
                                 for op in opportunities {
-                                    let start_time = std::time::Instant::now();
-                                    
-                                    // Check MEV protection
-                                    if !mev_protection.check_sandwich_risk(&op.path).await? {
-                                        // Execute arbitrage through contracts
-                                        match error_recovery
-                                            .retry_with_backoff(|| {
-                                                arbitrage_manager.execute_arbitrage(&op, wallet.clone())
-                                            })
-                                            .await
-                                        {
-                                            Ok(result) => {
-                                                metrics.trades_executed.inc();
-                                                metrics.total_profit.add(result.actual_profit.as_u64() as f64);
-                                                metrics.execution_time.observe(
-                                                    start_time.elapsed().as_millis() as f64
-                                                );
-                                            }
-                                            Err(e) => {
-                                                error_recovery.handle_error(e, "Arbitrage execution failed").await;
-                                            }
-                                        }
-                                    } else {
-                                        metrics.sandwich_attempts.inc();
+                                    if shutdown.is_requested() {
+                                        break;
+                                    }
+                                    if circuit_breaker.is_open() {
+                                        warn!("Circuit breaker open, not submitting trades");
+                                        break;
+                                    }
+                                    if drawdown_monitor.is_paused() {
+                                        warn!("Drawdown limit reached, not submitting trades");
+                                        break;
+                                    }
+                                    if anomaly_monitor.is_stopped() {
+                                        warn!("Emergency stop triggered, not submitting trades");
+                                        break;
+                                    }
+
+                                    let pools = op.pools.iter().map(|pool| pool.address).collect();
+                                    executions.spawn(
+                                        execution_pool.clone().run(pools, execute_opportunity(op, ctx.clone())),
+                                    );
+                                }
+
+                                while let Some(res) = executions.join_next().await {
+                                    match res {
+                                        Ok(Ok(())) => {}
+                                        Ok(Err(e)) => error_recovery.handle_error(e, "Opportunity check failed").await,
+                                        Err(e) => error!("Execution task failed: {}", e),
                                     }
                                 }
                             }
@@ -289,6 +546,128 @@ fn spawn_arbitrage_handler(
     });
 }
 
+/// What a spawned execution needs, cloned per opportunity.
+#[derive(Clone)]
+struct ExecutionContext {
+    arbitrage_manager: Arc<ArbitrageManager>,
+    mev_protection: Arc<MEVProtection>,
+    wallet_pool: Arc<WalletPool>,
+    provider: Arc<Provider<Ws>>,
+    metrics: Arc<Metrics>,
+    error_recovery: Arc<ErrorRecovery>,
+    circuit_breaker: Arc<CircuitBreaker>,
+    drawdown_monitor: Arc<DrawdownMonitor>,
+    security_manager: Arc<SecurityManager>,
+    anomaly_monitor: Arc<AnomalyMonitor>,
+    shutdown: Shutdown,
+    latest_block: Arc<AtomicU64>,
+}
+
+/// Check, execute and record one opportunity. Runs inside the execution
+/// pool, so the checks happen after any wait for a worker or pool.
+async fn execute_opportunity(op: ArbitrageOpportunity, ctx: ExecutionContext) -> Result<()> {
+    if ctx.shutdown.is_requested()
+        || ctx.circuit_breaker.is_open()
+        || ctx.drawdown_monitor.is_paused()
+        || ctx.anomaly_monitor.is_stopped()
+    {
+        return Ok(());
+    }
+
+    let start_time = std::time::Instant::now();
+    let path = path_label(&op.path);
+    ctx.metrics.record_opportunity(&path);
+
+    let current_block = ctx.latest_block.load(Ordering::Relaxed);
+    if ctx.arbitrage_manager.is_expired(&op, current_block).await {
+        warn!(
+            "Skipping {} found at block {}, now at {}",
+            path, op.discovered_at_block, current_block
+        );
+        return Ok(());
+    }
+
+    // Check MEV protection
+    if ctx.mev_protection.check_sandwich_risk(&op.path).await? {
+        ctx.metrics.sandwich_attempts.inc();
+        return Ok(());
+    }
+
+    // Re-check the tokens, which may have been flagged since the opportunity was
+    // found. Only a failed transfer or sell probe trips the emergency stop; a
+    // token that merely misses a listing criterion, or can't be checked right
+    // now, just skips this opportunity.
+    for token in &op.path {
+        let validation = match ctx.security_manager.validate_token(*token).await {
+            Ok(validation) => validation,
+            Err(e) => {
+                warn!("Skipping {}: could not validate {:?}: {}", path, token, e);
+                return Ok(());
+            }
+        };
+        if validation.is_compromised() {
+            ctx.anomaly_monitor.record_compromised_token(*token, &validation.reason).await?;
+            return Ok(());
+        }
+        if !validation.is_valid {
+            info!("Skipping {}: {:?} failed validation: {}", path, token, validation.reason);
+            return Ok(());
+        }
+    }
+
+    // Execute arbitrage through contracts, from whichever wallet is idle
+    let _in_flight = ctx.shutdown.track();
+    let lease = ctx.wallet_pool.acquire().await;
+    match ctx
+        .error_recovery
+        .retry_with_backoff_async(|| async {
+            let nonce = lease.nonces().next(ctx.provider.as_ref()).await?;
+            let result = ctx
+                .arbitrage_manager
+                .execute_arbitrage(&op, lease.wallet().clone(), nonce, &ctx.error_recovery)
+                .await;
+            match &result {
+                // Nothing went out, so the nonce is free for the next attempt
+                Ok(result) if result.paper => {
+                    lease.nonces().release(nonce);
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    // Re-sync the nonce or raise fees before the next attempt
+                    if !lease.nonces().handle_error(&e.to_string()) {
+                        lease.nonces().release(nonce);
+                    }
+                    ctx.error_recovery.recover(&e.to_string()).await;
+                }
+            }
+            result
+        })
+        .await
+    {
+        Ok(result) if result.paper => {
+            ctx.metrics.record_paper_trade(&path);
+        }
+        Ok(result) => {
+            if ctx.circuit_breaker.record(result.success) {
+                ctx.anomaly_monitor.record_breaker_trip().await?;
+            }
+            // Recorded once into the window both monitors share
+            ctx.drawdown_monitor.record(&result);
+            ctx.anomaly_monitor.check_losses().await?;
+            ctx.metrics.record_trade(&path);
+            ctx.metrics.total_profit.add(u256_to_f64(result.actual_profit));
+            ctx.metrics.execution_time.observe(start_time.elapsed().as_millis() as f64);
+        }
+        Err(e) => {
+            if ctx.circuit_breaker.record(false) {
+                ctx.anomaly_monitor.record_breaker_trip().await?;
+            }
+            ctx.error_recovery.handle_error(e, "Arbitrage execution failed").await;
+        }
+    }
+    Ok(())
+}
+
 fn spawn_market_maker(
     set: &mut JoinSet<Result<()>>,
     market_maker: Arc<MarketMaker>,
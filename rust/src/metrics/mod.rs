@@ -1,5 +1,9 @@
 use anyhow::Result;
-use ethers::types::{Address, U256};
+use ethers::{
+    prelude::abigen,
+    providers::Middleware,
+    types::{Address, U256},
+};
 use log::{info, warn, error};
 use std::{
     collections::HashMap,
@@ -15,6 +19,13 @@ const METRIC_PROFITABLE_PATHS: &str = "profitable_paths_total";
 const METRIC_EXECUTION_TIME: &str = "execution_time_seconds";
 const METRIC_GAS_PRICE: &str = "gas_price_gwei";
 
+abigen!(
+    IMonitoredPair,
+    r#"[
+        function getReserves() external view returns (uint112 reserve0, uint112 reserve1, uint32 blockTimestampLast)
+    ]"#
+);
+
 #[derive(Debug, Clone)]
 pub struct PoolState {
     pub reserve0: U256,
@@ -23,52 +34,74 @@ pub struct PoolState {
     pub update_count: u64,
 }
 
-pub struct StateMonitor {
-    pool_states: Arc<RwLock<HashMap<Address, PoolState>>>,
-    price_thresholds: HashMap<Address, (U256, U256)>, // (min, max) prices
+pub struct StateMonitor<M: Middleware> {
+    provider: Arc<M>,
+    pub pool_states: Arc<RwLock<HashMap<Address, PoolState>>>,
+    price_thresholds: Arc<RwLock<HashMap<Address, (U256, U256)>>>, // (min, max) prices
     update_frequency: Duration,
 }
 
-impl StateMonitor {
-    pub fn new(update_frequency: Duration) -> Self {
+impl<M: Middleware + 'static> StateMonitor<M> {
+    pub fn new(provider: Arc<M>, update_frequency: Duration) -> Self {
         Self {
+            provider,
             pool_states: Arc::new(RwLock::new(HashMap::new())),
-            price_thresholds: HashMap::new(),
+            price_thresholds: Arc::new(RwLock::new(HashMap::new())),
             update_frequency,
         }
     }
 
+    /// Alert when the price of `pool` leaves `[min, max]` (1e18-scaled reserve1/reserve0).
+    pub async fn set_price_threshold(&self, pool: Address, min: U256, max: U256) {
+        self.price_thresholds.write().await.insert(pool, (min, max));
+    }
+
+    pub async fn clear_price_threshold(&self, pool: Address) {
+        self.price_thresholds.write().await.remove(&pool);
+    }
+
     pub async fn monitor_pools(&self, pools: Vec<Address>) -> Result<()> {
         info!("Starting pool monitoring for {} pools", pools.len());
         
         loop {
-            let start = Instant::now();
+            self.update_pools(&pools).await;
             
-            for pool in &pools {
-                if let Err(e) = self.update_pool_state(*pool).await {
-                    error!("Failed to update pool {}: {:?}", pool, e);
-                    continue;
-                }
-                
-                // Update metrics
-                counter!(METRIC_POOL_UPDATES, 1);
-                
-                // Check for significant changes
-                if let Some(changes) = self.check_significant_changes(*pool).await {
-                    warn!("Significant changes in pool {}: {:?}", pool, changes);
-                }
+            // Wait for next update
+            tokio::time::sleep(self.update_frequency).await;
+        }
+    }
+
+    /// Run a single monitoring pass over `pools`, recording update count and duration.
+    pub async fn update_pools(&self, pools: &[Address]) {
+        let start = Instant::now();
+        
+        for pool in pools {
+            if let Err(e) = self.update_pool_state(*pool).await {
+                error!("Failed to update pool {}: {:?}", pool, e);
+                continue;
             }
             
-            // Record execution time
-            let duration = start.elapsed();
-            histogram!(METRIC_EXECUTION_TIME, duration.as_secs_f64());
+            // Update metrics
+            counter!(METRIC_POOL_UPDATES, 1);
             
-            // Wait for next update
-            tokio::time::sleep(self.update_frequency).await;
+            // Check for significant changes
+            if let Some(changes) = self.check_significant_changes(*pool).await {
+                warn!("Significant changes in pool {}: {:?}", pool, changes);
+            }
         }
+        
+        // Record execution time
+        let duration = start.elapsed();
+        histogram!(METRIC_EXECUTION_TIME, duration.as_secs_f64());
     }
     
-    async fn update_pool_state(&self, pool: Address) -> Result<()> {
+    /// Fetch `getReserves` for `pool` and record them as its latest state.
+    pub async fn update_pool_state(&self, pool: Address) -> Result<()> {
+        let (reserve0, reserve1, _) = IMonitoredPair::new(pool, self.provider.clone())
+            .get_reserves()
+            .call()
+            .await?;
+
         let mut states = self.pool_states.write().await;
         
         // Update pool state
@@ -79,20 +112,22 @@ impl StateMonitor {
             update_count: 0,
         });
         
+        state.reserve0 = U256::from(reserve0);
+        state.reserve1 = U256::from(reserve1);
         state.update_count += 1;
         state.last_update = Instant::now();
         
         Ok(())
     }
     
-    async fn check_significant_changes(&self, pool: Address) -> Option<Vec<String>> {
+    pub async fn check_significant_changes(&self, pool: Address) -> Option<Vec<String>> {
         let states = self.pool_states.read().await;
         let state = states.get(&pool)?;
         
         let mut changes = Vec::new();
         
         // Check reserve changes
-        if let Some(thresholds) = self.price_thresholds.get(&pool) {
+        if let Some(thresholds) = self.price_thresholds.read().await.get(&pool) {
             let current_price = calculate_price(state.reserve0, state.reserve1);
             
             if current_price < thresholds.0 {
@@ -124,11 +159,26 @@ fn calculate_price(reserve0: U256, reserve1: U256) -> U256 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ethers::{
+        abi::{encode, Token},
+        providers::{MockProvider, Provider},
+        types::Bytes,
+    };
+
+    fn reserves_response(reserve0: U256, reserve1: U256) -> Bytes {
+        encode(&[Token::Uint(reserve0), Token::Uint(reserve1), Token::Uint(U256::zero())]).into()
+    }
+
+    fn mocked_monitor() -> (StateMonitor<Provider<MockProvider>>, MockProvider) {
+        let (provider, mock) = Provider::mocked();
+        (StateMonitor::new(Arc::new(provider), Duration::from_secs(1)), mock)
+    }
     
     #[tokio::test]
     async fn test_pool_monitoring() {
-        let monitor = StateMonitor::new(Duration::from_secs(1));
+        let (monitor, mock) = mocked_monitor();
         let pool = Address::random();
+        mock.push(reserves_response(U256::from(1_000), U256::from(2_000))).unwrap();
         
         // Test pool state updates
         monitor.update_pool_state(pool).await.unwrap();
@@ -137,23 +187,59 @@ mod tests {
         let state = states.get(&pool).unwrap();
         
         assert_eq!(state.update_count, 1);
+        assert_eq!(state.reserve0, U256::from(1_000));
+        assert_eq!(state.reserve1, U256::from(2_000));
     }
     
     #[tokio::test]
     async fn test_significant_changes() {
-        let monitor = StateMonitor::new(Duration::from_secs(1));
+        let (monitor, mock) = mocked_monitor();
         let pool = Address::random();
         
-        // Add price thresholds
-        monitor.price_thresholds.insert(
-            pool,
-            (U256::from(900), U256::from(1100)) // 10% threshold
-        );
+        // Add price thresholds around a 1:1 price
+        monitor
+            .set_price_threshold(pool, U256::exp10(17) * 9, U256::exp10(17) * 11) // 10% threshold
+            .await;
+        
+        // Price of 1.0 sits inside the band
+        mock.push(reserves_response(U256::exp10(21), U256::exp10(21))).unwrap();
+        monitor.update_pool_state(pool).await.unwrap();
+        assert!(monitor.check_significant_changes(pool).await.is_none());
         
-        // Test price changes
+        // Price of 1.2 breaches the maximum
+        mock.push(reserves_response(U256::exp10(21), U256::exp10(20) * 12)).unwrap();
         monitor.update_pool_state(pool).await.unwrap();
         
-        let changes = monitor.check_significant_changes(pool).await;
-        assert!(changes.is_some());
+        let changes = monitor.check_significant_changes(pool).await.unwrap();
+        assert_eq!(changes, vec!["Price above maximum threshold".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_failed_fetch_keeps_previous_state() {
+        let (monitor, _mock) = mocked_monitor();
+        let pool = Address::random();
+
+        // No queued response: the eth_call fails and nothing is recorded
+        assert!(monitor.update_pool_state(pool).await.is_err());
+        assert!(monitor.pool_states.read().await.get(&pool).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_threshold_set_after_start_is_honored() {
+        let (monitor, mock) = mocked_monitor();
+        let pool = Address::random();
+
+        // Monitoring is already tracking the pool, with no threshold configured
+        mock.push(reserves_response(U256::exp10(21), U256::exp10(20) * 12)).unwrap();
+        monitor.update_pool_state(pool).await.unwrap();
+        assert!(monitor.check_significant_changes(pool).await.is_none());
+
+        monitor
+            .set_price_threshold(pool, U256::exp10(17) * 9, U256::exp10(17) * 11)
+            .await;
+        assert!(monitor.check_significant_changes(pool).await.is_some());
+
+        monitor.clear_price_threshold(pool).await;
+        assert!(monitor.check_significant_changes(pool).await.is_none());
     }
 }
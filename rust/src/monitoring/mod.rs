@@ -1,18 +1,47 @@
 use anyhow::Result;
-use ethers::types::{Address, U256};
+use ethers::{
+    providers::{Middleware, Provider, Ws},
+    types::{transaction::eip2718::TypedTransaction, Address, U256},
+};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
 use prometheus::{
-    register_counter, register_gauge, register_histogram,
-    Counter, Gauge, Histogram,
+    default_registry, register_counter, register_counter_vec, register_gauge, register_histogram,
+    Counter, CounterVec, Encoder, Gauge, Histogram, TextEncoder,
+};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, VecDeque},
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
-use std::{sync::Arc, time::Duration};
-use tokio::sync::RwLock;
+use tokio::sync::{Notify, RwLock};
+use warp::{filters::BoxedFilter, http::StatusCode, Filter, Reply};
+
+use crate::config::RuntimeConfig;
+use crate::flashbot::types::{RiskConfig, TradeResult};
+
+/// Label used for trade metrics recorded without a known path.
+pub const UNKNOWN_PATH: &str = "unknown";
+
+/// Label value identifying a token path, e.g. `0xa0b8…->0xc02a…`.
+pub fn path_label(path: &[Address]) -> String {
+    path.iter()
+        .map(|address| format!("{:?}", address))
+        .collect::<Vec<_>>()
+        .join("->")
+}
 
 #[derive(Clone)]
 pub struct Metrics {
-    // Performance metrics
-    pub opportunities_found: Counter,
-    pub trades_executed: Counter,
-    pub trades_failed: Counter,
+    // Performance metrics, labeled by `path`
+    pub opportunities_found: CounterVec,
+    pub trades_executed: CounterVec,
+    pub trades_failed: CounterVec,
+    pub paper_trades: CounterVec,
     pub total_profit: Gauge,
     pub execution_time: Histogram,
     
@@ -34,14 +63,21 @@ pub struct Metrics {
     pub position_value: Gauge,
     pub current_spread: Gauge,
     pub inventory_ratio: Gauge,
+
+    // Risk metrics
+    pub circuit_breaker_open: Gauge,
+    pub emergency_stops: Counter,
+    pub drawdown: Gauge,
+    pub drawdown_paused: Gauge,
 }
 
 impl Metrics {
     pub fn new() -> Result<Self> {
         Ok(Self {
-            opportunities_found: register_counter!("flashbot_opportunities_total", "Total arbitrage opportunities found")?,
-            trades_executed: register_counter!("flashbot_trades_total", "Total trades executed")?,
-            trades_failed: register_counter!("flashbot_trades_failed", "Total failed trades")?,
+            opportunities_found: register_counter_vec!("flashbot_opportunities_total", "Total arbitrage opportunities found", &["path"])?,
+            trades_executed: register_counter_vec!("flashbot_trades_total", "Total trades executed", &["path"])?,
+            trades_failed: register_counter_vec!("flashbot_trades_failed", "Total failed trades", &["path"])?,
+            paper_trades: register_counter_vec!("flashbot_paper_trades_total", "Trades simulated in paper mode, not in flashbot_trades_total", &["path"])?,
             total_profit: register_gauge!("flashbot_total_profit", "Total profit in USD")?,
             execution_time: register_histogram!("flashbot_execution_time", "Trade execution time in ms")?,
             
@@ -59,71 +95,759 @@ impl Metrics {
             position_value: register_gauge!("flashbot_position_value", "Current position value in USD")?,
             current_spread: register_gauge!("flashbot_current_spread", "Current spread in bps")?,
             inventory_ratio: register_gauge!("flashbot_inventory_ratio", "Current inventory ratio")?,
+
+            circuit_breaker_open: register_gauge!("flashbot_circuit_breaker_open", "1 while the trading circuit breaker is open")?,
+            emergency_stops: register_counter!("flashbot_emergency_stops_total", "Emergency stops triggered on the executor")?,
+            drawdown: register_gauge!("flashbot_drawdown", "Realized P&L below its peak, in wei")?,
+            drawdown_paused: register_gauge!("flashbot_drawdown_paused", "1 while submissions are paused for drawdown or stop loss")?,
         })
     }
+
+    pub fn record_opportunity(&self, path: &str) {
+        self.opportunities_found.with_label_values(&[path]).inc();
+    }
+
+    pub fn record_trade(&self, path: &str) {
+        self.trades_executed.with_label_values(&[path]).inc();
+    }
+
+    pub fn record_paper_trade(&self, path: &str) {
+        self.paper_trades.with_label_values(&[path]).inc();
+    }
+
+    pub fn record_failure(&self, path: &str) {
+        self.trades_failed.with_label_values(&[path]).inc();
+    }
+}
+
+/// Install the global `metrics` recorder so that values emitted through the
+/// `counter!`/`histogram!` macros (see `crate::metrics::StateMonitor`) can be scraped.
+pub fn install_metrics_recorder() -> Result<PrometheusHandle> {
+    Ok(PrometheusBuilder::new().install_recorder()?)
+}
+
+/// Render the prometheus default registry followed by the `metrics` recorder,
+/// in text exposition format.
+pub fn render_metrics(recorder: &PrometheusHandle) -> Result<String> {
+    let mut buffer = Vec::new();
+    TextEncoder::new().encode(&default_registry().gather(), &mut buffer)?;
+
+    let mut output = String::from_utf8(buffer)?;
+    output.push_str(&recorder.render());
+    Ok(output)
+}
+
+/// How often the provider is pinged to refresh `connected_nodes`.
+pub const NODE_PING_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Ping the node with `eth_blockNumber` and set `gauge` to 1.0 if it answered, 0.0 otherwise.
+pub async fn update_connectivity<M: Middleware>(provider: &M, gauge: &Gauge) -> bool {
+    let connected = match provider.get_block_number().await {
+        Ok(_) => true,
+        Err(e) => {
+            log::warn!("Node ping failed: {}", e);
+            false
+        }
+    };
+    gauge.set(if connected { 1.0 } else { 0.0 });
+    connected
+}
+
+#[derive(Debug, Clone)]
+pub struct HealthThresholds {
+    pub max_block_staleness: Duration,
+    pub min_connected_nodes: u32,
+    pub max_memory_bytes: u64,
+}
+
+impl From<&RuntimeConfig> for HealthThresholds {
+    fn from(config: &RuntimeConfig) -> Self {
+        Self {
+            max_block_staleness: Duration::from_secs(config.max_block_staleness_secs),
+            min_connected_nodes: config.min_connected_nodes,
+            max_memory_bytes: config.max_memory_mb * 1024 * 1024,
+        }
+    }
+}
+
+impl Default for HealthThresholds {
+    fn default() -> Self {
+        Self::from(&RuntimeConfig::default())
+    }
+}
+
+impl HealthThresholds {
+    /// Evaluate a snapshot of the health gauges; `now` and `last_block_time` are unix seconds.
+    pub fn evaluate(
+        &self,
+        now: u64,
+        last_block_time: u64,
+        connected_nodes: f64,
+        memory_usage: f64,
+    ) -> HealthReport {
+        let mut failing_checks = Vec::new();
+
+        let staleness = now.saturating_sub(last_block_time);
+        if staleness > self.max_block_staleness.as_secs() {
+            failing_checks.push(format!("last block is {}s old", staleness));
+        }
+
+        if connected_nodes < self.min_connected_nodes as f64 {
+            failing_checks.push(format!(
+                "{} connected nodes, need {}",
+                connected_nodes, self.min_connected_nodes
+            ));
+        }
+
+        if memory_usage > self.max_memory_bytes as f64 {
+            failing_checks.push(format!(
+                "memory usage {} bytes exceeds {}",
+                memory_usage, self.max_memory_bytes
+            ));
+        }
+
+        HealthReport {
+            healthy: failing_checks.is_empty(),
+            failing_checks,
+        }
+    }
+}
+
+/// Whether a request carries `Authorization: Bearer <token>`. Always true
+/// when no token is configured.
+fn bearer_auth(token: Option<String>) -> BoxedFilter<(bool,)> {
+    warp::header::optional::<String>("authorization")
+        .map(move |header: Option<String>| match &token {
+            None => true,
+            Some(token) => header.map_or(false, |header| {
+                constant_time_eq(header.as_bytes(), format!("Bearer {}", token).as_bytes())
+            }),
+        })
+        .boxed()
+}
+
+/// Compares every byte regardless of where the first mismatch is, so response
+/// timing doesn't reveal how much of a guessed token was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    let len = a.len().max(b.len());
+    let diff = (0..len).fold(a.len() ^ b.len(), |diff, i| {
+        let x = a.get(i).copied().unwrap_or(0);
+        let y = b.get(i).copied().unwrap_or(0);
+        diff | usize::from(x ^ y)
+    });
+    diff == 0
+}
+
+/// `GET /health`: the last `HealthReport` as JSON, with 200 when healthy and
+/// 503 otherwise.
+pub fn health_route(health_checker: Arc<HealthChecker>) -> BoxedFilter<(warp::reply::Response,)> {
+    warp::path!("health")
+        .and(warp::get())
+        .then(move || {
+            let health_checker = health_checker.clone();
+            async move {
+                let report = health_checker.last_report().await;
+                let status = if health_checker.is_healthy().await {
+                    StatusCode::OK
+                } else {
+                    StatusCode::SERVICE_UNAVAILABLE
+                };
+                warp::reply::with_status(warp::reply::json(&report), status).into_response()
+            }
+        })
+        .boxed()
+}
+
+/// `GET /metrics` in prometheus text format, answering 401 without the bearer
+/// token when `auth_token` is set.
+pub fn metrics_route(
+    recorder: PrometheusHandle,
+    auth_token: Option<String>,
+) -> BoxedFilter<(warp::reply::Response,)> {
+    warp::path!("metrics")
+        .and(warp::get())
+        .and(bearer_auth(auth_token))
+        .map(move |authorized: bool| {
+            if !authorized {
+                return warp::reply::with_status("unauthorized", StatusCode::UNAUTHORIZED)
+                    .into_response();
+            }
+            render_metrics(&recorder)
+                .unwrap_or_else(|e| {
+                    log::error!("Failed to render metrics: {}", e);
+                    String::new()
+                })
+                .into_response()
+        })
+        .boxed()
+}
+
+/// `POST /resume`: close the circuit breaker and clear the drawdown pause
+/// after a manual review. Always requires the bearer token.
+pub fn resume_route(
+    circuit_breaker: Arc<CircuitBreaker>,
+    drawdown_monitor: Arc<DrawdownMonitor>,
+    auth_token: String,
+) -> BoxedFilter<(warp::reply::Response,)> {
+    warp::path!("resume")
+        .and(warp::post())
+        .and(bearer_auth(Some(auth_token)))
+        .map(move |authorized: bool| {
+            if !authorized {
+                return warp::reply::with_status("unauthorized", StatusCode::UNAUTHORIZED)
+                    .into_response();
+            }
+            log::warn!("Resuming submissions on request");
+            circuit_breaker.reset();
+            drawdown_monitor.reset();
+            StatusCode::NO_CONTENT.into_response()
+        })
+        .boxed()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    pub healthy: bool,
+    pub failing_checks: Vec<String>,
 }
 
 pub struct HealthChecker {
     metrics: Arc<Metrics>,
+    thresholds: HealthThresholds,
     last_health_check: Arc<RwLock<u64>>,
-    healthy: Arc<RwLock<bool>>,
+    last_report: Arc<RwLock<HealthReport>>,
 }
 
 impl HealthChecker {
-    pub fn new(metrics: Arc<Metrics>) -> Self {
+    pub fn new(metrics: Arc<Metrics>, thresholds: HealthThresholds) -> Self {
         Self {
             metrics,
+            thresholds,
             last_health_check: Arc::new(RwLock::new(0)),
-            healthy: Arc::new(RwLock::new(true)),
+            // Healthy until the first check says otherwise
+            last_report: Arc::new(RwLock::new(HealthReport {
+                healthy: true,
+                failing_checks: Vec::new(),
+            })),
         }
     }
 
-    pub async fn check_health(&self) -> Result<bool> {
-        let mut healthy = true;
-        
-        // Check block staleness
+    pub async fn check_health(&self) -> Result<HealthReport> {
         let now = chrono::Utc::now().timestamp() as u64;
-        let last_block = self.metrics.last_block_time.get() as u64;
-        if now - last_block > 120 { // 2 minutes
-            healthy = false;
-        }
-        
-        // Check node connections
-        if self.metrics.connected_nodes.get() < 1.0 {
-            healthy = false;
-        }
-        
-        // Check memory usage
-        let max_memory = 1024 * 1024 * 1024; // 1GB
-        if self.metrics.memory_usage.get() > max_memory as f64 {
-            healthy = false;
-        }
+        let report = self.thresholds.evaluate(
+            now,
+            self.metrics.last_block_time.get() as u64,
+            self.metrics.connected_nodes.get(),
+            self.metrics.memory_usage.get(),
+        );
         
         // Update health status
         *self.last_health_check.write().await = now;
-        *self.healthy.write().await = healthy;
+        *self.last_report.write().await = report.clone();
         
-        Ok(healthy)
+        Ok(report)
     }
 
     pub async fn is_healthy(&self) -> bool {
-        *self.healthy.read().await
+        self.last_report.read().await.healthy
+    }
+
+    /// Result of the most recent `check_health`.
+    pub async fn last_report(&self) -> HealthReport {
+        self.last_report.read().await.clone()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CircuitBreakerConfig {
+    pub max_consecutive_failures: usize,
+    /// Failure ratio over a full window above which the breaker trips.
+    pub max_failure_rate: f64,
+    pub window_size: usize,
+    pub cooldown: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            max_consecutive_failures: 5,
+            max_failure_rate: 0.5,
+            window_size: 20,
+            cooldown: Duration::from_secs(300),
+        }
+    }
+}
+
+#[derive(Default)]
+struct BreakerState {
+    outcomes: VecDeque<bool>,
+    consecutive_failures: usize,
+    opened_at: Option<Instant>,
+}
+
+/// Halts trading after repeated failures until `cooldown` elapses or it is reset.
+pub struct CircuitBreaker {
+    metrics: Arc<Metrics>,
+    config: CircuitBreakerConfig,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    pub fn new(metrics: Arc<Metrics>, config: CircuitBreakerConfig) -> Self {
+        Self {
+            metrics,
+            config,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    /// Record the outcome of a trade, tripping the breaker if limits are
+    /// exceeded. Returns true if this outcome tripped it.
+    pub fn record(&self, success: bool) -> bool {
+        let mut state = self.state.lock().unwrap();
+
+        if success {
+            state.consecutive_failures = 0;
+        } else {
+            state.consecutive_failures += 1;
+        }
+        state.outcomes.push_back(success);
+        while state.outcomes.len() > self.config.window_size {
+            state.outcomes.pop_front();
+        }
+
+        if state.opened_at.is_none() && self.should_trip(&state) {
+            log::warn!(
+                "Circuit breaker tripped after {} consecutive failures",
+                state.consecutive_failures
+            );
+            state.opened_at = Some(Instant::now());
+            self.metrics.circuit_breaker_open.set(1.0);
+            return true;
+        }
+        false
+    }
+
+    /// True while trading should be halted. Closes automatically once the cooldown elapses.
+    pub fn is_open(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        match state.opened_at {
+            Some(opened_at) if opened_at.elapsed() < self.config.cooldown => true,
+            Some(_) => {
+                *state = BreakerState::default();
+                self.metrics.circuit_breaker_open.set(0.0);
+                false
+            }
+            None => false,
+        }
+    }
+
+    pub fn reset(&self) {
+        *self.state.lock().unwrap() = BreakerState::default();
+        self.metrics.circuit_breaker_open.set(0.0);
+    }
+
+    fn should_trip(&self, state: &BreakerState) -> bool {
+        if state.consecutive_failures >= self.config.max_consecutive_failures {
+            return true;
+        }
+
+        if state.outcomes.len() < self.config.window_size {
+            return false;
+        }
+        let failures = state.outcomes.iter().filter(|success| !**success).count();
+        failures as f64 / state.outcomes.len() as f64 > self.config.max_failure_rate
+    }
+}
+
+/// Realized P&L of the trades within a rolling window, shared by the
+/// drawdown and anomaly monitors so both judge the same losses.
+pub struct PnlWindow {
+    window: Duration,
+    entries: Mutex<VecDeque<(Instant, i128)>>,
+}
+
+impl PnlWindow {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            entries: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn record(&self, pnl: i128) {
+        self.entries.lock().unwrap().push_back((Instant::now(), pnl));
+    }
+
+    /// P&L of each trade still in the window and recorded at or after `since`, oldest first.
+    pub fn since(&self, since: Option<Instant>) -> Vec<i128> {
+        let mut entries = self.entries.lock().unwrap();
+        let now = Instant::now();
+        while entries
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) > self.window)
+        {
+            entries.pop_front();
+        }
+        entries
+            .iter()
+            .filter(|(at, _)| since.map_or(true, |since| *at >= since))
+            .map(|(_, pnl)| *pnl)
+            .collect()
+    }
+}
+
+/// How often the drawdown monitor re-evaluates while no trades come in, so
+/// losses ageing out of the window can resume submissions.
+pub const DRAWDOWN_EVALUATION_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Tracks realized P&L over a rolling window and pauses new submissions
+/// while the drawdown from its peak exceeds `max_drawdown`, or the net loss
+/// exceeds `stop_loss_pct`, both as a percentage of `max_position_size`.
+pub struct DrawdownMonitor {
+    metrics: Arc<Metrics>,
+    max_drawdown: i128,
+    stop_loss: i128,
+    pnl: Arc<PnlWindow>,
+    /// Trades before the last manual reset no longer count
+    reset_at: Mutex<Option<Instant>>,
+    paused: Arc<AtomicBool>,
+}
+
+impl DrawdownMonitor {
+    pub fn new(metrics: Arc<Metrics>, risk: &RiskConfig, pnl: Arc<PnlWindow>) -> Self {
+        let capital = risk.max_position_size.min(U256::from(i128::MAX as u128)).as_u128() as i128;
+        Self {
+            metrics,
+            max_drawdown: capital.saturating_mul(risk.max_drawdown as i128) / 100,
+            stop_loss: capital.saturating_mul(risk.stop_loss_pct as i128) / 100,
+            pnl,
+            reset_at: Mutex::new(None),
+            paused: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Flag shared with whatever gates submissions.
+    pub fn paused_flag(&self) -> Arc<AtomicBool> {
+        self.paused.clone()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Record a trade in the shared window and re-evaluate. Returns the
+    /// current drawdown.
+    pub fn record(&self, result: &TradeResult) -> i128 {
+        self.pnl.record(result.pnl());
+        self.evaluate()
+    }
+
+    /// Pause or resume as the trades in the window cross the limits. Runs on
+    /// every trade and on a timer, since nothing trades while paused.
+    /// Returns the current drawdown.
+    pub fn evaluate(&self) -> i128 {
+        let since = *self.reset_at.lock().unwrap();
+        let (mut equity, mut peak) = (0i128, 0i128);
+        for pnl in self.pnl.since(since) {
+            equity += pnl;
+            peak = peak.max(equity);
+        }
+        let drawdown = peak - equity;
+        self.metrics.drawdown.set(drawdown as f64);
+
+        let breached = drawdown > self.max_drawdown || -equity > self.stop_loss;
+        if breached && !self.paused.swap(true, Ordering::SeqCst) {
+            log::warn!(
+                "Pausing submissions: drawdown {} (limit {}), net P&L {} (stop loss {})",
+                drawdown, self.max_drawdown, equity, self.stop_loss
+            );
+            self.metrics.drawdown_paused.set(1.0);
+        } else if !breached && self.paused.swap(false, Ordering::SeqCst) {
+            log::info!("Drawdown recovered to {}, resuming submissions", drawdown);
+            self.metrics.drawdown_paused.set(0.0);
+        }
+        drawdown
+    }
+
+    /// Resume manually, measuring drawdown and stop loss from the current P&L.
+    pub fn reset(&self) {
+        *self.reset_at.lock().unwrap() = Some(Instant::now());
+        self.paused.store(false, Ordering::SeqCst);
+        self.metrics.drawdown.set(0.0);
+        self.metrics.drawdown_paused.set(0.0);
+    }
+}
+
+/// Halts the executor contract. Implemented by `ContractManager`.
+#[async_trait::async_trait]
+pub trait EmergencyStop: Send + Sync {
+    async fn emergency_stop(&self) -> Result<()>;
+}
+
+#[derive(Debug, Clone)]
+pub struct AnomalyConfig {
+    /// Circuit breaker trips within `trip_window` that trigger the stop.
+    pub max_breaker_trips: usize,
+    pub trip_window: Duration,
+    /// Realized loss within `loss_window`, in wei, above which to stop.
+    pub max_loss: U256,
+    pub loss_window: Duration,
+}
+
+impl AnomalyConfig {
+    /// Limit realized losses to `max_drawdown` percent of `max_position_size`.
+    pub fn from_drawdown(max_position_size: U256, max_drawdown: u8) -> Self {
+        Self {
+            max_loss: max_position_size * U256::from(max_drawdown) / U256::from(100),
+            ..Default::default()
+        }
     }
 }
 
-pub struct ErrorRecovery {
+impl Default for AnomalyConfig {
+    fn default() -> Self {
+        Self {
+            max_breaker_trips: 3,
+            trip_window: Duration::from_secs(3600),
+            max_loss: U256::MAX,
+            loss_window: Duration::from_secs(3600),
+        }
+    }
+}
+
+#[derive(Default)]
+struct AnomalyState {
+    trips: VecDeque<Instant>,
+}
+
+/// Triggers the on-chain emergency stop, once, when trading looks unsafe:
+/// repeated circuit breaker trips, losses beyond the drawdown limit, or a
+/// token flagged by the security checks while a trade is in flight.
+pub struct AnomalyMonitor {
+    stopper: Arc<dyn EmergencyStop>,
+    metrics: Arc<Metrics>,
+    config: AnomalyConfig,
+    state: Mutex<AnomalyState>,
+    pnl: Arc<PnlWindow>,
+    stopped: AtomicBool,
+}
+
+impl AnomalyMonitor {
+    pub fn new(stopper: Arc<dyn EmergencyStop>, metrics: Arc<Metrics>, config: AnomalyConfig) -> Self {
+        Self {
+            stopper,
+            metrics,
+            pnl: Arc::new(PnlWindow::new(config.loss_window)),
+            config,
+            state: Mutex::new(AnomalyState::default()),
+            stopped: AtomicBool::new(false),
+        }
+    }
+
+    /// Judge losses on `pnl`, shared with the `DrawdownMonitor` that records
+    /// into it, instead of a window of its own.
+    pub fn with_pnl_window(mut self, pnl: Arc<PnlWindow>) -> Self {
+        self.pnl = pnl;
+        self
+    }
+
+    /// The window losses are judged on, `config.loss_window` long.
+    pub fn pnl_window(&self) -> Arc<PnlWindow> {
+        self.pnl.clone()
+    }
+
+    /// True once the emergency stop has been sent; nothing should trade after that.
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::SeqCst)
+    }
+
+    pub async fn record_breaker_trip(&self) -> Result<()> {
+        let trips = {
+            let mut state = self.state.lock().unwrap();
+            let now = Instant::now();
+            state.trips.push_back(now);
+            while state
+                .trips
+                .front()
+                .is_some_and(|at| now.duration_since(*at) > self.config.trip_window)
+            {
+                state.trips.pop_front();
+            }
+            state.trips.len()
+        };
+
+        if trips >= self.config.max_breaker_trips {
+            self.stop(&format!("circuit breaker tripped {} times", trips)).await?;
+        }
+        Ok(())
+    }
+
+    /// Record a trade's realized P&L, stopping if the losses over the window
+    /// exceed `max_loss`.
+    pub async fn record_pnl(&self, pnl: i128) -> Result<()> {
+        self.pnl.record(pnl);
+        self.check_losses().await
+    }
+
+    /// Stop if the losses in the window exceed `max_loss`, for when the
+    /// trade was recorded into a shared window elsewhere.
+    pub async fn check_losses(&self) -> Result<()> {
+        let total: i128 = self.pnl.since(None).iter().sum();
+        let loss = U256::from(total.min(0).unsigned_abs());
+        if loss > self.config.max_loss {
+            self.stop(&format!("realized loss of {} exceeds {}", loss, self.config.max_loss)).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn record_compromised_token(&self, token: Address, reason: &str) -> Result<()> {
+        self.stop(&format!("token {:?} flagged: {}", token, reason)).await
+    }
+
+    /// Send the emergency stop unless it already went out. A failed send is
+    /// retried on the next anomaly.
+    async fn stop(&self, reason: &str) -> Result<()> {
+        if self.stopped.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+        log::error!("Triggering emergency stop: {}", reason);
+        self.metrics.emergency_stops.inc();
+        if let Err(e) = self.stopper.emergency_stop().await {
+            self.stopped.store(false, Ordering::SeqCst);
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+/// Shared shutdown flag plus a count of executions that must finish before exiting.
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    requested: Arc<AtomicBool>,
+    in_flight: Arc<AtomicUsize>,
+    requested_notify: Arc<Notify>,
+    idle_notify: Arc<Notify>,
+}
+
+/// Marks an execution as in flight until dropped.
+pub struct InFlight {
+    shutdown: Shutdown,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if self.shutdown.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.shutdown.idle_notify.notify_waiters();
+        }
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn trigger(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+        self.requested_notify.notify_waiters();
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+
+    /// Resolves once `trigger` has been called.
+    pub async fn wait_requested(&self) {
+        loop {
+            let notified = self.requested_notify.notified();
+            if self.is_requested() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    pub fn track(&self) -> InFlight {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        InFlight {
+            shutdown: self.clone(),
+        }
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Wait for in-flight executions to finish. Returns false if `grace` elapsed first.
+    pub async fn drain(&self, grace: Duration) -> bool {
+        tokio::time::timeout(grace, async {
+            loop {
+                let notified = self.idle_notify.notified();
+                if self.in_flight() == 0 {
+                    return;
+                }
+                notified.await;
+            }
+        })
+        .await
+        .is_ok()
+    }
+}
+
+/// An operation that ran at least this long before failing is treated as a fresh
+/// failure rather than another attempt in the same backoff sequence.
+pub const BACKOFF_RESET_AFTER: Duration = Duration::from_secs(60);
+
+/// Factor applied to the shared max-fee multiplier on each "gas price too low" error.
+pub const GAS_BUMP_FACTOR: f64 = 1.125;
+pub const MAX_FEE_MULTIPLIER: f64 = 3.0;
+
+pub struct ErrorRecovery<M: Middleware = Provider<Ws>> {
     metrics: Arc<Metrics>,
     max_retries: u32,
     backoff_base: Duration,
+    reset_after: Duration,
+    provider: Option<Arc<M>>,
+    accounts: Vec<Address>,
+    nonces: Arc<RwLock<HashMap<Address, U256>>>,
+    fee_multiplier: Arc<RwLock<f64>>,
+    paused: Arc<AtomicBool>,
 }
 
-impl ErrorRecovery {
+impl<M: Middleware> ErrorRecovery<M> {
     pub fn new(metrics: Arc<Metrics>, max_retries: u32, backoff_base: Duration) -> Self {
         Self {
             metrics,
             max_retries,
             backoff_base,
+            reset_after: BACKOFF_RESET_AFTER,
+            provider: None,
+            accounts: Vec::new(),
+            nonces: Arc::new(RwLock::new(HashMap::new())),
+            fee_multiplier: Arc::new(RwLock::new(1.0)),
+            paused: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Attach the provider and a trading account for the nonce recovery
+    /// handler. Call once per account that sends transactions.
+    pub fn with_account(mut self, provider: Arc<M>, account: Address) -> Self {
+        self.provider = Some(provider);
+        if !self.accounts.contains(&account) {
+            self.accounts.push(account);
         }
+        self
+    }
+
+    pub fn with_reset_after(mut self, reset_after: Duration) -> Self {
+        self.reset_after = reset_after;
+        self
+    }
+
+    /// Delay before the given retry attempt (1-based): base, 2 * base, 4 * base, ...
+    pub fn backoff_delay(&self, attempt: u32) -> Duration {
+        self.backoff_base * 2u32.saturating_pow(attempt.saturating_sub(1))
     }
 
     pub async fn retry_with_backoff<F, T, E>(&self, operation: F) -> Result<T>
@@ -148,15 +872,52 @@ impl ErrorRecovery {
         }
     }
 
+    /// Async counterpart of `retry_with_backoff`, suited to long-running streams.
+    /// The retry counter resets when an attempt ran longer than `reset_after`.
+    pub async fn retry_with_backoff_async<F, Fut, T, E>(&self, operation: F) -> Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: std::fmt::Display,
+    {
+        let mut retries = 0;
+        loop {
+            let started = Instant::now();
+            match operation().await {
+                Ok(result) => return Ok(result),
+                Err(e) => {
+                    if started.elapsed() >= self.reset_after {
+                        retries = 0;
+                    }
+
+                    retries += 1;
+                    if retries >= self.max_retries {
+                        return Err(anyhow::anyhow!("Max retries exceeded: {}", e));
+                    }
+
+                    let backoff = self.backoff_delay(retries);
+                    log::warn!("Attempt {} failed: {}, retrying in {:?}", retries, e, backoff);
+                    tokio::time::sleep(backoff).await;
+                }
+            }
+        }
+    }
+
     pub async fn handle_error<E: std::error::Error>(&self, error: E, context: &str) {
         // Log error
         log::error!("{}: {}", context, error);
         
         // Update metrics
-        self.metrics.trades_failed.inc();
-        
+        self.metrics.record_failure(UNKNOWN_PATH);
+
+        self.recover(&error.to_string()).await;
+    }
+
+    /// Run the recovery for `error` without logging or counting it, e.g.
+    /// between attempts of a retried operation.
+    pub async fn recover(&self, error: &str) {
         // Implement recovery strategy based on error type
-        match error.to_string().as_str() {
+        match error {
             e if e.contains("insufficient funds") => {
                 // Handle balance issues
                 self.handle_insufficient_funds().await;
@@ -176,19 +937,740 @@ impl ErrorRecovery {
         }
     }
 
+    /// True once trading should stop, e.g. after the account ran out of funds.
+    pub fn should_pause(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+    }
+
+    /// Latest on-chain nonce of `account` fetched after a nonce error, if any.
+    pub async fn nonce(&self, account: Address) -> Option<U256> {
+        self.nonces.read().await.get(&account).copied()
+    }
+
+    /// Multiplier to apply to the max fee of subsequent transactions.
+    pub async fn fee_multiplier(&self) -> f64 {
+        *self.fee_multiplier.read().await
+    }
+
+    /// Apply the recovered nonce and the fee multiplier to a transaction about
+    /// to be sent. The nonce is used once, and only for the account it was
+    /// fetched for.
+    pub async fn apply_to(&self, tx: &mut TypedTransaction) {
+        if let Some(from) = tx.from().copied() {
+            if let Some(nonce) = self.nonces.write().await.remove(&from) {
+                tx.set_nonce(nonce);
+            }
+        }
+
+        let multiplier = self.fee_multiplier().await;
+        if multiplier <= 1.0 {
+            return;
+        }
+        let scale = |fee: U256| fee * U256::from((multiplier * 1_000.0) as u64) / U256::from(1_000);
+        match tx {
+            TypedTransaction::Eip1559(inner) => {
+                inner.max_fee_per_gas = inner.max_fee_per_gas.map(scale);
+                inner.max_priority_fee_per_gas = inner.max_priority_fee_per_gas.map(scale);
+            }
+            _ => {
+                if let Some(gas_price) = tx.gas_price() {
+                    tx.set_gas_price(scale(gas_price));
+                }
+            }
+        }
+    }
+
     async fn handle_insufficient_funds(&self) {
-        // Implement fund management recovery
+        log::warn!("Insufficient funds, pausing trading");
+        self.paused.store(true, Ordering::SeqCst);
     }
 
     async fn handle_nonce_error(&self) {
-        // Implement nonce synchronization
+        let provider = match &self.provider {
+            Some(provider) => provider,
+            None => {
+                log::warn!("No provider attached, cannot re-sync nonce");
+                return;
+            }
+        };
+
+        // The error doesn't say which sender it was for, so re-sync them all
+        for account in &self.accounts {
+            match provider.get_transaction_count(*account, None).await {
+                Ok(nonce) => {
+                    log::info!("Re-synced nonce for {:?}: {}", account, nonce);
+                    self.nonces.write().await.insert(*account, nonce);
+                }
+                Err(e) => log::error!("Failed to re-sync nonce for {:?}: {}", account, e),
+            }
+        }
     }
 
     async fn handle_gas_error(&self) {
-        // Implement gas price adjustment
+        let mut multiplier = self.fee_multiplier.write().await;
+        *multiplier = (*multiplier * GAS_BUMP_FACTOR).min(MAX_FEE_MULTIPLIER);
+        log::info!("Raised max fee multiplier to {:.3}", *multiplier);
     }
 
     async fn handle_generic_error(&self) {
         // Implement generic error recovery
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::opportunity;
+    use ethers::providers::MockProvider;
+    use lazy_static::lazy_static;
+    use std::sync::atomic::AtomicU32;
+
+    type Recovery = ErrorRecovery<Provider<MockProvider>>;
+
+    lazy_static! {
+        // Prometheus rejects registering the same metric twice in the default registry
+        static ref METRICS: Arc<Metrics> = Arc::new(Metrics::new().unwrap());
+    }
+
+    #[test]
+    fn test_labeled_opportunities_are_distinct_series() {
+        METRICS.record_opportunity("WETH->USDC");
+        METRICS.record_opportunity("WETH->DAI");
+        METRICS.record_opportunity("WETH->DAI");
+
+        let families = default_registry().gather();
+        let family = families
+            .iter()
+            .find(|family| family.get_name() == "flashbot_opportunities_total")
+            .unwrap();
+
+        let count_for = |path: &str| {
+            family
+                .get_metric()
+                .iter()
+                .find(|metric| metric.get_label().iter().any(|label| label.get_value() == path))
+                .map(|metric| metric.get_counter().get_value())
+        };
+        assert_eq!(count_for("WETH->USDC"), Some(1.0));
+        assert_eq!(count_for("WETH->DAI"), Some(2.0));
+    }
+
+    #[test]
+    fn test_path_label_joins_addresses() {
+        let path = [Address::from_low_u64_be(1), Address::from_low_u64_be(2)];
+        assert_eq!(
+            path_label(&path),
+            "0x0000000000000000000000000000000000000001->0x0000000000000000000000000000000000000002"
+        );
+    }
+
+    #[test]
+    fn test_backoff_grows_exponentially() {
+        let recovery = Recovery::new(METRICS.clone(), 5, Duration::from_millis(100));
+
+        assert_eq!(recovery.backoff_delay(1), Duration::from_millis(100));
+        assert_eq!(recovery.backoff_delay(2), Duration::from_millis(200));
+        assert_eq!(recovery.backoff_delay(3), Duration::from_millis(400));
+        assert_eq!(recovery.backoff_delay(4), Duration::from_millis(800));
+    }
+
+    #[tokio::test]
+    async fn test_flaky_async_op_eventually_succeeds() {
+        let recovery = Recovery::new(METRICS.clone(), 5, Duration::from_millis(1));
+        let attempts = AtomicU32::new(0);
+
+        let result = recovery
+            .retry_with_backoff_async(|| async {
+                let attempt = attempts.fetch_add(1, Ordering::SeqCst) + 1;
+                if attempt < 3 {
+                    Err(anyhow::anyhow!("connection reset"))
+                } else {
+                    Ok(attempt)
+                }
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(result, 3);
+    }
+
+    #[tokio::test]
+    async fn test_async_retry_gives_up_after_max_retries() {
+        let recovery = Recovery::new(METRICS.clone(), 3, Duration::from_millis(1));
+        let attempts = AtomicU32::new(0);
+
+        let result: Result<()> = recovery
+            .retry_with_backoff_async(|| async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err(anyhow::anyhow!("connection refused"))
+            })
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_async_retry_resets_after_sustained_run() {
+        let recovery = Recovery::new(METRICS.clone(), 2, Duration::from_millis(1))
+            .with_reset_after(Duration::from_millis(20));
+        let attempts = AtomicU32::new(0);
+
+        // Each failure follows a long run, so the retry budget is never exhausted
+        let result = recovery
+            .retry_with_backoff_async(|| async {
+                let attempt = attempts.fetch_add(1, Ordering::SeqCst) + 1;
+                if attempt < 4 {
+                    tokio::time::sleep(Duration::from_millis(30)).await;
+                    Err(anyhow::anyhow!("stream closed"))
+                } else {
+                    Ok(attempt)
+                }
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(result, 4);
+    }
+
+    fn io_error(message: &str) -> std::io::Error {
+        std::io::Error::new(std::io::ErrorKind::Other, message)
+    }
+
+    #[tokio::test]
+    async fn test_nonce_error_refetches_nonce() {
+        let (provider, mock) = Provider::mocked();
+        let account = Address::random();
+        let recovery = Recovery::new(METRICS.clone(), 3, Duration::from_millis(1))
+            .with_account(Arc::new(provider), account);
+        mock.push(U256::from(42)).unwrap();
+
+        recovery.handle_error(io_error("nonce too low"), "Sending bundle").await;
+
+        assert_eq!(recovery.nonce(account).await, Some(U256::from(42)));
+        assert!(!recovery.should_pause());
+    }
+
+    #[tokio::test]
+    async fn test_nonce_error_refetches_every_account() {
+        let (provider, mock) = Provider::mocked();
+        let provider = Arc::new(provider);
+        let (first, second) = (Address::random(), Address::random());
+        let recovery = Recovery::new(METRICS.clone(), 3, Duration::from_millis(1))
+            .with_account(provider.clone(), first)
+            .with_account(provider, second);
+        // Served last-in first-out: the first account is fetched first
+        mock.push(U256::from(9)).unwrap();
+        mock.push(U256::from(3)).unwrap();
+
+        recovery.handle_error(io_error("nonce too low"), "Sending bundle").await;
+
+        assert_eq!(recovery.nonce(first).await, Some(U256::from(3)));
+        assert_eq!(recovery.nonce(second).await, Some(U256::from(9)));
+    }
+
+    #[tokio::test]
+    async fn test_insufficient_funds_pauses_trading() {
+        let recovery = Recovery::new(METRICS.clone(), 3, Duration::from_millis(1));
+        assert!(!recovery.should_pause());
+
+        recovery
+            .handle_error(io_error("insufficient funds for gas * price + value"), "Sending bundle")
+            .await;
+        assert!(recovery.should_pause());
+
+        recovery.resume();
+        assert!(!recovery.should_pause());
+    }
+
+    #[tokio::test]
+    async fn test_gas_error_bumps_fee_multiplier_up_to_cap() {
+        let recovery = Recovery::new(METRICS.clone(), 3, Duration::from_millis(1));
+
+        recovery.handle_error(io_error("gas price too low"), "Sending bundle").await;
+        assert_eq!(recovery.fee_multiplier().await, GAS_BUMP_FACTOR);
+
+        for _ in 0..20 {
+            recovery.handle_error(io_error("gas price too low"), "Sending bundle").await;
+        }
+        assert_eq!(recovery.fee_multiplier().await, MAX_FEE_MULTIPLIER);
+    }
+
+    #[tokio::test]
+    async fn test_recovery_is_applied_to_the_next_transaction() {
+        use ethers::types::Eip1559TransactionRequest;
+
+        let (provider, mock) = Provider::mocked();
+        let account = Address::random();
+        let recovery = Recovery::new(METRICS.clone(), 3, Duration::from_millis(1))
+            .with_account(Arc::new(provider), account);
+        mock.push(U256::from(42)).unwrap();
+        recovery.handle_error(io_error("nonce too low"), "Sending bundle").await;
+        recovery.handle_error(io_error("gas price too low"), "Sending bundle").await;
+
+        let request = Eip1559TransactionRequest::new()
+            .from(account)
+            .nonce(7)
+            .max_fee_per_gas(8_000)
+            .max_priority_fee_per_gas(800);
+        let mut tx: TypedTransaction = request.clone().into();
+        recovery.apply_to(&mut tx).await;
+
+        let bumped = |fee: u64| Some(U256::from((fee as f64 * GAS_BUMP_FACTOR) as u64));
+        assert_eq!(tx.nonce(), Some(&U256::from(42)));
+        match &tx {
+            TypedTransaction::Eip1559(inner) => {
+                assert_eq!(inner.max_fee_per_gas, bumped(8_000));
+                assert_eq!(inner.max_priority_fee_per_gas, bumped(800));
+            }
+            _ => unreachable!(),
+        }
+
+        // The recovered nonce is spent, and never applied to another sender
+        let mut next: TypedTransaction = request.clone().into();
+        recovery.apply_to(&mut next).await;
+        assert_eq!(next.nonce(), Some(&U256::from(7)));
+
+        mock.push(U256::from(50)).unwrap();
+        recovery.handle_error(io_error("nonce too low"), "Sending bundle").await;
+        let mut other: TypedTransaction = request.from(Address::random()).into();
+        recovery.apply_to(&mut other).await;
+        assert_eq!(other.nonce(), Some(&U256::from(7)));
+    }
+
+    /// Percentages of a capital of 10_000.
+    fn drawdown_monitor(stop_loss_pct: u8, max_drawdown: u8) -> DrawdownMonitor {
+        drawdown_monitor_over(stop_loss_pct, max_drawdown, Duration::from_secs(3600))
+    }
+
+    fn drawdown_monitor_over(stop_loss_pct: u8, max_drawdown: u8, window: Duration) -> DrawdownMonitor {
+        DrawdownMonitor::new(
+            METRICS.clone(),
+            &RiskConfig {
+                max_position_size: U256::from(10_000),
+                max_leverage: 1,
+                stop_loss_pct,
+                max_drawdown,
+                min_pool_liquidity: U256::zero(),
+                max_price_impact_bps: 100,
+                blacklisted_tokens: vec![],
+                min_profit_threshold: U256::zero(),
+            },
+            Arc::new(PnlWindow::new(window)),
+        )
+    }
+
+    fn trade_result(pnl: i64) -> TradeResult {
+        let mut result = TradeResult::paper(
+            &opportunity(pnl.max(0) as u64, (-pnl.min(0)) as u64),
+            Duration::ZERO,
+        );
+        result.success = pnl > 0;
+        result.paper = false;
+        result
+    }
+
+    #[test]
+    fn test_losing_streak_pauses_submissions() {
+        let monitor = drawdown_monitor(20, 10);
+        let paused = monitor.paused_flag();
+
+        monitor.record(&trade_result(1_500));
+        for _ in 0..3 {
+            monitor.record(&trade_result(-300));
+        }
+        assert!(!paused.load(Ordering::SeqCst));
+
+        // 1_200 below the 1_500 peak
+        assert_eq!(monitor.record(&trade_result(-300)), 1_200);
+        assert!(paused.load(Ordering::SeqCst));
+
+        // Recovering to within the limit resumes
+        monitor.record(&trade_result(400));
+        assert!(!monitor.is_paused());
+    }
+
+    #[test]
+    fn test_stop_loss_pauses_until_reset() {
+        // 500 stop loss, well inside the 1_000 drawdown limit
+        let monitor = drawdown_monitor(5, 10);
+
+        monitor.record(&trade_result(-400));
+        assert!(!monitor.is_paused());
+        monitor.record(&trade_result(-200));
+        assert!(monitor.is_paused());
+
+        monitor.reset();
+        assert!(!monitor.is_paused());
+        monitor.record(&trade_result(-400));
+        assert!(!monitor.is_paused());
+    }
+
+    #[test]
+    fn test_pause_lifts_once_losses_leave_the_window() {
+        let monitor = drawdown_monitor_over(5, 10, Duration::from_millis(30));
+
+        monitor.record(&trade_result(-600));
+        assert!(monitor.is_paused());
+
+        // Nothing trades while paused; the timer's re-evaluation resumes
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(monitor.evaluate(), 0);
+        assert!(!monitor.is_paused());
+    }
+
+    #[tokio::test]
+    async fn test_shared_window_is_judged_by_both_monitors() {
+        let stopper = Arc::new(CountingStop::default());
+        let anomaly = anomaly_monitor(stopper.clone(), AnomalyConfig::from_drawdown(U256::from(10_000), 10));
+        let drawdown = DrawdownMonitor::new(
+            METRICS.clone(),
+            &RiskConfig {
+                max_position_size: U256::from(10_000),
+                max_leverage: 1,
+                stop_loss_pct: 5,
+                max_drawdown: 10,
+                min_pool_liquidity: U256::zero(),
+                max_price_impact_bps: 100,
+                blacklisted_tokens: vec![],
+                min_profit_threshold: U256::zero(),
+            },
+            anomaly.pnl_window(),
+        );
+
+        // Recorded once, seen by both
+        drawdown.record(&trade_result(-1_100));
+        anomaly.check_losses().await.unwrap();
+        assert!(drawdown.is_paused());
+        assert_eq!(stopper.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_resume_route_resets_breaker_and_drawdown() {
+        let circuit_breaker = Arc::new(breaker(Duration::from_secs(3600)));
+        let drawdown = Arc::new(drawdown_monitor(5, 10));
+        let route = resume_route(circuit_breaker.clone(), drawdown.clone(), "s3cret".to_string());
+        for _ in 0..5 {
+            circuit_breaker.record(false);
+        }
+        drawdown.record(&trade_result(-600));
+        assert!(circuit_breaker.is_open() && drawdown.is_paused());
+
+        let anonymous = warp::test::request().method("POST").path("/resume").reply(&route).await;
+        assert_eq!(anonymous.status(), StatusCode::UNAUTHORIZED);
+        assert!(drawdown.is_paused());
+
+        let resumed = warp::test::request()
+            .method("POST")
+            .path("/resume")
+            .header("authorization", "Bearer s3cret")
+            .reply(&route)
+            .await;
+        assert_eq!(resumed.status(), StatusCode::NO_CONTENT);
+        assert!(!circuit_breaker.is_open());
+        assert!(!drawdown.is_paused());
+    }
+
+    #[derive(Default)]
+    struct CountingStop {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl EmergencyStop for CountingStop {
+        async fn emergency_stop(&self) -> Result<()> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    fn anomaly_monitor(stopper: Arc<CountingStop>, config: AnomalyConfig) -> AnomalyMonitor {
+        AnomalyMonitor::new(stopper, METRICS.clone(), config)
+    }
+
+    #[tokio::test]
+    async fn test_drawdown_triggers_emergency_stop_once() {
+        let stopper = Arc::new(CountingStop::default());
+        let monitor = anomaly_monitor(stopper.clone(), AnomalyConfig::from_drawdown(U256::from(1_000), 10));
+
+        monitor.record_pnl(50).await.unwrap();
+        monitor.record_pnl(-120).await.unwrap();
+        assert!(!monitor.is_stopped());
+
+        monitor.record_pnl(-40).await.unwrap();
+        assert!(monitor.is_stopped());
+
+        monitor.record_pnl(-500).await.unwrap();
+        monitor.record_compromised_token(Address::zero(), "honeypot").await.unwrap();
+        assert_eq!(stopper.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_repeated_breaker_trips_trigger_emergency_stop() {
+        let stopper = Arc::new(CountingStop::default());
+        let monitor = anomaly_monitor(stopper.clone(), AnomalyConfig::default());
+
+        monitor.record_breaker_trip().await.unwrap();
+        monitor.record_breaker_trip().await.unwrap();
+        assert_eq!(stopper.calls.load(Ordering::SeqCst), 0);
+
+        monitor.record_breaker_trip().await.unwrap();
+        assert_eq!(stopper.calls.load(Ordering::SeqCst), 1);
+    }
+
+    fn breaker(cooldown: Duration) -> CircuitBreaker {
+        CircuitBreaker::new(
+            METRICS.clone(),
+            CircuitBreakerConfig {
+                max_consecutive_failures: 5,
+                max_failure_rate: 0.5,
+                window_size: 10,
+                cooldown,
+            },
+        )
+    }
+
+    #[test]
+    fn test_breaker_trips_on_consecutive_failures() {
+        let breaker = breaker(Duration::from_secs(60));
+
+        for _ in 0..4 {
+            breaker.record(false);
+        }
+        assert!(!breaker.is_open());
+
+        breaker.record(false);
+        assert!(breaker.is_open());
+    }
+
+    #[test]
+    fn test_breaker_trips_on_failure_rate() {
+        let breaker = breaker(Duration::from_secs(60));
+
+        // 6 failures out of 10, never more than 2 in a row
+        for success in [false, false, true, false, false, true, false, false, true, true] {
+            breaker.record(success);
+        }
+        assert!(breaker.is_open());
+    }
+
+    #[test]
+    fn test_breaker_stays_closed_below_limits() {
+        let breaker = breaker(Duration::from_secs(60));
+
+        for success in [false, true, false, true, true, false, true, true, false, true] {
+            breaker.record(success);
+        }
+        assert!(!breaker.is_open());
+    }
+
+    #[test]
+    fn test_breaker_closes_after_cooldown() {
+        let breaker = breaker(Duration::from_millis(20));
+        for _ in 0..5 {
+            breaker.record(false);
+        }
+        assert!(breaker.is_open());
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(!breaker.is_open());
+
+        // The failure history is cleared, so a single failure doesn't re-trip
+        breaker.record(false);
+        assert!(!breaker.is_open());
+    }
+
+    #[test]
+    fn test_breaker_reset() {
+        let breaker = breaker(Duration::from_secs(60));
+        for _ in 0..5 {
+            breaker.record(false);
+        }
+        assert!(breaker.is_open());
+
+        breaker.reset();
+        assert!(!breaker.is_open());
+    }
+
+    const NOW: u64 = 1_700_000_000;
+    const GB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+    #[test]
+    fn test_health_passes_within_thresholds() {
+        let report = HealthThresholds::default().evaluate(NOW, NOW - 10, 1.0, GB / 2.0);
+        assert!(report.healthy);
+        assert!(report.failing_checks.is_empty());
+    }
+
+    #[test]
+    fn test_health_fails_on_stale_block() {
+        let report = HealthThresholds::default().evaluate(NOW, NOW - 121, 1.0, GB / 2.0);
+        assert!(!report.healthy);
+        assert_eq!(report.failing_checks.len(), 1);
+        assert!(report.failing_checks[0].contains("last block"));
+    }
+
+    #[test]
+    fn test_health_fails_without_connected_nodes() {
+        let report = HealthThresholds::default().evaluate(NOW, NOW, 0.0, GB / 2.0);
+        assert!(!report.healthy);
+        assert_eq!(report.failing_checks.len(), 1);
+        assert!(report.failing_checks[0].contains("connected nodes"));
+    }
+
+    #[test]
+    fn test_health_fails_on_memory_usage() {
+        let report = HealthThresholds::default().evaluate(NOW, NOW, 1.0, GB * 2.0);
+        assert!(!report.healthy);
+        assert_eq!(report.failing_checks.len(), 1);
+        assert!(report.failing_checks[0].contains("memory usage"));
+    }
+
+    #[test]
+    fn test_health_thresholds_are_configurable() {
+        let thresholds = HealthThresholds {
+            max_block_staleness: Duration::from_secs(600),
+            min_connected_nodes: 3,
+            max_memory_bytes: 4 * 1024 * 1024 * 1024,
+        };
+
+        let report = thresholds.evaluate(NOW, NOW - 300, 2.0, GB * 2.0);
+        assert_eq!(report.failing_checks.len(), 1);
+        assert!(report.failing_checks[0].contains("connected nodes"));
+    }
+
+    #[tokio::test]
+    async fn test_connectivity_gauge_tracks_transitions() {
+        let (provider, mock) = Provider::mocked();
+        let gauge = Gauge::new("test_connected_nodes", "Connected nodes").unwrap();
+
+        mock.push(ethers::types::U64::from(100)).unwrap();
+        assert!(update_connectivity(&provider, &gauge).await);
+        assert_eq!(gauge.get(), 1.0);
+
+        // No queued response: the request fails like a dropped connection
+        assert!(!update_connectivity(&provider, &gauge).await);
+        assert_eq!(gauge.get(), 0.0);
+
+        mock.push(ethers::types::U64::from(101)).unwrap();
+        assert!(update_connectivity(&provider, &gauge).await);
+        assert_eq!(gauge.get(), 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_stops_handler_loop_within_grace() {
+        let shutdown = Shutdown::new();
+        let iterations = Arc::new(AtomicU32::new(0));
+
+        // Mirrors the arbitrage handler: stop taking work once shutdown is requested
+        let handler = tokio::spawn({
+            let shutdown = shutdown.clone();
+            let iterations = iterations.clone();
+            async move {
+                loop {
+                    tokio::select! {
+                        _ = shutdown.wait_requested() => break,
+                        _ = tokio::time::sleep(Duration::from_millis(5)) => {
+                            let _in_flight = shutdown.track();
+                            iterations.fetch_add(1, Ordering::SeqCst);
+                            tokio::time::sleep(Duration::from_millis(20)).await;
+                        }
+                    }
+                }
+            }
+        });
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        shutdown.trigger();
+
+        assert!(shutdown.drain(Duration::from_millis(200)).await);
+        tokio::time::timeout(Duration::from_millis(200), handler)
+            .await
+            .expect("handler did not stop within the grace period")
+            .unwrap();
+        assert!(iterations.load(Ordering::SeqCst) >= 1);
+        assert_eq!(shutdown.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_drain_times_out_with_stuck_execution() {
+        let shutdown = Shutdown::new();
+        let _stuck = shutdown.track();
+
+        shutdown.trigger();
+        assert!(!shutdown.drain(Duration::from_millis(20)).await);
+    }
+
+    #[tokio::test]
+    async fn test_metrics_route_requires_bearer_token() {
+        let recorder = PrometheusBuilder::new().build_recorder().handle();
+        let route = metrics_route(recorder, Some("s3cret".to_string()));
+
+        let missing = warp::test::request().path("/metrics").reply(&route).await;
+        assert_eq!(missing.status(), StatusCode::UNAUTHORIZED);
+
+        let wrong = warp::test::request()
+            .path("/metrics")
+            .header("authorization", "Bearer wrong")
+            .reply(&route)
+            .await;
+        assert_eq!(wrong.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(wrong.body().as_ref(), b"unauthorized");
+
+        METRICS.record_opportunity("WETH->DAI");
+        let right = warp::test::request()
+            .path("/metrics")
+            .header("authorization", "Bearer s3cret")
+            .reply(&route)
+            .await;
+        assert_eq!(right.status(), StatusCode::OK);
+        let body = String::from_utf8(right.body().to_vec()).unwrap();
+        assert!(body.contains("WETH->DAI"), "{}", body);
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"Bearer s3cret", b"Bearer s3cret"));
+        assert!(!constant_time_eq(b"Bearer s3cret", b"Bearer s3creT"));
+        assert!(!constant_time_eq(b"Bearer s3cret", b"Bearer s3cret2"));
+        assert!(!constant_time_eq(b"", b"Bearer"));
+    }
+
+    #[tokio::test]
+    async fn test_metrics_route_is_open_without_token() {
+        let recorder = PrometheusBuilder::new().build_recorder().handle();
+        let route = metrics_route(recorder, None);
+
+        let response = warp::test::request().path("/metrics").reply(&route).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_health_route_reports_status() {
+        let thresholds = HealthThresholds {
+            max_block_staleness: Duration::from_secs(u64::MAX / 2),
+            min_connected_nodes: 1_000,
+            max_memory_bytes: u64::MAX,
+        };
+        let checker = Arc::new(HealthChecker::new(METRICS.clone(), thresholds));
+        let route = health_route(checker.clone());
+
+        // No check has run yet
+        let healthy = warp::test::request().path("/health").reply(&route).await;
+        assert_eq!(healthy.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(healthy.body()).unwrap();
+        assert_eq!(body["healthy"], true);
+
+        // Nowhere near 1000 connected nodes
+        checker.check_health().await.unwrap();
+        let unhealthy = warp::test::request().path("/health").reply(&route).await;
+        assert_eq!(unhealthy.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body: serde_json::Value = serde_json::from_slice(unhealthy.body()).unwrap();
+        assert_eq!(body["healthy"], false);
+        assert!(body["failing_checks"][0].as_str().unwrap().contains("connected nodes"));
+    }
+}
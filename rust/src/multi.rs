@@ -1,14 +1,24 @@
-use anyhow::{Ok, Result};
+use anyhow::{anyhow, Ok, Result};
 use ethers::{
     abi,
-    providers::{Http, Provider},
-    types::{H160, H256, U256},
+    providers::{Http, Middleware, Provider},
+    types::{Bytes, H160, H256, U256},
 };
-use ethers_contract::{Contract, Multicall};
-use log::info;
+use ethers_contract::{abigen, Contract, Multicall};
+use log::{info, warn};
 use std::{collections::HashMap, sync::Arc, time::Instant};
+use tokio::sync::Semaphore;
 
-use crate::{abi::ABI, pools::Pool};
+use crate::{abi::ABI, constants::multicall3_address, pools::Pool};
+
+abigen!(
+    IMulticall3,
+    r#"[
+        struct Call3 { address target; bool allowFailure; bytes callData; }
+        struct Result3 { bool success; bytes returnData; }
+        function aggregate3(Call3[] calldata calls) external payable returns (Result3[] memory returnData)
+    ]"#,
+);
 
 #[derive(Default, Debug, Clone)]
 pub struct Reserve {
@@ -93,3 +103,212 @@ pub async fn batch_get_uniswap_v2_reserves(
     );
     reserves
 }
+
+/// Maximum number of Multicall3 aggregate requests in flight at once.
+pub const MULTICALL_CONCURRENCY: usize = 8;
+
+async fn get_uniswap_v2_reserves_chunk(
+    client: Arc<Provider<Http>>,
+    pools: Vec<Pool>,
+) -> Result<HashMap<H160, Reserve>> {
+    let abi = ABI::new();
+    let mut multicall = Multicall::new(client.clone(), None).await?;
+
+    for pool in &pools {
+        let contract = Contract::<Provider<Http>>::new(
+            pool.address,
+            abi.uniswap_v2_pair.clone(),
+            client.clone(),
+        );
+        let call = contract.method::<_, H256>("getReserves", ())?;
+        // Allow failure so a single reverting pair does not sink the whole batch
+        multicall.add_call(call, true);
+    }
+
+    let result = multicall.call_raw().await?;
+
+    let mut reserves = HashMap::new();
+    let mut failed = Vec::new();
+
+    for (pool, response) in pools.iter().zip(result.into_iter()) {
+        match response {
+            std::result::Result::Ok(abi::Token::Tuple(response)) => {
+                let reserve_data = Reserve {
+                    reserve0: response[0].clone().into_uint().unwrap_or_default(),
+                    reserve1: response[1].clone().into_uint().unwrap_or_default(),
+                };
+                reserves.insert(pool.address, reserve_data);
+            }
+            _ => failed.push(pool.clone()),
+        }
+    }
+
+    // Retry reverted calls one by one outside of the aggregate call
+    for pool in failed {
+        let contract = Contract::<Provider<Http>>::new(
+            pool.address,
+            abi.uniswap_v2_pair.clone(),
+            client.clone(),
+        );
+        match contract
+            .method::<_, (u128, u128, u32)>("getReserves", ())?
+            .call()
+            .await
+        {
+            std::result::Result::Ok((reserve0, reserve1, _)) => {
+                reserves.insert(
+                    pool.address,
+                    Reserve {
+                        reserve0: U256::from(reserve0),
+                        reserve1: U256::from(reserve1),
+                    },
+                );
+            }
+            Err(e) => warn!("getReserves failed for {:?}: {:?}", pool.address, e),
+        }
+    }
+
+    Ok(reserves)
+}
+
+pub async fn batch_get_uniswap_v2_reserves_multicall(
+    https_url: String,
+    pools: Vec<Pool>,
+    chunk_size: usize,
+) -> Result<HashMap<H160, Reserve>> {
+    let start_time = Instant::now();
+
+    let client = Arc::new(Provider::<Http>::try_from(https_url)?);
+    let semaphore = Arc::new(Semaphore::new(MULTICALL_CONCURRENCY));
+
+    let mut handles = vec![];
+
+    for chunk in pools.chunks(chunk_size.max(1)) {
+        let client = client.clone();
+        let semaphore = semaphore.clone();
+        let chunk = chunk.to_vec();
+        let handle = tokio::spawn(async move {
+            let _permit = semaphore.acquire_owned().await?;
+            get_uniswap_v2_reserves_chunk(client, chunk).await
+        });
+        handles.push(handle);
+    }
+
+    let mut reserves: HashMap<H160, Reserve> = HashMap::new();
+
+    for handle in handles {
+        reserves.extend(handle.await??);
+    }
+
+    info!(
+        "Multicall reserves for {} pools took: {} ms",
+        pools.len(),
+        start_time.elapsed().as_millis()
+    );
+    Ok(reserves)
+}
+
+/// Raw `aggregate3` batch against the chain's Multicall3, for calls that
+/// don't have a typed contract binding.
+pub struct Multicall3<M> {
+    contract: IMulticall3<M>,
+    calls: Vec<Call3>,
+}
+
+impl<M: Middleware> Multicall3<M> {
+    pub fn new(client: Arc<M>, chain_id: u64) -> Result<Self> {
+        let address = multicall3_address(chain_id)
+            .ok_or_else(|| anyhow!("No Multicall3 deployment known for chain {}", chain_id))?;
+        Ok(Self {
+            contract: IMulticall3::new(address, client),
+            calls: Vec::new(),
+        })
+    }
+
+    /// Queue `call_data` to `target`. With `allow_failure` a revert only fails
+    /// this call, otherwise it reverts the whole batch.
+    pub fn add_call(&mut self, target: H160, call_data: Bytes, allow_failure: bool) -> &mut Self {
+        self.calls.push(Call3 {
+            target,
+            allow_failure,
+            call_data,
+        });
+        self
+    }
+
+    /// Calldata of the `aggregate3` call for the queued calls.
+    pub fn calldata(&self) -> Bytes {
+        self.contract
+            .aggregate_3(self.calls.clone())
+            .calldata()
+            .unwrap_or_default()
+    }
+
+    /// `(success, return data)` of every queued call, in order.
+    pub async fn call(&self) -> Result<Vec<(bool, Bytes)>> {
+        let results = self
+            .contract
+            .aggregate_3(self.calls.clone())
+            .call()
+            .await
+            .map_err(|e| anyhow!("aggregate3 failed: {}", e))?;
+        Ok(results
+            .into_iter()
+            .map(|result| (result.success, result.return_data))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::{ParamType, Token};
+    use ethers::utils::keccak256;
+
+    #[test]
+    fn test_multicall3_encodes_aggregate3() {
+        let (provider, _mock) = Provider::mocked();
+        let client = Arc::new(provider);
+        let (pair, token) = (H160::random(), H160::random());
+        let get_reserves = Bytes::from(keccak256("getReserves()")[..4].to_vec());
+
+        let mut multicall = Multicall3::new(client.clone(), 1).unwrap();
+        multicall
+            .add_call(pair, get_reserves.clone(), true)
+            .add_call(token, Bytes::default(), false);
+        let calldata = multicall.calldata();
+
+        assert_eq!(calldata[..4], keccak256("aggregate3((address,bool,bytes)[])")[..4]);
+        let call3 = ParamType::Tuple(vec![ParamType::Address, ParamType::Bool, ParamType::Bytes]);
+        let decoded = abi::decode(&[ParamType::Array(Box::new(call3))], &calldata[4..]).unwrap();
+        assert_eq!(
+            decoded[0],
+            Token::Array(vec![
+                Token::Tuple(vec![Token::Address(pair), Token::Bool(true), Token::Bytes(get_reserves.to_vec())]),
+                Token::Tuple(vec![Token::Address(token), Token::Bool(false), Token::Bytes(vec![])]),
+            ])
+        );
+
+        assert!(Multicall3::new(client, 56).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_multicall3_returns_per_call_results() {
+        let (provider, mock) = Provider::mocked();
+        let response = abi::encode(&[Token::Array(vec![
+            Token::Tuple(vec![Token::Bool(true), Token::Bytes(vec![0xaa])]),
+            Token::Tuple(vec![Token::Bool(false), Token::Bytes(vec![])]),
+        ])]);
+        mock.push(Bytes::from(response)).unwrap();
+
+        let mut multicall = Multicall3::new(Arc::new(provider), 8453).unwrap();
+        multicall
+            .add_call(H160::random(), Bytes::default(), true)
+            .add_call(H160::random(), Bytes::default(), true);
+
+        assert_eq!(
+            multicall.call().await.unwrap(),
+            vec![(true, Bytes::from(vec![0xaa])), (false, Bytes::default())]
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use anyhow::Result;
+use ethers::{
+    providers::Middleware,
+    types::{Address, BlockNumber, U256, U64},
+};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use tokio::sync::Mutex;
+
+/// Hands out sequential nonces for one sender so several transactions built in
+/// the same block don't reuse a nonce. The counter is seeded from the pending
+/// transaction count and re-synced on a new block or after a nonce error.
+pub struct NonceManager {
+    address: Address,
+    next: AtomicU64,
+    block: AtomicU64,
+    stale: AtomicBool,
+    sync_lock: Mutex<()>,
+}
+
+impl NonceManager {
+    pub fn new(address: Address) -> Self {
+        Self {
+            address,
+            next: AtomicU64::new(0),
+            block: AtomicU64::new(0),
+            stale: AtomicBool::new(true),
+            sync_lock: Mutex::new(()),
+        }
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Reserve the next nonce, fetching the on-chain count first if needed.
+    pub async fn next<M: Middleware>(&self, provider: &M) -> Result<U256> {
+        if self.stale.load(Ordering::Acquire) {
+            let _guard = self.sync_lock.lock().await;
+            // Another caller may have re-synced while we waited
+            if self.stale.load(Ordering::Acquire) {
+                self.sync(provider).await?;
+            }
+        }
+        Ok(U256::from(self.next.fetch_add(1, Ordering::AcqRel)))
+    }
+
+    /// Give back a nonce whose transaction won't be sent. Only the most recently
+    /// reserved nonce can be returned; anything older would leave a gap anyway.
+    pub fn release(&self, nonce: U256) -> bool {
+        let nonce = nonce.as_u64();
+        self.next
+            .compare_exchange(nonce + 1, nonce, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+    }
+
+    /// Re-sync from chain on the first nonce requested in a new block.
+    pub fn on_new_block(&self, block_number: U64) {
+        let block = block_number.as_u64();
+        if self.block.swap(block, Ordering::AcqRel) != block {
+            self.invalidate();
+        }
+    }
+
+    /// Force a re-sync, e.g. after a "nonce too low" error.
+    pub fn invalidate(&self) {
+        self.stale.store(true, Ordering::Release);
+    }
+
+    /// Invalidate if `error` is a nonce error. Returns whether it was.
+    pub fn handle_error(&self, error: &str) -> bool {
+        let is_nonce_error = error.contains("nonce too low") || error.contains("nonce too high");
+        if is_nonce_error {
+            self.invalidate();
+        }
+        is_nonce_error
+    }
+
+    async fn sync<M: Middleware>(&self, provider: &M) -> Result<()> {
+        let nonce = provider
+            .get_transaction_count(self.address, Some(BlockNumber::Pending.into()))
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to fetch nonce for {:?}: {}", self.address, e))?;
+        self.next.store(nonce.as_u64(), Ordering::Release);
+        self.stale.store(false, Ordering::Release);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::providers::Provider;
+
+    #[tokio::test]
+    async fn test_bundles_in_one_block_get_consecutive_nonces() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(U256::from(7)).unwrap();
+
+        let nonces = NonceManager::new(Address::random());
+        nonces.on_new_block(U64::from(100));
+
+        let mut handed_out = Vec::new();
+        for _ in 0..4 {
+            handed_out.push(nonces.next(&provider).await.unwrap());
+        }
+
+        let expected: Vec<U256> = (7..11).map(U256::from).collect();
+        assert_eq!(handed_out, expected);
+    }
+
+    #[tokio::test]
+    async fn test_resyncs_on_new_block_and_nonce_error() {
+        let (provider, mock) = Provider::mocked();
+        let nonces = NonceManager::new(Address::random());
+
+        mock.push(U256::from(3)).unwrap();
+        nonces.on_new_block(U64::from(1));
+        assert_eq!(nonces.next(&provider).await.unwrap(), U256::from(3));
+        assert_eq!(nonces.next(&provider).await.unwrap(), U256::from(4));
+
+        // Same block: no re-sync, so no response needs to be queued
+        nonces.on_new_block(U64::from(1));
+        assert_eq!(nonces.next(&provider).await.unwrap(), U256::from(5));
+
+        // Only one of the three transactions landed
+        mock.push(U256::from(4)).unwrap();
+        nonces.on_new_block(U64::from(2));
+        assert_eq!(nonces.next(&provider).await.unwrap(), U256::from(4));
+
+        mock.push(U256::from(9)).unwrap();
+        assert!(nonces.handle_error("nonce too low: next nonce 9, tx nonce 5"));
+        assert_eq!(nonces.next(&provider).await.unwrap(), U256::from(9));
+
+        // A dropped transaction hands its nonce to the next one
+        let dropped = nonces.next(&provider).await.unwrap();
+        assert!(nonces.release(dropped));
+        assert!(!nonces.release(dropped - 1));
+        assert_eq!(nonces.next(&provider).await.unwrap(), dropped);
+
+        assert!(!nonces.handle_error("insufficient funds"));
+        assert_eq!(nonces.next(&provider).await.unwrap(), U256::from(11));
+    }
+}
@@ -5,55 +5,60 @@ use std::{collections::HashMap, time::Instant};
 
 use crate::bundler::PathParam;
 use crate::multi::Reserve;
-use crate::pools::{Pool, LOW_LIQUIDITY_THRESHOLD};
+use crate::pools::{DexVariant, Pool, LOW_LIQUIDITY_THRESHOLD};
+use crate::simulator::{UniswapV2Simulator, UniswapV3Simulator, V3PoolState};
+use crate::utils::{golden_section_max, min_amount_after_slippage};
 use log::info;
 
 #[derive(Debug, Clone)]
 pub struct ArbPath {
     pub nhop: u8,
-    pub pool_1: Pool,
-    pub pool_2: Pool,
-    pub pool_3: Pool,
-    pub zero_for_one_1: bool,
-    pub zero_for_one_2: bool,
-    pub zero_for_one_3: bool,
+    pub pools: Vec<Pool>,
+    pub zero_for_ones: Vec<bool>,
 }
 
 impl ArbPath {
+    pub fn new(pools: Vec<Pool>, zero_for_ones: Vec<bool>) -> Self {
+        Self {
+            nhop: pools.len() as u8,
+            pools,
+            zero_for_ones,
+        }
+    }
+
     pub fn has_pool(&self, pool: &H160) -> bool {
-        let is_pool_1 = self.pool_1.address == *pool;
-        let is_pool_2 = self.pool_2.address == *pool;
-        let is_pool_3 = self.pool_3.address == *pool;
-        return is_pool_1 || is_pool_2 || is_pool_3;
+        self.pools.iter().any(|p| p.address == *pool)
+    }
+
+    /// True if both paths trade through at least one common pool.
+    pub fn overlaps(&self, other: &ArbPath) -> bool {
+        self.pools.iter().any(|pool| other.has_pool(&pool.address))
     }
 
     pub fn _get_pool(&self, i: u8) -> &Pool {
-        match i {
-            0 => Some(&self.pool_1),
-            1 => Some(&self.pool_2),
-            2 => Some(&self.pool_3),
-            _ => None,
-        }
-        .unwrap()
+        &self.pools[i as usize]
     }
 
     pub fn _get_zero_for_one(&self, i: u8) -> bool {
-        match i {
-            0 => Some(self.zero_for_one_1),
-            1 => Some(self.zero_for_one_2),
-            2 => Some(self.zero_for_one_3),
-            _ => None,
-        }
-        .unwrap()
+        self.zero_for_ones[i as usize]
     }
 
     pub fn should_blacklist(&self, blacklist_tokens: &Vec<H160>) -> bool {
-        for i in 0..self.nhop {
-            let pool = self._get_pool(i);
-            return blacklist_tokens.contains(&pool.token0)
-                || blacklist_tokens.contains(&pool.token1);
-        }
-        false
+        self.should_blacklist_excluding(blacklist_tokens, None)
+    }
+
+    /// True if any token touched by any hop is blacklisted. `base_token`, the token the
+    /// path starts and ends in, is never counted even if it appears in the blacklist.
+    pub fn should_blacklist_excluding(
+        &self,
+        blacklist_tokens: &Vec<H160>,
+        base_token: Option<H160>,
+    ) -> bool {
+        self.pools
+            .iter()
+            .flat_map(|pool| [pool.token0, pool.token1])
+            .filter(|token| Some(*token) != base_token)
+            .any(|token| blacklist_tokens.contains(&token))
     }
 
     pub fn simulate_v2_path(
@@ -61,13 +66,23 @@ impl ArbPath {
         amount_in: U256,
         reserves: &HashMap<H160, Reserve>,
     ) -> Option<U256> {
-        let token_in_decimals = if self.zero_for_one_1 {
-            self.pool_1.decimals0
+        self.simulate_v2_hops(amount_in, reserves)?.last().copied()
+    }
+
+    /// Output of every hop for `amount_in` whole tokens, in each token's smallest unit.
+    pub fn simulate_v2_hops(
+        &self,
+        amount_in: U256,
+        reserves: &HashMap<H160, Reserve>,
+    ) -> Option<Vec<U256>> {
+        let token_in_decimals = if self.zero_for_ones[0] {
+            self.pools[0].decimals0
         } else {
-            self.pool_1.decimals1
+            self.pools[0].decimals1
         };
         let unit = U256::from(10).pow(U256::from(token_in_decimals));
         let mut amount_out = amount_in * unit;
+        let mut amounts_out = Vec::with_capacity(self.nhop as usize);
 
         for i in 0..self.nhop {
             let pool = self._get_pool(i);
@@ -90,45 +105,97 @@ impl ArbPath {
 
             amount_out =
                 UniswapV2Simulator::get_amount_out(amount_out, reserve_in, reserve_out, fee)?;
+            amounts_out.push(amount_out);
+        }
+
+        Some(amounts_out)
+    }
+
+    /// Like `simulate_v2_path`, but dispatches each hop on the pool's `DexVariant`.
+    /// V2 hops read `reserves`, V3 hops read `v3_states`; a missing entry aborts with `None`.
+    pub fn simulate_mixed_path(
+        &self,
+        amount_in: U256,
+        reserves: &HashMap<H160, Reserve>,
+        v3_states: &HashMap<H160, V3PoolState>,
+    ) -> Option<U256> {
+        let token_in_decimals = if self.zero_for_ones[0] {
+            self.pools[0].decimals0
+        } else {
+            self.pools[0].decimals1
+        };
+        let unit = U256::from(10).pow(U256::from(token_in_decimals));
+        let mut amount_out = amount_in * unit;
+
+        for i in 0..self.nhop {
+            let pool = self._get_pool(i);
+            let zero_for_one = self._get_zero_for_one(i);
+            let fee = U256::from(pool.fee);
+
+            amount_out = match pool.version {
+                DexVariant::UniswapV2 => {
+                    let reserve = reserves.get(&pool.address)?;
+                    let (reserve_in, reserve_out) = if zero_for_one {
+                        (reserve.reserve0, reserve.reserve1)
+                    } else {
+                        (reserve.reserve1, reserve.reserve0)
+                    };
+                    UniswapV2Simulator::get_amount_out(amount_out, reserve_in, reserve_out, fee)?
+                }
+                DexVariant::UniswapV3 => {
+                    let state = v3_states.get(&pool.address)?;
+                    UniswapV3Simulator::get_amount_out(amount_out, state, fee, zero_for_one)?
+                }
+            };
         }
 
         Some(amount_out)
     }
 
+    /// Find the input (in whole tokens, up to `max_amount_in`) maximizing profit in the
+    /// token's smallest unit. Constant-product profit is concave in the input, so a
+    /// golden-section search narrows to `tolerance` tokens in O(log) simulations.
     pub fn optimize_amount_in(
         &self,
         max_amount_in: U256,
-        step_size: usize,
+        tolerance: usize,
         reserves: &HashMap<H160, Reserve>,
     ) -> (U256, U256) {
-        let token_in_decimals = if self.zero_for_one_1 {
-            self.pool_1.decimals0
+        let token_in_decimals = if self.zero_for_ones[0] {
+            self.pools[0].decimals0
         } else {
-            self.pool_1.decimals1
+            self.pools[0].decimals1
         };
+        let unit = U256::from(10).pow(U256::from(token_in_decimals));
 
-        let mut optimized_in = U256::zero();
-        let mut profit = 0;
-
-        for amount_in in (0..max_amount_in.as_u64()).step_by(step_size) {
+        let profit = |amount_in: u64| {
             let amount_in = U256::from(amount_in);
-            let unit = U256::from(10).pow(U256::from(token_in_decimals));
-            if let Some(amount_out) = self.simulate_v2_path(amount_in, &reserves) {
-                let this_profit =
-                    (amount_out.as_u128() as i128) - ((amount_in * unit).as_u128() as i128);
-                if this_profit >= profit {
-                    optimized_in = amount_in;
-                    profit = this_profit;
-                } else {
-                    break;
+            match self.simulate_v2_path(amount_in, &reserves) {
+                Some(amount_out) => {
+                    (amount_out.as_u128() as i128) - ((amount_in * unit).as_u128() as i128)
                 }
+                // Unsimulatable inputs (zero, or too large for the pools) are never optimal
+                None => i128::MIN,
             }
-        }
+        };
 
-        (optimized_in, U256::from(profit))
+        let (optimized_in, profit) =
+            golden_section_max(0, max_amount_in.as_u64(), tolerance as u64, profit);
+
+        if profit <= 0 {
+            return (U256::zero(), U256::zero());
+        }
+        (U256::from(optimized_in), U256::from(profit))
     }
 
-    pub fn to_path_params(&self, routers: &Vec<H160>) -> Vec<PathParam> {
+    /// Executor hops for this path. Each hop's minimum output is its simulated
+    /// output in `amounts_out` less `max_slippage_bps`.
+    pub fn to_path_params(
+        &self,
+        routers: &Vec<H160>,
+        amounts_out: &[U256],
+        max_slippage_bps: u16,
+    ) -> Vec<PathParam> {
         let mut path_params = Vec::new();
         for i in 0..self.nhop {
             let pool = self._get_pool(i);
@@ -148,6 +215,7 @@ impl ArbPath {
                 router: routers[i as usize],
                 token_in: token_in,
                 token_out: token_out,
+                min_amount_out: min_amount_after_slippage(amounts_out[i as usize], max_slippage_bps),
             };
             path_params.push(param);
         }
@@ -155,6 +223,21 @@ impl ArbPath {
     }
 }
 
+/// Greedily pick paths in `ranked` order (best first), skipping any that shares a
+/// pool with one already picked, until `max_paths` are selected.
+pub fn select_non_overlapping(paths: &[ArbPath], ranked: &[usize], max_paths: usize) -> Vec<usize> {
+    let mut selected: Vec<usize> = Vec::new();
+    for &idx in ranked {
+        if selected.len() >= max_paths {
+            break;
+        }
+        if selected.iter().all(|&picked| !paths[picked].overlaps(&paths[idx])) {
+            selected.push(idx);
+        }
+    }
+    selected
+}
+
 pub fn generate_triangular_paths(pools: &Vec<Pool>, token_in: H160) -> Vec<ArbPath> {
     let start_time = Instant::now();
 
@@ -250,15 +333,10 @@ pub fn generate_triangular_paths(pools: &Vec<Pool>, token_in: H160) -> Vec<ArbPa
                                     pool3_liq.as_u128() / 1_000_000
                                 );
 
-                                let arb_path = ArbPath {
-                                    nhop: 3,
-                                    pool_1: pool_1.clone(),
-                                    pool_2: pool_2.clone(),
-                                    pool_3: pool_3.clone(),
-                                    zero_for_one_1: zero_for_one_1,
-                                    zero_for_one_2: zero_for_one_2,
-                                    zero_for_one_3: zero_for_one_3,
-                                };
+                                let arb_path = ArbPath::new(
+                                    vec![pool_1.clone(), pool_2.clone(), pool_3.clone()],
+                                    vec![zero_for_one_1, zero_for_one_2, zero_for_one_3],
+                                );
 
                                 paths.push(arb_path);
                             }
@@ -278,3 +356,370 @@ pub fn generate_triangular_paths(pools: &Vec<Pool>, token_in: H160) -> Vec<ArbPa
     ));
     paths
 }
+
+/// Generate every cycle of 2..=max_hops pools that starts and ends at `token_in`.
+/// Pools are never reused within a path and intermediate tokens are visited once.
+pub fn generate_cyclic_paths(pools: &Vec<Pool>, token_in: H160, max_hops: u8) -> Vec<ArbPath> {
+    let start_time = Instant::now();
+
+    let mut adjacency: HashMap<H160, Vec<usize>> = HashMap::new();
+    for (idx, pool) in pools.iter().enumerate() {
+        adjacency.entry(pool.token0).or_default().push(idx);
+        adjacency.entry(pool.token1).or_default().push(idx);
+    }
+
+    let mut paths = Vec::new();
+    let mut pool_stack = Vec::new();
+    let mut zero_for_ones = Vec::new();
+    let mut visited_tokens = vec![token_in];
+
+    extend_cycle(
+        pools,
+        &adjacency,
+        token_in,
+        token_in,
+        max_hops as usize,
+        &mut pool_stack,
+        &mut zero_for_ones,
+        &mut visited_tokens,
+        &mut paths,
+    );
+
+    info!(
+        "Generated {} cyclic arbitrage paths (max {} hops) in {} seconds",
+        paths.len(),
+        max_hops,
+        start_time.elapsed().as_secs()
+    );
+    paths
+}
+
+fn extend_cycle(
+    pools: &Vec<Pool>,
+    adjacency: &HashMap<H160, Vec<usize>>,
+    base_token: H160,
+    current_token: H160,
+    max_hops: usize,
+    pool_stack: &mut Vec<usize>,
+    zero_for_ones: &mut Vec<bool>,
+    visited_tokens: &mut Vec<H160>,
+    paths: &mut Vec<ArbPath>,
+) {
+    if pool_stack.len() >= max_hops {
+        return;
+    }
+
+    let candidates = match adjacency.get(&current_token) {
+        Some(candidates) => candidates,
+        None => return,
+    };
+
+    for &idx in candidates {
+        if pool_stack.contains(&idx) {
+            continue;
+        }
+
+        let pool = &pools[idx];
+        let zero_for_one = pool.token0 == current_token;
+        let next_token = if zero_for_one { pool.token1 } else { pool.token0 };
+
+        if next_token == base_token {
+            // A cycle needs at least two distinct pools
+            if pool_stack.len() >= 1 {
+                let mut cycle_pools: Vec<Pool> =
+                    pool_stack.iter().map(|&i| pools[i].clone()).collect();
+                cycle_pools.push(pool.clone());
+                let mut cycle_directions = zero_for_ones.clone();
+                cycle_directions.push(zero_for_one);
+                paths.push(ArbPath::new(cycle_pools, cycle_directions));
+            }
+            continue;
+        }
+
+        if visited_tokens.contains(&next_token) {
+            continue;
+        }
+
+        pool_stack.push(idx);
+        zero_for_ones.push(zero_for_one);
+        visited_tokens.push(next_token);
+
+        extend_cycle(
+            pools,
+            adjacency,
+            base_token,
+            next_token,
+            max_hops,
+            pool_stack,
+            zero_for_ones,
+            visited_tokens,
+            paths,
+        );
+
+        pool_stack.pop();
+        zero_for_ones.pop();
+        visited_tokens.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{make_pool, reserve};
+
+    #[test]
+    fn test_optimize_amount_in_matches_brute_force() {
+        let (a, b, c) = (H160::random(), H160::random(), H160::random());
+        let path = ArbPath::new(
+            vec![make_pool(a, b, 18, 18), make_pool(b, c, 18, 18), make_pool(c, a, 18, 18)],
+            vec![true, true, true],
+        );
+        let mut reserves = HashMap::new();
+        reserves.insert(path.pools[0].address, reserve(1_000_000, 18, 1_050_000, 18));
+        reserves.insert(path.pools[1].address, reserve(1_000_000, 18, 1_000_000, 18));
+        reserves.insert(path.pools[2].address, reserve(1_000_000, 18, 1_000_000, 18));
+
+        let max_amount_in = 20_000u64;
+        let (optimized_in, profit) = path.optimize_amount_in(U256::from(max_amount_in), 10, &reserves);
+
+        let unit = U256::exp10(18);
+        let (brute_in, brute_profit) = (1..=max_amount_in)
+            .filter_map(|amount_in| {
+                let amount_out = path.simulate_v2_path(U256::from(amount_in), &reserves)?;
+                Some((amount_in, amount_out.as_u128() as i128 - (U256::from(amount_in) * unit).as_u128() as i128))
+            })
+            .max_by_key(|(_, profit)| *profit)
+            .unwrap();
+
+        assert!(brute_profit > 0);
+        assert!((optimized_in.as_u64() as i64 - brute_in as i64).abs() <= 10);
+        assert!(U256::from(brute_profit) - profit <= U256::from(brute_profit / 1000));
+    }
+
+    #[test]
+    fn test_encoded_hops_carry_slippage_minimums() {
+        use crate::bundler::{decode_order_calldata, encode_order_calldata, Flashloan};
+
+        let (a, b, c) = (H160::random(), H160::random(), H160::random());
+        let path = ArbPath::new(
+            vec![make_pool(a, b, 18, 18), make_pool(b, c, 18, 18), make_pool(c, a, 18, 18)],
+            vec![true, true, true],
+        );
+        let mut reserves = HashMap::new();
+        reserves.insert(path.pools[0].address, reserve(1_000_000, 18, 1_050_000, 18));
+        reserves.insert(path.pools[1].address, reserve(1_000_000, 18, 1_000_000, 18));
+        reserves.insert(path.pools[2].address, reserve(1_000_000, 18, 1_000_000, 18));
+
+        let amounts_out = path.simulate_v2_hops(U256::from(100), &reserves).unwrap();
+        assert_eq!(amounts_out.len(), 3);
+        assert_eq!(amounts_out.last().copied(), path.simulate_v2_path(U256::from(100), &reserves));
+
+        // 50 bps below each simulated output
+        let routers = vec![H160::random(); 3];
+        let params = path.to_path_params(&routers, &amounts_out, 50);
+        let amount_in = U256::from(100) * U256::exp10(18);
+        let calldata = encode_order_calldata(&params, amount_in, Flashloan::NotUsed, H160::zero());
+
+        let (_, _, _, hops) = decode_order_calldata(&calldata).unwrap();
+        for (hop, amount_out) in hops.iter().zip(&amounts_out) {
+            assert_eq!(hop.min_amount_out, *amount_out - *amount_out * 50 / 10_000);
+            assert!(hop.min_amount_out < *amount_out);
+        }
+        assert_eq!(hops[0].token_in, a);
+        assert_eq!(hops[2].token_out, a);
+    }
+
+    #[test]
+    fn test_optimize_amount_in_unprofitable_path() {
+        let (a, b, c) = (H160::random(), H160::random(), H160::random());
+        let path = ArbPath::new(
+            vec![make_pool(a, b, 18, 18), make_pool(b, c, 18, 18), make_pool(c, a, 18, 18)],
+            vec![true, true, true],
+        );
+        let mut reserves = HashMap::new();
+        for pool in &path.pools {
+            reserves.insert(pool.address, reserve(1_000_000, 18, 1_000_000, 18));
+        }
+
+        assert_eq!(
+            path.optimize_amount_in(U256::from(20_000), 10, &reserves),
+            (U256::zero(), U256::zero())
+        );
+    }
+
+    #[test]
+    fn test_blacklisted_mid_token_drops_path() {
+        let (usdc, weth, mid) = (H160::random(), H160::random(), H160::random());
+        let path = ArbPath::new(
+            vec![make_pool(usdc, weth, 18, 18), make_pool(weth, mid, 18, 18), make_pool(mid, usdc, 18, 18)],
+            vec![true, true, true],
+        );
+
+        assert!(path.should_blacklist(&vec![mid]));
+        assert!(path.should_blacklist_excluding(&vec![mid], Some(usdc)));
+        // Only the last hop's tokens are listed: every hop must be checked
+        assert!(path.should_blacklist(&vec![H160::random(), mid]));
+        assert!(!path.should_blacklist(&vec![H160::random()]));
+    }
+
+    #[test]
+    fn test_blacklisted_base_token_is_ignored_when_flagged() {
+        let (usdc, weth, dai) = (H160::random(), H160::random(), H160::random());
+        let path = ArbPath::new(
+            vec![make_pool(usdc, weth, 18, 18), make_pool(weth, dai, 18, 18), make_pool(dai, usdc, 18, 18)],
+            vec![true, true, true],
+        );
+
+        assert!(path.should_blacklist(&vec![usdc]));
+        assert!(!path.should_blacklist_excluding(&vec![usdc], Some(usdc)));
+        assert!(path.should_blacklist_excluding(&vec![usdc, dai], Some(usdc)));
+    }
+
+    #[test]
+    fn test_select_non_overlapping_paths() {
+        let (usdc, weth, dai, wbtc) = (H160::random(), H160::random(), H160::random(), H160::random());
+        let usdc_weth = make_pool(usdc, weth, 18, 18);
+        let best = ArbPath::new(
+            vec![usdc_weth.clone(), make_pool(weth, dai, 18, 18), make_pool(dai, usdc, 18, 18)],
+            vec![true, true, true],
+        );
+        let overlapping = ArbPath::new(
+            vec![usdc_weth, make_pool(weth, wbtc, 18, 18), make_pool(wbtc, usdc, 18, 18)],
+            vec![true, true, true],
+        );
+        let disjoint = ArbPath::new(
+            vec![make_pool(usdc, dai, 18, 18), make_pool(dai, wbtc, 18, 18), make_pool(wbtc, usdc, 18, 18)],
+            vec![true, true, true],
+        );
+        let paths = vec![overlapping, disjoint, best];
+
+        // Ranked by spread: best, overlapping, disjoint
+        let selected = select_non_overlapping(&paths, &[2, 0, 1], 5);
+        assert_eq!(selected, vec![2, 1]);
+
+        assert_eq!(select_non_overlapping(&paths, &[2, 0, 1], 1), vec![2]);
+    }
+
+    #[test]
+    fn test_cyclic_paths_include_triangles() {
+        let (a, b, c) = (H160::random(), H160::random(), H160::random());
+        let pools = vec![make_pool(a, b, 18, 18), make_pool(b, c, 18, 18), make_pool(c, a, 18, 18)];
+
+        let paths = generate_cyclic_paths(&pools, a, 3);
+
+        // The triangle is found once in each direction
+        assert_eq!(paths.len(), 2);
+        assert!(paths.iter().all(|p| p.nhop == 3));
+    }
+
+    #[test]
+    fn test_four_hop_cycle_found_beyond_three_hops() {
+        let (a, b, c, d) = (H160::random(), H160::random(), H160::random(), H160::random());
+        let pools = vec![
+            make_pool(a, b, 18, 18),
+            make_pool(b, c, 18, 18),
+            make_pool(c, d, 18, 18),
+            make_pool(d, a, 18, 18),
+        ];
+
+        assert!(generate_cyclic_paths(&pools, a, 3).is_empty());
+
+        let paths = generate_cyclic_paths(&pools, a, 4);
+        assert_eq!(paths.len(), 2);
+        assert!(paths.iter().all(|p| p.nhop == 4));
+
+        // A mispriced 4-hop loop is simulated end to end
+        let mut reserves = HashMap::new();
+        reserves.insert(pools[0].address, reserve(1_000_000, 18, 2_000_000, 18));
+        reserves.insert(pools[1].address, reserve(1_000_000, 18, 1_000_000, 18));
+        reserves.insert(pools[2].address, reserve(1_000_000, 18, 1_000_000, 18));
+        reserves.insert(pools[3].address, reserve(1_000_000, 18, 1_000_000, 18));
+
+        let forward = paths.iter().find(|p| p.pools[0].address == pools[0].address).unwrap();
+        let amount_out = forward.simulate_v2_path(U256::from(1), &reserves).unwrap();
+        assert!(amount_out > U256::exp10(18));
+    }
+
+    #[test]
+    fn test_cyclic_paths_two_hop() {
+        let (a, b) = (H160::random(), H160::random());
+        let pools = vec![make_pool(a, b, 18, 18), make_pool(a, b, 18, 18)];
+
+        let paths = generate_cyclic_paths(&pools, a, 2);
+        assert_eq!(paths.len(), 2);
+        assert!(paths.iter().all(|p| p.nhop == 2 && p.pools[0].address != p.pools[1].address));
+    }
+
+    fn mixed_triangle() -> (ArbPath, HashMap<H160, Reserve>, HashMap<H160, V3PoolState>) {
+        let (a, b, c) = (H160::random(), H160::random(), H160::random());
+        let pool_1 = make_pool(a, b, 18, 18);
+        let pool_2 = Pool {
+            version: DexVariant::UniswapV3,
+            fee: 3000,
+            ..make_pool(b, c, 18, 18)
+        };
+        let pool_3 = make_pool(c, a, 18, 18);
+
+        let mut reserves = HashMap::new();
+        reserves.insert(pool_1.address, reserve(1_000, 18, 2_000, 18));
+        reserves.insert(pool_3.address, reserve(8_000, 18, 1_000, 18));
+
+        let mut v3_states = HashMap::new();
+        v3_states.insert(
+            pool_2.address,
+            V3PoolState {
+                // price of 4 C per B
+                sqrt_price_x96: U256::one() << 97,
+                liquidity: U256::exp10(21),
+            },
+        );
+
+        let path = ArbPath::new(vec![pool_1, pool_2, pool_3], vec![true, true, true]);
+        (path, reserves, v3_states)
+    }
+
+    #[test]
+    fn test_simulate_mixed_v2_v3_v2_path() {
+        let (path, reserves, v3_states) = mixed_triangle();
+
+        // Hop 1 (V2): 1e18 * 997 * 2000e18 / (1000e18 * 1000 + 1e18 * 997)
+        let hop_1 = UniswapV2Simulator::get_amount_out(
+            U256::exp10(18),
+            U256::exp10(21),
+            U256::from(2) * U256::exp10(21),
+            U256::from(300),
+        )
+        .unwrap();
+        assert_eq!(hop_1, U256::from_dec_str("1992013962079806432").unwrap());
+
+        // Hop 2 (V3, zero for one) yields 7912721749879864431, hop 3 (V2) closes the loop
+        let amount_out = path
+            .simulate_mixed_path(U256::from(1), &reserves, &v3_states)
+            .unwrap();
+        assert_eq!(amount_out, U256::from_dec_str("985151467609235150").unwrap());
+    }
+
+    #[test]
+    fn test_v3_hop_one_for_zero() {
+        let state = V3PoolState {
+            sqrt_price_x96: U256::one() << 96,
+            liquidity: U256::exp10(21),
+        };
+        let amount_out = UniswapV3Simulator::get_amount_out(
+            U256::from_dec_str("1992013962079806432").unwrap(),
+            &state,
+            U256::from(3000),
+            false,
+        )
+        .unwrap();
+        assert_eq!(amount_out, U256::from_dec_str("1982101391668045831").unwrap());
+    }
+
+    #[test]
+    fn test_simulate_mixed_path_missing_state() {
+        let (path, reserves, _) = mixed_triangle();
+        assert!(path
+            .simulate_mixed_path(U256::from(1), &reserves, &HashMap::new())
+            .is_none());
+    }
+}
@@ -1,18 +1,29 @@
-use anyhow::{Ok, Result};
+use anyhow::{anyhow, Ok, Result};
 use cfmms::{
     dex::{Dex, DexVariant as CfmmsDexVariant},
-    pool::Pool as CfmmsPool,
+    pool::{Pool as CfmmsPool, UniswapV2Pool},
     sync::sync_pairs,
 };
 use csv::StringRecord;
 use ethers::{
-    providers::{Provider, Ws},
-    types::{H160, U256},
+    providers::{Middleware, Provider, Ws},
+    types::{Filter, H160, U256},
+};
+use log::{info, warn};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, OpenOptions},
+    future::Future,
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
 };
-use log::info;
-use std::{path::Path, str::FromStr, sync::Arc};
 
-#[derive(Debug, Clone)]
+use crate::utils::u256_to_f64;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum DexVariant {
     UniswapV2,
     UniswapV3,
@@ -29,6 +40,12 @@ pub struct Pool {
     pub fee: u32,
     pub reserve0: U256,
     pub reserve1: U256,
+    /// V3 price, liquidity and tick at sync time; `None` for V2 pools.
+    pub sqrt_price_x96: Option<U256>,
+    pub liquidity: Option<u128>,
+    pub tick: Option<i32>,
+    /// Factory the pool was synced from; `None` in caches written before it was recorded.
+    pub factory: Option<H160>,
 }
 
 impl From<StringRecord> for Pool {
@@ -48,12 +65,73 @@ impl From<StringRecord> for Pool {
             fee: record.get(6).unwrap().parse().unwrap(),
             reserve0: U256::zero(),
             reserve1: U256::zero(),
+            // Empty for V2 pools, and absent from caches written before V3 state
+            sqrt_price_x96: optional_field(&record, 7).and_then(|value| U256::from_dec_str(value).ok()),
+            liquidity: optional_field(&record, 8).and_then(|value| value.parse().ok()),
+            tick: optional_field(&record, 9).and_then(|value| value.parse().ok()),
+            factory: optional_field(&record, 10).and_then(|value| H160::from_str(value).ok()),
+        }
+    }
+}
+
+fn optional_field(record: &StringRecord, idx: usize) -> Option<&str> {
+    record.get(idx).filter(|value| !value.is_empty())
+}
+
+impl From<CfmmsPool> for Pool {
+    fn from(pool: CfmmsPool) -> Self {
+        match pool {
+            CfmmsPool::UniswapV2(pool) => Pool {
+                address: pool.address,
+                version: DexVariant::UniswapV2,
+                token0: pool.token_a,
+                token1: pool.token_b,
+                decimals0: pool.token_a_decimals,
+                decimals1: pool.token_b_decimals,
+                fee: pool.fee,
+                reserve0: pool.reserve_a,
+                reserve1: pool.reserve_b,
+                sqrt_price_x96: None,
+                liquidity: None,
+                tick: None,
+                factory: None,
+            },
+            CfmmsPool::UniswapV3(pool) => Pool {
+                address: pool.address,
+                version: DexVariant::UniswapV3,
+                token0: pool.token_a,
+                token1: pool.token_b,
+                decimals0: pool.token_a_decimals,
+                decimals1: pool.token_b_decimals,
+                fee: pool.fee,
+                reserve0: pool.reserve_a,
+                reserve1: pool.reserve_b,
+                sqrt_price_x96: Some(pool.sqrt_price),
+                liquidity: Some(pool.liquidity),
+                tick: Some(pool.tick),
+                factory: None,
+            },
         }
     }
 }
 
 impl Pool {
-    pub fn cache_row(&self) -> (String, i32, String, String, u8, u8, u32) {
+    #[allow(clippy::type_complexity)]
+    pub fn cache_row(
+        &self,
+    ) -> (
+        String,
+        i32,
+        String,
+        String,
+        u8,
+        u8,
+        u32,
+        Option<String>,
+        Option<u128>,
+        Option<i32>,
+        Option<String>,
+    ) {
         (
             format!("{:?}", self.address),
             match self.version {
@@ -65,6 +143,10 @@ impl Pool {
             self.decimals0,
             self.decimals1,
             self.fee,
+            self.sqrt_price_x96.map(|sqrt_price| sqrt_price.to_string()),
+            self.liquidity,
+            self.tick,
+            self.factory.map(|factory| format!("{:?}", factory)),
         )
     }
 
@@ -105,100 +187,724 @@ impl Pool {
     }
 }
 
+impl Pool {
+    /// Value of both reserves in whole USD at `prices` (USD per whole token).
+    /// If only one token has a price, the pool is valued at twice that side,
+    /// since a V2 pool holds equal value of both; without prices it's zero.
+    pub fn get_liquidity_usd_with(&self, prices: &HashMap<H160, f64>) -> f64 {
+        let side = |reserve: U256, decimals: u8, token: H160| {
+            prices
+                .get(&token)
+                .map(|price| u256_to_f64(reserve) / 10f64.powi(decimals as i32) * price)
+        };
+        match (
+            side(self.reserve0, self.decimals0, self.token0),
+            side(self.reserve1, self.decimals1, self.token1),
+        ) {
+            (Some(value0), Some(value1)) => value0 + value1,
+            (Some(value), None) | (None, Some(value)) => value * 2.0,
+            (None, None) => 0.0,
+        }
+    }
+}
+
+/// Pools worth less than `min_liquidity_usd` at `prices` are left out of the
+/// pool cache. The default keeps every pool.
+#[derive(Debug, Clone, Default)]
+pub struct LiquidityFilter {
+    pub min_liquidity_usd: f64,
+    pub prices: HashMap<H160, f64>,
+}
+
+impl LiquidityFilter {
+    pub fn new(min_liquidity_usd: f64, prices: HashMap<H160, f64>) -> Self {
+        Self {
+            min_liquidity_usd,
+            prices,
+        }
+    }
+
+    pub fn keeps(&self, pool: &Pool) -> bool {
+        self.min_liquidity_usd <= 0.0 || pool.get_liquidity_usd_with(&self.prices) >= self.min_liquidity_usd
+    }
+}
+
+/// Keep only the deepest pool per token pair and DEX variant, so forks of the
+/// same AMM don't each contribute a shallow copy of a path. Depth is the
+/// product of the reserves; ties keep the pool seen first. Cross-fork
+/// arbitrage needs every pool of a pair and shouldn't call this.
+pub fn dedup_by_pair_keep_deepest(pools: Vec<Pool>) -> Vec<Pool> {
+    let mut deepest: HashMap<(DexVariant, H160, H160), usize> = HashMap::new();
+    let mut kept: Vec<Pool> = Vec::with_capacity(pools.len());
+
+    for pool in pools {
+        let (low, high) = if pool.token0 < pool.token1 {
+            (pool.token0, pool.token1)
+        } else {
+            (pool.token1, pool.token0)
+        };
+        match deepest.get(&(pool.version.clone(), low, high)) {
+            Some(&idx) => {
+                let depth = |pool: &Pool| pool.reserve0.full_mul(pool.reserve1);
+                if depth(&pool) > depth(&kept[idx]) {
+                    kept[idx] = pool;
+                }
+            }
+            None => {
+                deepest.insert((pool.version.clone(), low, high), kept.len());
+                kept.push(pool);
+            }
+        }
+    }
+    kept
+}
+
 // Example thresholds for different risk levels
 pub const LOW_LIQUIDITY_THRESHOLD: U256 = U256([1_000_000_000_000, 0, 0, 0]);     // $1,000
 pub const MEDIUM_LIQUIDITY_THRESHOLD: U256 = U256([10_000_000_000_000, 0, 0, 0]); // $10,000
 pub const HIGH_LIQUIDITY_THRESHOLD: U256 = U256([100_000_000_000_000, 0, 0, 0]);  // $100,000
 
+pub const POOL_CACHE_PATH: &str = "src/.cached-pools.csv";
+/// Attempts per factory before the initial sync gives up.
+pub const POOL_SYNC_MAX_ATTEMPTS: u32 = 5;
+/// Doubled after every failed attempt.
+pub const POOL_SYNC_BACKOFF_BASE: Duration = Duration::from_secs(2);
+/// A progress line is logged every this many cached pools.
+pub const POOL_PROGRESS_INTERVAL: usize = 1_000;
+/// Blocks per `eth_getLogs` request when scanning for new pairs.
+pub const PAIR_CREATED_LOG_STEP: u64 = 3_000;
+const PAIR_CREATED_EVENT: &str = "PairCreated(address,address,address,uint256)";
+
+const POOL_CACHE_HEADER: [&str; 11] = [
+    "address",
+    "version",
+    "token0",
+    "token1",
+    "decimals0",
+    "decimals1",
+    "fee",
+    "sqrt_price_x96",
+    "liquidity",
+    "tick",
+    "factory",
+];
+
+/// Sync every V2 pool of `factory_addresses`, or read them from the cache.
+/// A cache built with a different `min_liquidity_usd` is discarded and re-synced,
+/// since filtered-out pools can't be recovered from it.
 pub async fn load_all_pools_from_v2(
     wss_url: String,
     factory_addresses: Vec<&str>,
     from_blocks: Vec<u64>,
+    filter: &LiquidityFilter,
 ) -> Result<Vec<Pool>> {
     // Load from cached file if the file exists
-    let file_path = Path::new("src/.cached-pools.csv");
+    let file_path = Path::new(POOL_CACHE_PATH);
     if file_path.exists() {
-        let mut reader = csv::Reader::from_path(file_path)?;
-
-        let mut pools_vec: Vec<Pool> = Vec::new();
-        for row in reader.records() {
-            let row = row.unwrap();
-            let pool = Pool::from(row);
-            pools_vec.push(pool);
+        if cache_filtered_at(file_path, filter.min_liquidity_usd) {
+            return read_pool_cache(file_path);
         }
-        return Ok(pools_vec);
+        info!(
+            "Pool cache was built for another liquidity threshold, re-syncing at ${}",
+            filter.min_liquidity_usd
+        );
+        fs::remove_file(file_path)?;
     }
 
     let ws = Ws::connect(wss_url).await?;
     let provider = Arc::new(Provider::new(ws));
+    // Pairs created while syncing are picked up again by the next update
+    let tip = provider.get_block_number().await?.as_u64();
 
-    let mut dexes_data = Vec::new();
-
-    for i in 0..factory_addresses.len() {
-        dexes_data.push((
-            factory_addresses[i].clone(),
-            CfmmsDexVariant::UniswapV2,
-            from_blocks[i],
-        ))
-    }
-
-    let dexes: Vec<_> = dexes_data
-        .into_iter()
-        .map(|(address, variant, number)| {
-            Dex::new(
-                H160::from_str(&address).unwrap(),
-                variant,
-                number,
-                Some(3000),
-            )
-        })
-        .collect();
+    let factories = factory_addresses
+        .iter()
+        .map(|address| H160::from_str(address))
+        .zip(from_blocks)
+        .map(|(address, from_block)| Ok((address?, from_block)))
+        .collect::<Result<Vec<_>>>()?;
 
-    let pools_vec: Vec<CfmmsPool> = sync_pairs(dexes.clone(), provider.clone(), None).await?;
-    let pools_vec: Vec<Pool> = pools_vec
-        .into_iter()
-        .map(|pool| match pool {
-            CfmmsPool::UniswapV2(pool) => Pool {
-                address: pool.address,
-                version: DexVariant::UniswapV2,
-                token0: pool.token_a,
-                token1: pool.token_b,
-                decimals0: pool.token_a_decimals,
-                decimals1: pool.token_b_decimals,
-                fee: pool.fee,
-                reserve0: pool.reserve_a,
-                reserve1: pool.reserve_b,
-            },
-            CfmmsPool::UniswapV3(pool) => Pool {
-                address: pool.address,
-                version: DexVariant::UniswapV3,
-                token0: pool.token_a,
-                token1: pool.token_b,
-                decimals0: pool.token_a_decimals,
-                decimals1: pool.token_b_decimals,
-                fee: pool.fee,
-                reserve0: pool.reserve_a,
-                reserve1: pool.reserve_b,
-            },
-        })
-        .collect();
+    let sync_factory = |factory: H160, from_block: u64| {
+        let provider = provider.clone();
+        async move {
+            let dex = Dex::new(factory, CfmmsDexVariant::UniswapV2, from_block, Some(3000));
+            let synced: Vec<CfmmsPool> = sync_pairs(vec![dex], provider, None).await?;
+            Ok(synced
+                .into_iter()
+                .map(|pool| Pool {
+                    factory: Some(factory),
+                    ..Pool::from(pool)
+                })
+                .collect())
+        }
+    };
+
+    let pools_vec = sync_into_cache(
+        file_path,
+        &factories,
+        sync_factory,
+        filter,
+        POOL_SYNC_MAX_ATTEMPTS,
+        POOL_SYNC_BACKOFF_BASE,
+    )
+    .await?;
+    write_cache_tip(file_path, tip)?;
+    Ok(pools_vec)
+}
+
+/// Append V2 pairs created since the cache was last synced to the cache,
+/// returning only the new pools. `from_block` is where the scan starts for
+/// caches without a recorded tip block.
+pub async fn update_pool_cache(
+    wss_url: String,
+    factory_addresses: Vec<&str>,
+    from_block: u64,
+    filter: &LiquidityFilter,
+) -> Result<Vec<Pool>> {
+    let ws = Ws::connect(wss_url).await?;
+    let provider = Arc::new(Provider::new(ws));
+    let to_block = provider.get_block_number().await?.as_u64();
+
+    let factories = factory_addresses
+        .iter()
+        .map(|address| Ok(H160::from_str(address)?))
+        .collect::<Result<Vec<_>>>()?;
+
+    let fetch_pairs = |factory: H160, from_block: u64, to_block: u64| {
+        fetch_created_pairs(provider.clone(), factory, from_block, to_block)
+    };
+
+    append_new_pairs(
+        Path::new(POOL_CACHE_PATH),
+        &factories,
+        from_block,
+        to_block,
+        fetch_pairs,
+        filter,
+    )
+    .await
+}
+
+/// V2 pools created by `factory` within `from_block..=to_block`.
+pub async fn fetch_created_pairs<M: Middleware + 'static>(
+    provider: Arc<M>,
+    factory: H160,
+    from_block: u64,
+    to_block: u64,
+) -> Result<Vec<Pool>> {
+    let mut pools_vec = Vec::new();
+    let mut start = from_block;
+    while start <= to_block {
+        let end = (start + PAIR_CREATED_LOG_STEP - 1).min(to_block);
+        let filter = Filter::new()
+            .address(factory)
+            .event(PAIR_CREATED_EVENT)
+            .from_block(start)
+            .to_block(end);
+        let logs = provider
+            .get_logs(&filter)
+            .await
+            .map_err(|e| anyhow!("PairCreated logs of {:?} failed: {}", factory, e))?;
+
+        for log in logs {
+            // The pair is the first word of the data, after the indexed tokens
+            if log.data.len() < 32 {
+                continue;
+            }
+            let pair = H160::from_slice(&log.data[12..32]);
+            let pool = UniswapV2Pool::new_from_address(pair, provider.clone())
+                .await
+                .map_err(|e| anyhow!("Failed to load pair {:?}: {}", pair, e))?;
+            pools_vec.push(Pool {
+                factory: Some(factory),
+                ..Pool::from(CfmmsPool::UniswapV2(pool))
+            });
+        }
+        start = end + 1;
+    }
+    Ok(pools_vec)
+}
+
+pub fn read_pool_cache(path: &Path) -> Result<Vec<Pool>> {
+    // Older caches have no V3 or factory columns, and may have had new rows appended since
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_path(path)?;
+    let mut pools_vec: Vec<Pool> = Vec::new();
+    for row in reader.records() {
+        pools_vec.push(Pool::from(row?));
+    }
+    Ok(pools_vec)
+}
+
+/// Pools of finished factories, before the cache is complete.
+fn partial_cache_path(path: &Path) -> PathBuf {
+    path.with_extension("csv.partial")
+}
+
+/// Factories whose pools are all in the partial cache, one per line.
+fn sync_progress_path(path: &Path) -> PathBuf {
+    path.with_extension("csv.progress")
+}
+
+/// `min_liquidity_usd` the cache at `path` was filtered with.
+fn cache_filter_path(path: &Path) -> PathBuf {
+    path.with_extension("csv.min-liquidity")
+}
+
+/// Last block whose new pairs are in the cache at `path`.
+fn cache_tip_path(path: &Path) -> PathBuf {
+    path.with_extension("csv.tip")
+}
+
+pub fn read_cache_tip(path: &Path) -> Option<u64> {
+    fs::read_to_string(cache_tip_path(path)).ok()?.trim().parse().ok()
+}
+
+pub fn write_cache_tip(path: &Path, block: u64) -> Result<()> {
+    fs::write(cache_tip_path(path), block.to_string())?;
+    Ok(())
+}
+
+/// Whether the cache at `path` was built with `min_liquidity_usd`. Caches
+/// from before the threshold was recorded count as unfiltered.
+pub fn cache_filtered_at(path: &Path, min_liquidity_usd: f64) -> bool {
+    let cached = fs::read_to_string(cache_filter_path(path))
+        .ok()
+        .and_then(|threshold| threshold.trim().parse::<f64>().ok())
+        .unwrap_or(0.0);
+    cached == min_liquidity_usd.max(0.0)
+}
+
+/// Sync `factories` one at a time with `sync_factory`, retrying each with
+/// exponential backoff, and write the pools `filter` keeps to the cache at
+/// `path`. Pools are written as each factory finishes, so a sync interrupted
+/// by an error or a restart resumes after the last finished factory.
+pub async fn sync_into_cache<F, Fut>(
+    path: &Path,
+    factories: &[(H160, u64)],
+    mut sync_factory: F,
+    filter: &LiquidityFilter,
+    max_attempts: u32,
+    backoff_base: Duration,
+) -> Result<Vec<Pool>>
+where
+    F: FnMut(H160, u64) -> Fut,
+    Fut: Future<Output = Result<Vec<Pool>>>,
+{
+    let partial_path = partial_cache_path(path);
+    let progress_path = sync_progress_path(path);
+
+    let done: HashSet<H160> = fs::read_to_string(&progress_path)
+        .map(|progress| progress.lines().filter_map(|line| H160::from_str(line).ok()).collect())
+        .unwrap_or_default();
+    let mut pools_vec = if partial_path.exists() {
+        let pools_vec = read_pool_cache(&partial_path)?;
+        info!(
+            "Resuming pool sync with {} pools from {} finished factories",
+            pools_vec.len(),
+            done.len()
+        );
+        pools_vec
+    } else {
+        let mut writer = csv::Writer::from_path(&partial_path)?;
+        writer.write_record(POOL_CACHE_HEADER)?;
+        writer.flush()?;
+        Vec::new()
+    };
+    // Rows of a factory interrupted mid-write are in the file but not in `done`
+    let mut cached: HashSet<H160> = pools_vec.iter().map(|pool| pool.address).collect();
+
+    for (factory, from_block) in factories.iter().filter(|(factory, _)| !done.contains(factory)) {
+        let mut attempt = 1;
+        let synced = loop {
+            match sync_factory(*factory, *from_block).await {
+                std::result::Result::Ok(synced) => break synced,
+                Err(e) if attempt < max_attempts => {
+                    let delay = backoff_base * 2u32.pow(attempt - 1);
+                    warn!(
+                        "Pool sync of {:?} failed (attempt {}/{}), retrying in {:?}: {:?}",
+                        factory, attempt, max_attempts, delay, e
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => {
+                    return Err(anyhow!(
+                        "Pool sync of {:?} failed after {} attempts: {:?}",
+                        factory,
+                        attempt,
+                        e
+                    ))
+                }
+            }
+        };
+
+        let file = OpenOptions::new().append(true).open(&partial_path)?;
+        let mut writer = csv::WriterBuilder::new().has_headers(false).from_writer(file);
+        let synced_count = synced.len();
+        let mut kept = 0;
+        for pool in synced {
+            if !filter.keeps(&pool) || !cached.insert(pool.address) {
+                continue;
+            }
+            kept += 1;
+            writer.serialize(pool.cache_row())?;
+            pools_vec.push(pool);
+            if pools_vec.len() % POOL_PROGRESS_INTERVAL == 0 {
+                info!("Cached {} pools", pools_vec.len());
+            }
+        }
+        writer.flush()?;
+
+        let mut progress = OpenOptions::new().create(true).append(true).open(&progress_path)?;
+        writeln!(progress, "{:?}", factory)?;
+        info!(
+            "Synced factory {:?}: kept {} of {} pools, {} so far",
+            factory,
+            kept,
+            synced_count,
+            pools_vec.len()
+        );
+    }
+
+    fs::rename(&partial_path, path)?;
+    fs::remove_file(&progress_path).ok();
+    fs::write(cache_filter_path(path), filter.min_liquidity_usd.max(0.0).to_string())?;
     info!("Synced to {} pools", pools_vec.len());
 
-    let mut writer = csv::Writer::from_path(file_path)?;
-    writer.write_record(&[
-        "address",
-        "version",
-        "token0",
-        "token1",
-        "decimals0",
-        "decimals1",
-        "fee",
-    ])?;
-
-    for pool in &pools_vec {
+    Ok(pools_vec)
+}
+
+/// Append the pools `fetch_pairs` finds for `factories` between the cache's
+/// tip block (or `from_block` without one) and `to_block` to the cache at
+/// `path`, then move the tip to `to_block`. Pools already cached are skipped.
+pub async fn append_new_pairs<F, Fut>(
+    path: &Path,
+    factories: &[H160],
+    from_block: u64,
+    to_block: u64,
+    mut fetch_pairs: F,
+    filter: &LiquidityFilter,
+) -> Result<Vec<Pool>>
+where
+    F: FnMut(H160, u64, u64) -> Fut,
+    Fut: Future<Output = Result<Vec<Pool>>>,
+{
+    if !path.exists() {
+        return Err(anyhow!("No pool cache at {:?} to update", path));
+    }
+    let start = read_cache_tip(path).map_or(from_block, |tip| tip + 1);
+    if start > to_block {
+        return Ok(Vec::new());
+    }
+
+    let mut cached: HashSet<H160> = read_pool_cache(path)?.iter().map(|pool| pool.address).collect();
+    let mut new_pools = Vec::new();
+    for factory in factories {
+        for pool in fetch_pairs(*factory, start, to_block).await? {
+            if filter.keeps(&pool) && cached.insert(pool.address) {
+                new_pools.push(pool);
+            }
+        }
+    }
+
+    let file = OpenOptions::new().append(true).open(path)?;
+    let mut writer = csv::WriterBuilder::new().has_headers(false).from_writer(file);
+    for pool in &new_pools {
         writer.serialize(pool.cache_row())?;
     }
     writer.flush()?;
+    write_cache_tip(path, to_block)?;
+    info!(
+        "Added {} new pools from blocks {}..={}",
+        new_pools.len(),
+        start,
+        to_block
+    );
 
-    Ok(pools_vec)
+    Ok(new_pools)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::make_pool;
+    use std::cell::Cell;
+
+    fn temp_cache() -> PathBuf {
+        std::env::temp_dir().join(format!("pools-{:?}.csv", H160::random()))
+    }
+
+    #[tokio::test]
+    async fn test_sync_retries_transient_failures() {
+        let path = temp_cache();
+        let (sushi, uni) = (H160::random(), H160::random());
+        let sushi_pools = vec![
+            make_pool(H160::random(), H160::random(), 18, 6),
+            make_pool(H160::random(), H160::random(), 18, 6),
+        ];
+        let uni_pools = vec![make_pool(H160::random(), H160::random(), 18, 6)];
+
+        // The first two attempts at the second factory time out
+        let failures = Cell::new(2);
+        let sync_factory = |factory: H160, _from_block: u64| {
+            let result = if factory == sushi {
+                Ok(sushi_pools.clone())
+            } else if failures.get() > 0 {
+                failures.set(failures.get() - 1);
+                Err(anyhow!("request timed out"))
+            } else {
+                Ok(uni_pools.clone())
+            };
+            async move { result }
+        };
+
+        let pools_vec = sync_into_cache(
+            &path,
+            &[(sushi, 1), (uni, 1)],
+            sync_factory,
+            &LiquidityFilter::default(),
+            3,
+            Duration::from_millis(1),
+        )
+        .await
+        .unwrap();
+        assert_eq!(pools_vec.len(), 3);
+        assert_eq!(failures.get(), 0);
+
+        let cached = read_pool_cache(&path).unwrap();
+        let cached: HashSet<H160> = cached.iter().map(|pool| pool.address).collect();
+        assert_eq!(cached.len(), 3);
+        assert!(sushi_pools.iter().chain(&uni_pools).all(|pool| cached.contains(&pool.address)));
+        assert!(!partial_cache_path(&path).exists());
+        assert!(!sync_progress_path(&path).exists());
+        assert!(cache_filtered_at(&path, 0.0));
+
+        fs::remove_file(&path).ok();
+        fs::remove_file(cache_filter_path(&path)).ok();
+    }
+
+    #[tokio::test]
+    async fn test_sync_resumes_from_partial_cache() {
+        let path = temp_cache();
+        let (sushi, uni) = (H160::random(), H160::random());
+        let sushi_pools = vec![make_pool(H160::random(), H160::random(), 18, 6)];
+        let uni_pools = vec![make_pool(H160::random(), H160::random(), 18, 6)];
+
+        // A run that gives up on the second factory keeps the first one's pools
+        let flaky = |factory: H160, _from_block: u64| {
+            let result = if factory == sushi {
+                Ok(sushi_pools.clone())
+            } else {
+                Err(anyhow!("429 Too Many Requests"))
+            };
+            async move { result }
+        };
+        let err = sync_into_cache(&path, &[(sushi, 1), (uni, 1)], flaky, &LiquidityFilter::default(), 2, Duration::from_millis(1)).await;
+        assert!(err.is_err());
+        assert!(!path.exists());
+
+        // The restart only syncs the factory that didn't finish
+        let synced = Cell::new(Vec::new());
+        let recovered = |factory: H160, _from_block: u64| {
+            let mut seen = synced.take();
+            seen.push(factory);
+            synced.set(seen);
+            let result = Ok(uni_pools.clone());
+            async move { result }
+        };
+        let pools_vec = sync_into_cache(
+            &path,
+            &[(sushi, 1), (uni, 1)],
+            recovered,
+            &LiquidityFilter::default(),
+            2,
+            Duration::from_millis(1),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(synced.take(), vec![uni]);
+        assert_eq!(pools_vec.len(), 2);
+        assert_eq!(read_pool_cache(&path).unwrap().len(), 2);
+
+        fs::remove_file(&path).ok();
+        fs::remove_file(cache_filter_path(&path)).ok();
+    }
+
+    #[test]
+    fn test_dedup_keeps_deepest_pool_per_pair() {
+        let (usdc, weth, dai) = (H160::random(), H160::random(), H160::random());
+        let with_reserves = |mut pool: Pool, reserve0: u64, reserve1: u64| {
+            pool.reserve0 = U256::from(reserve0);
+            pool.reserve1 = U256::from(reserve1);
+            pool
+        };
+
+        // Two forks list WETH/USDC, one with the tokens the other way round
+        let shallow = with_reserves(make_pool(weth, usdc, 18, 6), 10, 20_000);
+        let deep = with_reserves(make_pool(usdc, weth, 18, 6), 2_000_000, 1_000);
+        let other_pair = with_reserves(make_pool(dai, usdc, 18, 6), 1, 1);
+        let mut v3 = with_reserves(make_pool(weth, usdc, 18, 6), 1, 1);
+        v3.version = DexVariant::UniswapV3;
+
+        let kept: Vec<H160> = dedup_by_pair_keep_deepest(vec![
+            shallow,
+            other_pair.clone(),
+            deep.clone(),
+            v3.clone(),
+        ])
+        .iter()
+        .map(|pool| pool.address)
+        .collect();
+        // The deeper pool takes the shallow one's place
+        assert_eq!(kept, vec![deep.address, other_pair.address, v3.address]);
+    }
+
+    #[test]
+    fn test_v3_state_round_trips_through_cache() {
+        let path = temp_cache();
+        let v2 = Pool {
+            factory: Some(H160::random()),
+            ..pool(H160::random(), H160::random())
+        };
+        let mut v3 = make_pool(H160::random(), H160::random(), 18, 6);
+        v3.version = DexVariant::UniswapV3;
+        v3.fee = 500;
+        v3.sqrt_price_x96 = Some(U256::from_dec_str("1771595571142957166518320255467520").unwrap());
+        v3.liquidity = Some(u128::MAX / 3);
+        v3.tick = Some(-887_272);
+
+        let mut writer = csv::Writer::from_path(&path).unwrap();
+        writer.write_record(POOL_CACHE_HEADER).unwrap();
+        writer.serialize(v2.cache_row()).unwrap();
+        writer.serialize(v3.cache_row()).unwrap();
+        writer.flush().unwrap();
+
+        let cached = read_pool_cache(&path).unwrap();
+        assert_eq!(cached[0].version, DexVariant::UniswapV2);
+        assert_eq!(cached[0].sqrt_price_x96, None);
+        assert_eq!(cached[0].liquidity, None);
+        assert_eq!(cached[0].tick, None);
+        assert_eq!(cached[0].factory, v2.factory);
+        assert_eq!(cached[1].factory, None);
+        assert_eq!(cached[1].version, DexVariant::UniswapV3);
+        assert_eq!(cached[1].fee, 500);
+        assert_eq!(cached[1].sqrt_price_x96, v3.sqrt_price_x96);
+        assert_eq!(cached[1].liquidity, v3.liquidity);
+        assert_eq!(cached[1].tick, v3.tick);
+
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_reads_cache_without_v3_columns() {
+        let path = temp_cache();
+        let (address, token0, token1) = (H160::random(), H160::random(), H160::random());
+        fs::write(
+            &path,
+            format!(
+                "address,version,token0,token1,decimals0,decimals1,fee\n{:?},2,{:?},{:?},18,6,300\n",
+                address, token0, token1
+            ),
+        )
+        .unwrap();
+
+        let cached = read_pool_cache(&path).unwrap();
+        assert_eq!(cached.len(), 1);
+        assert_eq!(cached[0].address, address);
+        assert_eq!(cached[0].tick, None);
+
+        fs::remove_file(&path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_update_appends_pairs_created_after_sync() {
+        let path = temp_cache();
+        let factory = H160::random();
+        let initial = vec![make_pool(H160::random(), H160::random(), 18, 6)];
+        let sync_factory = |_factory: H160, _from_block: u64| {
+            let result = Ok(initial.clone());
+            async move { result }
+        };
+        sync_into_cache(&path, &[(factory, 1)], sync_factory, &LiquidityFilter::default(), 1, Duration::from_millis(1))
+            .await
+            .unwrap();
+        write_cache_tip(&path, 100).unwrap();
+
+        // A pair created at block 150, after the initial sync, and the
+        // original pair seen again
+        let created = make_pool(H160::random(), H160::random(), 18, 6);
+        let scanned = Cell::new(None);
+        let fetch_pairs = |_factory: H160, from_block: u64, to_block: u64| {
+            scanned.set(Some((from_block, to_block)));
+            let result = Ok(vec![initial[0].clone(), created.clone()]);
+            async move { result }
+        };
+        let new_pools = append_new_pairs(&path, &[factory], 1, 200, fetch_pairs, &LiquidityFilter::default())
+            .await
+            .unwrap();
+
+        assert_eq!(scanned.get(), Some((101, 200)));
+        assert_eq!(new_pools.len(), 1);
+        assert_eq!(new_pools[0].address, created.address);
+        let cached: Vec<H160> = read_pool_cache(&path).unwrap().iter().map(|pool| pool.address).collect();
+        assert_eq!(cached, vec![initial[0].address, created.address]);
+        assert_eq!(read_cache_tip(&path), Some(200));
+
+        // Nothing to scan until the chain moves past the tip
+        let idle = |_factory: H160, _from_block: u64, _to_block: u64| async { Err(anyhow!("not called")) };
+        let new_pools = append_new_pairs(&path, &[factory], 1, 200, idle, &LiquidityFilter::default())
+            .await
+            .unwrap();
+        assert!(new_pools.is_empty());
+
+        fs::remove_file(&path).ok();
+        fs::remove_file(cache_filter_path(&path)).ok();
+        fs::remove_file(cache_tip_path(&path)).ok();
+    }
+
+    #[tokio::test]
+    async fn test_sync_filters_dust_pools() {
+        let path = temp_cache();
+        let (usdc, weth, dust) = (H160::random(), H160::random(), H160::random());
+        let prices = HashMap::from([(usdc, 1.0), (weth, 2000.0)]);
+
+        // $2M, $4k and $400 of liquidity
+        let mut deep = make_pool(weth, usdc, 18, 6);
+        deep.reserve0 = U256::from(500) * U256::exp10(18);
+        deep.reserve1 = U256::from(1_000_000) * U256::exp10(6);
+        let mut shallow = make_pool(dust, usdc, 18, 6);
+        shallow.reserve1 = U256::from(2_000) * U256::exp10(6);
+        let mut tiny = make_pool(dust, usdc, 18, 6);
+        tiny.reserve1 = U256::from(200) * U256::exp10(6);
+        assert_eq!(deep.get_liquidity_usd_with(&prices), 2_000_000.0);
+        assert_eq!(shallow.get_liquidity_usd_with(&prices), 4_000.0);
+        // Reserves past u128 are valued rather than panicking
+        let mut huge = make_pool(dust, usdc, 18, 6);
+        huge.reserve1 = U256::MAX;
+        assert!(huge.get_liquidity_usd_with(&prices) > 1e60);
+
+        let synced = vec![deep.clone(), shallow.clone(), tiny.clone()];
+        let sync_factory = |_factory: H160, _from_block: u64| {
+            let result = Ok(synced.clone());
+            async move { result }
+        };
+        let filter = LiquidityFilter::new(1_000.0, prices);
+        let pools_vec =
+            sync_into_cache(&path, &[(H160::random(), 1)], sync_factory, &filter, 1, Duration::from_millis(1))
+                .await
+                .unwrap();
+
+        let kept: Vec<H160> = pools_vec.iter().map(|pool| pool.address).collect();
+        assert_eq!(kept, vec![deep.address, shallow.address]);
+        let cached: Vec<H160> = read_pool_cache(&path).unwrap().iter().map(|pool| pool.address).collect();
+        assert_eq!(cached, kept);
+
+        // The cache only serves loads with the same threshold
+        assert!(cache_filtered_at(&path, 1_000.0));
+        assert!(!cache_filtered_at(&path, 0.0));
+        assert!(!cache_filtered_at(&path, 5_000.0));
+
+        fs::remove_file(&path).ok();
+        fs::remove_file(cache_filter_path(&path)).ok();
+    }
 }
@@ -1,10 +1,11 @@
 use ethers::types::{Address, U256, Bytes};
 use ethers::prelude::*;
 use std::collections::HashMap;
+use std::sync::Arc;
 use serde::{Serialize, Deserialize};
 use lazy_static::lazy_static;
 use anyhow::Result;
-use crate::protocols::routing::{MultiChainRouter, ChainConfig};
+use crate::protocols::routing::{MultiChainRouter, ChainConfig, DEFAULT_HOLDING_PERIOD_SECS};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AaveConfig {
@@ -265,6 +266,27 @@ impl<M: Middleware> AaveProtocol<M> {
         Ok(self.oracle_contract.get_asset_price(asset).call().await?)
     }
 
+    /// Outstanding aToken supply and variable + stable debt for a reserve, in asset units.
+    pub async fn get_reserve_totals(&self, asset: Address) -> Result<(U256, U256)> {
+        let reserve_data = self.get_reserve_data(asset).await?;
+        let client = self.pool_contract.client();
+
+        let total_supply = IAaveToken::new(reserve_data.a_token_address, client.clone())
+            .total_supply()
+            .call()
+            .await?;
+        let variable_debt = IAaveToken::new(reserve_data.variable_debt_token_address, client.clone())
+            .total_supply()
+            .call()
+            .await?;
+        let stable_debt = IAaveToken::new(reserve_data.stable_debt_token_address, client)
+            .total_supply()
+            .call()
+            .await?;
+
+        Ok((total_supply, variable_debt.saturating_add(stable_debt)))
+    }
+
     pub async fn get_reserve_normalized_income(&self, asset: Address) -> Result<U256> {
         Ok(self.pool_contract.get_reserve_normalized_income(asset).call().await?)
     }
@@ -318,6 +340,8 @@ impl<M: Middleware> AaveProtocol<M> {
     }
 
     // Helper Functions
+    /// Health factor in WAD (1e18 = 1.0), matching `getUserAccountData`.
+    /// `liquidation_threshold` is in basis points, as Aave reports it.
     pub async fn calculate_health_factor_from_balances(
         &self,
         total_collateral_in_base_currency: U256,
@@ -328,10 +352,20 @@ impl<M: Middleware> AaveProtocol<M> {
             return Ok(U256::MAX);
         }
 
-        Ok((total_collateral_in_base_currency
-            .checked_mul(liquidation_threshold)?
-            .checked_div(10000)?)
-            .checked_div(total_debt_in_base_currency)?)
+        let overflow = || anyhow::anyhow!(
+            "Health factor overflow: collateral {} threshold {}",
+            total_collateral_in_base_currency,
+            liquidation_threshold
+        );
+
+        total_collateral_in_base_currency
+            .checked_mul(liquidation_threshold)
+            .ok_or_else(overflow)?
+            .checked_mul(U256::exp10(18))
+            .ok_or_else(overflow)?
+            .checked_div(U256::from(10000))
+            .and_then(|adjusted| adjusted.checked_div(total_debt_in_base_currency))
+            .ok_or_else(overflow)
     }
 
     pub async fn calculate_user_debt_position(
@@ -396,7 +430,8 @@ impl<M: Middleware> AaveProtocol<M> {
             asset,
             amount,
             self.chain_id,
-            U256::zero() // Include all routes
+            U256::zero(), // Include all routes
+            DEFAULT_HOLDING_PERIOD_SECS,
         ).await?;
         
         // Execute best route
@@ -426,7 +461,8 @@ impl<M: Middleware> AaveProtocol<M> {
                 asset,
                 amount,
                 self.chain_id,
-                min_profit
+                min_profit,
+                DEFAULT_HOLDING_PERIOD_SECS,
             ).await {
                 Ok(routes) => {
                     for route in routes {
@@ -471,9 +507,55 @@ abigen!(
     ]"#
 );
 
+abigen!(
+    IAaveToken,
+    r#"[
+        function totalSupply() external view returns (uint256)
+    ]"#
+);
+
 abigen!(
     IPoolDataProvider,
     r#"[
         function getReserveData(address asset) external view returns (uint256, uint256, uint256, uint256, uint256, uint256, uint256, uint256, uint256, uint256)
     ]"#
 );
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aave() -> AaveProtocol<Provider<MockProvider>> {
+        let (provider, _) = Provider::mocked();
+        AaveProtocol::new(1, Arc::new(provider)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_health_factor_zero_debt() {
+        let hf = aave()
+            .calculate_health_factor_from_balances(U256::from(1_000), U256::zero(), U256::from(8_250))
+            .await
+            .unwrap();
+        assert_eq!(hf, U256::MAX);
+    }
+
+    #[tokio::test]
+    async fn test_health_factor_from_balances() {
+        // $10,000 collateral at an 82.5% threshold against $5,000 debt => 1.65
+        let collateral = U256::from(10_000) * U256::exp10(8);
+        let debt = U256::from(5_000) * U256::exp10(8);
+        let hf = aave()
+            .calculate_health_factor_from_balances(collateral, debt, U256::from(8_250))
+            .await
+            .unwrap();
+        assert_eq!(hf, U256::from(1_650_000_000_000_000_000u64));
+    }
+
+    #[tokio::test]
+    async fn test_health_factor_overflow_is_an_error() {
+        let result = aave()
+            .calculate_health_factor_from_balances(U256::MAX, U256::one(), U256::from(8_250))
+            .await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,394 @@
+use ethers::prelude::*;
+use ethers::types::{Address, Bytes, H256, U256};
+use ethers::utils::keccak256;
+use std::sync::Arc;
+use anyhow::Result;
+use lazy_static::lazy_static;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use tokio::time::{sleep, Duration, Instant};
+
+pub const CIRCLE_ATTESTATION_URL: &str = "https://iris-api.circle.com";
+const ATTESTATION_POLL_INTERVAL: u64 = 5; // seconds between attestation polls
+
+// Circle CCTP contract interfaces
+abigen!(
+    ITokenMessenger,
+    r#"[
+        function depositForBurn(uint256 amount, uint32 destinationDomain, bytes32 mintRecipient, address burnToken) external returns (uint64 nonce)
+    ]"#
+);
+
+abigen!(
+    IMessageTransmitter,
+    r#"[
+        function receiveMessage(bytes calldata message, bytes calldata attestation) external returns (bool success)
+        event MessageSent(bytes message)
+    ]"#
+);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CctpConfig {
+    pub domain: u32,
+    pub token_messenger: Address,
+    pub message_transmitter: Address,
+    pub usdc: Address,
+}
+
+lazy_static! {
+    pub static ref CCTP_DEPLOYMENTS: HashMap<u64, CctpConfig> = {
+        let mut m = HashMap::new();
+
+        // Ethereum Mainnet (ChainID: 1)
+        m.insert(1, CctpConfig {
+            domain: 0,
+            token_messenger: "0xBd3fa81B58Ba92a82136038B25aDec7066af3155".parse().unwrap(),
+            message_transmitter: "0x0a992d191DEeC32aFe36203Ad87D7d289a738F81".parse().unwrap(),
+            usdc: "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48".parse().unwrap(),
+        });
+
+        // Avalanche (ChainID: 43114)
+        m.insert(43114, CctpConfig {
+            domain: 1,
+            token_messenger: "0x6B25532e1060CE10cc3B0A99e5683b91BFDe6982".parse().unwrap(),
+            message_transmitter: "0x8186359aF5F57FbB40c6b14A588d2A59C0C29880".parse().unwrap(),
+            usdc: "0xB97EF9Ef8734C71904D8002F8b6Bc66Dd9c48a6E".parse().unwrap(),
+        });
+
+        // Optimism (ChainID: 10)
+        m.insert(10, CctpConfig {
+            domain: 2,
+            token_messenger: "0x2B4069517957735bE00ceE0fadAE88a26365528f".parse().unwrap(),
+            message_transmitter: "0x4D41f22c5a0e5c74090899E5a8Fb597a8842b3e8".parse().unwrap(),
+            usdc: "0x0b2C639c533813f4Aa9D7837CAf62653d097Ff85".parse().unwrap(),
+        });
+
+        // Arbitrum (ChainID: 42161)
+        m.insert(42161, CctpConfig {
+            domain: 3,
+            token_messenger: "0x19330d10D9Cc8751218eaf51E8885D058642E08A".parse().unwrap(),
+            message_transmitter: "0xC30362313FBBA5cf9163F0bb16a0e01f01A896ca".parse().unwrap(),
+            usdc: "0xaf88d065e77c8cC2239327C5EDb3A432268e5831".parse().unwrap(),
+        });
+
+        // Base (ChainID: 8453)
+        m.insert(8453, CctpConfig {
+            domain: 6,
+            token_messenger: "0x1682Ae6375C4E4A97e4B583BC394c861A46D8962".parse().unwrap(),
+            message_transmitter: "0xAD09780d193884d503182aD4588450C416D6F9D4".parse().unwrap(),
+            usdc: "0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913".parse().unwrap(),
+        });
+
+        // Polygon (ChainID: 137)
+        m.insert(137, CctpConfig {
+            domain: 7,
+            token_messenger: "0x9daF8c91AEFAE50b9c0E69629D3F6Ca40cA3B3FE".parse().unwrap(),
+            message_transmitter: "0xF3be9355363857F3e001be68856A2f96b4C39Ba9".parse().unwrap(),
+            usdc: "0x3c499c542cEF5E3811e1192ce70d8cC03d5c3359".parse().unwrap(),
+        });
+
+        m
+    };
+}
+
+pub fn get_cctp_config(chain_id: u64) -> Option<&'static CctpConfig> {
+    CCTP_DEPLOYMENTS.get(&chain_id)
+}
+
+pub fn is_cctp_supported(from_chain: u64, to_chain: u64) -> bool {
+    from_chain != to_chain
+        && CCTP_DEPLOYMENTS.contains_key(&from_chain)
+        && CCTP_DEPLOYMENTS.contains_key(&to_chain)
+}
+
+/// CCTP addresses recipients as bytes32, left-padded like an ABI-encoded address.
+pub fn address_to_bytes32(address: Address) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    out[12..].copy_from_slice(address.as_bytes());
+    out
+}
+
+/// The attestation service indexes messages by the keccak256 of the raw message bytes.
+pub fn message_hash(message: &[u8]) -> H256 {
+    H256::from(keccak256(message))
+}
+
+/// Pull the `MessageSent` payload emitted by the MessageTransmitter during `depositForBurn`.
+pub fn extract_message_sent(receipt: &TransactionReceipt) -> Option<Bytes> {
+    let topic = H256::from(keccak256("MessageSent(bytes)"));
+    receipt
+        .logs
+        .iter()
+        .find(|log| log.topics.first() == Some(&topic))
+        .and_then(|log| {
+            ethers::abi::decode(&[ethers::abi::ParamType::Bytes], &log.data)
+                .ok()?
+                .into_iter()
+                .next()?
+                .into_bytes()
+        })
+        .map(Bytes::from)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttestationStatus {
+    Pending,
+    Complete(Bytes),
+}
+
+/// Parse a response from `GET /attestations/{messageHash}`. The service answers 404
+/// until the burn is indexed, then `pending_confirmations` until it is signed.
+pub fn parse_attestation_response(body: &serde_json::Value) -> Result<AttestationStatus> {
+    match body.get("status").and_then(|s| s.as_str()) {
+        Some("complete") => {
+            let attestation = body
+                .get("attestation")
+                .and_then(|a| a.as_str())
+                .ok_or_else(|| anyhow::anyhow!("Complete attestation without signature"))?;
+            let attestation = hex::decode(attestation.trim_start_matches("0x"))?;
+            Ok(AttestationStatus::Complete(Bytes::from(attestation)))
+        }
+        Some(_) => Ok(AttestationStatus::Pending),
+        None => Err(anyhow::anyhow!("Malformed attestation response: {}", body)),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AttestationClient {
+    base_url: String,
+    poll_interval: Duration,
+    client: reqwest::Client,
+}
+
+impl AttestationClient {
+    pub fn new(base_url: &str, poll_interval: Duration) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            poll_interval,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub async fn get_attestation(&self, message_hash: H256) -> Result<AttestationStatus> {
+        let url = format!("{}/attestations/{:?}", self.base_url, message_hash);
+        let response = self.client.get(&url).send().await?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(AttestationStatus::Pending);
+        }
+
+        let body: serde_json::Value = response.error_for_status()?.json().await?;
+        parse_attestation_response(&body)
+    }
+
+    /// Poll until the attestation is signed or `max_wait` elapses.
+    pub async fn wait_for_attestation(&self, message_hash: H256, max_wait: Duration) -> Result<Bytes> {
+        let deadline = Instant::now() + max_wait;
+
+        loop {
+            match self.get_attestation(message_hash).await {
+                Ok(AttestationStatus::Complete(attestation)) => return Ok(attestation),
+                Ok(AttestationStatus::Pending) => {}
+                Err(e) => log::warn!("Attestation poll for {:?} failed: {}", message_hash, e),
+            }
+
+            if Instant::now() + self.poll_interval > deadline {
+                return Err(anyhow::anyhow!(
+                    "Timed out waiting for attestation of {:?}",
+                    message_hash
+                ));
+            }
+            sleep(self.poll_interval).await;
+        }
+    }
+}
+
+impl Default for AttestationClient {
+    fn default() -> Self {
+        Self::new(CIRCLE_ATTESTATION_URL, Duration::from_secs(ATTESTATION_POLL_INTERVAL))
+    }
+}
+
+pub struct CctpProtocol<M: Middleware> {
+    config: CctpConfig,
+    token_messenger: ITokenMessenger<M>,
+    message_transmitter: IMessageTransmitter<M>,
+}
+
+impl<M: Middleware> CctpProtocol<M> {
+    pub fn new(chain_id: u64, provider: Arc<M>) -> Result<Self> {
+        let config = get_cctp_config(chain_id)
+            .ok_or_else(|| anyhow::anyhow!("CCTP not deployed on chain {}", chain_id))?;
+
+        let token_messenger = ITokenMessenger::new(config.token_messenger, provider.clone());
+        let message_transmitter = IMessageTransmitter::new(config.message_transmitter, provider);
+
+        Ok(Self {
+            config: config.clone(),
+            token_messenger,
+            message_transmitter,
+        })
+    }
+
+    /// Burn USDC on this chain and return the receipt with the emitted message bytes.
+    pub async fn deposit_for_burn(
+        &self,
+        amount: U256,
+        destination_domain: u32,
+        recipient: Address,
+        burn_token: Address,
+    ) -> Result<(TransactionReceipt, Bytes)> {
+        let receipt = self.token_messenger
+            .deposit_for_burn(amount, destination_domain, address_to_bytes32(recipient), burn_token)
+            .send()
+            .await?
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Transaction failed"))?;
+
+        let message = extract_message_sent(&receipt)
+            .ok_or_else(|| anyhow::anyhow!("No MessageSent event in burn receipt"))?;
+
+        Ok((receipt, message))
+    }
+
+    /// Mint on this chain using a message burned elsewhere and its attestation.
+    pub async fn receive_message(&self, message: Bytes, attestation: Bytes) -> Result<TransactionReceipt> {
+        let receipt = self.message_transmitter
+            .receive_message(message, attestation)
+            .send()
+            .await?
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Transaction failed"))?;
+
+        Ok(receipt)
+    }
+
+    pub fn get_domain(&self) -> u32 {
+        self.config.domain
+    }
+
+    pub fn get_usdc_address(&self) -> Address {
+        self.config.usdc
+    }
+
+    /// The spender `deposit_for_burn` pulls USDC through.
+    pub fn get_token_messenger(&self) -> Address {
+        self.config.token_messenger
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serve `pending` for the first `pending_polls` requests, then a signed attestation.
+    async fn mock_attestation_server(pending_polls: usize, attestation: &'static str) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let hits = Arc::new(AtomicUsize::new(0));
+
+        let counter = hits.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = match listener.accept().await {
+                    Ok(conn) => conn,
+                    Err(_) => return,
+                };
+                let mut buf = [0u8; 1024];
+                let _ = socket.read(&mut buf).await;
+
+                let hit = counter.fetch_add(1, Ordering::SeqCst);
+                let body = if hit < pending_polls {
+                    r#"{"attestation":"PENDING","status":"pending_confirmations"}"#.to_string()
+                } else {
+                    format!(r#"{{"attestation":"{}","status":"complete"}}"#, attestation)
+                };
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+
+        (url, hits)
+    }
+
+    #[test]
+    fn test_address_to_bytes32() {
+        let address: Address = "0x1111111111111111111111111111111111111111".parse().unwrap();
+        let encoded = address_to_bytes32(address);
+
+        assert_eq!(&encoded[..12], &[0u8; 12]);
+        assert_eq!(&encoded[12..], address.as_bytes());
+        // Matches the ABI encoding of an address word
+        assert_eq!(encoded.to_vec(), ethers::abi::encode(&[ethers::abi::Token::Address(address)]));
+    }
+
+    #[test]
+    fn test_extract_message_sent_and_hash() {
+        let message = Bytes::from(vec![0u8, 0, 0, 0, 0, 0, 0, 3, 0xde, 0xad, 0xbe, 0xef]);
+        let receipt = TransactionReceipt {
+            logs: vec![
+                Log {
+                    topics: vec![H256::random()],
+                    ..Default::default()
+                },
+                Log {
+                    topics: vec![H256::from(keccak256("MessageSent(bytes)"))],
+                    data: ethers::abi::encode(&[ethers::abi::Token::Bytes(message.to_vec())]).into(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let extracted = extract_message_sent(&receipt).unwrap();
+        assert_eq!(extracted, message);
+        assert_eq!(message_hash(&extracted), H256::from(keccak256(&message)));
+    }
+
+    #[test]
+    fn test_parse_attestation_response() {
+        let pending = serde_json::json!({"attestation": "PENDING", "status": "pending_confirmations"});
+        assert_eq!(parse_attestation_response(&pending).unwrap(), AttestationStatus::Pending);
+
+        let complete = serde_json::json!({"attestation": "0xabcd", "status": "complete"});
+        assert_eq!(
+            parse_attestation_response(&complete).unwrap(),
+            AttestationStatus::Complete(Bytes::from(vec![0xab, 0xcd]))
+        );
+
+        assert!(parse_attestation_response(&serde_json::json!({"error": "bad"})).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_attestation_polls_until_complete() {
+        let (url, hits) = mock_attestation_server(2, "0x1234").await;
+        let client = AttestationClient::new(&url, Duration::from_millis(10));
+
+        let attestation = client
+            .wait_for_attestation(H256::random(), Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        assert_eq!(attestation, Bytes::from(vec![0x12, 0x34]));
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_attestation_times_out() {
+        let (url, _) = mock_attestation_server(usize::MAX, "0x1234").await;
+        let client = AttestationClient::new(&url, Duration::from_millis(10));
+
+        let result = client
+            .wait_for_attestation(H256::random(), Duration::from_millis(50))
+            .await;
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,551 @@
+use ethers::prelude::*;
+use ethers::abi::AbiEncode;
+use ethers::types::{transaction::eip2718::TypedTransaction, Address, Bytes, U256, U512};
+use anyhow::Result;
+use std::collections::HashMap;
+
+use crate::protocols::swap::{
+    build_v2_swap_calldata, build_v3_swap_calldata, swap_deadline, ApprovalManager, SwapRouterConfig,
+    DEFAULT_V3_FEE,
+};
+use crate::simulator::{CurvePoolState, CurveSimulator, UniswapV2Simulator, UniswapV3Simulator, V3PoolState};
+use crate::strategies::types::DexProtocol;
+
+/// Uniswap V2 pair fee, in the units `UniswapV2Simulator` expects (300 = 0.3%).
+pub const DEFAULT_V2_FEE: u32 = 300;
+
+abigen!(
+    ICurveExchange,
+    r#"[
+        function exchange(int128 i, int128 j, uint256 dx, uint256 min_dy) external returns (uint256)
+    ]"#
+);
+
+abigen!(
+    IBalancerVault,
+    r#"[
+        function swap(SingleSwap singleSwap, FundManagement funds, uint256 limit, uint256 deadline) external payable returns (uint256)
+        struct SingleSwap { bytes32 poolId; uint8 kind; address assetIn; address assetOut; uint256 amount; bytes userData; }
+        struct FundManagement { address sender; bool fromInternalBalance; address recipient; bool toInternalBalance; }
+    ]"#
+);
+
+/// Quotes and builds single-hop swaps on one DEX. Quotes come from the pool
+/// state the adapter was last given, so they don't touch the network.
+pub trait DexAdapter: Send + Sync {
+    fn protocol(&self) -> DexProtocol;
+
+    /// Contract that pulls `token_in`, i.e. the address to approve.
+    fn spender(&self) -> Address;
+
+    fn quote(&self, token_in: Address, token_out: Address, amount_in: U256) -> Result<U256>;
+
+    fn build_swap(
+        &self,
+        token_in: Address,
+        token_out: Address,
+        amount_in: U256,
+        min_amount_out: U256,
+        recipient: Address,
+    ) -> Result<TypedTransaction>;
+}
+
+/// `adapter`'s swap, preceded by an approval of its spender when `approvals`
+/// finds the owner's allowance short, for sending as one bundle.
+pub async fn build_swap_with_approval<M: Middleware + 'static>(
+    adapter: &dyn DexAdapter,
+    approvals: &ApprovalManager<M>,
+    token_in: Address,
+    token_out: Address,
+    amount_in: U256,
+    min_amount_out: U256,
+) -> Result<Vec<TypedTransaction>> {
+    let mut txs = Vec::with_capacity(2);
+    if let Some(approve) = approvals.approval_tx(token_in, adapter.spender(), amount_in).await? {
+        txs.push(approve);
+    }
+    txs.push(adapter.build_swap(token_in, token_out, amount_in, min_amount_out, approvals.owner())?);
+    Ok(txs)
+}
+
+fn swap_tx(to: Address, calldata: Bytes) -> TypedTransaction {
+    TransactionRequest::new().to(to).data(calldata).into()
+}
+
+fn sorted(a: Address, b: Address) -> (Address, Address) {
+    if a < b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+pub struct UniswapV2Adapter {
+    router: Address,
+    fee: U256,
+    /// Reserves by `(token0, token1)`, token0 being the lower address.
+    reserves: HashMap<(Address, Address), (U256, U256)>,
+}
+
+impl UniswapV2Adapter {
+    pub fn new(router: Address) -> Self {
+        Self {
+            router,
+            fee: U256::from(DEFAULT_V2_FEE),
+            reserves: HashMap::new(),
+        }
+    }
+
+    pub fn update_reserves(&mut self, token0: Address, token1: Address, reserve0: U256, reserve1: U256) {
+        let key = sorted(token0, token1);
+        let reserves = if key.0 == token0 { (reserve0, reserve1) } else { (reserve1, reserve0) };
+        self.reserves.insert(key, reserves);
+    }
+}
+
+impl DexAdapter for UniswapV2Adapter {
+    fn protocol(&self) -> DexProtocol {
+        DexProtocol::UniswapV2
+    }
+
+    fn spender(&self) -> Address {
+        self.router
+    }
+
+    fn quote(&self, token_in: Address, token_out: Address, amount_in: U256) -> Result<U256> {
+        let key = sorted(token_in, token_out);
+        let (reserve0, reserve1) = self
+            .reserves
+            .get(&key)
+            .ok_or_else(|| anyhow::anyhow!("No V2 reserves for {:?}/{:?}", token_in, token_out))?;
+        let (reserve_in, reserve_out) =
+            if key.0 == token_in { (*reserve0, *reserve1) } else { (*reserve1, *reserve0) };
+
+        UniswapV2Simulator::get_amount_out(amount_in, reserve_in, reserve_out, self.fee)
+            .ok_or_else(|| anyhow::anyhow!("V2 pair can't fill {} of {:?}", amount_in, token_in))
+    }
+
+    fn build_swap(
+        &self,
+        token_in: Address,
+        token_out: Address,
+        amount_in: U256,
+        min_amount_out: U256,
+        recipient: Address,
+    ) -> Result<TypedTransaction> {
+        let calldata = build_v2_swap_calldata(
+            amount_in,
+            min_amount_out,
+            vec![token_in, token_out],
+            recipient,
+            swap_deadline(),
+        );
+        Ok(swap_tx(self.router, calldata))
+    }
+}
+
+pub struct UniswapV3Adapter {
+    config: SwapRouterConfig,
+    router: Address,
+    fee: u32,
+    /// Active-range state by `(token0, token1)`, token0 being the lower address.
+    states: HashMap<(Address, Address), V3PoolState>,
+}
+
+impl UniswapV3Adapter {
+    pub fn new(config: SwapRouterConfig) -> Result<Self> {
+        let router = config
+            .v3_router
+            .ok_or_else(|| anyhow::anyhow!("Router config has no Uniswap V3 router"))?;
+        Ok(Self {
+            config,
+            router,
+            fee: DEFAULT_V3_FEE,
+            states: HashMap::new(),
+        })
+    }
+
+    pub fn update_state(&mut self, token0: Address, token1: Address, state: V3PoolState) {
+        self.states.insert(sorted(token0, token1), state);
+    }
+}
+
+impl DexAdapter for UniswapV3Adapter {
+    fn protocol(&self) -> DexProtocol {
+        DexProtocol::UniswapV3
+    }
+
+    fn spender(&self) -> Address {
+        self.router
+    }
+
+    fn quote(&self, token_in: Address, token_out: Address, amount_in: U256) -> Result<U256> {
+        let key = sorted(token_in, token_out);
+        let state = self
+            .states
+            .get(&key)
+            .ok_or_else(|| anyhow::anyhow!("No V3 state for {:?}/{:?}", token_in, token_out))?;
+
+        UniswapV3Simulator::get_amount_out(amount_in, state, U256::from(self.fee), key.0 == token_in)
+            .ok_or_else(|| anyhow::anyhow!("V3 pool can't fill {} of {:?}", amount_in, token_in))
+    }
+
+    fn build_swap(
+        &self,
+        token_in: Address,
+        token_out: Address,
+        amount_in: U256,
+        min_amount_out: U256,
+        recipient: Address,
+    ) -> Result<TypedTransaction> {
+        let calldata = build_v3_swap_calldata(
+            &self.config,
+            token_in,
+            token_out,
+            self.fee,
+            amount_in,
+            min_amount_out,
+            recipient,
+            swap_deadline(),
+        );
+        Ok(swap_tx(self.router, calldata))
+    }
+}
+
+pub struct CurveAdapter {
+    pool: Address,
+    state: CurvePoolState,
+}
+
+impl CurveAdapter {
+    pub fn new(pool: Address, state: CurvePoolState) -> Self {
+        Self { pool, state }
+    }
+
+    pub fn update_state(&mut self, state: CurvePoolState) {
+        self.state = state;
+    }
+
+    fn indices(&self, token_in: Address, token_out: Address) -> Result<(usize, usize)> {
+        let index = |token: Address| {
+            self.state
+                .coins
+                .iter()
+                .position(|coin| *coin == token)
+                .ok_or_else(|| anyhow::anyhow!("{:?} is not a coin of Curve pool {:?}", token, self.pool))
+        };
+        Ok((index(token_in)?, index(token_out)?))
+    }
+}
+
+impl DexAdapter for CurveAdapter {
+    fn protocol(&self) -> DexProtocol {
+        DexProtocol::Curve
+    }
+
+    fn spender(&self) -> Address {
+        self.pool
+    }
+
+    fn quote(&self, token_in: Address, token_out: Address, amount_in: U256) -> Result<U256> {
+        let (i, j) = self.indices(token_in, token_out)?;
+        CurveSimulator::get_dy(i, j, amount_in, &self.state)
+            .ok_or_else(|| anyhow::anyhow!("Curve pool {:?} can't fill {}", self.pool, amount_in))
+    }
+
+    fn build_swap(
+        &self,
+        token_in: Address,
+        token_out: Address,
+        amount_in: U256,
+        min_amount_out: U256,
+        _recipient: Address,
+    ) -> Result<TypedTransaction> {
+        // Plain pools always pay out to the caller
+        let (i, j) = self.indices(token_in, token_out)?;
+        let calldata = ExchangeCall {
+            i: i as i128,
+            j: j as i128,
+            dx: amount_in,
+            min_dy: min_amount_out,
+        }
+        .encode();
+        Ok(swap_tx(self.pool, calldata.into()))
+    }
+}
+
+/// Two-token weighted pool held by the Balancer vault.
+#[derive(Debug, Clone)]
+pub struct BalancerPoolState {
+    pub pool_id: [u8; 32],
+    pub tokens: Vec<Address>,
+    pub balances: Vec<U256>,
+    /// Normalized weights, summing to 1e18.
+    pub weights: Vec<U256>,
+    /// Swap fee as an 18-decimal fraction.
+    pub swap_fee: U256,
+}
+
+pub struct BalancerAdapter {
+    vault: Address,
+    pools: HashMap<(Address, Address), BalancerPoolState>,
+}
+
+impl BalancerAdapter {
+    pub fn new(vault: Address) -> Self {
+        Self {
+            vault,
+            pools: HashMap::new(),
+        }
+    }
+
+    pub fn update_pool(&mut self, state: BalancerPoolState) {
+        for (i, a) in state.tokens.iter().enumerate() {
+            for b in &state.tokens[i + 1..] {
+                self.pools.insert(sorted(*a, *b), state.clone());
+            }
+        }
+    }
+
+    fn pool(&self, token_in: Address, token_out: Address) -> Result<&BalancerPoolState> {
+        self.pools
+            .get(&sorted(token_in, token_out))
+            .ok_or_else(|| anyhow::anyhow!("No Balancer pool for {:?}/{:?}", token_in, token_out))
+    }
+}
+
+impl DexAdapter for BalancerAdapter {
+    fn protocol(&self) -> DexProtocol {
+        DexProtocol::Balancer
+    }
+
+    fn spender(&self) -> Address {
+        self.vault
+    }
+
+    /// Weighted math: out = Bo * (1 - (Bi / (Bi + Ai * (1 - fee))) ^ (Wi / Wo)).
+    fn quote(&self, token_in: Address, token_out: Address, amount_in: U256) -> Result<U256> {
+        let pool = self.pool(token_in, token_out)?;
+        let index = |token: Address| pool.tokens.iter().position(|t| *t == token).unwrap();
+        let (i, o) = (index(token_in), index(token_out));
+
+        let wad = U256::exp10(18);
+        let cannot_fill = || anyhow::anyhow!("Balancer pool can't fill {:?}", token_in);
+        // Callers keep b <= c, so the quotient fits back into a U256
+        let mul_div = |a: U256, b: U256, c: U256| U256::try_from(a.full_mul(b) / c).unwrap_or(U256::MAX);
+
+        let amount_in = mul_div(amount_in, wad.saturating_sub(pool.swap_fee), wad);
+        let (balance_in, balance_out) = (pool.balances[i], pool.balances[o]);
+        if balance_in.is_zero() || balance_out.is_zero() || amount_in.is_zero() {
+            return Err(cannot_fill());
+        }
+
+        // Bi / (Bi + Ai) as an 18-decimal fraction, rounded up so the quote rounds down
+        let denominator = U512::from(balance_in) + U512::from(amount_in);
+        let base = U256::try_from((U512::from(balance_in) * U512::from(wad) + denominator - 1) / denominator)
+            .unwrap_or(wad);
+        let power = if pool.weights[i] == pool.weights[o] {
+            base
+        } else {
+            // Only the power of a fraction in [0, 1] goes through floating point
+            let exponent = pool.weights[i].low_u128() as f64 / pool.weights[o].low_u128() as f64;
+            let power = (base.low_u128() as f64 / 1e18).powf(exponent);
+            if !power.is_finite() {
+                return Err(cannot_fill());
+            }
+            U256::from((power * 1e18).ceil() as u128).min(wad)
+        };
+
+        let amount_out = mul_div(balance_out, wad - power, wad);
+        if amount_out.is_zero() {
+            return Err(cannot_fill());
+        }
+        Ok(amount_out)
+    }
+
+    fn build_swap(
+        &self,
+        token_in: Address,
+        token_out: Address,
+        amount_in: U256,
+        min_amount_out: U256,
+        recipient: Address,
+    ) -> Result<TypedTransaction> {
+        let pool = self.pool(token_in, token_out)?;
+        let calldata = SwapCall {
+            single_swap: SingleSwap {
+                pool_id: pool.pool_id,
+                kind: 0, // GIVEN_IN
+                asset_in: token_in,
+                asset_out: token_out,
+                amount: amount_in,
+                user_data: Bytes::new(),
+            },
+            funds: FundManagement {
+                sender: recipient,
+                from_internal_balance: false,
+                recipient,
+                to_internal_balance: false,
+            },
+            limit: min_amount_out,
+            deadline: swap_deadline(),
+        }
+        .encode();
+        Ok(swap_tx(self.vault, calldata.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::AbiDecode;
+    use crate::protocols::swap::{get_swap_router_config, SwapExactTokensForTokensCall};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_v2_quote_matches_simulator() {
+        let (weth, usdc) = (Address::random(), Address::random());
+        let reserve_weth = U256::from(1_000u64) * U256::exp10(18);
+        let reserve_usdc = U256::from(2_000_000u64) * U256::exp10(6);
+
+        let mut adapter = UniswapV2Adapter::new(Address::random());
+        adapter.update_reserves(weth, usdc, reserve_weth, reserve_usdc);
+
+        let amount_in = U256::exp10(18);
+        let expected = UniswapV2Simulator::get_amount_out(
+            amount_in,
+            reserve_weth,
+            reserve_usdc,
+            U256::from(DEFAULT_V2_FEE),
+        )
+        .unwrap();
+        assert_eq!(adapter.quote(weth, usdc, amount_in).unwrap(), expected);
+
+        // Reversed direction reads the reserves the other way round
+        let amount_in = U256::from(2_000u64) * U256::exp10(6);
+        let expected = UniswapV2Simulator::get_amount_out(
+            amount_in,
+            reserve_usdc,
+            reserve_weth,
+            U256::from(DEFAULT_V2_FEE),
+        )
+        .unwrap();
+        assert_eq!(adapter.quote(usdc, weth, amount_in).unwrap(), expected);
+
+        assert!(adapter.quote(weth, Address::random(), amount_in).is_err());
+    }
+
+    #[test]
+    fn test_v2_build_swap_targets_router() {
+        let router = Address::random();
+        let (token_in, token_out, recipient) = (Address::random(), Address::random(), Address::random());
+        let adapter = UniswapV2Adapter::new(router);
+
+        let tx = adapter
+            .build_swap(token_in, token_out, U256::from(1_000), U256::from(990), recipient)
+            .unwrap();
+        assert_eq!(tx.to_addr(), Some(&router));
+
+        let decoded = SwapExactTokensForTokensCall::decode(tx.data().unwrap()).unwrap();
+        assert_eq!(decoded.path, vec![token_in, token_out]);
+        assert_eq!(decoded.amount_out_min, U256::from(990));
+    }
+
+    #[tokio::test]
+    async fn test_swap_is_preceded_by_missing_approval() {
+        let router = Address::random();
+        let (token_in, token_out, owner) = (Address::random(), Address::random(), Address::random());
+        let adapter = UniswapV2Adapter::new(router);
+
+        let (provider, mock) = Provider::mocked();
+        mock.push(Bytes::from(ethers::abi::encode(&[ethers::abi::Token::Uint(U256::zero())])))
+            .unwrap();
+        let approvals = ApprovalManager::new(Arc::new(provider), owner);
+
+        let txs = build_swap_with_approval(&adapter, &approvals, token_in, token_out, U256::from(1_000), U256::from(990))
+            .await
+            .unwrap();
+        assert_eq!(txs.len(), 2);
+        assert_eq!(txs[0].to_addr(), Some(&token_in));
+        assert_eq!(txs[1].to_addr(), Some(&router));
+        let decoded = SwapExactTokensForTokensCall::decode(txs[1].data().unwrap()).unwrap();
+        assert_eq!(decoded.to, owner);
+
+        // Approved now: the swap goes alone
+        approvals.record_approval(token_in, router).await;
+        let txs = build_swap_with_approval(&adapter, &approvals, token_in, token_out, U256::from(1_000), U256::from(990))
+            .await
+            .unwrap();
+        assert_eq!(txs.len(), 1);
+    }
+
+    fn balancer_pool(tokens: Vec<Address>, balances: Vec<U256>, weights: Vec<U256>) -> BalancerPoolState {
+        BalancerPoolState {
+            pool_id: [0u8; 32],
+            tokens,
+            balances,
+            weights,
+            swap_fee: U256::exp10(15), // 0.1%
+        }
+    }
+
+    #[test]
+    fn test_balancer_equal_weights_quote_constant_product() {
+        let (a, b) = (Address::random(), Address::random());
+        // Balances past u128 must not panic
+        let balance = U256::exp10(40);
+        let half = U256::exp10(18) / 2;
+        let mut adapter = BalancerAdapter::new(Address::random());
+        adapter.update_pool(balancer_pool(vec![a, b], vec![balance, balance], vec![half, half]));
+
+        let amount_in = U256::exp10(39);
+        let after_fee = amount_in * 999 / 1000;
+        let expected = balance * after_fee / (balance + after_fee);
+        let quoted = adapter.quote(a, b, amount_in).unwrap();
+        assert!(quoted <= expected);
+        assert!(expected - quoted < expected / U256::exp10(15));
+    }
+
+    #[test]
+    fn test_balancer_registers_every_pair() {
+        let (a, b, c) = (Address::random(), Address::random(), Address::random());
+        let third = U256::exp10(18) / 3;
+        let balances = vec![U256::exp10(24); 3];
+        let mut adapter = BalancerAdapter::new(Address::random());
+        adapter.update_pool(balancer_pool(vec![a, b, c], balances, vec![third; 3]));
+
+        assert!(adapter.quote(a, c, U256::exp10(18)).is_ok());
+        assert!(adapter.quote(c, b, U256::exp10(18)).is_ok());
+    }
+
+    #[test]
+    fn test_balancer_weighted_quote() {
+        let (a, b) = (Address::random(), Address::random());
+        let wad = U256::exp10(18);
+        let balance = U256::exp10(24);
+        // 80/20 pool at equal balances: one a is worth four b
+        let mut adapter = BalancerAdapter::new(Address::random());
+        adapter.update_pool(balancer_pool(vec![a, b], vec![balance, balance], vec![wad * 4 / 5, wad / 5]));
+
+        let quoted = adapter.quote(a, b, U256::exp10(18)).unwrap();
+        let spot = U256::exp10(18) * 4 * 999 / 1000;
+        assert!(quoted < spot);
+        assert!(quoted > spot * 9_999 / 10_000);
+    }
+
+    #[test]
+    fn test_adapters_report_their_protocol() {
+        let config = get_swap_router_config(1).unwrap().clone();
+        let adapters: Vec<Box<dyn DexAdapter>> = vec![
+            Box::new(UniswapV2Adapter::new(Address::random())),
+            Box::new(UniswapV3Adapter::new(config).unwrap()),
+            Box::new(BalancerAdapter::new(Address::random())),
+        ];
+
+        let protocols: Vec<DexProtocol> = adapters.iter().map(|a| a.protocol()).collect();
+        assert_eq!(
+            protocols,
+            vec![DexProtocol::UniswapV2, DexProtocol::UniswapV3, DexProtocol::Balancer]
+        );
+    }
+}
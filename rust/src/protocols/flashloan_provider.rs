@@ -0,0 +1,193 @@
+use ethers::prelude::*;
+use ethers::abi::AbiEncode;
+use ethers::types::{transaction::eip2718::TypedTransaction, Address, Bytes, U256};
+use anyhow::Result;
+use std::collections::HashMap;
+
+use crate::core::FlashloanProvider;
+use crate::protocols::aave::FlashLoanCall as AaveFlashLoanCall;
+
+/// Aave V3 `FLASHLOAN_PREMIUM_TOTAL`, 0.05%.
+pub const AAVE_FLASHLOAN_FEE_BPS: u16 = 5;
+
+abigen!(
+    IBalancerFlashVault,
+    r#"[
+        function flashLoan(address recipient, address[] tokens, uint256[] amounts, bytes userData) external
+    ]"#
+);
+
+/// A lender that can fund a single-token flashloan. Liquidity comes from the
+/// amounts the provider was last given, so selection doesn't touch the network.
+pub trait FlashLoanProvider: Send + Sync {
+    fn kind(&self) -> FlashloanProvider;
+
+    /// Contract the flashloan transaction is sent to.
+    fn lender(&self) -> Address;
+
+    fn max_available(&self, token: Address) -> U256;
+
+    fn fee_bps(&self) -> u16;
+
+    fn build_flashloan(
+        &self,
+        token: Address,
+        amount: U256,
+        callback: Address,
+        params: Bytes,
+    ) -> Result<TypedTransaction>;
+}
+
+fn flashloan_tx(to: Address, calldata: Bytes) -> TypedTransaction {
+    TransactionRequest::new().to(to).data(calldata).into()
+}
+
+pub struct AaveFlashLoanProvider {
+    pool: Address,
+    fee_bps: u16,
+    liquidity: HashMap<Address, U256>,
+}
+
+impl AaveFlashLoanProvider {
+    pub fn new(pool: Address) -> Self {
+        Self {
+            pool,
+            fee_bps: AAVE_FLASHLOAN_FEE_BPS,
+            liquidity: HashMap::new(),
+        }
+    }
+
+    pub fn update_liquidity(&mut self, token: Address, available: U256) {
+        self.liquidity.insert(token, available);
+    }
+}
+
+impl FlashLoanProvider for AaveFlashLoanProvider {
+    fn kind(&self) -> FlashloanProvider {
+        FlashloanProvider::AAVE
+    }
+
+    fn lender(&self) -> Address {
+        self.pool
+    }
+
+    fn max_available(&self, token: Address) -> U256 {
+        self.liquidity.get(&token).copied().unwrap_or_default()
+    }
+
+    fn fee_bps(&self) -> u16 {
+        self.fee_bps
+    }
+
+    fn build_flashloan(
+        &self,
+        token: Address,
+        amount: U256,
+        callback: Address,
+        params: Bytes,
+    ) -> Result<TypedTransaction> {
+        let calldata = AaveFlashLoanCall {
+            receiver_address: callback,
+            assets: vec![token],
+            amounts: vec![amount],
+            modes: vec![U256::zero()], // Repay in the same transaction
+            on_behalf_of: callback,
+            params,
+            referral_code: 0,
+        }
+        .encode();
+        Ok(flashloan_tx(self.pool, calldata.into()))
+    }
+}
+
+/// Balancer flashloans are free and draw on the vault's whole balance.
+pub struct BalancerFlashLoanProvider {
+    vault: Address,
+    balances: HashMap<Address, U256>,
+}
+
+impl BalancerFlashLoanProvider {
+    pub fn new(vault: Address) -> Self {
+        Self {
+            vault,
+            balances: HashMap::new(),
+        }
+    }
+
+    pub fn update_balance(&mut self, token: Address, balance: U256) {
+        self.balances.insert(token, balance);
+    }
+}
+
+impl FlashLoanProvider for BalancerFlashLoanProvider {
+    fn kind(&self) -> FlashloanProvider {
+        FlashloanProvider::Balancer
+    }
+
+    fn lender(&self) -> Address {
+        self.vault
+    }
+
+    fn max_available(&self, token: Address) -> U256 {
+        self.balances.get(&token).copied().unwrap_or_default()
+    }
+
+    fn fee_bps(&self) -> u16 {
+        0
+    }
+
+    fn build_flashloan(
+        &self,
+        token: Address,
+        amount: U256,
+        callback: Address,
+        params: Bytes,
+    ) -> Result<TypedTransaction> {
+        let calldata = FlashLoanCall {
+            recipient: callback,
+            tokens: vec![token],
+            amounts: vec![amount],
+            user_data: params,
+        }
+        .encode();
+        Ok(flashloan_tx(self.vault, calldata.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::AbiDecode;
+
+    #[test]
+    fn test_balancer_flashloan_calldata() {
+        let (vault, token, callback) = (Address::random(), Address::random(), Address::random());
+        let provider = BalancerFlashLoanProvider::new(vault);
+
+        let tx = provider
+            .build_flashloan(token, U256::from(1_000), callback, Bytes::from(vec![1, 2, 3]))
+            .unwrap();
+        assert_eq!(tx.to_addr(), Some(&vault));
+
+        let decoded = FlashLoanCall::decode(tx.data().unwrap()).unwrap();
+        assert_eq!(decoded.recipient, callback);
+        assert_eq!(decoded.tokens, vec![token]);
+        assert_eq!(decoded.amounts, vec![U256::from(1_000)]);
+    }
+
+    #[test]
+    fn test_aave_flashloan_calldata() {
+        let (pool, token, callback) = (Address::random(), Address::random(), Address::random());
+        let provider = AaveFlashLoanProvider::new(pool);
+
+        let tx = provider
+            .build_flashloan(token, U256::from(5_000), callback, Bytes::new())
+            .unwrap();
+        assert_eq!(tx.to_addr(), Some(&pool));
+
+        let decoded = AaveFlashLoanCall::decode(tx.data().unwrap()).unwrap();
+        assert_eq!(decoded.receiver_address, callback);
+        assert_eq!(decoded.assets, vec![token]);
+        assert_eq!(decoded.modes, vec![U256::zero()]);
+    }
+}
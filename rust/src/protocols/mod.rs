@@ -0,0 +1,7 @@
+pub mod aave;
+pub mod cctp;
+pub mod dex_adapter;
+pub mod flashloan_provider;
+pub mod routing;
+pub mod stargate;
+pub mod swap;
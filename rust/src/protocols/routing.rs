@@ -9,6 +9,9 @@ use tokio::time::{timeout, Duration};
 use serde::{Serialize, Deserialize};
 
 const TIMEOUT_DURATION: u64 = 5; // 5 seconds timeout for RPC calls
+const SECONDS_PER_YEAR: f64 = 31_536_000.0;
+/// Holding period used when a caller has no specific horizon (30 days).
+pub const DEFAULT_HOLDING_PERIOD_SECS: u64 = 30 * 24 * 60 * 60;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChainConfig {
@@ -100,79 +103,68 @@ impl<M: Middleware + 'static> MultiChainRouter<M> {
         amount: U256,
         source_chain: u64,
     ) -> Result<Vec<RateInfo>> {
-        let mut rates = Vec::new();
-        let mut futures = Vec::new();
-
-        // Query rates on all chains in parallel
+        // Each query owns its handles so no future borrows `self`
+        let mut queries = Vec::new();
         for (chain_id, aave) in self.aave_pools.iter() {
-            let asset = asset;
-            let amount = amount;
-            
-            futures.push(async move {
-                let result = timeout(
-                    Duration::from_secs(TIMEOUT_DURATION),
-                    self.get_chain_rates(*chain_id, asset, amount)
-                ).await;
-                
-                match result {
-                    Ok(Ok(rate)) => Some(rate),
-                    _ => None,
-                }
-            });
-        }
+            let (provider, chain_config) = match (
+                self.providers.get(chain_id),
+                self.chains.get(chain_id),
+            ) {
+                (Some(provider), Some(chain_config)) => (provider.clone(), chain_config.clone()),
+                _ => continue,
+            };
 
-        // Collect results
-        let results = join_all(futures).await;
-        for result in results.into_iter().flatten() {
-            rates.push(result);
+            queries.push(Self::query_chain_rates(
+                aave.clone(),
+                provider,
+                chain_config,
+                asset,
+                amount,
+            ));
         }
 
+        // Query rates on all chains in parallel
+        let mut rates = gather_rates(queries).await;
+
         // Sort by supply APY descending
         rates.sort_by(|a, b| b.supply_apy.partial_cmp(&a.supply_apy).unwrap());
         
         Ok(rates)
     }
 
-    async fn get_chain_rates(&self, 
-        chain_id: u64,
+    async fn query_chain_rates(
+        aave: Arc<AaveProtocol<M>>,
+        provider: Arc<M>,
+        chain_config: ChainConfig,
         asset: Address,
-        amount: U256,
+        _amount: U256,
     ) -> Result<RateInfo> {
-        let aave = self.aave_pools.get(&chain_id)
-            .ok_or_else(|| anyhow::anyhow!("Chain not supported"))?;
-
+        let chain_id = chain_config.chain_id;
         let reserve_data = aave.get_reserve_data(asset).await?;
         let asset_price = aave.get_asset_price(asset).await?;
         
         // Calculate APYs
-        let supply_apy = self.calculate_apy(reserve_data.current_liquidity_rate)?;
-        let borrow_apy = self.calculate_apy(reserve_data.current_variable_borrow_rate)?;
+        let supply_apy = Self::calculate_apy(reserve_data.current_liquidity_rate)?;
+        let borrow_apy = Self::calculate_apy(reserve_data.current_variable_borrow_rate)?;
         
-        // Get gas token price
-        let chain_config = self.chains.get(&chain_id)
-            .ok_or_else(|| anyhow::anyhow!("Chain config not found"))?;
+        // Price the gas token in asset units per wei, both quoted by the same oracle
         let gas_price = aave.get_asset_price(chain_config.gas_token).await?;
-        let gas_token_price = ethers::utils::format_units(gas_price, "ether")
-            .parse::<f64>()?;
+        let asset_decimals = reserve_decimals(reserve_data.configuration.data);
+        let gas_token_price = gas_price_in_asset(gas_price, asset_price, asset_decimals);
 
         // Estimate gas costs for common operations
-        let estimated_gas = self.estimate_gas_cost(chain_id).await?;
-
-        // Calculate utilization rate
-        let total_supply = reserve_data.liquidity_index;
-        let total_borrow = reserve_data.variable_borrow_index;
-        let utilization = if !total_supply.is_zero() {
-            total_borrow.as_u128() as f64 / total_supply.as_u128() as f64
-        } else {
-            0.0
-        };
+        let estimated_gas = Self::estimate_gas_cost(provider.as_ref()).await?;
+
+        // Utilization from aToken supply and outstanding debt, not the interest indices
+        let (total_supply, total_borrow) = aave.get_reserve_totals(asset).await?;
+        let utilization = calculate_utilization(total_supply, total_borrow);
 
         Ok(RateInfo {
             chain_id,
             asset,
             supply_apy,
             borrow_apy,
-            liquidity: total_supply,
+            liquidity: total_supply.saturating_sub(total_borrow),
             utilization,
             gas_token_price,
             estimated_gas_cost: estimated_gas,
@@ -184,6 +176,7 @@ impl<M: Middleware + 'static> MultiChainRouter<M> {
         amount: U256,
         source_chain: u64,
         min_profit: U256,
+        holding_period_secs: u64,
     ) -> Result<Vec<CrossChainRoute>> {
         let mut routes = Vec::new();
         let rates = self.find_best_rates(asset, amount, source_chain).await?;
@@ -195,10 +188,11 @@ impl<M: Middleware + 'static> MultiChainRouter<M> {
                     continue;
                 }
 
-                let profit = self.calculate_route_profit(
+                let profit = Self::calculate_route_profit(
                     source_rate,
                     target_rate,
-                    amount
+                    amount,
+                    holding_period_secs,
                 )?;
 
                 if profit > min_profit {
@@ -264,44 +258,57 @@ impl<M: Middleware + 'static> MultiChainRouter<M> {
     }
 
     // Helper functions
-    fn calculate_apy(&self, rate: U256) -> Result<f64> {
+    /// Aave rates are annual APRs scaled by RAY (1e27) and compound per second.
+    fn calculate_apy(rate: U256) -> Result<f64> {
         let ray = U256::from(10).pow(U256::from(27));
+        if rate > U256::from(u128::MAX) {
+            return Err(anyhow::anyhow!("Rate out of range: {}", rate));
+        }
         let rate_f64 = rate.as_u128() as f64 / ray.as_u128() as f64;
-        Ok(((1.0 + rate_f64 / 31536000.0).powf(31536000.0) - 1.0) * 100.0)
+        Ok(((1.0 + rate_f64 / SECONDS_PER_YEAR).powf(SECONDS_PER_YEAR) - 1.0) * 100.0)
     }
 
-    async fn estimate_gas_cost(&self, chain_id: u64) -> Result<U256> {
-        let provider = self.providers.get(&chain_id)
-            .ok_or_else(|| anyhow::anyhow!("Provider not found"))?;
-            
+    async fn estimate_gas_cost(provider: &M) -> Result<U256> {
         let gas_price = provider.get_gas_price().await?;
         
         // Estimate gas units for common operations
         let supply_gas = U256::from(200_000);
         let borrow_gas = U256::from(300_000);
         let bridge_gas = U256::from(500_000);
+        let swap_gas = U256::from(150_000);
         
-        Ok(gas_price.checked_mul(supply_gas + borrow_gas + bridge_gas)
+        Ok(gas_price.checked_mul(supply_gas + borrow_gas + bridge_gas + swap_gas)
             .ok_or_else(|| anyhow::anyhow!("Gas calculation overflow"))?)
     }
 
+    /// Net gain of moving `amount` from the source to the target market for
+    /// `holding_period_secs`: the compounded yield differential over that period minus
+    /// the gas spent on both chains. `gas_token_price` is taken as asset units per wei
+    /// of the chain's gas token. Unprofitable routes return zero.
     fn calculate_route_profit(
-        &self,
         source_rate: &RateInfo,
         target_rate: &RateInfo,
         amount: U256,
+        holding_period_secs: u64,
     ) -> Result<U256> {
-        let source_yield = (source_rate.supply_apy / 100.0) * amount.as_u128() as f64;
-        let target_yield = (target_rate.supply_apy / 100.0) * amount.as_u128() as f64;
-        
-        let bridge_cost = source_rate.estimated_gas_cost
-            .checked_add(target_rate.estimated_gas_cost)
-            .ok_or_else(|| anyhow::anyhow!("Gas cost overflow"))?;
+        let amount = amount.to_string().parse::<f64>()?;
+        let years = holding_period_secs as f64 / SECONDS_PER_YEAR;
 
-        let total_yield = target_yield - source_yield;
-        let profit = total_yield - (bridge_cost.as_u128() as f64 * source_rate.gas_token_price);
-        
-        Ok(U256::from((profit.max(0.0)) as u128))
+        let period_growth = |apy: f64| (1.0 + apy / 100.0).powf(years) - 1.0;
+        let yield_diff =
+            amount * (period_growth(target_rate.supply_apy) - period_growth(source_rate.supply_apy));
+
+        let gas_cost = |rate: &RateInfo| {
+            rate.estimated_gas_cost.to_string().parse::<f64>().unwrap_or(f64::MAX) * rate.gas_token_price
+        };
+        let total_gas_cost = gas_cost(source_rate) + gas_cost(target_rate);
+
+        let profit = yield_diff - total_gas_cost;
+        if !profit.is_finite() || profit <= 0.0 {
+            return Ok(U256::zero());
+        }
+
+        Ok(U256::from(profit as u128))
     }
 
     fn build_route(
@@ -344,3 +351,183 @@ impl<M: Middleware + 'static> MultiChainRouter<M> {
         })
     }
 }
+
+/// Share of supplied liquidity that is borrowed, clamped to `[0, 1]`.
+fn calculate_utilization(total_supply: U256, total_borrow: U256) -> f64 {
+    if total_supply.is_zero() {
+        return 0.0;
+    }
+    let supply = total_supply.to_string().parse::<f64>().unwrap_or(0.0);
+    let borrow = total_borrow.to_string().parse::<f64>().unwrap_or(0.0);
+    (borrow / supply).clamp(0.0, 1.0)
+}
+
+/// Run per-chain rate queries concurrently, dropping any that fail or time out.
+async fn gather_rates<F>(queries: Vec<F>) -> Vec<RateInfo>
+where
+    F: std::future::Future<Output = Result<RateInfo>>,
+{
+    let futures = queries.into_iter().map(|query| async move {
+        match timeout(Duration::from_secs(TIMEOUT_DURATION), query).await {
+            Ok(Ok(rate)) => Some(rate),
+            _ => None,
+        }
+    });
+
+    join_all(futures).await.into_iter().flatten().collect()
+}
+
+/// Decimals of an Aave V3 reserve, stored in bits 48-55 of its configuration.
+fn reserve_decimals(configuration: U256) -> u8 {
+    ((configuration >> 48) & U256::from(0xff)).as_u32() as u8
+}
+
+/// Asset base units worth one wei of an 18-decimal gas token, from two oracle
+/// prices in the same base currency. Zero if the asset has no price.
+pub fn gas_price_in_asset(gas_token_price: U256, asset_price: U256, asset_decimals: u8) -> f64 {
+    if asset_price.is_zero() {
+        return 0.0;
+    }
+    let to_f64 = |value: U256| value.to_string().parse::<f64>().unwrap_or(f64::MAX);
+    to_f64(gas_token_price) / to_f64(asset_price) * 10f64.powi(asset_decimals as i32 - 18)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::Barrier;
+
+    fn rate(chain_id: u64, supply_apy: f64) -> RateInfo {
+        RateInfo {
+            chain_id,
+            asset: Address::zero(),
+            supply_apy,
+            borrow_apy: 0.0,
+            liquidity: U256::zero(),
+            utilization: 0.0,
+            gas_token_price: 0.0,
+            estimated_gas_cost: U256::zero(),
+        }
+    }
+
+    #[test]
+    fn test_calculate_apy_from_ray_rate() {
+        // 5% APR compounded per second
+        let rate = U256::from(5) * U256::exp10(25);
+        let apy = MultiChainRouter::<Provider<Http>>::calculate_apy(rate).unwrap();
+        assert!((apy - 5.127109).abs() < 1e-4);
+
+        let zero = MultiChainRouter::<Provider<Http>>::calculate_apy(U256::zero()).unwrap();
+        assert_eq!(zero, 0.0);
+    }
+
+    #[test]
+    fn test_utilization_is_bounded() {
+        let supply = U256::from(1_000_000) * U256::exp10(6);
+        let borrow = U256::from(750_000) * U256::exp10(6);
+        assert!((calculate_utilization(supply, borrow) - 0.75).abs() < 1e-12);
+
+        assert_eq!(calculate_utilization(U256::zero(), borrow), 0.0);
+        assert_eq!(calculate_utilization(supply, U256::zero()), 0.0);
+        // Debt briefly exceeding the aToken supply (accrued interest) still reads as fully utilized
+        assert_eq!(calculate_utilization(borrow, supply), 1.0);
+
+        let huge = U256::MAX;
+        let u = calculate_utilization(huge, huge / 2);
+        assert!((0.0..=1.0).contains(&u));
+    }
+
+    fn usdc_rate(chain_id: u64, supply_apy: f64) -> RateInfo {
+        RateInfo {
+            // 0.01 ETH of gas at 2000 USDC/ETH, expressed in USDC base units per wei
+            estimated_gas_cost: U256::exp10(16),
+            gas_token_price: 2000.0 * 1e6 / 1e18,
+            ..rate(chain_id, supply_apy)
+        }
+    }
+
+    #[test]
+    fn test_gas_price_in_asset_units() {
+        // ETH at $2000 and USDC at $1, as 8-decimal oracle prices
+        let eth = U256::from(2_000) * U256::exp10(8);
+        let usdc = U256::exp10(8);
+        assert!((gas_price_in_asset(eth, usdc, 6) - 2000.0 * 1e6 / 1e18).abs() < 1e-18);
+
+        // 0.01 ETH of gas is $20, i.e. 20 USDC
+        let gas = U256::exp10(16).to_string().parse::<f64>().unwrap();
+        assert!((gas * gas_price_in_asset(eth, usdc, 6) - 20.0e6).abs() < 1e-3);
+        // Priced in an 18-decimal asset worth the same as ETH, a wei is a wei
+        assert!((gas_price_in_asset(eth, eth, 18) - 1.0).abs() < 1e-12);
+        assert_eq!(gas_price_in_asset(eth, U256::zero(), 6), 0.0);
+
+        // USDC: 6 decimals in bits 48-55, next to an LTV of 77%
+        let configuration = (U256::from(6) << 48) | U256::from(7_700);
+        assert_eq!(reserve_decimals(configuration), 6);
+    }
+
+    #[test]
+    fn test_short_holding_period_is_unprofitable() {
+        type Router = MultiChainRouter<Provider<Http>>;
+        let amount = U256::from(10_000) * U256::exp10(6); // 10k USDC
+        let source = usdc_rate(1, 2.0);
+        let target = usdc_rate(10, 20.0);
+
+        // One hour of an 18% APY edge is worth ~$0.2, far below $40 of gas
+        let hourly = Router::calculate_route_profit(&source, &target, amount, 3600).unwrap();
+        assert!(hourly.is_zero());
+
+        // Held for a year the same route clears its gas costs: 10k * 18% - $40
+        let yearly = Router::calculate_route_profit(&source, &target, amount, 31_536_000).unwrap();
+        let expected = 1_800.0e6 - 40.0e6;
+        assert!((yearly.as_u128() as f64 - expected).abs() < 1e3);
+    }
+
+    #[test]
+    fn test_lower_target_apy_is_never_profitable() {
+        type Router = MultiChainRouter<Provider<Http>>;
+        let amount = U256::from(10_000) * U256::exp10(6);
+        let mut source = usdc_rate(1, 20.0);
+        let mut target = usdc_rate(10, 2.0);
+        source.estimated_gas_cost = U256::zero();
+        target.estimated_gas_cost = U256::zero();
+
+        let profit = Router::calculate_route_profit(&source, &target, amount, 31_536_000).unwrap();
+        assert!(profit.is_zero());
+    }
+
+    #[tokio::test]
+    async fn test_gather_rates_runs_chains_concurrently() {
+        // Every query waits for all three to start, so a sequential gather would time out
+        let barrier = Arc::new(Barrier::new(3));
+        let queries: Vec<_> = [(1u64, 3.0), (10, 4.5), (42161, 2.0)]
+            .into_iter()
+            .map(|(chain_id, apy)| {
+                let barrier = barrier.clone();
+                async move {
+                    barrier.wait().await;
+                    Ok(rate(chain_id, apy))
+                }
+            })
+            .collect();
+
+        let rates = tokio::time::timeout(Duration::from_secs(1), gather_rates(queries))
+            .await
+            .expect("rate queries did not run concurrently");
+
+        let mut chain_ids: Vec<u64> = rates.iter().map(|r| r.chain_id).collect();
+        chain_ids.sort();
+        assert_eq!(chain_ids, vec![1, 10, 42161]);
+    }
+
+    #[tokio::test]
+    async fn test_gather_rates_skips_failed_chains() {
+        let queries = vec![
+            Box::pin(async { Ok(rate(1, 3.0)) }) as std::pin::Pin<Box<dyn std::future::Future<Output = Result<RateInfo>>>>,
+            Box::pin(async { Err(anyhow::anyhow!("rpc unavailable")) }),
+        ];
+
+        let rates = gather_rates(queries).await;
+        assert_eq!(rates.len(), 1);
+        assert_eq!(rates[0].chain_id, 1);
+    }
+}
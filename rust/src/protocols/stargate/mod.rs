@@ -1,4 +1,5 @@
 mod config;
+mod protocol;
 
 pub use config::*;
-pub use super::stargate::*;
+pub use protocol::*;
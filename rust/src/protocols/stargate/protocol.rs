@@ -0,0 +1,244 @@
+use ethers::prelude::*;
+use std::sync::Arc;
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+
+// Stargate Router ABI functions we need. Human-readable entries must be single-line,
+// with the struct declared on its own so abigen emits `LzTxObj`.
+abigen!(
+    StargateRouter,
+    r#"[
+        struct lzTxObj { uint256 dstGasForCall; uint256 dstNativeAmount; bytes dstNativeAddr; }
+        function quoteLayerZeroFee(uint16 _dstChainId, uint8 _functionType, bytes calldata _toAddress, bytes calldata _transferAndCallPayload, lzTxObj memory _lzTxParams) external view returns (uint256, uint256)
+        function swap(uint16 _dstChainId, uint256 _srcPoolId, uint256 _dstPoolId, address _refundAddress, uint256 _amountLD, uint256 _minAmountLD, lzTxObj memory _lzTxParams, bytes calldata _to, bytes calldata _payload) external payable
+    ]"#
+);
+
+/// Router function type for `swap`, used when quoting LayerZero fees
+pub const TYPE_SWAP_REMOTE: u8 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StargateConfig {
+    pub router_address: Address,
+    pub pool_id: U256,
+    pub chain_id: u16,
+    pub native_gas_amount: U256,
+    pub gas_for_call: U256,
+}
+
+pub struct StargateProtocol<M: Middleware> {
+    config: StargateConfig,
+    router: StargateRouter<M>,
+    provider: Arc<M>,
+}
+
+impl<M: Middleware> StargateProtocol<M> {
+    pub fn new(
+        config: StargateConfig,
+        provider: Arc<M>,
+    ) -> Self {
+        let router = StargateRouter::new(config.router_address, provider.clone());
+        
+        Self {
+            config,
+            router,
+            provider,
+        }
+    }
+
+    /// Quote the LayerZero messaging fee for a swap to `dst_chain_id`.
+    /// Returns `(native_fee, zro_fee)`; the native fee must be sent as `msg.value`.
+    pub async fn quote_layer_zero_fee(
+        &self,
+        dst_chain_id: u16,
+        to: Address,
+        payload: &[u8],
+    ) -> Result<(U256, U256)> {
+        let fees = self.router
+            .quote_layer_zero_fee(
+                dst_chain_id,
+                TYPE_SWAP_REMOTE,
+                Self::encode_destination(to).into(),
+                payload.to_vec().into(),
+                self.lz_tx_params(to),
+            )
+            .call()
+            .await?;
+
+        Ok(fees)
+    }
+
+    pub async fn bridge_token(
+        &self,
+        dst_chain_id: u16,
+        src_pool_id: U256,
+        dst_pool_id: U256,
+        amount: U256,
+        min_amount: U256,
+        dst_wallet_addr: Address,
+        payload: Vec<u8>,
+    ) -> Result<TransactionReceipt> {
+        let tx = self.build_bridge_call(
+            dst_chain_id,
+            src_pool_id,
+            dst_pool_id,
+            amount,
+            min_amount,
+            dst_wallet_addr,
+            payload,
+        ).await?;
+
+        // Send transaction and wait for receipt
+        let receipt = tx
+            .send()
+            .await?
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Transaction failed"))?;
+
+        Ok(receipt)
+    }
+
+    /// Build the router `swap` call with the quoted LayerZero fee attached as value.
+    pub async fn build_bridge_call(
+        &self,
+        dst_chain_id: u16,
+        src_pool_id: U256,
+        dst_pool_id: U256,
+        amount: U256,
+        min_amount: U256,
+        dst_wallet_addr: Address,
+        payload: Vec<u8>,
+    ) -> Result<ContractCall<M, ()>> {
+        let (native_fee, _) = self
+            .quote_layer_zero_fee(dst_chain_id, dst_wallet_addr, &payload)
+            .await?;
+
+        // Get refund address (use sender's address)
+        let refund_address = self.provider.default_sender()
+            .ok_or_else(|| anyhow::anyhow!("No wallet address found"))?;
+
+        // Call Stargate Router swap function
+        let tx = self.router
+            .swap(
+                dst_chain_id,
+                src_pool_id,
+                dst_pool_id,
+                refund_address,
+                amount,
+                min_amount,
+                self.lz_tx_params(dst_wallet_addr),
+                Self::encode_destination(dst_wallet_addr).into(),
+                payload.into(),
+            )
+            .value(native_fee);
+
+        Ok(tx)
+    }
+
+    fn lz_tx_params(&self, dst_wallet_addr: Address) -> LzTxObj {
+        LzTxObj {
+            dst_gas_for_call: self.config.gas_for_call,
+            dst_native_amount: self.config.native_gas_amount,
+            dst_native_addr: dst_wallet_addr.as_bytes().to_vec().into(),
+        }
+    }
+
+    fn encode_destination(dst_wallet_addr: Address) -> Vec<u8> {
+        ethers::abi::encode(&[
+            ethers::abi::Token::Address(dst_wallet_addr)
+        ])
+    }
+
+    // Helper functions
+    pub fn get_router_address(&self) -> Address {
+        self.config.router_address
+    }
+
+    pub fn get_pool_id(&self) -> U256 {
+        self.config.pool_id
+    }
+
+    pub fn get_chain_id(&self) -> u16 {
+        self.config.chain_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::{AbiDecode, AbiEncode};
+
+    #[test]
+    fn test_swap_call_encodes_lz_tx_obj() {
+        let dst_wallet: Address = "0x2222222222222222222222222222222222222222".parse().unwrap();
+        let lz_tx_params = LzTxObj {
+            dst_gas_for_call: U256::from(200_000),
+            dst_native_amount: U256::zero(),
+            dst_native_addr: dst_wallet.as_bytes().to_vec().into(),
+        };
+
+        let call = SwapCall {
+            dst_chain_id: 110,
+            src_pool_id: U256::from(1),
+            dst_pool_id: U256::from(1),
+            refund_address: Address::random(),
+            amount_ld: U256::from(1_000_000_000u64),
+            min_amount_ld: U256::from(995_000_000u64),
+            lz_tx_params: lz_tx_params.clone(),
+            to: ethers::abi::encode(&[ethers::abi::Token::Address(dst_wallet)]).into(),
+            payload: Bytes::default(),
+        };
+
+        let encoded = call.clone().encode();
+        let decoded = SwapCall::decode(&encoded).unwrap();
+
+        assert_eq!(decoded.dst_chain_id, 110);
+        assert_eq!(decoded.min_amount_ld, U256::from(995_000_000u64));
+        assert_eq!(decoded.lz_tx_params.dst_gas_for_call, U256::from(200_000));
+        assert_eq!(decoded.lz_tx_params.dst_native_addr, lz_tx_params.dst_native_addr);
+        assert_eq!(decoded, call);
+    }
+
+    #[tokio::test]
+    async fn test_bridge_call_attaches_quoted_fee() {
+        let (provider, mock) = Provider::mocked();
+        let sender = Address::random();
+        let provider = Arc::new(provider.with_sender(sender));
+
+        let config = StargateConfig {
+            router_address: "0x8731d54E9D02c286767d56ac03e8037C07e01e98".parse().unwrap(),
+            pool_id: U256::from(1),
+            chain_id: 101,
+            native_gas_amount: U256::zero(),
+            gas_for_call: U256::zero(),
+        };
+        let stargate = StargateProtocol::new(config, provider);
+
+        // quoteLayerZeroFee returns (nativeFee, zroFee)
+        let native_fee = U256::from(1_234_567_890_000_000u64);
+        let response: Bytes = ethers::abi::encode(&[
+            ethers::abi::Token::Uint(native_fee),
+            ethers::abi::Token::Uint(U256::zero()),
+        ])
+        .into();
+        mock.push(response).unwrap();
+
+        let call = stargate
+            .build_bridge_call(
+                110,
+                U256::from(1),
+                U256::from(1),
+                U256::from(1_000_000u64),
+                U256::from(995_000u64),
+                Address::random(),
+                vec![],
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(call.tx.value(), Some(&native_fee));
+        let decoded = SwapCall::decode(call.tx.data().unwrap()).unwrap();
+        assert_eq!(decoded.refund_address, sender);
+        assert_eq!(decoded.dst_chain_id, 110);
+    }
+}
@@ -0,0 +1,717 @@
+use ethers::prelude::*;
+use ethers::abi::AbiEncode;
+use ethers::types::{transaction::eip2718::TypedTransaction, Address, Bytes, U256};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use anyhow::Result;
+use lazy_static::lazy_static;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+use crate::flashbot::mev_protection::ensure_public_send_allowed;
+
+pub const SWAP_DEADLINE_SECS: u64 = 300; // 5 minutes
+pub const DEFAULT_V3_FEE: u32 = 3000; // 0.3% fee tier
+pub const ONEINCH_API_URL: &str = "https://api.1inch.dev/swap/v5.2";
+/// EIP-2612 `Permit(address owner,address spender,uint256 value,uint256 nonce,uint256 deadline)`
+pub const PERMIT_TYPEHASH: &str = "Permit(address owner,address spender,uint256 value,uint256 nonce,uint256 deadline)";
+
+abigen!(
+    IUniswapV2Router,
+    r#"[
+        function swapExactTokensForTokens(uint256 amountIn, uint256 amountOutMin, address[] calldata path, address to, uint256 deadline) external returns (uint256[] memory amounts)
+    ]"#
+);
+
+abigen!(
+    IV3SwapRouter,
+    r#"[
+        function exactInputSingle(ExactInputSingleParams calldata params) external payable returns (uint256 amountOut)
+        struct ExactInputSingleParams { address tokenIn; address tokenOut; uint24 fee; address recipient; uint256 deadline; uint256 amountIn; uint256 amountOutMinimum; uint160 sqrtPriceLimitX96; }
+    ]"#
+);
+
+abigen!(
+    IV3SwapRouter02,
+    r#"[
+        function exactInputSingle(ExactInputSingleParams calldata params) external payable returns (uint256 amountOut)
+        struct ExactInputSingleParams { address tokenIn; address tokenOut; uint24 fee; address recipient; uint256 amountIn; uint256 amountOutMinimum; uint160 sqrtPriceLimitX96; }
+    ]"#
+);
+
+abigen!(
+    ISwapToken,
+    r#"[
+        function approve(address spender, uint256 amount) external returns (bool)
+        function allowance(address owner, address spender) external view returns (uint256)
+        function DOMAIN_SEPARATOR() external view returns (bytes32)
+        function nonces(address owner) external view returns (uint256)
+        function permit(address owner, address spender, uint256 value, uint256 deadline, uint8 v, bytes32 r, bytes32 s) external
+    ]"#
+);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SwapRouterConfig {
+    pub v2_router: Option<Address>,
+    pub v3_router: Option<Address>,
+    /// SwapRouter02 drops `deadline` from `ExactInputSingleParams`
+    pub v3_router02: bool,
+}
+
+lazy_static! {
+    pub static ref SWAP_ROUTERS: HashMap<u64, SwapRouterConfig> = {
+        let mut m = HashMap::new();
+
+        // Ethereum Mainnet (ChainID: 1)
+        m.insert(1, SwapRouterConfig {
+            v2_router: Some("0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D".parse().unwrap()),
+            v3_router: Some("0xE592427A0AEce92De3Edee1F18E0157C05861564".parse().unwrap()),
+            v3_router02: false,
+        });
+
+        // Polygon (ChainID: 137), V2 through QuickSwap
+        m.insert(137, SwapRouterConfig {
+            v2_router: Some("0xa5E0829CaCEd8fFDD4De3c43696c57F7D7A678ff".parse().unwrap()),
+            v3_router: Some("0xE592427A0AEce92De3Edee1F18E0157C05861564".parse().unwrap()),
+            v3_router02: false,
+        });
+
+        // Arbitrum (ChainID: 42161), V2 through SushiSwap
+        m.insert(42161, SwapRouterConfig {
+            v2_router: Some("0x1b02dA8Cb0d097eB8D57A175b88c7D8b47997506".parse().unwrap()),
+            v3_router: Some("0xE592427A0AEce92De3Edee1F18E0157C05861564".parse().unwrap()),
+            v3_router02: false,
+        });
+
+        // Optimism (ChainID: 10)
+        m.insert(10, SwapRouterConfig {
+            v2_router: Some("0x4A7b5Da61326A6379179b40d00F57E5bbDC962c2".parse().unwrap()),
+            v3_router: Some("0xE592427A0AEce92De3Edee1F18E0157C05861564".parse().unwrap()),
+            v3_router02: false,
+        });
+
+        // Base (ChainID: 8453)
+        m.insert(8453, SwapRouterConfig {
+            v2_router: None,
+            v3_router: Some("0x2626664c2603336E57B271c5C0b26F421741e481".parse().unwrap()),
+            v3_router02: true,
+        });
+
+        m
+    };
+}
+
+pub fn get_swap_router_config(chain_id: u64) -> Option<&'static SwapRouterConfig> {
+    SWAP_ROUTERS.get(&chain_id)
+}
+
+pub fn swap_deadline() -> U256 {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    U256::from(now + SWAP_DEADLINE_SECS)
+}
+
+pub fn build_v2_swap_calldata(
+    amount_in: U256,
+    min_amount_out: U256,
+    path: Vec<Address>,
+    recipient: Address,
+    deadline: U256,
+) -> Bytes {
+    SwapExactTokensForTokensCall {
+        amount_in,
+        amount_out_min: min_amount_out,
+        path,
+        to: recipient,
+        deadline,
+    }
+    .encode()
+    .into()
+}
+
+pub fn build_v3_swap_calldata(
+    config: &SwapRouterConfig,
+    token_in: Address,
+    token_out: Address,
+    fee: u32,
+    amount_in: U256,
+    min_amount_out: U256,
+    recipient: Address,
+    deadline: U256,
+) -> Bytes {
+    if config.v3_router02 {
+        i_v3_swap_router_02::ExactInputSingleCall {
+            params: i_v3_swap_router_02::ExactInputSingleParams {
+                token_in,
+                token_out,
+                fee,
+                recipient,
+                amount_in,
+                amount_out_minimum: min_amount_out,
+                sqrt_price_limit_x96: U256::zero(),
+            },
+        }
+        .encode()
+        .into()
+    } else {
+        i_v3_swap_router::ExactInputSingleCall {
+            params: i_v3_swap_router::ExactInputSingleParams {
+                token_in,
+                token_out,
+                fee,
+                recipient,
+                deadline,
+                amount_in,
+                amount_out_minimum: min_amount_out,
+                sqrt_price_limit_x96: U256::zero(),
+            },
+        }
+        .encode()
+        .into()
+    }
+}
+
+/// Allowances of one owner, read from chain once per `(token, spender)` and
+/// kept up to date as approvals land and swaps spend them, so an approval is
+/// only sent when the allowance falls short.
+pub struct ApprovalManager<M> {
+    client: Arc<M>,
+    owner: Address,
+    allowances: RwLock<HashMap<(Address, Address), U256>>,
+    /// Owner's key, to approve through a signed permit where tokens allow it
+    permit_signer: Option<LocalWallet>,
+    /// Refuse to send approvals, which go through the public mempool
+    private_only: bool,
+}
+
+/// A call that reverted or returned nothing, as it does on a token without
+/// the function, as opposed to one that never got an answer.
+fn is_unsupported_call<M: Middleware>(error: &ContractError<M>) -> bool {
+    error.is_revert() || matches!(error, ContractError::AbiError(_) | ContractError::DecodingError(_))
+}
+
+impl<M: Middleware + 'static> ApprovalManager<M> {
+    pub fn new(client: Arc<M>, owner: Address) -> Self {
+        Self {
+            client,
+            owner,
+            allowances: RwLock::new(HashMap::new()),
+            permit_signer: None,
+            private_only: false,
+        }
+    }
+
+    pub fn with_private_only(mut self, private_only: bool) -> Self {
+        self.private_only = private_only;
+        self
+    }
+
+    /// Approve through EIP-2612 permits signed by `wallet`, the owner, for
+    /// tokens that support them.
+    pub fn with_permit_signer(mut self, wallet: LocalWallet) -> Result<Self> {
+        if wallet.address() != self.owner {
+            return Err(anyhow::anyhow!(
+                "Permit signer {:?} is not the owner {:?}",
+                wallet.address(),
+                self.owner
+            ));
+        }
+        self.permit_signer = Some(wallet);
+        Ok(self)
+    }
+
+    pub fn owner(&self) -> Address {
+        self.owner
+    }
+
+    /// Allowance of `spender` over the owner's `token`.
+    pub async fn allowance(&self, token: Address, spender: Address) -> Result<U256> {
+        if let Some(allowance) = self.allowances.read().await.get(&(token, spender)) {
+            return Ok(*allowance);
+        }
+        let allowance = ISwapToken::new(token, self.client.clone())
+            .allowance(self.owner, spender)
+            .call()
+            .await?;
+        self.allowances.write().await.insert((token, spender), allowance);
+        Ok(allowance)
+    }
+
+    /// A transaction approving `spender` for the max amount if the allowance
+    /// doesn't cover `amount`: a signed `permit` when there's a permit signer
+    /// and the token supports it, else `approve`. Call `record_approval` once
+    /// it's mined.
+    pub async fn approval_tx(
+        &self,
+        token: Address,
+        spender: Address,
+        amount: U256,
+    ) -> Result<Option<TypedTransaction>> {
+        if self.allowance(token, spender).await? >= amount {
+            return Ok(None);
+        }
+        if let Some(wallet) = &self.permit_signer {
+            if let Some(permit) = self.build_permit(token, spender, U256::MAX, swap_deadline(), wallet).await? {
+                return Ok(Some(TransactionRequest::new().from(self.owner).to(token).data(permit).into()));
+            }
+        }
+        let approve = ISwapToken::new(token, self.client.clone())
+            .approve(spender, U256::MAX)
+            .from(self.owner);
+        Ok(Some(approve.tx))
+    }
+
+    pub async fn record_approval(&self, token: Address, spender: Address) {
+        self.allowances.write().await.insert((token, spender), U256::MAX);
+    }
+
+    /// `spender` pulled `amount` of `token`. Unlimited allowances stay unlimited.
+    pub async fn record_spend(&self, token: Address, spender: Address, amount: U256) {
+        if let Some(allowance) = self.allowances.write().await.get_mut(&(token, spender)) {
+            if *allowance != U256::MAX {
+                *allowance = allowance.saturating_sub(amount);
+            }
+        }
+    }
+
+    /// Signed EIP-2612 `permit` calldata for `token`, to bundle ahead of the
+    /// swap instead of an approval transaction. `None` for tokens whose
+    /// `DOMAIN_SEPARATOR` or `nonces` reverts or returns nothing; a call that
+    /// fails to get an answer is an error.
+    pub async fn build_permit(
+        &self,
+        token: Address,
+        spender: Address,
+        amount: U256,
+        deadline: U256,
+        wallet: &LocalWallet,
+    ) -> Result<Option<Bytes>> {
+        if wallet.address() != self.owner {
+            return Err(anyhow::anyhow!(
+                "Permit signer {:?} is not the owner {:?}",
+                wallet.address(),
+                self.owner
+            ));
+        }
+        let erc20 = ISwapToken::new(token, self.client.clone());
+        let domain_separator = match erc20.domain_separator().call().await {
+            Ok(domain_separator) => domain_separator,
+            Err(e) if is_unsupported_call(&e) => return Ok(None),
+            Err(e) => return Err(anyhow::anyhow!("DOMAIN_SEPARATOR of {:?} failed: {}", token, e)),
+        };
+        let nonce = match erc20.nonces(self.owner).call().await {
+            Ok(nonce) => nonce,
+            Err(e) if is_unsupported_call(&e) => return Ok(None),
+            Err(e) => return Err(anyhow::anyhow!("Permit nonce of {:?} failed: {}", token, e)),
+        };
+
+        let digest = permit_digest(domain_separator, self.owner, spender, amount, nonce, deadline);
+        let signature = wallet.sign_hash(digest)?;
+        let mut r = [0u8; 32];
+        let mut s = [0u8; 32];
+        signature.r.to_big_endian(&mut r);
+        signature.s.to_big_endian(&mut s);
+
+        Ok(Some(
+            PermitCall {
+                owner: self.owner,
+                spender,
+                value: amount,
+                deadline,
+                v: signature.v as u8,
+                r,
+                s,
+            }
+            .encode()
+            .into(),
+        ))
+    }
+
+    /// Send and wait for an approval if the allowance doesn't cover `amount`.
+    /// In private-only mode a missing allowance is an error.
+    pub async fn ensure(&self, token: Address, spender: Address, amount: U256) -> Result<()> {
+        let tx = match self.approval_tx(token, spender, amount).await? {
+            Some(tx) => tx,
+            None => return Ok(()),
+        };
+        ensure_public_send_allowed(self.private_only, "an approval")?;
+        self.client
+            .send_transaction(tx, None)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to send approval: {}", e))?
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Approval failed"))?;
+        self.record_approval(token, spender).await;
+        Ok(())
+    }
+}
+
+/// EIP-712 digest of a `Permit` under `domain_separator`.
+pub fn permit_digest(
+    domain_separator: [u8; 32],
+    owner: Address,
+    spender: Address,
+    value: U256,
+    nonce: U256,
+    deadline: U256,
+) -> H256 {
+    let struct_hash = ethers::utils::keccak256(ethers::abi::encode(&[
+        ethers::abi::Token::FixedBytes(ethers::utils::keccak256(PERMIT_TYPEHASH).to_vec()),
+        ethers::abi::Token::Address(owner),
+        ethers::abi::Token::Address(spender),
+        ethers::abi::Token::Uint(value),
+        ethers::abi::Token::Uint(nonce),
+        ethers::abi::Token::Uint(deadline),
+    ]));
+    let mut message = Vec::with_capacity(66);
+    message.extend_from_slice(&[0x19, 0x01]);
+    message.extend_from_slice(&domain_separator);
+    message.extend_from_slice(&struct_hash);
+    H256::from(ethers::utils::keccak256(message))
+}
+
+/// Send `calldata` to `router` through the public mempool and wait for the
+/// receipt. Fails in private-only mode.
+pub async fn send_swap<M: Middleware + 'static>(
+    provider: Arc<M>,
+    router: Address,
+    calldata: Bytes,
+    value: U256,
+    private_only: bool,
+) -> Result<TransactionReceipt> {
+    ensure_public_send_allowed(private_only, "a swap")?;
+    let tx = TransactionRequest::new().to(router).data(calldata).value(value);
+
+    let receipt = provider
+        .send_transaction(tx, None)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to send swap: {}", e))?
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Transaction failed"))?;
+
+    Ok(receipt)
+}
+
+#[derive(Debug, Clone)]
+pub struct OneInchSwap {
+    pub to: Address,
+    pub data: Bytes,
+    pub value: U256,
+    pub to_amount: U256,
+}
+
+/// Parse the `/swap` response of the 1inch aggregation API.
+pub fn parse_oneinch_swap(body: &serde_json::Value) -> Result<OneInchSwap> {
+    let tx = body.get("tx").ok_or_else(|| anyhow::anyhow!("1inch response missing tx"))?;
+    let field = |value: &serde_json::Value, key: &str| -> Result<String> {
+        value
+            .get(key)
+            .and_then(|v| v.as_str())
+            .map(|v| v.to_string())
+            .ok_or_else(|| anyhow::anyhow!("1inch response missing {}", key))
+    };
+
+    Ok(OneInchSwap {
+        to: field(tx, "to")?.parse()?,
+        data: field(tx, "data")?.parse()?,
+        value: U256::from_dec_str(&field(tx, "value")?)?,
+        to_amount: U256::from_dec_str(&field(body, "toAmount")?)?,
+    })
+}
+
+/// Quote and build a 1inch swap. Slippage is derived from the quote so the
+/// aggregator's own minimum return matches `min_amount_out`.
+pub async fn build_oneinch_swap(
+    chain_id: u64,
+    api_key: &str,
+    token_in: Address,
+    token_out: Address,
+    amount_in: U256,
+    min_amount_out: U256,
+    from: Address,
+) -> Result<OneInchSwap> {
+    let client = reqwest::Client::new();
+    let base = format!("{}/{}", ONEINCH_API_URL, chain_id);
+
+    let quote: serde_json::Value = client
+        .get(format!("{}/quote", base))
+        .bearer_auth(api_key)
+        .query(&[
+            ("src", format!("{:?}", token_in)),
+            ("dst", format!("{:?}", token_out)),
+            ("amount", amount_in.to_string()),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let quoted = quote
+        .get("toAmount")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow::anyhow!("1inch quote missing toAmount"))?;
+    let quoted = U256::from_dec_str(quoted)?;
+    if quoted < min_amount_out {
+        return Err(anyhow::anyhow!("1inch quote {} below minimum {}", quoted, min_amount_out));
+    }
+
+    // Slippage in percent with two decimals, as the API expects
+    let slippage_bps = if quoted.is_zero() {
+        U256::zero()
+    } else {
+        (quoted - min_amount_out) * U256::from(10_000) / quoted
+    };
+    let slippage = format!("{:.2}", slippage_bps.as_u64().min(5_000) as f64 / 100.0);
+
+    let body: serde_json::Value = client
+        .get(format!("{}/swap", base))
+        .bearer_auth(api_key)
+        .query(&[
+            ("src", format!("{:?}", token_in)),
+            ("dst", format!("{:?}", token_out)),
+            ("amount", amount_in.to_string()),
+            ("from", format!("{:?}", from)),
+            ("slippage", slippage),
+            ("disableEstimate", "true".to_string()),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    parse_oneinch_swap(&body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::AbiDecode;
+
+    #[test]
+    fn test_v2_swap_calldata() {
+        let token_in = Address::random();
+        let token_out = Address::random();
+        let recipient = Address::random();
+        let deadline = U256::from(1_700_000_300u64);
+
+        let calldata = build_v2_swap_calldata(
+            U256::from(1_000_000),
+            U256::from(990_000),
+            vec![token_in, token_out],
+            recipient,
+            deadline,
+        );
+
+        // swapExactTokensForTokens(uint256,uint256,address[],address,uint256)
+        assert_eq!(&calldata[..4], &[0x38, 0xed, 0x17, 0x39]);
+
+        let decoded = SwapExactTokensForTokensCall::decode(&calldata).unwrap();
+        assert_eq!(decoded.amount_in, U256::from(1_000_000));
+        assert_eq!(decoded.amount_out_min, U256::from(990_000));
+        assert_eq!(decoded.path, vec![token_in, token_out]);
+        assert_eq!(decoded.to, recipient);
+        assert_eq!(decoded.deadline, deadline);
+    }
+
+    #[test]
+    fn test_swap_deadline_is_in_the_future() {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let deadline = swap_deadline().as_u64();
+        assert!(deadline >= now + SWAP_DEADLINE_SECS);
+        assert!(deadline <= now + SWAP_DEADLINE_SECS + 5);
+    }
+
+    #[test]
+    fn test_v3_calldata_matches_router_version() {
+        let base = get_swap_router_config(8453).unwrap();
+        let mainnet = get_swap_router_config(1).unwrap();
+        let (a, b, to) = (Address::random(), Address::random(), Address::random());
+
+        let router02 = build_v3_swap_calldata(base, a, b, DEFAULT_V3_FEE, U256::one(), U256::one(), to, U256::one());
+        let router = build_v3_swap_calldata(mainnet, a, b, DEFAULT_V3_FEE, U256::one(), U256::one(), to, U256::one());
+
+        // Selector plus 7 vs 8 static words
+        assert_eq!(router02.len(), 4 + 7 * 32);
+        assert_eq!(router.len(), 4 + 8 * 32);
+    }
+
+    fn allowance_response(allowance: U256) -> Bytes {
+        ethers::abi::encode(&[ethers::abi::Token::Uint(allowance)]).into()
+    }
+
+    #[tokio::test]
+    async fn test_approved_pair_skips_approval() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(allowance_response(U256::MAX)).unwrap();
+        let approvals = ApprovalManager::new(Arc::new(provider), Address::random());
+        let (token, router) = (Address::random(), Address::random());
+
+        assert!(approvals.approval_tx(token, router, U256::from(1_000)).await.unwrap().is_none());
+        // Served from the cache: a second eth_call would find no response
+        assert!(approvals.approval_tx(token, router, U256::exp10(30)).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_private_only_refuses_approval_sends() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(allowance_response(U256::from(500))).unwrap();
+        mock.push(allowance_response(U256::MAX)).unwrap();
+        let approvals = ApprovalManager::new(Arc::new(provider), Address::random()).with_private_only(true);
+        let (approved, unapproved, router) = (Address::random(), Address::random(), Address::random());
+
+        // Nothing to send for a covered allowance
+        approvals.ensure(approved, router, U256::from(1_000)).await.unwrap();
+        let err = approvals.ensure(unapproved, router, U256::from(1_000)).await.unwrap_err();
+        assert!(err.to_string().contains("Private-only"), "{}", err);
+
+        let (provider, _) = Provider::mocked();
+        let err = send_swap(Arc::new(provider), router, Bytes::default(), U256::zero(), true)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Private-only"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_unapproved_pair_emits_approval() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(allowance_response(U256::from(500))).unwrap();
+        let owner = Address::random();
+        let approvals = ApprovalManager::new(Arc::new(provider), owner);
+        let (token, router) = (Address::random(), Address::random());
+
+        let tx = approvals.approval_tx(token, router, U256::from(1_000)).await.unwrap().unwrap();
+        assert_eq!(tx.to_addr(), Some(&token));
+        assert_eq!(tx.from(), Some(&owner));
+        let call = ApproveCall::decode(tx.data().unwrap()).unwrap();
+        assert_eq!(call.spender, router);
+        assert_eq!(call.amount, U256::MAX);
+
+        // Once mined, the pair counts as approved
+        approvals.record_approval(token, router).await;
+        assert!(approvals.approval_tx(token, router, U256::from(1_000)).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_spends_draw_down_limited_allowances() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(allowance_response(U256::from(1_500))).unwrap();
+        let approvals = ApprovalManager::new(Arc::new(provider), Address::random());
+        let (token, router) = (Address::random(), Address::random());
+
+        assert!(approvals.approval_tx(token, router, U256::from(1_000)).await.unwrap().is_none());
+        approvals.record_spend(token, router, U256::from(1_000)).await;
+        assert!(approvals.approval_tx(token, router, U256::from(1_000)).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_permit_recovers_to_wallet() {
+        let wallet = LocalWallet::new(&mut rand::thread_rng());
+        let domain_separator = [7u8; 32];
+        let (provider, mock) = Provider::mocked();
+        // Responses are served last in, first out: DOMAIN_SEPARATOR, then nonces
+        mock.push(allowance_response(U256::from(3))).unwrap();
+        mock.push(Bytes::from(domain_separator.to_vec())).unwrap();
+        let approvals = ApprovalManager::new(Arc::new(provider), wallet.address());
+        let (token, router) = (Address::random(), Address::random());
+        let deadline = U256::from(1_700_000_300u64);
+
+        let calldata = approvals
+            .build_permit(token, router, U256::from(1_000), deadline, &wallet)
+            .await
+            .unwrap()
+            .unwrap();
+        let permit = PermitCall::decode(&calldata).unwrap();
+        assert_eq!(permit.owner, wallet.address());
+        assert_eq!(permit.spender, router);
+        assert_eq!(permit.value, U256::from(1_000));
+        assert_eq!(permit.deadline, deadline);
+
+        let signature = Signature {
+            r: U256::from_big_endian(&permit.r),
+            s: U256::from_big_endian(&permit.s),
+            v: permit.v as u64,
+        };
+        let digest = permit_digest(domain_separator, wallet.address(), router, U256::from(1_000), U256::from(3), deadline);
+        assert_eq!(signature.recover(digest).unwrap(), wallet.address());
+    }
+
+    #[tokio::test]
+    async fn test_no_permit_for_plain_erc20() {
+        let wallet = LocalWallet::new(&mut rand::thread_rng());
+        // No DOMAIN_SEPARATOR: the fallback returns nothing
+        let (provider, mock) = Provider::mocked();
+        mock.push(Bytes::default()).unwrap();
+        let approvals = ApprovalManager::new(Arc::new(provider), wallet.address());
+
+        let permit = approvals
+            .build_permit(Address::random(), Address::random(), U256::one(), U256::MAX, &wallet)
+            .await
+            .unwrap();
+        assert!(permit.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_permit_lookup_transport_error_propagates() {
+        let wallet = LocalWallet::new(&mut rand::thread_rng());
+        // Nothing queued: the call never gets an answer
+        let (provider, _mock) = Provider::mocked();
+        let approvals = ApprovalManager::new(Arc::new(provider), wallet.address());
+
+        assert!(approvals
+            .build_permit(Address::random(), Address::random(), U256::one(), U256::MAX, &wallet)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_approval_uses_permit_when_supported() {
+        let wallet = LocalWallet::new(&mut rand::thread_rng());
+        let (provider, mock) = Provider::mocked();
+        // Served last in, first out: the allowance, DOMAIN_SEPARATOR, then nonces
+        mock.push(allowance_response(U256::zero())).unwrap();
+        mock.push(Bytes::from([7u8; 32].to_vec())).unwrap();
+        mock.push(allowance_response(U256::zero())).unwrap();
+        let approvals = ApprovalManager::new(Arc::new(provider), wallet.address())
+            .with_permit_signer(wallet.clone())
+            .unwrap();
+        let (token, router) = (Address::random(), Address::random());
+
+        let tx = approvals.approval_tx(token, router, U256::from(1_000)).await.unwrap().unwrap();
+        assert_eq!(tx.to_addr(), Some(&token));
+        let permit = PermitCall::decode(tx.data().unwrap()).unwrap();
+        assert_eq!(permit.spender, router);
+        assert_eq!(permit.value, U256::MAX);
+
+        let stranger = LocalWallet::new(&mut rand::thread_rng());
+        let (provider, _mock) = Provider::mocked();
+        assert!(ApprovalManager::new(Arc::new(provider), wallet.address())
+            .with_permit_signer(stranger)
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_oneinch_swap() {
+        let body = serde_json::json!({
+            "toAmount": "995000",
+            "tx": {
+                "from": "0x1111111111111111111111111111111111111111",
+                "to": "0x1111111254eeb25477b68fb85ed929f73a960582",
+                "data": "0x12aa3caf",
+                "value": "0",
+                "gas": 0,
+                "gasPrice": "1000000000"
+            }
+        });
+
+        let swap = parse_oneinch_swap(&body).unwrap();
+        assert_eq!(swap.to_amount, U256::from(995_000));
+        assert_eq!(swap.value, U256::zero());
+        assert_eq!(swap.data, Bytes::from(vec![0x12, 0xaa, 0x3c, 0xaf]));
+    }
+}
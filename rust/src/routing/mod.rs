@@ -4,9 +4,13 @@ use log::{info, warn, error};
 use std::collections::{HashMap, HashSet};
 use crate::pools::Pool;
 use crate::security::{SecurityManager, SecurityConfig};
+use crate::utils::to_usd;
 
 const MAX_HOPS: usize = 4;
-const MIN_PROFIT_THRESHOLD: u64 = 1_000_000; // $1 in USDC (6 decimals)
+const MIN_PROFIT_USD: f64 = 1.0;
+// Path profits are denominated in the USDC base token
+const PROFIT_TOKEN_DECIMALS: u8 = 6;
+const PROFIT_TOKEN_PRICE_USD: f64 = 1.0;
 const MAX_IMPACT_THRESHOLD: u64 = 300; // 3% max price impact
 
 #[derive(Debug, Clone)]
@@ -20,6 +24,7 @@ pub struct Path {
 
 pub struct PathFinder {
     max_hops: usize,
+    /// Minimum profit in 18-decimal USD
     min_profit: U256,
     max_impact: u64,
     visited_pairs: HashSet<(Address, Address)>,
@@ -31,7 +36,7 @@ impl PathFinder {
         let security = Arc::new(SecurityManager::new(SecurityConfig::default()));
         Self {
             max_hops: MAX_HOPS,
-            min_profit: U256::from(MIN_PROFIT_THRESHOLD),
+            min_profit: to_usd(U256::one(), 0, MIN_PROFIT_USD),
             max_impact: MAX_IMPACT_THRESHOLD,
             visited_pairs: HashSet::new(),
             security,
@@ -156,7 +161,7 @@ impl PathFinder {
         let (profit, impact) = self.simulate_path(&tokens, amount)?;
         
         // Check profitability
-        if profit < self.min_profit {
+        if to_usd(profit, PROFIT_TOKEN_DECIMALS, PROFIT_TOKEN_PRICE_USD) < self.min_profit {
             return Ok(None);
         }
         
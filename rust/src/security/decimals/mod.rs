@@ -0,0 +1,69 @@
+use anyhow::Result;
+use ethers::{providers::Middleware, types::Address};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::sync::RwLock;
+
+use crate::security::{rpc_call, ERC20, TOKEN_METADATA};
+
+/// Token decimals shared across managers, seeded from `TOKEN_METADATA` and
+/// filled from `decimals()` the first time an unknown token is seen.
+pub struct TokenInfoCache<M> {
+    client: Arc<M>,
+    rpc_timeout: Duration,
+    decimals: RwLock<HashMap<Address, u8>>,
+}
+
+impl<M: Middleware + 'static> TokenInfoCache<M> {
+    pub fn new(client: Arc<M>, rpc_timeout: Duration) -> Self {
+        let decimals = TOKEN_METADATA
+            .iter()
+            .filter_map(|(address, _, decimals)| Some((address.parse().ok()?, *decimals)))
+            .collect();
+        Self {
+            client,
+            rpc_timeout,
+            decimals: RwLock::new(decimals),
+        }
+    }
+
+    pub async fn get_decimals(&self, token: Address) -> Result<u8> {
+        if let Some(decimals) = self.decimals.read().await.get(&token) {
+            return Ok(*decimals);
+        }
+
+        let erc20 = ERC20::new(token, self.client.clone());
+        let decimals = rpc_call(self.rpc_timeout, erc20.decimals().call()).await?;
+        self.decimals.write().await.insert(token, decimals);
+        Ok(decimals)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::DEFAULT_RPC_TIMEOUT;
+    use ethers::{abi::Token, providers::Provider, types::{Bytes, U256}};
+
+    #[tokio::test]
+    async fn test_known_token_is_cached() {
+        // No responses are queued, so any RPC would fail
+        let (provider, _mock) = Provider::mocked();
+        let cache = TokenInfoCache::new(Arc::new(provider), DEFAULT_RPC_TIMEOUT);
+
+        let wbtc = "0x2260FAC5E5542a773Aa44fBCfeDf7C193bc2C599".parse().unwrap();
+        assert_eq!(cache.get_decimals(wbtc).await.unwrap(), 8);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_token_is_fetched_once() {
+        let (provider, mock) = Provider::mocked();
+        let response: Bytes = ethers::abi::encode(&[Token::Uint(U256::from(9))]).into();
+        mock.push(response).unwrap();
+        let cache = TokenInfoCache::new(Arc::new(provider), DEFAULT_RPC_TIMEOUT);
+
+        let token = Address::random();
+        assert_eq!(cache.get_decimals(token).await.unwrap(), 9);
+        // Served from the cache; a second eth_call would find no response
+        assert_eq!(cache.get_decimals(token).await.unwrap(), 9);
+    }
+}
@@ -1,8 +1,10 @@
 use anyhow::{anyhow, Result};
 use ethers::{
-    types::{Address, U256, H256, BlockNumber},
-    providers::{Provider, Http},
-    contract::{Contract, abigen},
+    abi::Token,
+    types::{Address, Filter, Log, I256, U256, U512, H256, BlockNumber},
+    providers::{Middleware, Provider, Http},
+    contract::{Contract, Multicall, abigen},
+    utils::keccak256,
 };
 use std::{sync::Arc, time::{Duration, SystemTime}, collections::HashMap};
 use tokio::sync::RwLock;
@@ -31,6 +33,35 @@ pub const MIN_HOLDERS: u64 = 1000;
 /// Minimum trading volume in USD
 pub const MIN_VOLUME_USD: u64 = 100_000;
 
+/// Maximum age of cached token metadata before re-validation (24 hours)
+pub const METADATA_CACHE_TTL_SECS: u64 = 86_400;
+
+/// Where the token metadata cache is persisted between runs
+pub const METADATA_CACHE_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/.cached-metadata.json");
+
+/// Longest a single RPC call made by the security managers may take
+pub const DEFAULT_RPC_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// An RPC call didn't answer in time. Recoverable: the pool or token itself
+/// may be fine, so callers can `downcast_ref` for it and retry later.
+#[derive(Debug, thiserror::Error)]
+#[error("RPC call timed out after {0:?}")]
+pub struct RpcTimeout(pub Duration);
+
+/// Await an RPC-bound future, giving up with `RpcTimeout` after `limit`.
+pub async fn rpc_call<T, E>(
+    limit: Duration,
+    call: impl std::future::Future<Output = std::result::Result<T, E>>,
+) -> Result<T>
+where
+    E: Into<anyhow::Error>,
+{
+    match tokio::time::timeout(limit, call).await {
+        Ok(result) => result.map_err(Into::into),
+        Err(_) => Err(RpcTimeout(limit).into()),
+    }
+}
+
 /// Price oracle interface for common stablecoins
 const USDC_ADDRESS: &str = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48";
 const USDT_ADDRESS: &str = "0xdAC17F958D2ee523a2206206994597C13D831ec7";
@@ -162,6 +193,56 @@ pub struct SecurityConfig {
     pub trusted_creators: Vec<Address>,
     /// Etherscan API key
     pub etherscan_api_key: String,
+    /// Seconds a cached metadata entry stays valid after `last_updated`
+    pub metadata_cache_ttl: u64,
+    /// Uniswap V3 subgraph used for pool volume
+    pub subgraph: SubgraphConfig,
+    /// Limit on each RPC call made while pricing and validating
+    pub rpc_timeout: Duration,
+}
+
+/// Uniswap V3 subgraph on The Graph's decentralized network
+pub const UNISWAP_V3_SUBGRAPH_ID: &str = "5zvR82QoaXYFyDEKLZ9t6v9adgnptxYpKpSbxtgVENFV";
+/// Env var holding The Graph gateway API key
+pub const SUBGRAPH_API_KEY_ENV: &str = "THEGRAPH_API_KEY";
+/// Blocks of `Swap` logs summed when the subgraph is unreachable (~24h)
+pub const ONCHAIN_VOLUME_BLOCKS: u64 = 7200;
+
+#[derive(Debug, Clone)]
+pub struct SubgraphConfig {
+    /// GraphQL endpoint, including the API key for gateway URLs
+    pub url: String,
+    /// Attempts after the first before falling back to on-chain volume
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each one after
+    pub backoff_base: Duration,
+}
+
+impl SubgraphConfig {
+    /// Uniswap V3 subgraph through the decentralized-network gateway
+    pub fn gateway(api_key: &str) -> Self {
+        Self {
+            url: format!(
+                "https://gateway.thegraph.com/api/{}/subgraphs/id/{}",
+                api_key, UNISWAP_V3_SUBGRAPH_ID
+            ),
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for SubgraphConfig {
+    fn default() -> Self {
+        Self {
+            url: format!(
+                "https://gateway.thegraph.com/api/{}/subgraphs/id/{}",
+                std::env::var(SUBGRAPH_API_KEY_ENV).unwrap_or_default(),
+                UNISWAP_V3_SUBGRAPH_ID
+            ),
+            max_retries: 3,
+            backoff_base: Duration::from_millis(500),
+        }
+    }
 }
 
 impl Default for SecurityConfig {
@@ -187,6 +268,9 @@ impl Default for SecurityConfig {
                 Address::from_slice(&hex::decode("1111111111111111111111111111111111111111").unwrap()),
             ],
             etherscan_api_key: "YOUR_API_KEY".to_string(),
+            metadata_cache_ttl: METADATA_CACHE_TTL_SECS,
+            subgraph: SubgraphConfig::default(),
+            rpc_timeout: DEFAULT_RPC_TIMEOUT,
         }
     }
 }
@@ -208,11 +292,13 @@ pub struct Pool {
     pub fee: u64,
 }
 
+mod decimals;
 mod price;
 mod token;
 mod twap;
 mod types;
 
+pub use decimals::TokenInfoCache;
 pub use price::PriceManager;
 pub use token::TokenManager;
 pub use twap::TWAPManager;
@@ -221,26 +307,141 @@ pub use types::*;
 use anyhow::Result;
 use ethers::types::Address;
 use std::sync::Arc;
+use std::{fs, path::{Path, PathBuf}};
+use crate::constants::{multicall3_address, record_transfer_flag, BLACKLIST_PATH};
 use crate::dex::DexPool;
+use crate::streams::V3_SWAP_EVENT;
 
 pub struct SecurityManager {
     price_manager: Arc<PriceManager>,
     token_manager: Arc<TokenManager>,
     twap_manager: Arc<TWAPManager>,
+    token_cache: Arc<TokenInfoCache<Provider<Http>>>,
+    config: SecurityConfig,
+    /// Blacklist file tokens flagged by the transfer probe are added to
+    blacklist_path: PathBuf,
+    /// Fresh validations with the time they were run, reused for `metadata_cache_ttl`
+    validations: RwLock<HashMap<Address, (u64, TokenValidation)>>,
 }
 
 impl SecurityManager {
     pub fn new() -> Self {
+        let config = SecurityConfig::default();
+        let client = Provider::<Http>::try_from("https://eth-mainnet.alchemyapi.io/v2/your-api-key").unwrap();
+        let token_cache = Arc::new(TokenInfoCache::new(Arc::new(client), config.rpc_timeout));
         Self {
-            price_manager: Arc::new(PriceManager::new()),
-            token_manager: Arc::new(TokenManager::new()),
-            twap_manager: Arc::new(TWAPManager::new()),
+            price_manager: Arc::new(PriceManager::new(token_cache.clone(), config.rpc_timeout)),
+            token_manager: Arc::new(
+                TokenManager::new(config.rpc_timeout).with_etherscan_api_key(config.etherscan_api_key.clone()),
+            ),
+            twap_manager: Arc::new(TWAPManager::new(config.rpc_timeout)),
+            token_cache,
+            config,
+            blacklist_path: PathBuf::from(BLACKLIST_PATH),
+            validations: RwLock::new(HashMap::new()),
         }
     }
 
+    pub fn with_blacklist_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.blacklist_path = path.into();
+        self
+    }
+
+    /// Decimals cache shared with the other managers
+    pub fn token_cache(&self) -> Arc<TokenInfoCache<Provider<Http>>> {
+        self.token_cache.clone()
+    }
+
+    pub fn blacklisted_tokens(&self) -> &[Address] {
+        &self.config.blacklisted_tokens
+    }
+
     /// Validate token and get its metadata
     pub async fn validate_token(&self, token: Address) -> Result<TokenValidation> {
-        self.token_manager.validate_token(token).await
+        // Skip re-validation for tokens with fresh cached metadata
+        if let Some(metadata) = self.get_cached_metadata(token).await {
+            return Ok(TokenValidation {
+                is_valid: metadata.blacklist_reason.is_none(),
+                reason: metadata
+                    .blacklist_reason
+                    .unwrap_or_else(|| "Cached metadata".to_string()),
+                ..Default::default()
+            });
+        }
+
+        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?.as_secs();
+        if let Some((validated_at, validation)) = self.validations.read().await.get(&token) {
+            if now.saturating_sub(*validated_at) <= self.config.metadata_cache_ttl {
+                return Ok(validation.clone());
+            }
+        }
+
+        // Errors aren't cached, so a flaky RPC doesn't stick to the token
+        let validation = self.token_manager.validate_token(token).await?;
+        // Keep flagged tokens out of future runs as soon as the probe catches them
+        if let Err(e) = record_transfer_flag(&self.blacklist_path, token, &validation) {
+            warn!("Failed to blacklist {:?}: {}", token, e);
+        }
+        self.validations.write().await.insert(token, (now, validation.clone()));
+        Ok(validation)
+    }
+
+    /// Get cached metadata for a token if it is still within the TTL
+    pub async fn get_cached_metadata(&self, token: Address) -> Option<TokenMetadata> {
+        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).ok()?.as_secs();
+        let cache = self.config.token_metadata.read().await;
+        cache
+            .get(&token)
+            .filter(|m| is_metadata_fresh(m, self.config.metadata_cache_ttl, now))
+            .cloned()
+    }
+
+    /// Load token metadata from a JSON cache file, dropping expired entries.
+    /// Returns the number of entries loaded. A missing file is not an error.
+    pub async fn load_metadata_cache(&self, path: impl AsRef<Path>) -> Result<usize> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(0);
+        }
+
+        let json = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read metadata cache {:?}: {}", path, e))?;
+        let entries: HashMap<Address, TokenMetadata> = serde_json::from_str(&json)
+            .map_err(|e| anyhow!("Failed to parse metadata cache {:?}: {}", path, e))?;
+
+        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?.as_secs();
+        let ttl = self.config.metadata_cache_ttl;
+
+        let mut cache = self.config.token_metadata.write().await;
+        let mut loaded = 0;
+        for (token, metadata) in entries {
+            if is_metadata_fresh(&metadata, ttl, now) {
+                cache.insert(token, metadata);
+                loaded += 1;
+            }
+        }
+
+        info!("Loaded {} token metadata entries from {:?}", loaded, path);
+        Ok(loaded)
+    }
+
+    /// Persist the non-expired token metadata cache to a JSON file
+    pub async fn persist_metadata_cache(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?.as_secs();
+        let ttl = self.config.metadata_cache_ttl;
+
+        let cache = self.config.token_metadata.read().await;
+        let fresh: HashMap<&Address, &TokenMetadata> = cache
+            .iter()
+            .filter(|(_, m)| is_metadata_fresh(m, ttl, now))
+            .collect();
+
+        let json = serde_json::to_string_pretty(&fresh)?;
+        fs::write(path, json)
+            .map_err(|e| anyhow!("Failed to write metadata cache {:?}: {}", path, e))?;
+
+        Ok(())
     }
 
     /// Get TWAP price for a token
@@ -265,15 +466,35 @@ impl SecurityManager {
     }
 }
 
+/// Check whether cached metadata is younger than `ttl` seconds
+fn is_metadata_fresh(metadata: &TokenMetadata, ttl: u64, now: u64) -> bool {
+    now.saturating_sub(metadata.last_updated) <= ttl
+}
+
 /// Normalize token amount to 18 decimals
 fn normalize_to_18_decimals(amount: U256, token_decimals: u8) -> U256 {
     if token_decimals == 18 {
         amount
     } else if token_decimals < 18 {
-        amount.saturating_mul(U256::exp10(18 - token_decimals))
+        amount.saturating_mul(U256::exp10((18 - token_decimals) as usize))
     } else {
-        amount.saturating_div(U256::exp10(token_decimals - 18))
+        amount.saturating_div(U256::exp10((token_decimals - 18) as usize))
+    }
+}
+
+/// USD price (18 decimals) of a token from its pool balance against a stablecoin's
+pub fn balance_price_usd(
+    token_balance: U256,
+    token_decimals: u8,
+    stable_balance: U256,
+    stable_decimals: u8,
+) -> Option<U256> {
+    let token_balance = normalize_to_18_decimals(token_balance, token_decimals);
+    if token_balance.is_zero() {
+        return None;
     }
+    let stable_balance = normalize_to_18_decimals(stable_balance, stable_decimals);
+    U256::try_from(stable_balance.full_mul(U256::exp10(18)) / U512::from(token_balance)).ok()
 }
 
 // Generate type-safe contract bindings
@@ -377,6 +598,89 @@ abigen!(
     ]"#,
 );
 
+/// State of an existing V3 pool needed to value its liquidity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct V3PoolSnapshot {
+    pub address: Address,
+    pub fee: u32,
+    pub liquidity: u128,
+    pub sqrt_price_x96: U256,
+}
+
+/// Look up the `token`/`paired_tokens` pools in every fee tier and read the
+/// liquidity and slot0 of those that exist, in two round-trips of at most
+/// `rpc_timeout` each to `chain_id`'s Multicall3.
+pub async fn fetch_uniswap_v3_pools<M: Middleware + 'static>(
+    client: Arc<M>,
+    chain_id: u64,
+    factory: Address,
+    token: Address,
+    paired_tokens: &[Address],
+    fee_tiers: &[u32],
+    rpc_timeout: Duration,
+) -> Result<Vec<V3PoolSnapshot>> {
+    let factory = UniswapV3Factory::new(factory, client.clone());
+
+    let candidates: Vec<(Address, Address, u32)> = paired_tokens
+        .iter()
+        .filter(|paired| **paired != token)
+        .flat_map(|paired| {
+            let (token_a, token_b) = (std::cmp::min(token, *paired), std::cmp::max(token, *paired));
+            fee_tiers.iter().map(move |fee| (token_a, token_b, *fee))
+        })
+        .collect();
+    if candidates.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let multicall_address = multicall3_address(chain_id)
+        .ok_or_else(|| anyhow!("No Multicall3 deployment known for chain {}", chain_id))?;
+    let mut multicall = Multicall::new(client.clone(), Some(multicall_address)).await?;
+    for (token_a, token_b, fee) in &candidates {
+        multicall.add_call(factory.get_pool(*token_a, *token_b, *fee), false);
+    }
+    let pool_addrs: Vec<Address> = rpc_call(rpc_timeout, multicall.call_array()).await?;
+
+    let existing: Vec<(Address, u32)> = candidates
+        .iter()
+        .zip(pool_addrs)
+        .filter(|(_, pool)| !pool.is_zero())
+        .map(|((_, _, fee), pool)| (pool, *fee))
+        .collect();
+    if existing.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    multicall.clear_calls();
+    for (pool, _) in &existing {
+        let pool = UniswapV3Pool::new(*pool, client.clone());
+        // Allow failure so one broken pool doesn't sink the batch
+        multicall.add_call(pool.liquidity(), true);
+        multicall.add_call(pool.slot0(), true);
+    }
+    let results = rpc_call(rpc_timeout, multicall.call_raw()).await?;
+
+    let snapshots = existing
+        .iter()
+        .zip(results.chunks(2))
+        .filter_map(|((address, fee), result)| {
+            let liquidity = result[0].clone().ok()?.into_uint()?;
+            let sqrt_price_x96 = match result.get(1)?.clone().ok()? {
+                Token::Tuple(slot0) => slot0.first()?.clone().into_uint()?,
+                _ => return None,
+            };
+            Some(V3PoolSnapshot {
+                address: *address,
+                fee: *fee,
+                liquidity: liquidity.as_u128(),
+                sqrt_price_x96,
+            })
+        })
+        .collect();
+
+    Ok(snapshots)
+}
+
 #[derive(Debug, Clone)]
 pub enum DexType {
     UniswapV2,
@@ -458,28 +762,34 @@ impl SecurityManager {
         Ok(pools)
     }
 
-    /// Get price from Uniswap V3 pool
+    async fn token_decimals(&self, token: Address) -> Result<u8> {
+        self.token_cache.get_decimals(token).await
+    }
+
+    /// Get price from Uniswap V3 pool, if it pairs `token` with a stablecoin
     async fn get_uniswap_v3_price(&self, pool: &DexPool, token: Address) -> Result<Option<PriceSource>> {
         let client = Provider::<Http>::try_from("https://eth-mainnet.alchemyapi.io/v2/your-api-key")?;
         let pool_contract = UniswapV3Pool::new(pool.address, client.clone());
 
         // Get current price from slot0
-        let (sqrt_price_x96, _, _, _, _, _, _) = pool_contract.slot0().call().await?;
-        
-        // Convert sqrtPriceX96 to price
-        let price_x96 = U256::from(sqrt_price_x96)
-            .saturating_mul(U256::from(sqrt_price_x96))
-            .checked_div(U256::from(1u128 << 96))
-            .ok_or_else(|| anyhow!("Price calculation overflow"))?;
+        let (sqrt_price_x96, _, _, _, _, _, _) = rpc_call(self.config.rpc_timeout, pool_contract.slot0().call()).await?;
 
-        let is_token0 = token == pool_contract.token0().call().await?;
-        let price = if is_token0 {
-            price_x96
-        } else {
-            U256::from(1u128 << 96)
-                .checked_div(price_x96)
-                .ok_or_else(|| anyhow!("Price inversion overflow"))?
-        };
+        let token0 = rpc_call(self.config.rpc_timeout, pool_contract.token0().call()).await?;
+        let token1 = rpc_call(self.config.rpc_timeout, pool_contract.token1().call()).await?;
+        let is_token0 = token == token0;
+        let quote = if is_token0 { token1 } else { token0 };
+        if !self.is_usd_token(quote) {
+            return Ok(None);
+        }
+
+        // Price in the stablecoin is the USD price
+        let price = price::sqrt_price_x96_to_price(
+            sqrt_price_x96,
+            self.token_decimals(token0).await?,
+            self.token_decimals(token1).await?,
+            is_token0,
+        )
+        .ok_or_else(|| anyhow!("Price calculation overflow"))?;
 
         Ok(Some(PriceSource {
             price,
@@ -493,12 +803,12 @@ impl SecurityManager {
     async fn get_balancer_price(&self, pool: &DexPool, token: Address) -> Result<Option<PriceSource>> {
         let client = Provider::<Http>::try_from("https://eth-mainnet.alchemyapi.io/v2/your-api-key")?;
         let pool_contract = BalancerPool::new(pool.address, client.clone());
-        let vault_address = pool_contract.get_vault().call().await?;
+        let vault_address = rpc_call(self.config.rpc_timeout, pool_contract.get_vault().call()).await?;
         let vault = BalancerVault::new(vault_address, client);
 
         // Get pool tokens and balances
-        let pool_id = pool_contract.get_pool_id().call().await?;
-        let (tokens, balances, _) = vault.get_pool_tokens(pool_id).call().await?;
+        let pool_id = rpc_call(self.config.rpc_timeout, pool_contract.get_pool_id().call()).await?;
+        let (tokens, balances, _) = rpc_call(self.config.rpc_timeout, vault.get_pool_tokens(pool_id).call()).await?;
 
         // Find token index and stable index
         let token_idx = tokens.iter().position(|&t| t == token)
@@ -510,10 +820,13 @@ impl SecurityManager {
         }).ok_or_else(|| anyhow!("No stablecoin found in pool"))?;
 
         // Calculate price based on balances
-        let price = U256::from(balances[stable_idx])
-            .saturating_mul(U256::exp10(18))
-            .checked_div(U256::from(balances[token_idx]))
-            .ok_or_else(|| anyhow!("Price calculation overflow"))?;
+        let price = balance_price_usd(
+            balances[token_idx],
+            self.token_decimals(token).await?,
+            balances[stable_idx],
+            self.token_decimals(tokens[stable_idx]).await?,
+        )
+        .ok_or_else(|| anyhow!("Price calculation overflow"))?;
 
         Ok(Some(PriceSource {
             price,
@@ -532,7 +845,7 @@ impl SecurityManager {
         let mut token_idx = None;
         let mut stable_idx = None;
         for i in 0..8 { // Curve pools can have up to 8 tokens
-            if let Ok(coin) = pool_contract.coins(U256::from(i)).call().await {
+            if let Ok(coin) = rpc_call(self.config.rpc_timeout, pool_contract.coins(U256::from(i)).call()).await {
                 if coin == token {
                     token_idx = Some(i);
                 }
@@ -547,20 +860,16 @@ impl SecurityManager {
         }
 
         if let (Some(token_i), Some(stable_i)) = (token_idx, stable_idx) {
-            // Get price using get_dy
-            let virtual_price = pool_contract.get_virtual_price().call().await?;
-            let dy = pool_contract
-                .get_dy(
-                    token_i as i128,
-                    stable_i as i128,
-                    U256::exp10(18)
-                )
-                .call()
-                .await?;
+            // Stablecoin received for one whole token
+            let stable = rpc_call(self.config.rpc_timeout, pool_contract.coins(U256::from(stable_i)).call()).await?;
+            let dx = U256::exp10(self.token_decimals(token).await? as usize);
+            let dy = rpc_call(
+                self.config.rpc_timeout,
+                pool_contract.get_dy(token_i as i128, stable_i as i128, dx).call(),
+            )
+            .await?;
 
-            let price = dy.saturating_mul(virtual_price)
-                .checked_div(U256::exp10(18))
-                .ok_or_else(|| anyhow!("Price calculation overflow"))?;
+            let price = normalize_to_18_decimals(dy, self.token_decimals(stable).await?);
 
             Ok(Some(PriceSource {
                 price,
@@ -577,15 +886,10 @@ impl SecurityManager {
     async fn find_uniswap_v3_pools(&self, token: Address) -> Result<Vec<DexPool>> {
         let mut pools = Vec::new();
         let client = Arc::new(Provider::<Http>::try_from("https://eth-mainnet.alchemyapi.io/v2/your-api-key")?);
-        
-        // Initialize Uniswap V3 factory
-        let factory = UniswapV3Factory::new(
-            Address::from_slice(&hex::decode("1F98431c8aD98523631AE4a59f267346ea31F984").unwrap()),
-            client.clone()
-        );
+        let factory = Address::from_slice(&hex::decode("1F98431c8aD98523631AE4a59f267346ea31F984").unwrap());
 
         // Common paired tokens to check
-        let paired_tokens = [
+        let paired_tokens: Vec<Address> = [
             // Stablecoins
             ("USDC", "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"),
             ("USDT", "0xdAC17F958D2ee523a2206206994597C13D831ec7"),
@@ -593,64 +897,46 @@ impl SecurityManager {
             // Major tokens
             ("WETH", "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"),
             ("WBTC", "0x2260FAC5E5542a773Aa44fBCfeDf7C193bc2C599"),
-        ];
+        ]
+        .iter()
+        .map(|(_, paired)| Address::from_slice(&hex::decode(paired.trim_start_matches("0x")).unwrap()))
+        .collect();
 
         // Fee tiers to check (0.01%, 0.05%, 0.3%, 1%)
         let fee_tiers = [100, 500, 3000, 10000];
 
-        // Batch pool queries for efficiency
-        let mut pool_promises = Vec::new();
-
-        for (_, paired_token) in paired_tokens.iter() {
-            let paired_addr = Address::from_slice(&hex::decode(paired_token.trim_start_matches("0x")).unwrap());
-            
-            for &fee in fee_tiers.iter() {
-                let factory_clone = factory.clone();
-                let token_a = std::cmp::min(token, paired_addr);
-                let token_b = std::cmp::max(token, paired_addr);
-                
-                pool_promises.push(tokio::spawn(async move {
-                    let pool_addr = factory_clone.get_pool(token_a, token_b, fee).call().await?;
-                    if pool_addr != Address::zero() {
-                        // Initialize pool contract
-                        let pool = UniswapV3Pool::new(pool_addr, Arc::new(client.clone()));
-                        
-                        // Get pool data
-                        let liquidity = pool.liquidity().call().await?;
-                        let (sqrt_price_x96, _, _, _, _, _, _) = pool.slot0().call().await?;
-                        
-                        Ok::<_, Error>((pool_addr, liquidity, sqrt_price_x96, fee))
-                    } else {
-                        Err(anyhow!("Pool does not exist"))
-                    }
-                }));
-            }
-        }
-
-        // Process pool results
-        for promise in pool_promises {
-            if let Ok(Ok((pool_addr, liquidity, sqrt_price_x96, fee))) = promise.await {
-                // Calculate pool liquidity in USD
-                let total_liquidity = self.calculate_v3_liquidity(
-                    pool_addr,
-                    U256::from(liquidity),
-                    sqrt_price_x96,
-                    fee
-                ).await?;
-
-                // Get 24h volume from subgraph
-                let volume_24h = self.get_v3_volume(pool_addr).await?;
-
-                // Only add pools with sufficient liquidity
-                if total_liquidity > U256::from(50_000) * U256::exp10(18) { // $50k min liquidity
-                    pools.push(DexPool {
-                        address: pool_addr,
-                        dex_type: DexType::UniswapV3,
-                        tokens: vec![token], // Add paired token
-                        liquidity_usd: total_liquidity,
-                        volume_24h,
-                    });
-                }
+        let snapshots = fetch_uniswap_v3_pools(
+            client,
+            1,
+            factory,
+            token,
+            &paired_tokens,
+            &fee_tiers,
+            self.config.rpc_timeout,
+        )
+        .await?;
+
+        for snapshot in snapshots {
+            // Calculate pool liquidity in USD
+            let total_liquidity = self.calculate_v3_liquidity(
+                snapshot.address,
+                U256::from(snapshot.liquidity),
+                snapshot.sqrt_price_x96,
+                snapshot.fee
+            ).await?;
+
+            // Get 24h volume from subgraph
+            let volume_24h = self.get_v3_volume(snapshot.address).await?;
+
+            // Only add pools with sufficient liquidity
+            if total_liquidity > U256::from(50_000) * U256::exp10(18) { // $50k min liquidity
+                pools.push(DexPool {
+                    address: snapshot.address,
+                    dex_type: DexType::UniswapV3,
+                    tokens: vec![token], // Add paired token
+                    liquidity_usd: total_liquidity,
+                    volume_24h,
+                });
             }
         }
 
@@ -672,8 +958,8 @@ impl SecurityManager {
         let pool_contract = UniswapV3Pool::new(pool, client.clone());
 
         // Get tokens
-        let token0 = pool_contract.token0().call().await?;
-        let token1 = pool_contract.token1().call().await?;
+        let token0 = rpc_call(self.config.rpc_timeout, pool_contract.token0().call()).await?;
+        let token1 = rpc_call(self.config.rpc_timeout, pool_contract.token1().call()).await?;
 
         // Get token prices
         let price0 = self.get_token_price(token0).await?;
@@ -710,43 +996,97 @@ impl SecurityManager {
         Ok(value0.saturating_add(value1))
     }
 
-    /// Get 24h volume for Uniswap V3 pool from subgraph
+    /// Get 24h volume for Uniswap V3 pool, from the subgraph or else `Swap` logs
     async fn get_v3_volume(&self, pool: Address) -> Result<U256> {
-        // Query the Uniswap V3 subgraph
-        let query = format!(
-            r#"{{
-                pool(id: "{:?}") {{
-                    volumeUSD
-                }}
-            }}"#,
-            pool
-        );
+        match query_v3_volume(&self.config.subgraph, pool).await {
+            Ok(volume) => Ok(volume),
+            Err(e) => {
+                warn!("Subgraph volume for {:?} unavailable, using on-chain estimate: {}", pool, e);
+                self.estimate_v3_volume_onchain(pool).await
+            }
+        }
+    }
 
-        let client = reqwest::Client::new();
-        let res = client
-            .post("https://api.thegraph.com/subgraphs/name/uniswap/uniswap-v3")
-            .json(&json!({
-                "query": query
-            }))
-            .send()
-            .await?
-            .json::<Value>()
-            .await?;
+    /// Sum the token0 side of the pool's recent swaps, valued in USD
+    async fn estimate_v3_volume_onchain(&self, pool: Address) -> Result<U256> {
+        let client = Arc::new(Provider::<Http>::try_from("https://eth-mainnet.alchemyapi.io/v2/your-api-key")?);
+        let latest = rpc_call(self.config.rpc_timeout, client.get_block_number()).await?.as_u64();
+
+        let filter = Filter::new()
+            .address(pool)
+            .topic0(H256::from(keccak256(V3_SWAP_EVENT)))
+            .from_block(latest.saturating_sub(ONCHAIN_VOLUME_BLOCKS))
+            .to_block(latest);
+        let logs = rpc_call(self.config.rpc_timeout, client.get_logs(&filter)).await?;
+
+        let pool_contract = UniswapV3Pool::new(pool, client);
+        let token0 = rpc_call(self.config.rpc_timeout, pool_contract.token0().call()).await?;
+        let price = self.get_token_price(token0).await?;
+        Ok(swap_volume_usd(&logs, price.price_usd, price.decimals))
+    }
+}
 
-        // Parse volume from response
-        let volume = res
-            .get("data")
-            .and_then(|d| d.get("pool"))
-            .and_then(|p| p.get("volumeUSD"))
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| anyhow!("Failed to get volume from subgraph"))?;
+/// 24h `volumeUSD` of `pool` with 18 decimals, retrying with exponential backoff.
+pub async fn query_v3_volume(config: &SubgraphConfig, pool: Address) -> Result<U256> {
+    let query = format!(
+        r#"{{
+            pool(id: "{:?}") {{
+                volumeUSD
+            }}
+        }}"#,
+        pool
+    );
+    let client = reqwest::Client::new();
+
+    let mut attempt = 0;
+    loop {
+        let result = async {
+            let res = client
+                .post(&config.url)
+                .json(&serde_json::json!({ "query": query }))
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<serde_json::Value>()
+                .await?;
 
-        // Convert volume string to U256
-        let volume_float: f64 = volume.parse()?;
-        Ok(U256::from((volume_float * 1e18) as u64))
+            let volume = res
+                .get("data")
+                .and_then(|d| d.get("pool"))
+                .and_then(|p| p.get("volumeUSD"))
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| anyhow!("Failed to get volume from subgraph: {}", res))?;
+
+            let volume_float: f64 = volume.parse()?;
+            Ok::<_, anyhow::Error>(U256::from((volume_float * 1e18) as u128))
+        }
+        .await;
+
+        match result {
+            Ok(volume) => return Ok(volume),
+            Err(e) if attempt < config.max_retries => {
+                attempt += 1;
+                let backoff = config.backoff_base * 2u32.saturating_pow(attempt - 1);
+                warn!("Subgraph query failed: {}, retrying in {:?}", e, backoff);
+                tokio::time::sleep(backoff).await;
+            }
+            Err(e) => return Err(e),
+        }
     }
 }
 
+/// USD value (18 decimals) of the token0 amounts moved by V3 `Swap` logs.
+pub fn swap_volume_usd(logs: &[Log], price_usd: U256, decimals: u8) -> U256 {
+    let swap_topic = H256::from(keccak256(V3_SWAP_EVENT));
+    let amount0 = logs
+        .iter()
+        .filter(|log| log.topics.first() == Some(&swap_topic) && log.data.len() >= 32)
+        .map(|log| I256::from_raw(U256::from_big_endian(&log.data[..32])).unsigned_abs())
+        .fold(U256::zero(), |total, amount| total.saturating_add(amount));
+
+    amount0.saturating_mul(price_usd) / U256::exp10(decimals as usize)
+}
+
 #[derive(Debug, Clone)]
 pub enum DexType {
     UniswapV2,
@@ -774,7 +1114,7 @@ impl SecurityManager {
         let pool_contract = UniswapV3Pool::new(pool.address, client.clone());
 
         // Get current state and validate pool health
-        let (sqrt_price_x96, tick, _, _, _, fee_protocol, _) = pool_contract.slot0().call().await?;
+        let (sqrt_price_x96, tick, _, _, _, fee_protocol, _) = rpc_call(self.config.rpc_timeout, pool_contract.slot0().call()).await?;
         
         // Validate pool is active
         if sqrt_price_x96.is_zero() {
@@ -782,8 +1122,8 @@ impl SecurityManager {
         }
 
         // Get pool tokens and validate
-        let token0 = pool_contract.token0().call().await?;
-        let token1 = pool_contract.token1().call().await?;
+        let token0 = rpc_call(self.config.rpc_timeout, pool_contract.token0().call()).await?;
+        let token1 = rpc_call(self.config.rpc_timeout, pool_contract.token1().call()).await?;
         let (base_token, quote_token) = if token == token0 {
             (token0, token1)
         } else if token == token1 {
@@ -797,7 +1137,7 @@ impl SecurityManager {
         let seconds_ago: Vec<u32> = self.get_twap_observation_times(now)?;
         
         // Get observations
-        let (ticks, initialized) = pool_contract.observe(seconds_ago.clone()).call().await?;
+        let (ticks, initialized) = rpc_call(self.config.rpc_timeout, pool_contract.observe(seconds_ago.clone()).call()).await?;
         
         // Validate observations
         if !self.validate_observations(&initialized)? {
@@ -805,7 +1145,7 @@ impl SecurityManager {
         }
 
         // Calculate TWAP with cardinality checking
-        let cardinality = pool_contract.observation_cardinality().call().await?;
+        let cardinality = rpc_call(self.config.rpc_timeout, pool_contract.observation_cardinality().call()).await?;
         if cardinality < MIN_TWAP_CARDINALITY {
             return Ok(None); // Not enough historical data
         }
@@ -947,21 +1287,13 @@ impl SecurityManager {
 /// Price source with weight and timestamp
 #[derive(Debug, Clone)]
 pub struct PriceSource {
+    /// USD price of one whole token, with 18 decimals, whatever the source
     pub price: U256,
     pub weight: u32,
     pub timestamp: u64,
     pub source: String,
 }
 
-/// Token validation result
-#[derive(Debug, Clone)]
-pub struct TokenValidation {
-    pub is_valid: bool,
-    pub has_transfer_fee: bool,
-    pub has_transfer_restrictions: bool,
-    pub error: Option<String>,
-}
-
 /// TWAP data with timestamp and sample count
 #[derive(Debug, Clone)]
 pub struct TWAPData {
@@ -996,3 +1328,305 @@ struct ContractData {
     malicious_patterns: Vec<String>,
     last_updated: u64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::providers::{JsonRpcClient, MockError};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn sample_metadata(last_updated: u64) -> TokenMetadata {
+        TokenMetadata {
+            created_at: 1_600_000_000,
+            holder_count: 5000,
+            volume_24h: 1_000_000,
+            price_usd: 1.0,
+            last_updated,
+            blacklist_reason: None,
+            is_verified: true,
+            source_hash: Some("abc".to_string()),
+        }
+    }
+
+    fn now() -> u64 {
+        SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs()
+    }
+
+    #[tokio::test]
+    async fn test_metadata_cache_round_trip() {
+        let path = std::env::temp_dir().join(format!("metadata-cache-{}.json", Address::random()));
+        let token = Address::random();
+
+        let manager = SecurityManager::new();
+        manager.config.token_metadata.write().await.insert(token, sample_metadata(now()));
+        manager.persist_metadata_cache(&path).await.unwrap();
+
+        let restored = SecurityManager::new();
+        assert_eq!(restored.load_metadata_cache(&path).await.unwrap(), 1);
+
+        let metadata = restored.get_cached_metadata(token).await.unwrap();
+        assert_eq!(metadata.holder_count, 5000);
+        assert_eq!(metadata.source_hash.as_deref(), Some("abc"));
+
+        fs::remove_file(&path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_metadata_cache_ttl_invalidation() {
+        let path = std::env::temp_dir().join(format!("metadata-cache-{}.json", Address::random()));
+        let fresh = Address::random();
+        let stale = Address::random();
+
+        let mut entries = HashMap::new();
+        entries.insert(fresh, sample_metadata(now()));
+        entries.insert(stale, sample_metadata(now() - METADATA_CACHE_TTL_SECS - 1));
+        fs::write(&path, serde_json::to_string(&entries).unwrap()).unwrap();
+
+        let manager = SecurityManager::new();
+        assert_eq!(manager.load_metadata_cache(&path).await.unwrap(), 1);
+        assert!(manager.get_cached_metadata(fresh).await.is_some());
+        assert!(manager.get_cached_metadata(stale).await.is_none());
+
+        fs::remove_file(&path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_missing_metadata_cache_is_empty() {
+        let manager = SecurityManager::new();
+        let path = std::env::temp_dir().join("does-not-exist-metadata-cache.json");
+        assert_eq!(manager.load_metadata_cache(&path).await.unwrap(), 0);
+    }
+
+    /// Return data of a Multicall3 `aggregate3` whose calls all succeeded.
+    fn aggregate3_response(results: Vec<Vec<Token>>) -> ethers::types::Bytes {
+        let results = results
+            .into_iter()
+            .map(|tokens| Token::Tuple(vec![Token::Bool(true), Token::Bytes(ethers::abi::encode(&tokens))]))
+            .collect();
+        ethers::abi::encode(&[Token::Array(results)]).into()
+    }
+
+    fn slot0(sqrt_price_x96: U256) -> Vec<Token> {
+        vec![
+            Token::Uint(sqrt_price_x96),
+            Token::Int(U256::zero()),
+            Token::Uint(U256::zero()),
+            Token::Uint(U256::one()),
+            Token::Uint(U256::one()),
+            Token::Uint(U256::zero()),
+            Token::Bool(true),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_v3_pool_discovery_is_batched() {
+        let (provider, mock) = Provider::mocked();
+        let (token, usdc, weth) = (Address::random(), Address::random(), Address::random());
+        let (usdc_pool, weth_pool) = (Address::random(), Address::random());
+
+        // Only two responses are queued, so anything beyond two round-trips
+        // fails; the old per-pool calls would have taken 2 * 2 * 3 + 2.
+        // Responses are popped last-in first-out.
+        mock.push(aggregate3_response(vec![
+            vec![Token::Uint(U256::from(1_000u64))],
+            slot0(U256::from(1u64) << 96),
+            vec![Token::Uint(U256::from(2_000u64))],
+            slot0(U256::from(2u64) << 96),
+        ]))
+        .unwrap();
+        // (usdc, 500), (usdc, 3000), (weth, 500), (weth, 3000)
+        mock.push(aggregate3_response(vec![
+            vec![Token::Address(Address::zero())],
+            vec![Token::Address(usdc_pool)],
+            vec![Token::Address(weth_pool)],
+            vec![Token::Address(Address::zero())],
+        ]))
+        .unwrap();
+
+        let pools = fetch_uniswap_v3_pools(
+            Arc::new(provider),
+            1,
+            Address::random(),
+            token,
+            &[usdc, weth],
+            &[500, 3000],
+            DEFAULT_RPC_TIMEOUT,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            pools,
+            vec![
+                V3PoolSnapshot {
+                    address: usdc_pool,
+                    fee: 3000,
+                    liquidity: 1_000,
+                    sqrt_price_x96: U256::from(1u64) << 96,
+                },
+                V3PoolSnapshot {
+                    address: weth_pool,
+                    fee: 500,
+                    liquidity: 2_000,
+                    sqrt_price_x96: U256::from(2u64) << 96,
+                },
+            ]
+        );
+    }
+
+    /// Serve `responses` as (status, body) in order, repeating the last one.
+    async fn mock_subgraph(responses: Vec<(u16, &'static str)>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let hits = Arc::new(AtomicUsize::new(0));
+
+        let counter = hits.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = match listener.accept().await {
+                    Ok(conn) => conn,
+                    Err(_) => return,
+                };
+                let mut buf = [0u8; 4096];
+                let _ = socket.read(&mut buf).await;
+
+                let hit = counter.fetch_add(1, Ordering::SeqCst);
+                let (status, body) = responses[hit.min(responses.len() - 1)];
+                let response = format!(
+                    "HTTP/1.1 {} OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+
+        (url, hits)
+    }
+
+    fn subgraph_config(url: String, max_retries: u32) -> SubgraphConfig {
+        SubgraphConfig {
+            url,
+            max_retries,
+            backoff_base: Duration::from_millis(1),
+        }
+    }
+
+    const VOLUME_RESPONSE: &str = r#"{"data":{"pool":{"volumeUSD":"1250000.5"}}}"#;
+
+    #[tokio::test]
+    async fn test_subgraph_volume() {
+        let (url, hits) = mock_subgraph(vec![(200, VOLUME_RESPONSE)]).await;
+
+        let volume = query_v3_volume(&subgraph_config(url, 3), Address::random()).await.unwrap();
+        assert_eq!(volume, U256::from(1_250_000_500_000_000_000_000_000u128));
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_subgraph_volume_retries_errors() {
+        let (url, hits) = mock_subgraph(vec![
+            (500, r#"{"error":"internal"}"#),
+            (200, r#"{"errors":[{"message":"indexer unavailable"}]}"#),
+            (200, VOLUME_RESPONSE),
+        ])
+        .await;
+
+        let volume = query_v3_volume(&subgraph_config(url, 3), Address::random()).await.unwrap();
+        assert_eq!(volume, U256::from(1_250_000_500_000_000_000_000_000u128));
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_subgraph_falls_back_to_swap_logs() {
+        let (url, hits) = mock_subgraph(vec![(503, "{}")]).await;
+
+        assert!(query_v3_volume(&subgraph_config(url, 2), Address::random()).await.is_err());
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+
+        // The fallback sums |amount0| of each swap: 1000 USDC in, then 400 out
+        let swap = |amount0: i64| Log {
+            topics: vec![H256::from(keccak256(V3_SWAP_EVENT)), H256::random(), H256::random()],
+            data: ethers::abi::encode(&[
+                Token::Int(I256::from(amount0).into_raw()),
+                Token::Int(I256::from(-amount0).into_raw()),
+                Token::Uint(U256::one() << 96),
+                Token::Uint(U256::from(1_000u64)),
+                Token::Int(U256::zero()),
+            ])
+            .into(),
+            ..Default::default()
+        };
+        let logs = vec![swap(1_000_000_000), swap(-400_000_000), Log::default()];
+
+        let volume = swap_volume_usd(&logs, U256::exp10(18), 6);
+        assert_eq!(volume, U256::from(1_400) * U256::exp10(18));
+    }
+
+    /// Transport that never answers, like a hung RPC node.
+    #[derive(Debug)]
+    struct StalledClient;
+
+    #[async_trait::async_trait]
+    impl JsonRpcClient for StalledClient {
+        type Error = MockError;
+
+        async fn request<T, R>(&self, _method: &str, _params: T) -> std::result::Result<R, MockError>
+        where
+            T: std::fmt::Debug + Serialize + Send + Sync,
+            R: serde::de::DeserializeOwned + Send,
+        {
+            std::future::pending().await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stalled_rpc_times_out() {
+        let client = Arc::new(Provider::new(StalledClient));
+        let timeout = Duration::from_millis(50);
+
+        let err = fetch_uniswap_v3_pools(
+            client.clone(),
+            1,
+            Address::random(),
+            Address::random(),
+            &[Address::random()],
+            &[3000],
+            timeout,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err.downcast_ref::<RpcTimeout>(), Some(RpcTimeout(t)) if *t == timeout));
+
+        let cache = TokenInfoCache::new(client, timeout);
+        let err = cache.get_decimals(Address::random()).await.unwrap_err();
+        assert!(err.downcast_ref::<RpcTimeout>().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_v3_pool_discovery_can_be_spawned() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(aggregate3_response(vec![vec![Token::Address(Address::zero())]]))
+            .unwrap();
+
+        // tokio::spawn only accepts the future if it is Send
+        let (token, paired) = (Address::random(), Address::random());
+        let handle = tokio::spawn(async move {
+            fetch_uniswap_v3_pools(
+                Arc::new(provider),
+                1,
+                Address::random(),
+                token,
+                &[paired],
+                &[3000],
+                DEFAULT_RPC_TIMEOUT,
+            )
+            .await
+        });
+
+        assert!(handle.await.unwrap().unwrap().is_empty());
+    }
+}
@@ -1,18 +1,47 @@
 use anyhow::{Result, anyhow};
 use ethers::{
     providers::{Provider, Http},
-    types::{U256, Address},
+    types::{U256, U512, Address},
 };
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 use crate::security::types::PriceSource;
+use crate::security::{
+    balance_price_usd, rpc_call, BalancerPool, BalancerVault, TokenInfoCache, UniswapV3Pool,
+};
 use crate::dex::DexPool;
 
+/// Price of one whole token0 in token1 (or the reverse when `base_is_token0`
+/// is false) as an 18-decimal number: `sqrtPriceX96^2 / 2^192`, adjusted for
+/// the decimals of both tokens.
+pub fn sqrt_price_x96_to_price(
+    sqrt_price_x96: U256,
+    decimals0: u8,
+    decimals1: u8,
+    base_is_token0: bool,
+) -> Option<U256> {
+    let sqrt_price = U512::from(sqrt_price_x96);
+    let ratio_x192 = sqrt_price * sqrt_price;
+    let q192 = U512::one() << 192;
+
+    let (numerator, denominator) = if base_is_token0 {
+        (ratio_x192 * U512::exp10(18 + decimals0 as usize), q192 * U512::exp10(decimals1 as usize))
+    } else {
+        (q192 * U512::exp10(18 + decimals1 as usize), ratio_x192 * U512::exp10(decimals0 as usize))
+    };
+    if denominator.is_zero() {
+        return None;
+    }
+    U256::try_from(numerator / denominator).ok()
+}
+
 pub struct PriceManager {
     usd_tokens: Vec<Address>,
+    token_cache: Arc<TokenInfoCache<Provider<Http>>>,
+    rpc_timeout: Duration,
 }
 
 impl PriceManager {
-    pub fn new() -> Self {
+    pub fn new(token_cache: Arc<TokenInfoCache<Provider<Http>>>, rpc_timeout: Duration) -> Self {
         // Initialize with known USD-based tokens
         let usd_tokens = vec![
             "A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48", // USDC
@@ -22,7 +51,7 @@ impl PriceManager {
          .map(|addr| Address::from_slice(&hex::decode(addr).unwrap()))
          .collect();
 
-        Self { usd_tokens }
+        Self { usd_tokens, token_cache, rpc_timeout }
     }
 
     /// Get price from Uniswap V3 pool
@@ -31,23 +60,27 @@ impl PriceManager {
         let pool_contract = UniswapV3Pool::new(pool.address, client.clone());
 
         // Get current price from slot0
-        let (sqrt_price_x96, _, _, _, _, _, _) = pool_contract.slot0().call().await?;
+        let (sqrt_price_x96, _, _, _, _, _, _) = rpc_call(self.rpc_timeout, pool_contract.slot0().call()).await?;
         
         if sqrt_price_x96.is_zero() {
             return Ok(None);
         }
 
-        // Get tokens
-        let token0 = pool_contract.token0().call().await?;
-        
-        // Calculate price based on token order
-        let price = if token == token0 {
-            sqrt_price_x96
-        } else {
-            U256::from(1u128 << 96)
-                .checked_div(sqrt_price_x96)
-                .ok_or_else(|| anyhow!("Price inversion overflow"))?
-        };
+        // Get tokens; only a stablecoin quote gives a USD price
+        let token0 = rpc_call(self.rpc_timeout, pool_contract.token0().call()).await?;
+        let token1 = rpc_call(self.rpc_timeout, pool_contract.token1().call()).await?;
+        let is_token0 = token == token0;
+        if !self.is_usd_token(if is_token0 { token1 } else { token0 }) {
+            return Ok(None);
+        }
+
+        let price = sqrt_price_x96_to_price(
+            sqrt_price_x96,
+            self.token_cache.get_decimals(token0).await?,
+            self.token_cache.get_decimals(token1).await?,
+            is_token0,
+        )
+        .ok_or_else(|| anyhow!("Price conversion overflow"))?;
 
         Ok(Some(PriceSource {
             price,
@@ -60,27 +93,31 @@ impl PriceManager {
     pub async fn get_balancer_price(&self, pool: &DexPool, token: Address) -> Result<Option<PriceSource>> {
         let client = Provider::<Http>::try_from("https://eth-mainnet.alchemyapi.io/v2/your-api-key")?;
         let pool_contract = BalancerPool::new(pool.address, client.clone());
-        let vault_address = pool_contract.get_vault().call().await?;
-        let vault = BalancerVault::new(vault_address, client);
+        let vault_address = rpc_call(self.rpc_timeout, pool_contract.get_vault().call()).await?;
+        let vault = BalancerVault::new(vault_address, client.clone());
 
         // Get pool tokens and balances
-        let pool_id = pool_contract.get_pool_id().call().await?;
-        let (tokens, balances, _) = vault.get_pool_tokens(pool_id).call().await?;
+        let pool_id = rpc_call(self.rpc_timeout, pool_contract.get_pool_id().call()).await?;
+        let (tokens, balances, _) = rpc_call(self.rpc_timeout, vault.get_pool_tokens(pool_id).call()).await?;
 
-        // Find token index
+        // Find token index and a stablecoin to quote it in
         let token_index = tokens.iter().position(|&t| t == token)
             .ok_or_else(|| anyhow!("Token not found in pool"))?;
+        let stable_index = match tokens.iter().position(|&t| self.is_usd_token(t)) {
+            Some(index) => index,
+            None => return Ok(None),
+        };
 
-        // Get spot price
-        let spot_price = pool_contract.get_spot_price(
-            tokens[token_index],
-            tokens[1 - token_index],
+        let price = balance_price_usd(
             balances[token_index],
-            balances[1 - token_index],
-        ).call().await?;
+            self.token_cache.get_decimals(token).await?,
+            balances[stable_index],
+            self.token_cache.get_decimals(tokens[stable_index]).await?,
+        )
+        .ok_or_else(|| anyhow!("Price calculation overflow"))?;
 
         Ok(Some(PriceSource {
-            price: spot_price,
+            price,
             weight: 0.8, // Lower weight due to potential manipulation
             source: "Balancer".to_string(),
         }))
@@ -91,3 +128,64 @@ impl PriceManager {
         self.usd_tokens.contains(&token)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// USDC/WETH 0.05% pool (token0 USDC, token1 WETH) with ETH at ~$2000.
+    const USDC_WETH_SQRT_PRICE_X96: u128 = 1_771_595_571_142_957_166_518_320_255_467_520;
+
+    #[test]
+    fn test_sqrt_price_x96_to_price() {
+        let sqrt_price = U256::from(USDC_WETH_SQRT_PRICE_X96);
+
+        // 1 USDC = 0.0005 WETH
+        let usdc_in_weth = sqrt_price_x96_to_price(sqrt_price, 6, 18, true).unwrap();
+        assert_eq!(usdc_in_weth, U256::from(500_000_000_000_000u64));
+
+        // 1 WETH = 2000 USDC, less the rounding of the sqrt price
+        let weth_in_usdc = sqrt_price_x96_to_price(sqrt_price, 6, 18, false).unwrap();
+        assert_eq!(weth_in_usdc, U256::from(1_999_999_999_999_999_856_497u128));
+    }
+
+    #[test]
+    fn test_sqrt_price_x96_to_price_without_overflow() {
+        // Largest uint160 sqrt price still converts in 512-bit math
+        let max_sqrt_price = (U256::one() << 160) - 1;
+        assert!(sqrt_price_x96_to_price(max_sqrt_price, 18, 18, true).is_some());
+        assert!(sqrt_price_x96_to_price(max_sqrt_price, 18, 18, false).is_some());
+        assert!(sqrt_price_x96_to_price(U256::zero(), 18, 18, false).is_none());
+    }
+
+    #[test]
+    fn test_prices_share_18_decimal_usd_scale() {
+        // WBTC/USDC V3 pool (token0 WBTC) at $60,000
+        let sqrt_price = U256::from_dec_str("1940685714182491852533977682922").unwrap();
+        let wbtc_v3 = sqrt_price_x96_to_price(sqrt_price, 8, 6, true).unwrap();
+        assert_eq!(wbtc_v3 / U256::exp10(18), U256::from(59_999));
+
+        // Balancer pool holding 10 WBTC against 600,000 USDC
+        let wbtc_balancer = balance_price_usd(
+            U256::from(10) * U256::exp10(8),
+            8,
+            U256::from(600_000) * U256::exp10(6),
+            6,
+        )
+        .unwrap();
+        assert_eq!(wbtc_balancer, U256::from(60_000) * U256::exp10(18));
+
+        // USDC against DAI in an even pool is $1, not 10^12 off
+        let usdc_balancer = balance_price_usd(
+            U256::from(1_000_000) * U256::exp10(6),
+            6,
+            U256::from(1_000_000) * U256::exp10(18),
+            18,
+        )
+        .unwrap();
+        assert_eq!(usdc_balancer, U256::exp10(18));
+
+        // Both sources agree on WBTC to within a dollar
+        assert!(wbtc_balancer - wbtc_v3 < U256::exp10(18));
+    }
+}
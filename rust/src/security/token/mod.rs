@@ -1,26 +1,325 @@
 use anyhow::{Result, anyhow};
 use ethers::{
-    providers::{Provider, Http},
-    types::{U256, Address},
+    abi::{self, ParamType, Token},
+    providers::{
+        call_raw::{spoof, RawCall},
+        Provider, ProviderError, Http, JsonRpcClient, Middleware, RpcError,
+    },
+    types::{transaction::eip2718::TypedTransaction, Address, Bytes, TransactionRequest, H256, U256, U512},
+    utils::keccak256,
 };
-use std::{sync::Arc, time::SystemTime};
+use log::warn;
+use std::{collections::HashMap, str::FromStr, sync::Arc, time::{Duration, SystemTime}};
+use tokio::sync::RwLock;
+use crate::security::{rpc_call, MIN_TOKEN_AGE_DAYS};
 use crate::security::types::{TokenValidation, VolumeData, HolderData, ContractData};
 
+/// Runtime code placed at a holder's address to probe a transfer. Takes
+/// `(token, to, amount)`, calls `token.transfer(to, amount)` as the holder and
+/// returns `(bool success, uint256 balanceOf(to))`:
+///
+/// ```text
+/// mstore(0, 0xa9059cbb << 224) mstore(4, to) mstore(36, amount)
+/// mstore(0x80, call(gas(), token, 0, 0, 68, 0, 32))
+/// mstore(0, 0x70a08231 << 224) mstore(4, to)
+/// pop(staticcall(gas(), token, 0, 36, 0xa0, 32))
+/// return(0x80, 64)
+/// ```
+const TRANSFER_PROBE_CODE: &str = "0x63a9059cbb60e01b60005260203560045260403560245260206000604460006000600035\
+5af16080526370a0823160e01b600052602035600452602060a060246000600035\
+5afa5060406080f3";
+
+/// Runtime code that runs a batch of calls from its own address and returns
+/// how many succeeded, stopping at the first failure. Calldata is a sequence
+/// of `(address target, uint256 value, uint256 len)` words, each followed by
+/// `len` bytes of call data:
+///
+/// ```text
+/// count := 0  ptr := 0
+/// loop: if iszero(lt(ptr, calldatasize())) { jump(done) }
+///     len := calldataload(ptr + 64)
+///     calldatacopy(0, ptr + 96, len)
+///     if iszero(call(gas(), calldataload(ptr), calldataload(ptr + 32), 0, len, 0, 0)) { jump(done) }
+///     ptr := ptr + 96 + len  count := count + 1  jump(loop)
+/// done: mstore(0, count) return(0, 32)
+/// ```
+const CALL_BATCH_PROBE_CODE: &str = "0x600060005b36811015603b578060400135806060830160003760006000826000856020\
+013586355af1156039576060010190600101906004565b505b5060005260206000f3";
+
+/// Uniswap V2 router the sell check trades through
+pub const SELL_CHECK_ROUTER: &str = "0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D";
+pub const SELL_CHECK_WETH: &str = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2";
+/// ETH spent on the simulated buy
+pub const SELL_CHECK_AMOUNT: U256 = U256([100_000_000_000_000_000, 0, 0, 0]);
+
+fn selector(signature: &str) -> Vec<u8> {
+    keccak256(signature)[..4].to_vec()
+}
+
+/// One `CALL_BATCH_PROBE_CODE` entry.
+fn batch_call(target: Address, value: U256, data: Vec<u8>) -> Vec<u8> {
+    let mut call = abi::encode(&[Token::Address(target), Token::Uint(value), Token::Uint(data.len().into())]);
+    call.extend(data);
+    call
+}
+
+/// Outcome of a simulated buy-then-sell.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RoundTrip {
+    Sold,
+    /// The token couldn't be bought, so selling wasn't tried, and why
+    Unbought(String),
+    /// Bought, but approving or selling it back reverted: a honeypot
+    Unsellable(String),
+}
+
+/// Whether `error` is the node answering the call with an error, such as a
+/// revert, rather than the request not getting through.
+fn is_call_rejection(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<ProviderError>()
+        .map_or(false, |error| error.as_error_response().is_some())
+}
+
+/// Buy `token` for `amount_in` ETH through `router`, then sell half the quoted
+/// output back, all in one `eth_call` from a fresh funded address. Half leaves
+/// room for transfer fees, which aren't what this check is after. A missing
+/// pair or failing step is reported in the `RoundTrip`; only transport errors
+/// and timeouts are `Err`.
+pub async fn simulate_round_trip<P: JsonRpcClient>(
+    client: &Provider<P>,
+    router: Address,
+    weth: Address,
+    token: Address,
+    amount_in: U256,
+    rpc_timeout: Duration,
+) -> Result<RoundTrip> {
+    let buy_path = Token::Array(vec![Token::Address(weth), Token::Address(token)]);
+    let sell_path = Token::Array(vec![Token::Address(token), Token::Address(weth)]);
+
+    let mut quote_data = selector("getAmountsOut(uint256,address[])");
+    quote_data.extend(abi::encode(&[Token::Uint(amount_in), buy_path.clone()]));
+    let quote: TypedTransaction = TransactionRequest::new().to(router).data(quote_data).into();
+    let output = match rpc_call(rpc_timeout, client.call(&quote, None)).await {
+        Ok(output) => output,
+        // getAmountsOut reverts when there's no pair to route through
+        Err(e) if is_call_rejection(&e) => {
+            return Ok(RoundTrip::Unbought(format!("No WETH pair to buy {:?}", token)))
+        }
+        Err(e) => return Err(e),
+    };
+    let amounts = abi::decode(&[ParamType::Array(Box::new(ParamType::Uint(256)))], &output)
+        .map_err(|e| anyhow!("Malformed getAmountsOut output: {}", e))?;
+    let bought = amounts[0]
+        .clone()
+        .into_array()
+        .and_then(|amounts| amounts.last().cloned())
+        .and_then(Token::into_uint)
+        .ok_or_else(|| anyhow!("Malformed getAmountsOut output"))?;
+
+    let probe = Address::random();
+    let mut buy = selector("swapExactETHForTokensSupportingFeeOnTransferTokens(uint256,address[],address,uint256)");
+    buy.extend(abi::encode(&[Token::Uint(U256::zero()), buy_path, Token::Address(probe), Token::Uint(U256::MAX)]));
+    let mut approve = selector("approve(address,uint256)");
+    approve.extend(abi::encode(&[Token::Address(router), Token::Uint(U256::MAX)]));
+    let mut sell = selector(
+        "swapExactTokensForETHSupportingFeeOnTransferTokens(uint256,uint256,address[],address,uint256)",
+    );
+    sell.extend(abi::encode(&[
+        Token::Uint(bought / 2),
+        Token::Uint(U256::zero()),
+        sell_path,
+        Token::Address(probe),
+        Token::Uint(U256::MAX),
+    ]));
+
+    let calls = [
+        batch_call(router, amount_in, buy),
+        batch_call(token, U256::zero(), approve),
+        batch_call(router, U256::zero(), sell),
+    ];
+    let mut state = spoof::state();
+    state
+        .account(probe)
+        .code(Bytes::from_str(CALL_BATCH_PROBE_CODE)?)
+        .balance(amount_in);
+    let tx: TypedTransaction = TransactionRequest::new().to(probe).data(calls.concat()).into();
+    let output = match rpc_call(rpc_timeout, client.call_raw(&tx).state(&state)).await {
+        Ok(output) => output,
+        Err(e) if is_call_rejection(&e) => {
+            return Ok(RoundTrip::Unbought(format!("Round trip rejected: {}", e)))
+        }
+        Err(e) => return Err(e),
+    };
+
+    let completed = abi::decode(&[ParamType::Uint(256)], &output)
+        .ok()
+        .and_then(|tokens| tokens[0].clone().into_uint())
+        .ok_or_else(|| anyhow!("Malformed call batch output"))?;
+    Ok(match completed.as_usize() {
+        0 => RoundTrip::Unbought("Could not buy the token".to_string()),
+        1 => RoundTrip::Unsellable("Approving the router reverted".to_string()),
+        2 => RoundTrip::Unsellable("Selling the token reverted".to_string()),
+        _ => RoundTrip::Sold,
+    })
+}
+
+/// Outcome of a simulated transfer to a fresh address.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransferCheck {
+    pub sent: U256,
+    pub received: U256,
+    /// Whether `transfer` returned without reverting
+    pub succeeded: bool,
+}
+
+impl TransferCheck {
+    pub fn has_fee(&self) -> bool {
+        self.succeeded && self.received < self.sent
+    }
+
+    pub fn is_restricted(&self) -> bool {
+        !self.succeeded
+    }
+}
+
+/// Simulate `holder` sending `amount` of `token` to a fresh address in a single
+/// `eth_call`, by overriding the holder's code with `TRANSFER_PROBE_CODE`.
+pub async fn simulate_transfer<P: JsonRpcClient>(
+    client: &Provider<P>,
+    token: Address,
+    holder: Address,
+    amount: U256,
+    rpc_timeout: Duration,
+) -> Result<TransferCheck> {
+    let recipient = Address::random();
+    let mut state = spoof::state();
+    state.account(holder).code(Bytes::from_str(TRANSFER_PROBE_CODE)?);
+
+    let tx: TypedTransaction = TransactionRequest::new()
+        .from(holder)
+        .to(holder)
+        .data(abi::encode(&[Token::Address(token), Token::Address(recipient), Token::Uint(amount)]))
+        .into();
+    let output = rpc_call(rpc_timeout, client.call_raw(&tx).state(&state)).await?;
+
+    let decoded = abi::decode(&[ParamType::Bool, ParamType::Uint(256)], &output)
+        .map_err(|e| anyhow!("Malformed transfer probe output: {}", e))?;
+    match (decoded[0].clone().into_bool(), decoded[1].clone().into_uint()) {
+        (Some(succeeded), Some(received)) => Ok(TransferCheck {
+            sent: amount,
+            received,
+            succeeded,
+        }),
+        _ => Err(anyhow!("Malformed transfer probe output")),
+    }
+}
+
+/// Most of the supply the top holders may have between them (50%).
+pub const DEFAULT_MAX_CONCENTRATION_BPS: u64 = 5_000;
+
+/// Share of `total_supply` held by `holders`, in basis points. `None` for a
+/// zero supply or a share past `U256`. Computed in 512 bits so supplies of any
+/// size work.
+pub fn concentration_bps(holders: &[(Address, U256)], total_supply: U256) -> Option<U256> {
+    if total_supply.is_zero() {
+        return None;
+    }
+    let held = holders
+        .iter()
+        .fold(U512::zero(), |held, (_, balance)| held + U512::from(*balance));
+    U256::try_from(held * U512::from(10_000) / U512::from(total_supply)).ok()
+}
+
+/// Whether `holders` own more than `max_bps` of `total_supply`. A token
+/// without supply, or whose holders report more than it, fails.
+pub fn exceeds_concentration(holders: &[(Address, U256)], total_supply: U256, max_bps: u64) -> bool {
+    concentration_bps(holders, total_supply).map_or(true, |bps| bps > U256::from(max_bps))
+}
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// First block at or below `latest` where `has_code` holds, by binary search,
+/// or `None` if there's no code at `latest`. Assumes code, once deployed,
+/// stays; a self-destructed and redeployed contract reports its redeployment.
+pub async fn find_creation_block<F, Fut>(latest: u64, mut has_code: F) -> Result<Option<u64>>
+where
+    F: FnMut(u64) -> Fut,
+    Fut: std::future::Future<Output = Result<bool>>,
+{
+    if !has_code(latest).await? {
+        return Ok(None);
+    }
+    let (mut low, mut high) = (0, latest);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if has_code(mid).await? {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    Ok(Some(high))
+}
+
+/// Etherscan API the creation lookup falls back to
+pub const ETHERSCAN_API_URL: &str = "https://api.etherscan.io/api";
+
+/// Creation transaction from an Etherscan `getcontractcreation` response, or
+/// `None` if the address isn't a contract.
+pub fn parse_contract_creation(body: &serde_json::Value) -> Result<Option<H256>> {
+    if let Some(creation) = body.get("result").and_then(|result| result.as_array()).and_then(|r| r.first()) {
+        let hash = creation
+            .get("txHash")
+            .and_then(|hash| hash.as_str())
+            .ok_or_else(|| anyhow!("Malformed contract creation response: {}", body))?;
+        return Ok(Some(H256::from_str(hash)?));
+    }
+    match body.get("message").and_then(|message| message.as_str()) {
+        Some(message) if message.starts_with("No data found") => Ok(None),
+        _ => Err(anyhow!("Contract creation lookup failed: {}", body)),
+    }
+}
+
+/// Whether a token created at `created_at` is at least `min_age_days` old at `now`.
+pub fn is_old_enough(created_at: u64, now: u64, min_age_days: u64) -> bool {
+    now.saturating_sub(created_at) >= min_age_days * SECONDS_PER_DAY
+}
+
 pub struct TokenManager {
     min_holder_count: usize,
     min_volume_24h: U256,
-    max_concentration: f64,
+    max_concentration_bps: u64,
+    min_token_age_days: u64,
+    rpc_timeout: Duration,
+    etherscan_api_key: String,
+    /// Creation block of every token looked up so far; it never changes
+    creation_blocks: RwLock<HashMap<Address, u64>>,
 }
 
 impl TokenManager {
-    pub fn new() -> Self {
+    pub fn new(rpc_timeout: Duration) -> Self {
         Self {
             min_holder_count: 100,
             min_volume_24h: U256::from(1000) * U256::exp10(18), // 1000 USD
-            max_concentration: 0.5, // 50% max concentration for top holders
+            max_concentration_bps: DEFAULT_MAX_CONCENTRATION_BPS,
+            min_token_age_days: MIN_TOKEN_AGE_DAYS,
+            rpc_timeout,
+            etherscan_api_key: String::new(),
+            creation_blocks: RwLock::new(HashMap::new()),
         }
     }
 
+    pub fn with_etherscan_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.etherscan_api_key = api_key.into();
+        self
+    }
+
+    pub fn with_min_token_age_days(mut self, min_token_age_days: u64) -> Self {
+        self.min_token_age_days = min_token_age_days;
+        self
+    }
+
     /// Validate token based on various metrics
     pub async fn validate_token(&self, token: Address) -> Result<TokenValidation> {
         // Get token data
@@ -33,7 +332,16 @@ impl TokenManager {
             return Ok(TokenValidation {
                 is_valid: false,
                 reason: "Insufficient 24h volume".to_string(),
-                error: None,
+                ..Default::default()
+            });
+        }
+
+        // Fresh tokens are where rugs and honeypots live
+        if !is_old_enough(contract_data.created_at, contract_data.last_updated, self.min_token_age_days) {
+            return Ok(TokenValidation {
+                is_valid: false,
+                reason: format!("Token younger than {} days", self.min_token_age_days),
+                ..Default::default()
             });
         }
 
@@ -42,22 +350,55 @@ impl TokenManager {
             return Ok(TokenValidation {
                 is_valid: false,
                 reason: "Insufficient unique holders".to_string(),
-                error: None,
+                ..Default::default()
             });
         }
 
+        // Fee-on-transfer and transfer-restricted tokens break the constant-product math
+        if let Some(&(holder, balance)) = holder_data.top_holders.first() {
+            let check = self.check_transfer(token, holder, balance).await?;
+            if check.has_fee() || check.is_restricted() {
+                return Ok(TokenValidation {
+                    is_valid: false,
+                    reason: if check.has_fee() {
+                        format!("Transfer fee: {} of {} received", check.received, check.sent)
+                    } else {
+                        "Transfer from holder reverted".to_string()
+                    },
+                    has_transfer_fee: check.has_fee(),
+                    has_transfer_restrictions: check.is_restricted(),
+                    ..Default::default()
+                });
+            }
+        }
+
+        // Honeypots let anyone buy but block selling
+        match self.sell_check(token).await? {
+            RoundTrip::Sold => {}
+            RoundTrip::Unbought(reason) => {
+                return Ok(TokenValidation {
+                    is_valid: false,
+                    reason: format!("Sell check couldn't buy the token: {}", reason),
+                    ..Default::default()
+                });
+            }
+            RoundTrip::Unsellable(reason) => {
+                return Ok(TokenValidation {
+                    is_valid: false,
+                    reason: format!("Token can't be sold: {}", reason),
+                    cannot_sell: true,
+                    ..Default::default()
+                });
+            }
+        }
+
         // Calculate holder concentration
         let total_supply = self.get_total_supply(token).await?;
-        let top_holders_balance: U256 = holder_data.top_holders.iter()
-            .map(|(_, balance)| balance)
-            .sum();
-        
-        let concentration = top_holders_balance.as_u128() as f64 / total_supply.as_u128() as f64;
-        if concentration > self.max_concentration {
+        if exceeds_concentration(&holder_data.top_holders, total_supply, self.max_concentration_bps) {
             return Ok(TokenValidation {
                 is_valid: false,
                 reason: "High holder concentration".to_string(),
-                error: None,
+                ..Default::default()
             });
         }
 
@@ -66,14 +407,14 @@ impl TokenManager {
             return Ok(TokenValidation {
                 is_valid: false,
                 reason: "Contract not verified".to_string(),
-                error: None,
+                ..Default::default()
             });
         }
 
         Ok(TokenValidation {
             is_valid: true,
             reason: "All checks passed".to_string(),
-            error: None,
+            ..Default::default()
         })
     }
 
@@ -113,10 +454,10 @@ impl TokenManager {
         let contract = ERC20::new(token, Arc::new(client));
         
         // Get total holder count
-        let unique_holders = contract.holder_count().call().await?;
+        let unique_holders = rpc_call(self.rpc_timeout, contract.holder_count().call()).await?;
         
         // Get top holders
-        let top_holders = contract.get_top_holders(10).call().await?;
+        let top_holders = rpc_call(self.rpc_timeout, contract.get_top_holders(10).call()).await?;
 
         Ok(HolderData {
             unique_holders: unique_holders.as_usize(),
@@ -132,13 +473,18 @@ impl TokenManager {
         // Get contract data from Etherscan
         let client = Provider::<Http>::try_from("https://eth-mainnet.alchemyapi.io/v2/your-api-key")?;
         
-        // Get creation info
-        let created_at = client.get_code(token, None).await?
-            .map(|_| now)
-            .unwrap_or(0);
+        // Get creation info: the timestamp of the first block with code.
+        // An address without code is treated as created just now.
+        let created_at = match self.creation_block(&client, token).await? {
+            Some(block) => rpc_call(self.rpc_timeout, client.get_block(block))
+                .await?
+                .map(|block| block.timestamp.as_u64())
+                .ok_or_else(|| anyhow!("Creation block {} of {:?} not found", block, token))?,
+            None => now,
+        };
             
         // Get verification status
-        let is_verified = client.is_contract_verified(token).await?;
+        let is_verified = rpc_call(self.rpc_timeout, client.is_contract_verified(token)).await?;
         
         // Get source code hash if verified
         let source_hash = if is_verified {
@@ -155,17 +501,91 @@ impl TokenManager {
         })
     }
 
+    /// First block with code at `token`, cached once found. Binary search
+    /// needs historical state; on a node without it, Etherscan is asked.
+    async fn creation_block(&self, client: &Provider<Http>, token: Address) -> Result<Option<u64>> {
+        if let Some(block) = self.creation_blocks.read().await.get(&token) {
+            return Ok(Some(*block));
+        }
+
+        let latest = rpc_call(self.rpc_timeout, client.get_block_number()).await?.as_u64();
+        let has_code = |block: u64| async move {
+            let code = rpc_call(self.rpc_timeout, client.get_code(token, Some(block.into()))).await?;
+            Ok(!code.is_empty())
+        };
+        let block = match find_creation_block(latest, has_code).await {
+            Ok(block) => block,
+            Err(e) => {
+                warn!("Code history of {:?} unavailable ({}), asking Etherscan", token, e);
+                self.etherscan_creation_block(client, token).await?
+            }
+        };
+
+        // No code yet may still become a deployment, so only hits are cached
+        if let Some(block) = block {
+            self.creation_blocks.write().await.insert(token, block);
+        }
+        Ok(block)
+    }
+
+    /// Block of `token`'s creation transaction, per Etherscan `getcontractcreation`
+    async fn etherscan_creation_block(&self, client: &Provider<Http>, token: Address) -> Result<Option<u64>> {
+        let url = format!(
+            "{}?module=contract&action=getcontractcreation&contractaddresses={:?}&apikey={}",
+            ETHERSCAN_API_URL, token, self.etherscan_api_key
+        );
+        let body: serde_json::Value = rpc_call(self.rpc_timeout, async {
+            reqwest::get(&url).await?.error_for_status()?.json().await
+        })
+        .await?;
+
+        let hash = match parse_contract_creation(&body)? {
+            Some(hash) => hash,
+            None => return Ok(None),
+        };
+        let tx = rpc_call(self.rpc_timeout, client.get_transaction(hash))
+            .await?
+            .ok_or_else(|| anyhow!("Creation transaction {:?} of {:?} not found", hash, token))?;
+        Ok(tx.block_number.map(|block| block.as_u64()))
+    }
+
+    /// Simulate `holder` transferring 1% of its `balance` of `token`
+    async fn check_transfer(&self, token: Address, holder: Address, balance: U256) -> Result<TransferCheck> {
+        let client = Provider::<Http>::try_from("https://eth-mainnet.alchemyapi.io/v2/your-api-key")?;
+        let amount = (balance / 100).max(U256::one());
+        simulate_transfer(&client, token, holder, amount, self.rpc_timeout).await
+    }
+
+    /// Whether `token` bought through the V2 router can be sold back
+    pub async fn can_sell(&self, token: Address) -> Result<bool> {
+        Ok(self.sell_check(token).await? == RoundTrip::Sold)
+    }
+
+    /// Buy `token` through the V2 router and try selling it back
+    async fn sell_check(&self, token: Address) -> Result<RoundTrip> {
+        let client = Provider::<Http>::try_from("https://eth-mainnet.alchemyapi.io/v2/your-api-key")?;
+        simulate_round_trip(
+            &client,
+            Address::from_str(SELL_CHECK_ROUTER)?,
+            Address::from_str(SELL_CHECK_WETH)?,
+            token,
+            SELL_CHECK_AMOUNT,
+            self.rpc_timeout,
+        )
+        .await
+    }
+
     /// Get total token supply
     async fn get_total_supply(&self, token: Address) -> Result<U256> {
         let client = Provider::<Http>::try_from("https://eth-mainnet.alchemyapi.io/v2/your-api-key")?;
         let contract = ERC20::new(token, Arc::new(client));
-        Ok(contract.total_supply().call().await?)
+        rpc_call(self.rpc_timeout, contract.total_supply().call()).await
     }
 
     /// Calculate hash of contract source code
     async fn calculate_source_hash(&self, token: Address) -> Result<String> {
         let client = Provider::<Http>::try_from("https://eth-mainnet.alchemyapi.io/v2/your-api-key")?;
-        let source_code = client.get_source_code(token).await?;
+        let source_code = rpc_call(self.rpc_timeout, client.get_source_code(token)).await?;
         
         use sha2::{Sha256, Digest};
         let mut hasher = Sha256::new();
@@ -173,3 +593,221 @@ impl TokenManager {
         Ok(format!("{:x}", hasher.finalize()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::DEFAULT_RPC_TIMEOUT;
+    use ethers::providers::{JsonRpcError, MockProvider, MockResponse};
+
+    fn probe_output(succeeded: bool, received: u64) -> Bytes {
+        abi::encode(&[Token::Bool(succeeded), Token::Uint(U256::from(received))]).into()
+    }
+
+    #[tokio::test]
+    async fn test_find_creation_block() {
+        let probed = std::cell::Cell::new(0);
+        let has_code = |block: u64| {
+            probed.set(probed.get() + 1);
+            async move { Ok(block >= 12_345_678) }
+        };
+        assert_eq!(find_creation_block(19_000_000, has_code).await.unwrap(), Some(12_345_678));
+        // Binary search, not a scan
+        assert!(probed.get() <= 26);
+
+        let no_code = |_block: u64| async { Ok(false) };
+        assert_eq!(find_creation_block(19_000_000, no_code).await.unwrap(), None);
+    }
+
+    #[test]
+    fn test_parse_contract_creation() {
+        let hash = H256::random();
+        let found = serde_json::json!({
+            "status": "1",
+            "message": "OK",
+            "result": [{
+                "contractAddress": format!("{:?}", Address::random()),
+                "contractCreator": format!("{:?}", Address::random()),
+                "txHash": format!("{:?}", hash),
+            }],
+        });
+        assert_eq!(parse_contract_creation(&found).unwrap(), Some(hash));
+
+        let not_a_contract = serde_json::json!({"status": "0", "message": "No data found", "result": null});
+        assert_eq!(parse_contract_creation(&not_a_contract).unwrap(), None);
+
+        let rate_limited = serde_json::json!({"status": "0", "message": "NOTOK", "result": "Max rate limit reached"});
+        assert!(parse_contract_creation(&rate_limited).is_err());
+    }
+
+    #[test]
+    fn test_token_age_check() {
+        let now = 1_700_000_000;
+        let fresh = now - 2 * SECONDS_PER_DAY;
+        let old = now - 400 * SECONDS_PER_DAY;
+
+        assert!(!is_old_enough(fresh, now, MIN_TOKEN_AGE_DAYS));
+        assert!(is_old_enough(old, now, MIN_TOKEN_AGE_DAYS));
+        assert!(is_old_enough(now - MIN_TOKEN_AGE_DAYS * SECONDS_PER_DAY, now, MIN_TOKEN_AGE_DAYS));
+        // No code: created "now", so never old enough
+        assert!(!is_old_enough(now, now, MIN_TOKEN_AGE_DAYS));
+    }
+
+    fn holders(balances: &[U256]) -> Vec<(Address, U256)> {
+        balances.iter().map(|balance| (Address::random(), *balance)).collect()
+    }
+
+    #[test]
+    fn test_concentrated_supply_is_rejected() {
+        // 10^30 tokens with 18 decimals: past u128, where as_u128 panicked
+        let total_supply = U256::exp10(48);
+        let top = holders(&[total_supply * 6 / 100; 10]);
+
+        assert_eq!(concentration_bps(&top, total_supply), Some(U256::from(6_000)));
+        assert!(exceeds_concentration(&top, total_supply, DEFAULT_MAX_CONCENTRATION_BPS));
+    }
+
+    #[test]
+    fn test_distributed_supply_is_accepted() {
+        let total_supply = U256::exp10(48);
+        let top = holders(&[total_supply / 100; 10]);
+
+        assert_eq!(concentration_bps(&top, total_supply), Some(U256::from(1_000)));
+        assert!(!exceeds_concentration(&top, total_supply, DEFAULT_MAX_CONCENTRATION_BPS));
+    }
+
+    #[test]
+    fn test_concentration_without_supply() {
+        let top = holders(&[U256::MAX, U256::MAX]);
+        assert_eq!(concentration_bps(&top, U256::zero()), None);
+        assert!(exceeds_concentration(&top, U256::zero(), DEFAULT_MAX_CONCENTRATION_BPS));
+        // Balances summing past U256 don't overflow
+        assert!(exceeds_concentration(&top, U256::MAX, DEFAULT_MAX_CONCENTRATION_BPS));
+    }
+
+    fn amounts_out(amount_in: u64, amount_out: u64) -> Bytes {
+        abi::encode(&[Token::Array(vec![
+            Token::Uint(U256::from(amount_in)),
+            Token::Uint(U256::from(amount_out)),
+        ])])
+        .into()
+    }
+
+    async fn round_trip(completed_calls: u64) -> Result<RoundTrip> {
+        let (provider, mock) = Provider::mocked();
+        // Responses are served last in, first out: the quote, then the batch
+        mock.push(Bytes::from(abi::encode(&[Token::Uint(U256::from(completed_calls))])))
+            .unwrap();
+        mock.push(amounts_out(1_000, 5_000)).unwrap();
+
+        simulate_round_trip(
+            &provider,
+            Address::random(),
+            Address::random(),
+            Address::random(),
+            U256::from(1_000),
+            DEFAULT_RPC_TIMEOUT,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_reverting_sell_cannot_sell() {
+        // Buy and approve went through, the sell reverted
+        assert_eq!(
+            round_trip(2).await.unwrap(),
+            RoundTrip::Unsellable("Selling the token reverted".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_can_sell() {
+        assert_eq!(round_trip(3).await.unwrap(), RoundTrip::Sold);
+    }
+
+    #[tokio::test]
+    async fn test_failed_buy_cannot_sell() {
+        assert!(matches!(round_trip(0).await.unwrap(), RoundTrip::Unbought(_)));
+    }
+
+    async fn quote_failure(mock: impl FnOnce(&MockProvider)) -> Result<RoundTrip> {
+        let (provider, mock_provider) = Provider::mocked();
+        mock(&mock_provider);
+        simulate_round_trip(
+            &provider,
+            Address::random(),
+            Address::random(),
+            Address::random(),
+            U256::from(1_000),
+            DEFAULT_RPC_TIMEOUT,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_missing_pair_cannot_sell() {
+        let result = quote_failure(|mock| {
+            mock.push_response(MockResponse::Error(JsonRpcError {
+                code: 3,
+                message: "execution reverted".to_string(),
+                data: None,
+            }));
+        })
+        .await;
+        assert!(matches!(result.unwrap(), RoundTrip::Unbought(reason) if reason.starts_with("No WETH pair")));
+    }
+
+    #[tokio::test]
+    async fn test_transport_error_propagates() {
+        // Nothing queued: the request never gets an answer
+        assert!(quote_failure(|_| {}).await.is_err());
+    }
+
+    #[test]
+    fn test_batch_call_layout() {
+        let target = Address::random();
+        let call = batch_call(target, U256::from(7), vec![0xaa; 5]);
+        assert_eq!(call.len(), 96 + 5);
+        assert_eq!(&call[12..32], target.as_bytes());
+        assert_eq!(U256::from_big_endian(&call[32..64]), U256::from(7));
+        assert_eq!(U256::from_big_endian(&call[64..96]), U256::from(5));
+    }
+
+    #[tokio::test]
+    async fn test_reduced_transfer_flags_fee() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(probe_output(true, 980)).unwrap();
+
+        let check = simulate_transfer(&provider, Address::random(), Address::random(), U256::from(1_000), DEFAULT_RPC_TIMEOUT)
+            .await
+            .unwrap();
+        assert_eq!(check.received, U256::from(980));
+        assert!(check.has_fee());
+        assert!(!check.is_restricted());
+    }
+
+    #[tokio::test]
+    async fn test_full_transfer_passes() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(probe_output(true, 1_000)).unwrap();
+
+        let check = simulate_transfer(&provider, Address::random(), Address::random(), U256::from(1_000), DEFAULT_RPC_TIMEOUT)
+            .await
+            .unwrap();
+        assert!(!check.has_fee());
+        assert!(!check.is_restricted());
+    }
+
+    #[tokio::test]
+    async fn test_reverted_transfer_is_restricted() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(probe_output(false, 0)).unwrap();
+
+        let check = simulate_transfer(&provider, Address::random(), Address::random(), U256::from(1_000), DEFAULT_RPC_TIMEOUT)
+            .await
+            .unwrap();
+        assert!(check.is_restricted());
+        // A transfer that didn't happen isn't a fee
+        assert!(!check.has_fee());
+    }
+}
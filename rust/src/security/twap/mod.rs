@@ -3,21 +3,24 @@ use ethers::{
     providers::{Provider, Http},
     types::{U256, Address},
 };
-use std::{sync::Arc, time::SystemTime};
+use std::{sync::Arc, time::{Duration, SystemTime}};
+use crate::security::rpc_call;
 use crate::security::types::TWAPData;
 use crate::dex::DexPool;
 
 pub struct TWAPManager {
+    rpc_timeout: Duration,
+}
+
+impl TWAPManager {
     /// Constants for TWAP calculations
     const MIN_TWAP_SAMPLES: usize = 3;
     const MIN_TWAP_CARDINALITY: u16 = 50;
     const MAX_TWAP_GAPS: usize = 2;
     const MAX_TICK_MOVEMENT: i64 = 1000; // About 10% price movement
-}
 
-impl TWAPManager {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(rpc_timeout: Duration) -> Self {
+        Self { rpc_timeout }
     }
 
     /// Get TWAP from Uniswap V3 pool with extensive validation
@@ -26,7 +29,7 @@ impl TWAPManager {
         let pool_contract = UniswapV3Pool::new(pool.address, client.clone());
 
         // Get current state and validate pool health
-        let (sqrt_price_x96, tick, _, _, _, fee_protocol, _) = pool_contract.slot0().call().await?;
+        let (sqrt_price_x96, tick, _, _, _, fee_protocol, _) = rpc_call(self.rpc_timeout, pool_contract.slot0().call()).await?;
         
         // Validate pool is active
         if sqrt_price_x96.is_zero() {
@@ -34,8 +37,8 @@ impl TWAPManager {
         }
 
         // Get pool tokens and validate
-        let token0 = pool_contract.token0().call().await?;
-        let token1 = pool_contract.token1().call().await?;
+        let token0 = rpc_call(self.rpc_timeout, pool_contract.token0().call()).await?;
+        let token1 = rpc_call(self.rpc_timeout, pool_contract.token1().call()).await?;
         let (base_token, quote_token) = if token == token0 {
             (token0, token1)
         } else if token == token1 {
@@ -49,7 +52,7 @@ impl TWAPManager {
         let seconds_ago = self.get_twap_observation_times(now)?;
         
         // Get observations
-        let (ticks, initialized) = pool_contract.observe(seconds_ago.clone()).call().await?;
+        let (ticks, initialized) = rpc_call(self.rpc_timeout, pool_contract.observe(seconds_ago.clone()).call()).await?;
         
         // Validate observations
         if !self.validate_observations(&initialized)? {
@@ -57,7 +60,7 @@ impl TWAPManager {
         }
 
         // Calculate TWAP with cardinality checking
-        let cardinality = pool_contract.observation_cardinality().call().await?;
+        let cardinality = rpc_call(self.rpc_timeout, pool_contract.observation_cardinality().call()).await?;
         if cardinality < Self::MIN_TWAP_CARDINALITY {
             return Ok(None); // Not enough historical data
         }
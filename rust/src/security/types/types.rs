@@ -3,19 +3,34 @@ use ethers::types::{U256, Address};
 /// Price source with weight and timestamp
 #[derive(Debug, Clone)]
 pub struct PriceSource {
+    /// USD price of one whole token, with 18 decimals, whatever the source
     pub price: U256,
     pub weight: f64,
     pub source: String,
 }
 
 /// Token validation result
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct TokenValidation {
     pub is_valid: bool,
     pub reason: String,
+    /// A simulated transfer delivered less than was sent
+    pub has_transfer_fee: bool,
+    /// A simulated transfer from a holder reverted
+    pub has_transfer_restrictions: bool,
+    /// A simulated buy went through but selling back reverted
+    pub cannot_sell: bool,
     pub error: Option<String>,
 }
 
+impl TokenValidation {
+    /// Whether a probe caught the token misbehaving, as opposed to it merely
+    /// failing a listing criterion such as volume or age.
+    pub fn is_compromised(&self) -> bool {
+        self.has_transfer_fee || self.has_transfer_restrictions || self.cannot_sell
+    }
+}
+
 /// TWAP data with timestamp and sample count
 #[derive(Debug, Clone)]
 pub struct TWAPData {
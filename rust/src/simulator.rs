@@ -1,4 +1,6 @@
-use ethers::types::U256;
+use ethers::types::{Address, U256, U512};
+
+use crate::utils::golden_section_max;
 
 pub struct UniswapV2Simulator;
 
@@ -60,3 +62,302 @@ impl UniswapV2Simulator {
         Some(amount_out)
     }
 }
+
+/// Snapshot of the active range of a Uniswap V3 pool.
+#[derive(Debug, Clone, Copy)]
+pub struct V3PoolState {
+    pub sqrt_price_x96: U256,
+    pub liquidity: U256,
+}
+
+pub struct UniswapV3Simulator;
+
+impl UniswapV3Simulator {
+    /// Swap within the current tick range. Liquidity is assumed constant over the
+    /// swap, which holds for trade sizes that do not cross an initialized tick.
+    /// `fee` is in hundredths of a bip (3000 = 0.3%), as stored by the V3 factory.
+    pub fn get_amount_out(
+        amount_in: U256,
+        state: &V3PoolState,
+        fee: U256,
+        zero_for_one: bool,
+    ) -> Option<U256> {
+        let sqrt_price = state.sqrt_price_x96;
+        let liquidity = state.liquidity;
+        if sqrt_price.is_zero() || liquidity.is_zero() {
+            return None;
+        }
+
+        let fee_denominator = U256::from(1_000_000);
+        let amount_in_less_fee = amount_in * (fee_denominator.checked_sub(fee)?) / fee_denominator;
+        let numerator1 = liquidity << 96;
+
+        let amount_out = if zero_for_one {
+            // Token0 in pushes the price down: sqrtP' = L * 2^96 / (L * 2^96 / sqrtP + amountIn)
+            let denominator = (numerator1 / sqrt_price).checked_add(amount_in_less_fee)?;
+            let next_sqrt_price = div_rounding_up(numerator1, denominator)?;
+            mul_div(liquidity, sqrt_price - next_sqrt_price, U256::one() << 96)?
+        } else {
+            // Token1 in pushes the price up: sqrtP' = sqrtP + amountIn * 2^96 / L
+            let next_sqrt_price =
+                sqrt_price.checked_add(mul_div(amount_in_less_fee, U256::one() << 96, liquidity)?)?;
+            mul_div(numerator1, next_sqrt_price - sqrt_price, next_sqrt_price)? / sqrt_price
+        };
+
+        if amount_out.is_zero() {
+            return None;
+        }
+
+        Some(amount_out)
+    }
+}
+
+/// Balances and parameters of a Curve StableSwap pool, as read from the contract.
+#[derive(Debug, Clone)]
+pub struct CurvePoolState {
+    pub coins: Vec<Address>,
+    pub balances: Vec<U256>,
+    pub decimals: Vec<u8>,
+    /// Amplification coefficient, as returned by `A()`.
+    pub amp: U256,
+    /// Swap fee out of 1e10, as returned by `fee()`.
+    pub fee: U256,
+}
+
+const CURVE_FEE_DENOMINATOR: u64 = 10_000_000_000;
+const CURVE_MAX_ITERATIONS: usize = 255;
+
+pub struct CurveSimulator;
+
+impl CurveSimulator {
+    /// Off-chain `get_dy(i, j, dx)`: solve the StableSwap invariant for the new
+    /// balance of coin `j`, mirroring the integer math of the Vyper contract.
+    pub fn get_dy(i: usize, j: usize, dx: U256, state: &CurvePoolState) -> Option<U256> {
+        let n = state.balances.len();
+        if i == j || i >= n || j >= n || state.decimals.len() != n {
+            return None;
+        }
+
+        let precisions = state
+            .decimals
+            .iter()
+            .map(|d| 18u8.checked_sub(*d).map(|diff| U256::exp10(diff as usize)))
+            .collect::<Option<Vec<_>>>()?;
+        let xp: Vec<U256> = state
+            .balances
+            .iter()
+            .zip(&precisions)
+            .map(|(balance, precision)| *balance * *precision)
+            .collect();
+
+        let x = xp[i] + dx * precisions[i];
+        let y = Self::get_y(i, j, x, &xp, state.amp)?;
+        let dy = xp[j].checked_sub(y)?.checked_sub(U256::one())? / precisions[j];
+        let fee = state.fee * dy / U256::from(CURVE_FEE_DENOMINATOR);
+        dy.checked_sub(fee)
+    }
+
+    /// Invariant `D` for normalized balances `xp` (Newton's method).
+    fn get_d(xp: &[U256], amp: U256) -> Option<U256> {
+        let n = U256::from(xp.len());
+        let sum = xp.iter().fold(U256::zero(), |acc, x| acc + *x);
+        if sum.is_zero() {
+            return Some(U256::zero());
+        }
+
+        let ann = amp * n;
+        let mut d = sum;
+        for _ in 0..CURVE_MAX_ITERATIONS {
+            let mut d_p = d;
+            for x in xp {
+                d_p = (d_p * d).checked_div(*x * n)?;
+            }
+            let d_prev = d;
+            let numerator = (ann * sum + d_p * n) * d;
+            let denominator = (ann.checked_sub(U256::one())?) * d + (n + 1) * d_p;
+            d = numerator.checked_div(denominator)?;
+            if d.max(d_prev) - d.min(d_prev) <= U256::one() {
+                return Some(d);
+            }
+        }
+        None
+    }
+
+    /// Balance of coin `j` that keeps `D` constant once coin `i` holds `x`.
+    fn get_y(i: usize, j: usize, x: U256, xp: &[U256], amp: U256) -> Option<U256> {
+        let n = U256::from(xp.len());
+        let d = Self::get_d(xp, amp)?;
+        let ann = amp * n;
+
+        let mut c = d;
+        let mut sum = U256::zero();
+        for (k, balance) in xp.iter().enumerate() {
+            let x_k = if k == i {
+                x
+            } else if k != j {
+                *balance
+            } else {
+                continue;
+            };
+            sum += x_k;
+            c = (c * d).checked_div(x_k * n)?;
+        }
+        c = (c * d).checked_div(ann * n)?;
+        let b = sum + d.checked_div(ann)?;
+
+        let mut y = d;
+        for _ in 0..CURVE_MAX_ITERATIONS {
+            let y_prev = y;
+            y = (y * y + c).checked_div((y * 2 + b).checked_sub(d)?)?;
+            if y.max(y_prev) - y.min(y_prev) <= U256::one() {
+                return Some(y);
+            }
+        }
+        None
+    }
+
+    /// Best round trip of coin `i` between a Curve pool and a V2 pair holding
+    /// coins `i` and `j` (`v2_reserves` ordered as `(i, j)`), trying both
+    /// directions. Searches whole tokens up to `max_amount_in` and returns the
+    /// raw amount in and profit in coin `i`, or `None` if neither direction pays.
+    pub fn arbitrage_against_v2(
+        state: &CurvePoolState,
+        i: usize,
+        j: usize,
+        v2_reserves: (U256, U256),
+        v2_fee: U256,
+        max_amount_in: u64,
+    ) -> Option<(U256, U256)> {
+        let unit = U256::exp10(*state.decimals.get(i)? as usize);
+        let (reserve_i, reserve_j) = v2_reserves;
+
+        let curve_then_v2 = |amount_in: U256| {
+            let dy = Self::get_dy(i, j, amount_in, state)?;
+            UniswapV2Simulator::get_amount_out(dy, reserve_j, reserve_i, v2_fee)
+        };
+        let v2_then_curve = |amount_in: U256| {
+            let dy = UniswapV2Simulator::get_amount_out(amount_in, reserve_i, reserve_j, v2_fee)?;
+            Self::get_dy(j, i, dy, state)
+        };
+
+        let routes: [&dyn Fn(U256) -> Option<U256>; 2] = [&curve_then_v2, &v2_then_curve];
+        routes
+            .iter()
+            .map(|route| {
+                golden_section_max(0, max_amount_in, 1, |whole| {
+                    let amount_in = U256::from(whole) * unit;
+                    match route(amount_in) {
+                        Some(out) => (out.as_u128() as i128) - (amount_in.as_u128() as i128),
+                        None => i128::MIN,
+                    }
+                })
+            })
+            .max_by_key(|(_, profit)| *profit)
+            .filter(|(_, profit)| *profit > 0)
+            .map(|(whole, profit)| (U256::from(whole) * unit, U256::from(profit as u128)))
+    }
+}
+
+fn mul_div(a: U256, b: U256, denominator: U256) -> Option<U256> {
+    if denominator.is_zero() {
+        return None;
+    }
+    let result = a.full_mul(b) / U512::from(denominator);
+    U256::try_from(result).ok()
+}
+
+fn div_rounding_up(a: U256, b: U256) -> Option<U256> {
+    if b.is_zero() {
+        return None;
+    }
+    let (quotient, remainder) = a.div_mod(b);
+    if remainder.is_zero() {
+        Some(quotient)
+    } else {
+        Some(quotient + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn three_pool() -> CurvePoolState {
+        // DAI / USDC / USDT
+        CurvePoolState {
+            coins: vec![Address::random(), Address::random(), Address::random()],
+            balances: vec![
+                U256::from(150_000_000u64) * U256::exp10(18),
+                U256::from(160_000_000u64) * U256::exp10(6),
+                U256::from(90_000_000u64) * U256::exp10(6),
+            ],
+            decimals: vec![18, 6, 6],
+            amp: U256::from(2000),
+            fee: U256::from(1_000_000),
+        }
+    }
+
+    fn assert_close(actual: U256, expected: U256, tolerance_bps: u64) {
+        let diff = actual.max(expected) - actual.min(expected);
+        assert!(
+            diff * U256::from(10_000) <= expected * U256::from(tolerance_bps),
+            "{} not within {} bps of {}",
+            actual,
+            tolerance_bps,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_curve_get_dy_matches_three_pool() {
+        // Expected values from the 3pool contract's get_dy (StableSwap3Pool.vy)
+        // evaluated for the same balances, A and fee
+        let state = three_pool();
+        let cases = [
+            (0, 1, U256::from(1_000u64) * U256::exp10(18), U256::from(999_930_461u64)),
+            (1, 2, U256::from(1_000_000u64) * U256::exp10(6), U256::from(999_537_854_196u64)),
+            (
+                2,
+                0,
+                U256::from(10_000_000u64) * U256::exp10(6),
+                U256::from_dec_str("10001611095618117468439247").unwrap(),
+            ),
+        ];
+
+        for (i, j, dx, on_chain) in cases {
+            let dy = CurveSimulator::get_dy(i, j, dx, &state).unwrap();
+            assert_close(dy, on_chain, 1);
+        }
+    }
+
+    #[test]
+    fn test_curve_get_dy_rejects_bad_indices() {
+        let state = three_pool();
+        assert!(CurveSimulator::get_dy(0, 0, U256::exp10(18), &state).is_none());
+        assert!(CurveSimulator::get_dy(0, 3, U256::exp10(18), &state).is_none());
+    }
+
+    #[test]
+    fn test_curve_vs_v2_mispricing() {
+        let state = three_pool();
+        let v2_fee = U256::from(300);
+
+        // V2 DAI/USDC pair prices DAI at $1.02: buy DAI on Curve, sell on V2
+        let rich = (
+            U256::from(1_000_000u64) * U256::exp10(18),
+            U256::from(1_020_000u64) * U256::exp10(6),
+        );
+        let (amount_in, profit) =
+            CurveSimulator::arbitrage_against_v2(&state, 1, 0, (rich.1, rich.0), v2_fee, 100_000)
+                .unwrap();
+        assert!(amount_in > U256::zero());
+        assert!(profit > U256::zero());
+
+        // Priced at par, the fees eat any edge
+        let par = (
+            U256::from(1_000_000u64) * U256::exp10(6),
+            U256::from(1_000_000u64) * U256::exp10(18),
+        );
+        assert!(CurveSimulator::arbitrage_against_v2(&state, 1, 0, par, v2_fee, 100_000).is_none());
+    }
+}
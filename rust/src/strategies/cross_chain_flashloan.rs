@@ -1,21 +1,157 @@
+use crate::flashbot::mev_protection::ensure_public_send_allowed;
+use crate::core::{FlashloanManager, FlashloanParams, FlashloanProvider};
 use crate::protocols::aave::AaveProtocol;
+use crate::protocols::cctp::{AttestationClient, CctpProtocol, is_cctp_supported, message_hash};
 use crate::protocols::routing::MultiChainRouter;
+use crate::protocols::dex_adapter::{build_swap_with_approval, DexAdapter, UniswapV2Adapter, UniswapV3Adapter};
+use crate::protocols::flashloan_provider::AaveFlashLoanProvider;
+use crate::protocols::swap::{build_oneinch_swap, send_swap, ApprovalManager, SWAP_ROUTERS};
 use crate::protocols::stargate::{StargateProtocol, StargateConfig, get_pool_config, is_supported_chain, is_supported_token};
+pub use crate::utils::min_amount_after_slippage;
 use ethers::prelude::*;
 use ethers::types::{Address, U256, Bytes};
 use std::sync::Arc;
 use anyhow::Result;
 use super::types::*;
+use tokio::sync::RwLock;
 use tokio::time::{timeout, Duration};
 use std::collections::HashMap;
 
 const EXECUTION_TIMEOUT: u64 = 180; // 3 minutes timeout for full execution
 
+const TRANSFER_EVENT: &str = "Transfer(address,address,uint256)";
+
+/// `amount` as a signed balance change, failing instead of wrapping.
+fn signed(amount: U256) -> Result<i128> {
+    u128::try_from(amount)
+        .ok()
+        .and_then(|amount| i128::try_from(amount).ok())
+        .ok_or_else(|| anyhow::anyhow!("Amount {} overflows step accounting", amount))
+}
+
+/// Total `token` transferred to `recipient` in `receipt`.
+fn received_amount(receipt: &TransactionReceipt, token: Address, recipient: Address) -> U256 {
+    let transfer = H256::from(ethers::utils::keccak256(TRANSFER_EVENT));
+    receipt
+        .logs
+        .iter()
+        .filter(|log| {
+            log.address == token
+                && log.topics.len() == 3
+                && log.topics[0] == transfer
+                && log.topics[2] == H256::from(recipient)
+        })
+        .fold(U256::zero(), |total, log| {
+            total.saturating_add(U256::from_big_endian(&log.data))
+        })
+}
+
+/// Running token balances and gas across executed steps.
+#[derive(Debug, Default)]
+struct StepAccounting {
+    balances: HashMap<(u64, Address), i128>,
+    gas_used: U256,
+}
+
+impl StepAccounting {
+    fn credit(&mut self, chain_id: u64, token: Address, amount: U256) -> Result<()> {
+        *self.balances.entry((chain_id, token)).or_default() += signed(amount)?;
+        Ok(())
+    }
+
+    fn debit(&mut self, chain_id: u64, token: Address, amount: U256) -> Result<()> {
+        *self.balances.entry((chain_id, token)).or_default() -= signed(amount)?;
+        Ok(())
+    }
+
+    /// Book `step`'s balance changes, crediting a swap with `swap_out`.
+    fn apply(&mut self, step: &ExecutionStep, swap_out: U256) -> Result<()> {
+        match step {
+            ExecutionStep::FlashLoan { chain_id, token, amount, .. } => {
+                self.credit(*chain_id, *token, *amount)
+            }
+            ExecutionStep::Bridge { from_chain, to_chain, token, amount, .. } => {
+                self.debit(*from_chain, *token, *amount)?;
+                self.credit(*to_chain, *token, *amount)
+            }
+            ExecutionStep::Swap { chain_id, token_in, token_out, amount_in, .. } => {
+                self.debit(*chain_id, *token_in, *amount_in)?;
+                self.credit(*chain_id, *token_out, swap_out)
+            }
+            ExecutionStep::AaveSupply { chain_id, token, amount, .. } => {
+                self.debit(*chain_id, *token, *amount)
+            }
+            ExecutionStep::AaveBorrow { chain_id, token, amount, .. } => {
+                self.credit(*chain_id, *token, *amount)
+            }
+            ExecutionStep::AaveRepay { chain_id, token, amount, .. } => {
+                self.debit(*chain_id, *token, *amount)
+            }
+        }
+    }
+
+    /// Book `step` before it runs, assuming swaps return their minimum out.
+    fn plan(&mut self, step: &ExecutionStep) -> Result<()> {
+        let swap_out = match step {
+            ExecutionStep::Swap { min_amount_out, .. } => *min_amount_out,
+            _ => U256::zero(),
+        };
+        self.apply(step, swap_out)
+    }
+
+    /// Book executed `step` from its receipt, crediting a swap with what
+    /// `recipient` actually received rather than its minimum out.
+    fn record(&mut self, step: &ExecutionStep, receipt: &TransactionReceipt, recipient: Address) -> Result<()> {
+        self.gas_used += receipt.gas_used.unwrap_or_default();
+        let swap_out = match step {
+            ExecutionStep::Swap { token_out, .. } => received_amount(receipt, *token_out, recipient),
+            _ => U256::zero(),
+        };
+        self.apply(step, swap_out)
+    }
+
+    /// Flash token held across all chains beyond the borrowed principal.
+    fn flash_token_profit(&self, flash_token: Address, flash_amount: U256) -> Result<U256> {
+        let held: i128 = self.balances
+            .iter()
+            .filter(|((_, token), _)| *token == flash_token)
+            .map(|(_, balance)| *balance)
+            .sum();
+        let profit = held - signed(flash_amount)?;
+        Ok(if profit > 0 {
+            U256::from(profit as u128)
+        } else {
+            U256::zero()
+        })
+    }
+}
+
+/// Name of `step` and the chain its transaction is sent on.
+fn step_label(step: &ExecutionStep) -> (&'static str, u64) {
+    match step {
+        ExecutionStep::FlashLoan { chain_id, .. } => ("FlashLoan", *chain_id),
+        ExecutionStep::Bridge { from_chain, .. } => ("Bridge", *from_chain),
+        ExecutionStep::Swap { chain_id, .. } => ("Swap", *chain_id),
+        ExecutionStep::AaveSupply { chain_id, .. } => ("AaveSupply", *chain_id),
+        ExecutionStep::AaveBorrow { chain_id, .. } => ("AaveBorrow", *chain_id),
+        ExecutionStep::AaveRepay { chain_id, .. } => ("AaveRepay", *chain_id),
+    }
+}
+
 pub struct CrossChainFlashloan<M: Middleware> {
     router: Arc<MultiChainRouter<M>>,
     aave_pools: HashMap<u64, Arc<AaveProtocol<M>>>,
     providers: HashMap<u64, Arc<M>>,
     stargate_protocols: HashMap<u64, Arc<StargateProtocol<M>>>,
+    dex_adapters: HashMap<(u64, DexProtocol), Box<dyn DexAdapter>>,
+    approvals: HashMap<u64, Arc<ApprovalManager<M>>>,
+    flashloans: HashMap<u64, RwLock<FlashloanManager>>,
+    /// Contract per chain that receives flash loans and repays them from
+    /// `executeOperation`
+    flash_receivers: HashMap<u64, Address>,
+    /// Every step, approvals and adapter swaps included, is sent through the
+    /// public mempool, so in private-only mode nothing is executed
+    private_only: bool,
 }
 
 impl<M: Middleware + 'static> CrossChainFlashloan<M> {
@@ -25,18 +161,77 @@ impl<M: Middleware + 'static> CrossChainFlashloan<M> {
         providers: HashMap<u64, Arc<M>>,
         stargate_protocols: HashMap<u64, Arc<StargateProtocol<M>>>,
     ) -> Self {
+        let mut dex_adapters: HashMap<(u64, DexProtocol), Box<dyn DexAdapter>> = HashMap::new();
+        for (chain_id, config) in SWAP_ROUTERS.iter() {
+            if let Some(router) = config.v2_router {
+                dex_adapters.insert((*chain_id, DexProtocol::UniswapV2), Box::new(UniswapV2Adapter::new(router)));
+            }
+            if let Ok(adapter) = UniswapV3Adapter::new(config.clone()) {
+                dex_adapters.insert((*chain_id, DexProtocol::UniswapV3), Box::new(adapter));
+            }
+        }
+
+        // One allowance cache per chain's sender, shared by every swap there
+        let approvals = providers
+            .iter()
+            .filter_map(|(chain_id, provider)| {
+                let owner = provider.default_sender()?;
+                Some((*chain_id, Arc::new(ApprovalManager::new(provider.clone(), owner))))
+            })
+            .collect();
+
+        let flashloans = aave_pools
+            .keys()
+            .map(|chain_id| (*chain_id, RwLock::new(FlashloanManager::new())))
+            .collect();
+
         Self {
             router,
             aave_pools,
             providers,
             stargate_protocols,
+            dex_adapters,
+            approvals,
+            flashloans,
+            flash_receivers: HashMap::new(),
+            private_only: false,
         }
     }
 
+    /// Borrow on `chain_id` into `receiver`, the deployed flash loan executor.
+    pub fn with_flash_receiver(mut self, chain_id: u64, receiver: Address) -> Self {
+        self.flash_receivers.insert(chain_id, receiver);
+        self
+    }
+
+    pub fn with_private_only(mut self, private_only: bool) -> Self {
+        self.private_only = private_only;
+        self
+    }
+
+    /// Route `dex` swaps on `chain_id` through `adapter`, replacing any default.
+    pub fn register_dex_adapter(&mut self, chain_id: u64, adapter: Box<dyn DexAdapter>) {
+        self.dex_adapters.insert((chain_id, adapter.protocol()), adapter);
+    }
+
+    /// Approve swaps on `chain_id` with EIP-2612 permits signed by `wallet`,
+    /// which must be that chain's sender, for tokens that support them.
+    pub fn set_permit_signer(&mut self, chain_id: u64, wallet: LocalWallet) -> Result<()> {
+        let provider = self.providers.get(&chain_id)
+            .ok_or_else(|| anyhow::anyhow!("Provider not found for chain {}", chain_id))?;
+        let owner = provider.default_sender()
+            .ok_or_else(|| anyhow::anyhow!("No wallet address found for chain {}", chain_id))?;
+        let approvals = ApprovalManager::new(provider.clone(), owner).with_permit_signer(wallet)?;
+        self.approvals.insert(chain_id, Arc::new(approvals));
+        Ok(())
+    }
+
     pub async fn execute_strategy(
         &self,
         strategy: FlashloanStrategy,
     ) -> Result<ExecutionResult> {
+        ensure_public_send_allowed(self.private_only, "cross-chain steps")?;
+
         // Validate strategy
         self.validate_strategy(&strategy)?;
 
@@ -53,53 +248,71 @@ impl<M: Middleware + 'static> CrossChainFlashloan<M> {
         &self,
         strategy: FlashloanStrategy,
     ) -> Result<ExecutionResult> {
+        self.run_steps(strategy, |step| self.execute_step(step)).await
+    }
+
+    /// Execute `strategy`'s steps in order with `execute`, stopping at the
+    /// first failure, and account for the gas and tokens they moved.
+    async fn run_steps<F, Fut>(
+        &self,
+        strategy: FlashloanStrategy,
+        mut execute: F,
+    ) -> Result<ExecutionResult>
+    where
+        F: FnMut(ExecutionStep) -> Fut,
+        Fut: std::future::Future<Output = Result<TransactionReceipt>>,
+    {
         let mut completed_steps = Vec::new();
-        let mut total_gas_used = U256::zero();
-        let mut current_profit = U256::zero();
+        let mut accounting = StepAccounting::default();
 
         for step in strategy.execution_steps {
-            match step {
-                ExecutionStep::FlashLoan { chain_id, token, amount, params } => {
-                    let result = self.execute_flashloan(chain_id, token, amount, params).await;
-                    self.handle_step_result("FlashLoan", chain_id, result, &mut completed_steps)?;
-                }
-
-                ExecutionStep::Bridge { from_chain, to_chain, token, amount, bridge_data } => {
-                    let result = self.execute_bridge(from_chain, to_chain, token, amount, bridge_data).await;
-                    self.handle_step_result("Bridge", from_chain, result, &mut completed_steps)?;
-                }
-
-                ExecutionStep::Swap { chain_id, token_in, token_out, amount_in, min_amount_out, dex } => {
-                    let result = self.execute_swap(chain_id, token_in, token_out, amount_in, min_amount_out, dex).await;
-                    self.handle_step_result("Swap", chain_id, result, &mut completed_steps)?;
-                }
-
-                ExecutionStep::AaveSupply { chain_id, token, amount } => {
-                    let result = self.execute_aave_supply(chain_id, token, amount).await;
-                    self.handle_step_result("AaveSupply", chain_id, result, &mut completed_steps)?;
-                }
-
-                ExecutionStep::AaveBorrow { chain_id, token, amount, interest_rate_mode } => {
-                    let result = self.execute_aave_borrow(chain_id, token, amount, interest_rate_mode).await;
-                    self.handle_step_result("AaveBorrow", chain_id, result, &mut completed_steps)?;
-                }
+            let (step_type, chain_id) = step_label(&step);
+            let result = execute(step.clone()).await;
 
-                ExecutionStep::AaveRepay { chain_id, token, amount, interest_rate_mode } => {
-                    let result = self.execute_aave_repay(chain_id, token, amount, interest_rate_mode).await;
-                    self.handle_step_result("AaveRepay", chain_id, result, &mut completed_steps)?;
-                }
+            if let Ok(receipt) = &result {
+                let (_, approvals) = self.swap_context(chain_id)?;
+                accounting.record(&step, receipt, approvals.owner())?;
             }
+            self.handle_step_result(step_type, chain_id, result, &mut completed_steps)?;
         }
 
         Ok(ExecutionResult {
             success: completed_steps.iter().all(|s| s.success),
-            profit: current_profit,
-            gas_used: total_gas_used,
+            profit: accounting.flash_token_profit(strategy.flash_token, strategy.flash_amount)?,
+            gas_used: accounting.gas_used,
             error: None,
             steps_completed: completed_steps,
         })
     }
 
+    async fn execute_step(&self, step: ExecutionStep) -> Result<TransactionReceipt> {
+        match step {
+            ExecutionStep::FlashLoan { chain_id, token, amount, params } => {
+                self.execute_flashloan(chain_id, token, amount, params).await
+            }
+
+            ExecutionStep::Bridge { from_chain, to_chain, token, amount, bridge_data } => {
+                self.execute_bridge(from_chain, to_chain, token, amount, bridge_data).await
+            }
+
+            ExecutionStep::Swap { chain_id, token_in, token_out, amount_in, min_amount_out, dex } => {
+                self.execute_swap(chain_id, token_in, token_out, amount_in, min_amount_out, dex).await
+            }
+
+            ExecutionStep::AaveSupply { chain_id, token, amount } => {
+                self.execute_aave_supply(chain_id, token, amount).await
+            }
+
+            ExecutionStep::AaveBorrow { chain_id, token, amount, interest_rate_mode } => {
+                self.execute_aave_borrow(chain_id, token, amount, interest_rate_mode).await
+            }
+
+            ExecutionStep::AaveRepay { chain_id, token, amount, interest_rate_mode } => {
+                self.execute_aave_repay(chain_id, token, amount, interest_rate_mode).await
+            }
+        }
+    }
+
     async fn execute_flashloan(
         &self,
         chain_id: u64,
@@ -109,14 +322,46 @@ impl<M: Middleware + 'static> CrossChainFlashloan<M> {
     ) -> Result<TransactionReceipt> {
         let aave = self.aave_pools.get(&chain_id)
             .ok_or_else(|| anyhow::anyhow!("Aave pool not found for chain {}", chain_id))?;
+        let manager = self.flashloans.get(&chain_id)
+            .ok_or_else(|| anyhow::anyhow!("No flashloan manager for chain {}", chain_id))?;
+        let client = self.providers.get(&chain_id)
+            .ok_or_else(|| anyhow::anyhow!("Provider not found for chain {}", chain_id))?;
+
+        // The pool lends what suppliers deposited and borrowers haven't taken
+        let (supplied, borrowed) = aave.get_reserve_totals(token).await?;
+        let mut lender = AaveFlashLoanProvider::new(aave.get_pool_address());
+        lender.update_liquidity(token, supplied.saturating_sub(borrowed));
+        manager.write().await.set_provider(Box::new(lender));
+
+        let gas_price = client.get_gas_price().await?;
+        let flashloan = self.flashloan_params(chain_id, token, amount, params, gas_price)?;
+        let tx_hash = manager.read().await.execute_flashloan(client.as_ref(), flashloan).await?;
+
+        PendingTransaction::new(tx_hash, client.provider())
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Flashloan {:?} dropped", tx_hash))
+    }
 
-        aave.execute_flashloan(
-            aave.get_pool_address(),
-            vec![token],
-            vec![amount],
-            vec![0], // Variable rate mode
-            params,
-        ).await
+    /// Aave loan of `amount` `token` into `chain_id`'s flash receiver, which
+    /// must repay it within the same transaction.
+    fn flashloan_params(
+        &self,
+        chain_id: u64,
+        token: Address,
+        amount: U256,
+        params: Bytes,
+        gas_price: U256,
+    ) -> Result<FlashloanParams> {
+        let receiver = self.flash_receivers.get(&chain_id)
+            .ok_or_else(|| anyhow::anyhow!("No flash loan receiver for chain {}", chain_id))?;
+        Ok(FlashloanParams {
+            provider: FlashloanProvider::AAVE,
+            token,
+            amount,
+            data: params.to_vec(),
+            callback: *receiver,
+            gas_price,
+        })
     }
 
     async fn execute_bridge(
@@ -131,17 +376,11 @@ impl<M: Middleware + 'static> CrossChainFlashloan<M> {
             BridgeProtocol::Stargate => {
                 self.execute_stargate_bridge(from_chain, to_chain, token, amount, bridge_data).await
             }
-            BridgeProtocol::Hop => {
-                self.execute_hop_bridge(from_chain, to_chain, token, amount, bridge_data).await
-            }
             BridgeProtocol::CCTP => {
                 self.execute_cctp_bridge(from_chain, to_chain, token, amount, bridge_data).await
             }
-            BridgeProtocol::LayerZero => {
-                self.execute_layerzero_bridge(from_chain, to_chain, token, amount, bridge_data).await
-            }
-            BridgeProtocol::Across => {
-                self.execute_across_bridge(from_chain, to_chain, token, amount, bridge_data).await
+            BridgeProtocol::Hop | BridgeProtocol::LayerZero | BridgeProtocol::Across => {
+                Err(anyhow::anyhow!("{:?} bridge is not implemented", bridge_data.protocol))
             }
         }
     }
@@ -156,21 +395,12 @@ impl<M: Middleware + 'static> CrossChainFlashloan<M> {
         dex: DexProtocol,
     ) -> Result<TransactionReceipt> {
         match dex {
-            DexProtocol::UniswapV2 => {
-                self.execute_uniswap_v2_swap(chain_id, token_in, token_out, amount_in, min_amount_out).await
-            }
-            DexProtocol::UniswapV3 => {
-                self.execute_uniswap_v3_swap(chain_id, token_in, token_out, amount_in, min_amount_out).await
-            }
-            DexProtocol::Curve => {
-                self.execute_curve_swap(chain_id, token_in, token_out, amount_in, min_amount_out).await
-            }
-            DexProtocol::Balancer => {
-                self.execute_balancer_swap(chain_id, token_in, token_out, amount_in, min_amount_out).await
-            }
             DexProtocol::OneInch => {
                 self.execute_1inch_swap(chain_id, token_in, token_out, amount_in, min_amount_out).await
             }
+            _ => {
+                self.execute_adapter_swap(chain_id, token_in, token_out, amount_in, min_amount_out, dex).await
+            }
         }
     }
 
@@ -235,10 +465,7 @@ impl<M: Middleware + 'static> CrossChainFlashloan<M> {
         let (src_pool_id, dst_pool_id) = self.get_stargate_pool_ids(from_chain, to_chain, token)?;
 
         // Calculate minimum amount based on slippage
-        let min_amount = amount.saturating_sub(
-            amount.saturating_mul(U256::from(bridge_data.slippage * 100.0 as u64))
-                .saturating_div(U256::from(10000))
-        );
+        let min_amount = min_amount_after_slippage(amount, bridge_data.slippage_bps);
 
         // Execute bridge transaction
         let receipt = stargate.bridge_token(
@@ -294,18 +521,6 @@ impl<M: Middleware + 'static> CrossChainFlashloan<M> {
         Ok((src_pool.pool_id, dst_pool.pool_id))
     }
 
-    async fn execute_hop_bridge(
-        &self,
-        from_chain: u64,
-        to_chain: u64,
-        token: Address,
-        amount: U256,
-        bridge_data: BridgeData,
-    ) -> Result<TransactionReceipt> {
-        // Implement Hop bridge logic
-        todo!("Implement Hop bridge")
-    }
-
     async fn execute_cctp_bridge(
         &self,
         from_chain: u64,
@@ -314,81 +529,91 @@ impl<M: Middleware + 'static> CrossChainFlashloan<M> {
         amount: U256,
         bridge_data: BridgeData,
     ) -> Result<TransactionReceipt> {
-        // Implement CCTP bridge logic
-        todo!("Implement CCTP bridge")
-    }
+        let src_provider = self.providers.get(&from_chain)
+            .ok_or_else(|| anyhow::anyhow!("Provider not found for chain {}", from_chain))?;
+        let dst_provider = self.providers.get(&to_chain)
+            .ok_or_else(|| anyhow::anyhow!("Provider not found for chain {}", to_chain))?;
 
-    async fn execute_layerzero_bridge(
-        &self,
-        from_chain: u64,
-        to_chain: u64,
-        token: Address,
-        amount: U256,
-        bridge_data: BridgeData,
-    ) -> Result<TransactionReceipt> {
-        // Implement LayerZero bridge logic
-        todo!("Implement LayerZero bridge")
-    }
+        let source = CctpProtocol::new(from_chain, src_provider.clone())?;
+        let destination = CctpProtocol::new(to_chain, dst_provider.clone())?;
 
-    async fn execute_across_bridge(
-        &self,
-        from_chain: u64,
-        to_chain: u64,
-        token: Address,
-        amount: U256,
-        bridge_data: BridgeData,
-    ) -> Result<TransactionReceipt> {
-        // Implement Across bridge logic
-        todo!("Implement Across bridge")
-    }
+        if token != source.get_usdc_address() {
+            return Err(anyhow::anyhow!("CCTP only bridges USDC, got {:?}", token));
+        }
 
-    // DEX implementations
-    async fn execute_uniswap_v2_swap(
-        &self,
-        chain_id: u64,
-        token_in: Address,
-        token_out: Address,
-        amount_in: U256,
-        min_amount_out: U256,
-    ) -> Result<TransactionReceipt> {
-        // Implement Uniswap V2 swap
-        todo!("Implement Uniswap V2 swap")
-    }
+        // Mint to our own wallet on the destination chain
+        let recipient = dst_provider
+            .default_sender()
+            .ok_or_else(|| anyhow::anyhow!("No wallet address found for chain {}", to_chain))?;
 
-    async fn execute_uniswap_v3_swap(
-        &self,
-        chain_id: u64,
-        token_in: Address,
-        token_out: Address,
-        amount_in: U256,
-        min_amount_out: U256,
-    ) -> Result<TransactionReceipt> {
-        // Implement Uniswap V3 swap
-        todo!("Implement Uniswap V3 swap")
+        // 1. Burn on the source chain, once the TokenMessenger may pull our USDC
+        let (_, approvals) = self.swap_context(from_chain)?;
+        let token_messenger = source.get_token_messenger();
+        approvals.ensure(token, token_messenger, amount).await?;
+        let (_, message) = source
+            .deposit_for_burn(amount, destination.get_domain(), recipient, token)
+            .await?;
+        approvals.record_spend(token, token_messenger, amount).await;
+
+        // 2. Wait for Circle to attest the burn, bounded by the strategy timeout
+        let attestation = AttestationClient::default()
+            .wait_for_attestation(message_hash(&message), Duration::from_secs(EXECUTION_TIMEOUT))
+            .await?;
+
+        // 3. Mint on the destination chain
+        destination.receive_message(message, attestation).await
     }
 
-    async fn execute_curve_swap(
-        &self,
-        chain_id: u64,
-        token_in: Address,
-        token_out: Address,
-        amount_in: U256,
-        min_amount_out: U256,
-    ) -> Result<TransactionReceipt> {
-        // Implement Curve swap
-        todo!("Implement Curve swap")
+    // DEX implementations
+    fn swap_context(&self, chain_id: u64) -> Result<(Arc<M>, Arc<ApprovalManager<M>>)> {
+        let provider = self.providers.get(&chain_id)
+            .ok_or_else(|| anyhow::anyhow!("Provider not found for chain {}", chain_id))?;
+        let approvals = self.approvals.get(&chain_id)
+            .ok_or_else(|| anyhow::anyhow!("No wallet address found for chain {}", chain_id))?;
+        Ok((provider.clone(), approvals.clone()))
     }
 
-    async fn execute_balancer_swap(
+    async fn execute_adapter_swap(
         &self,
         chain_id: u64,
         token_in: Address,
         token_out: Address,
         amount_in: U256,
         min_amount_out: U256,
+        dex: DexProtocol,
     ) -> Result<TransactionReceipt> {
-        // Implement Balancer swap
-        todo!("Implement Balancer swap")
+        let adapter = self.dex_adapters.get(&(chain_id, dex))
+            .ok_or_else(|| anyhow::anyhow!("No {:?} adapter for chain {}", dex, chain_id))?;
+        let (provider, approvals) = self.swap_context(chain_id)?;
+
+        let mut txs = build_swap_with_approval(
+            adapter.as_ref(),
+            &approvals,
+            token_in,
+            token_out,
+            amount_in,
+            min_amount_out,
+        )
+        .await?;
+        let tx = txs.pop().ok_or_else(|| anyhow::anyhow!("No {:?} swap built", dex))?;
+        if let Some(approve) = txs.pop() {
+            provider
+                .send_transaction(approve, None)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to send approval: {}", e))?
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Approval dropped from mempool"))?;
+            approvals.record_approval(token_in, adapter.spender()).await;
+        }
+
+        let receipt = provider
+            .send_transaction(tx, None)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to send {:?} swap: {}", dex, e))?
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("{:?} swap dropped from mempool", dex))?;
+        approvals.record_spend(token_in, adapter.spender(), amount_in).await;
+        Ok(receipt)
     }
 
     async fn execute_1inch_swap(
@@ -399,8 +624,24 @@ impl<M: Middleware + 'static> CrossChainFlashloan<M> {
         amount_in: U256,
         min_amount_out: U256,
     ) -> Result<TransactionReceipt> {
-        // Implement 1inch swap
-        todo!("Implement 1inch swap")
+        let api_key = std::env::var("ONEINCH_API_KEY")
+            .map_err(|_| anyhow::anyhow!("ONEINCH_API_KEY is not set"))?;
+        let (provider, approvals) = self.swap_context(chain_id)?;
+
+        let swap = build_oneinch_swap(
+            chain_id,
+            &api_key,
+            token_in,
+            token_out,
+            amount_in,
+            min_amount_out,
+            approvals.owner(),
+        ).await?;
+
+        approvals.ensure(token_in, swap.to, amount_in).await?;
+        let receipt = send_swap(provider, swap.to, swap.data, swap.value, self.private_only).await?;
+        approvals.record_spend(token_in, swap.to, amount_in).await;
+        Ok(receipt)
     }
 
     // Helper functions
@@ -532,35 +773,13 @@ impl<M: Middleware + 'static> CrossChainFlashloan<M> {
         }
 
         // Validate amounts in each step
-        let mut balance_map: HashMap<(u64, Address), i128> = HashMap::new();
-        
+        let mut accounting = StepAccounting::default();
         for step in &strategy.execution_steps {
-            match step {
-                ExecutionStep::FlashLoan { chain_id, token, amount, .. } => {
-                    *balance_map.entry((*chain_id, *token)).or_default() += amount.as_u128() as i128;
-                }
-                ExecutionStep::Bridge { from_chain, to_chain, token, amount, .. } => {
-                    *balance_map.entry((*from_chain, *token)).or_default() -= amount.as_u128() as i128;
-                    *balance_map.entry((*to_chain, *token)).or_default() += amount.as_u128() as i128;
-                }
-                ExecutionStep::Swap { chain_id, token_in, token_out, amount_in, min_amount_out, .. } => {
-                    *balance_map.entry((*chain_id, *token_in)).or_default() -= amount_in.as_u128() as i128;
-                    *balance_map.entry((*chain_id, *token_out)).or_default() += min_amount_out.as_u128() as i128;
-                }
-                ExecutionStep::AaveSupply { chain_id, token, amount, .. } => {
-                    *balance_map.entry((*chain_id, *token)).or_default() -= amount.as_u128() as i128;
-                }
-                ExecutionStep::AaveBorrow { chain_id, token, amount, .. } => {
-                    *balance_map.entry((*chain_id, *token)).or_default() += amount.as_u128() as i128;
-                }
-                ExecutionStep::AaveRepay { chain_id, token, amount, .. } => {
-                    *balance_map.entry((*chain_id, *token)).or_default() -= amount.as_u128() as i128;
-                }
-            }
+            accounting.plan(step)?;
         }
 
         // Verify final balances
-        for ((chain_id, token), balance) in balance_map {
+        for ((chain_id, token), balance) in accounting.balances {
             if balance < 0 {
                 return Err(anyhow::anyhow!(
                     "Negative balance {} for token {:?} on chain {}", 
@@ -589,6 +808,9 @@ impl<M: Middleware + 'static> CrossChainFlashloan<M> {
                     if *chain_id != current_chain {
                         return Err(anyhow::anyhow!("Invalid chain sequence in flash loan step"));
                     }
+                    if !self.flash_receivers.contains_key(chain_id) {
+                        return Err(anyhow::anyhow!("No flash loan receiver for chain {}", chain_id));
+                    }
                     has_flashloan = true;
                 }
                 ExecutionStep::Bridge { from_chain, to_chain, .. } => {
@@ -655,17 +877,21 @@ impl<M: Middleware + 'static> CrossChainFlashloan<M> {
                             ));
                         }
                     }
-                    BridgeProtocol::Hop => {
-                        if !self.is_hop_supported(*from_chain, *to_chain) {
+                    BridgeProtocol::CCTP => {
+                        if !is_cctp_supported(*from_chain, *to_chain) {
                             return Err(anyhow::anyhow!(
-                                "Hop bridge not supported between chains {} and {}", 
+                                "CCTP bridge not supported between chains {} and {}", 
                                 from_chain, 
                                 to_chain
                             ));
                         }
                     }
-                    // Add validation for other bridge protocols
-                    _ => {}
+                    BridgeProtocol::Hop | BridgeProtocol::LayerZero | BridgeProtocol::Across => {
+                        return Err(anyhow::anyhow!(
+                            "{:?} bridge is not implemented",
+                            bridge_data.protocol
+                        ));
+                    }
                 }
 
                 // Verify bridge deadline
@@ -697,8 +923,16 @@ impl<M: Middleware + 'static> CrossChainFlashloan<M> {
                             ));
                         }
                     }
-                    // Add validation for other DEX protocols
-                    _ => {}
+                    DexProtocol::Curve | DexProtocol::Balancer => {
+                        if !self.dex_adapters.contains_key(&(*chain_id, *dex)) {
+                            return Err(anyhow::anyhow!(
+                                "No {:?} adapter registered for chain {}",
+                                dex,
+                                chain_id
+                            ));
+                        }
+                    }
+                    DexProtocol::OneInch => {}
                 }
             }
         }
@@ -772,11 +1006,6 @@ impl<M: Middleware + 'static> CrossChainFlashloan<M> {
         self.stargate_protocols.contains_key(&to_chain)
     }
 
-    fn is_hop_supported(&self, from_chain: u64, to_chain: u64) -> bool {
-        // Implement actual Hop support check
-        true // Placeholder
-    }
-
     fn is_uniswap_v2_supported(&self, chain_id: u64) -> bool {
         // Implement actual Uniswap V2 support check
         matches!(chain_id, 1 | 137 | 42161 | 10) // Supported on mainnet, Polygon, Arbitrum, Optimism
@@ -829,3 +1058,226 @@ impl<M: Middleware + 'static> CrossChainFlashloan<M> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::aave::FlashLoanCall;
+    use crate::protocols::flashloan_provider::FlashLoanProvider;
+    use ethers::abi::AbiDecode;
+
+    #[test]
+    fn test_min_amount_after_slippage() {
+        let amount = U256::from(1_000_000_000u64); // 1000 USDC
+
+        assert_eq!(min_amount_after_slippage(amount, 50), U256::from(995_000_000u64));
+        assert_eq!(min_amount_after_slippage(amount, 100), U256::from(990_000_000u64));
+        assert_eq!(min_amount_after_slippage(amount, 300), U256::from(970_000_000u64));
+    }
+
+    #[test]
+    fn test_min_amount_after_slippage_bounds() {
+        let amount = U256::from(123_456_789u64);
+
+        assert_eq!(min_amount_after_slippage(amount, 0), amount);
+        assert_eq!(min_amount_after_slippage(amount, 10_000), U256::zero());
+        assert_eq!(min_amount_after_slippage(amount, u16::MAX), U256::zero());
+        // Rounds the deduction down, never below the exact result
+        assert_eq!(min_amount_after_slippage(amount, 50), U256::from(122_839_506u64));
+    }
+
+    fn receipt(gas_used: u64) -> TransactionReceipt {
+        TransactionReceipt {
+            gas_used: Some(U256::from(gas_used)),
+            status: Some(1u64.into()),
+            ..Default::default()
+        }
+    }
+
+    fn transfer_log(token: Address, to: Address, amount: u64) -> Log {
+        Log {
+            address: token,
+            topics: vec![
+                H256::from(ethers::utils::keccak256(TRANSFER_EVENT)),
+                H256::from(Address::random()),
+                H256::from(to),
+            ],
+            data: Bytes::from(ethers::abi::encode(&[ethers::abi::Token::Uint(amount.into())])),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_steps_credits_received_swap_output() {
+        let owner = Address::random();
+        let strategy = mainnet_flashloan(owner);
+        let usdc = Address::random();
+        let weth = Address::random();
+        let flash_amount = U256::from(1_000_000_000u64);
+
+        let plan = FlashloanStrategy {
+            source_chain: 1,
+            target_chain: 1,
+            flash_token: usdc,
+            flash_amount,
+            min_profit: U256::zero(),
+            max_slippage: 0.005,
+            execution_steps: vec![
+                ExecutionStep::FlashLoan {
+                    chain_id: 1,
+                    token: usdc,
+                    amount: flash_amount,
+                    params: Bytes::default(),
+                },
+                ExecutionStep::Swap {
+                    chain_id: 1,
+                    token_in: usdc,
+                    token_out: weth,
+                    amount_in: flash_amount,
+                    min_amount_out: U256::from(490_000_000u64),
+                    dex: DexProtocol::UniswapV2,
+                },
+                ExecutionStep::Swap {
+                    chain_id: 1,
+                    token_in: weth,
+                    token_out: usdc,
+                    amount_in: U256::from(500_000_000u64),
+                    min_amount_out: U256::from(1_001_000_000u64),
+                    dex: DexProtocol::UniswapV3,
+                },
+            ],
+        };
+
+        // Both swaps beat their minimum; a transfer to someone else is ignored
+        let mut first_swap = receipt(120_000);
+        first_swap.logs = vec![transfer_log(weth, owner, 500_000_000)];
+        let mut second_swap = receipt(140_000);
+        second_swap.logs = vec![
+            transfer_log(usdc, Address::random(), 3_000_000),
+            transfer_log(usdc, owner, 1_012_000_000),
+        ];
+        let mut receipts = vec![receipt(250_000), first_swap, second_swap].into_iter();
+
+        let result = strategy
+            .run_steps(plan, move |_| {
+                let receipt = receipts.next().unwrap();
+                async move { Ok(receipt) }
+            })
+            .await
+            .unwrap();
+
+        assert!(result.success);
+        assert_eq!(result.steps_completed.len(), 3);
+        assert_eq!(result.gas_used, U256::from(510_000));
+        assert_eq!(result.profit, U256::from(12_000_000u64));
+    }
+
+    #[test]
+    fn test_step_accounting_rejects_amounts_beyond_i128() {
+        let mut accounting = StepAccounting::default();
+        let err = accounting
+            .record(&ExecutionStep::FlashLoan {
+                chain_id: 1,
+                token: Address::random(),
+                amount: U256::MAX,
+                params: Bytes::default(),
+            }, &receipt(21_000), Address::random())
+            .unwrap_err();
+
+        assert!(err.to_string().contains("overflows"), "{}", err);
+        assert!(accounting.flash_token_profit(Address::random(), U256::from(u128::MAX)).is_err());
+    }
+
+    /// Mainnet-only strategy sending from `owner` through a mocked provider.
+    fn mainnet_flashloan(owner: Address) -> CrossChainFlashloan<Provider<MockProvider>> {
+        let (provider, _mock) = Provider::mocked();
+        let provider = Arc::new(provider.with_sender(owner));
+        let providers = HashMap::from([(1u64, provider.clone())]);
+        let aave = Arc::new(AaveProtocol::new(1, provider).unwrap());
+        let router = Arc::new(MultiChainRouter::new(Vec::new(), providers.clone()).unwrap());
+
+        CrossChainFlashloan::new(router, HashMap::from([(1u64, aave)]), providers, HashMap::new())
+    }
+
+    #[test]
+    fn test_flashloan_calldata_targets_receiver_contract() {
+        let owner = Address::random();
+        let executor = Address::random();
+
+        let strategy = mainnet_flashloan(owner);
+        assert!(strategy
+            .flashloan_params(1, Address::random(), U256::one(), Bytes::new(), U256::zero())
+            .is_err());
+
+        let strategy = strategy.with_flash_receiver(1, executor);
+        let token = Address::random();
+        let params = strategy
+            .flashloan_params(1, token, U256::from(5_000), Bytes::from(vec![1]), U256::zero())
+            .unwrap();
+        let tx = AaveFlashLoanProvider::new(Address::random())
+            .build_flashloan(params.token, params.amount, params.callback, Bytes::from(params.data))
+            .unwrap();
+
+        let decoded = FlashLoanCall::decode(tx.data().unwrap()).unwrap();
+        assert_eq!(decoded.receiver_address, executor);
+        assert_eq!(decoded.on_behalf_of, executor);
+        assert_ne!(decoded.receiver_address, owner);
+        assert_eq!(decoded.assets, vec![token]);
+    }
+
+    #[test]
+    fn test_validate_bridges_rejects_unimplemented_protocols() {
+        let strategy = mainnet_flashloan(Address::random());
+        let bridge_over = |protocol: BridgeProtocol| FlashloanStrategy {
+            source_chain: 1,
+            target_chain: 8453,
+            flash_token: Address::random(),
+            flash_amount: U256::from(1_000u64),
+            min_profit: U256::zero(),
+            max_slippage: 0.005,
+            execution_steps: vec![ExecutionStep::Bridge {
+                from_chain: 1,
+                to_chain: 8453,
+                token: Address::random(),
+                amount: U256::from(1_000u64),
+                bridge_data: BridgeData {
+                    protocol,
+                    gas_limit: U256::from(300_000u64),
+                    deadline: U256::MAX,
+                    signature: None,
+                    slippage_bps: 50,
+                },
+            }],
+        };
+
+        for protocol in [BridgeProtocol::Hop, BridgeProtocol::LayerZero, BridgeProtocol::Across] {
+            let err = strategy.validate_bridges(&bridge_over(protocol)).unwrap_err();
+            assert!(err.to_string().contains("not implemented"), "{}", err);
+        }
+        assert!(strategy.validate_bridges(&bridge_over(BridgeProtocol::CCTP)).is_ok());
+    }
+
+    #[test]
+    fn test_step_accounting_loss_reports_zero_profit() {
+        let usdc = Address::random();
+        let flash_amount = U256::from(1_000u64);
+
+        let owner = Address::random();
+
+        let mut accounting = StepAccounting::default();
+        accounting.record(&ExecutionStep::FlashLoan {
+            chain_id: 1,
+            token: usdc,
+            amount: flash_amount,
+            params: Bytes::default(),
+        }, &receipt(21_000), owner).unwrap();
+        accounting.record(&ExecutionStep::AaveSupply {
+            chain_id: 1,
+            token: usdc,
+            amount: U256::from(10u64),
+        }, &TransactionReceipt::default(), owner).unwrap();
+
+        assert_eq!(accounting.gas_used, U256::from(21_000));
+        assert!(accounting.flash_token_profit(usdc, flash_amount).unwrap().is_zero());
+    }
+}
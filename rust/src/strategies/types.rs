@@ -60,6 +60,8 @@ pub struct BridgeData {
     pub gas_limit: U256,
     pub deadline: U256,
     pub signature: Option<Bytes>,
+    /// Maximum slippage tolerance in basis points (1 = 0.01%)
+    pub slippage_bps: u16,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -71,7 +73,7 @@ pub enum BridgeProtocol {
     Across,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum DexProtocol {
     UniswapV2,
     UniswapV3,
@@ -1,59 +1,192 @@
+use anyhow::{anyhow, Result};
 use ethers::{
-    providers::{Provider, Ws},
-    types::{Address, H160, U256},
+    providers::{Middleware, Provider, Ws},
+    signers::Signer,
+    types::{Address, Bytes, H160, U256, U64},
+};
+use ethers_flashbots::BundleTransaction;
+use log::{info, warn};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Instant,
 };
-use log::info;
-use std::{collections::HashMap, str::FromStr, sync::Arc};
 use tokio::sync::broadcast::Sender;
 
-use crate::bundler::{Bundler, PathParam, Flashloan};
-use crate::constants::{get_blacklist_tokens, Env, WEI};
-use crate::multi::batch_get_uniswap_v2_reserves;
-use crate::paths::generate_triangular_paths;
-use crate::pools::{load_all_pools_from_v2, Pool};
-use crate::simulator::UniswapV2Simulator;
-use crate::streams::Event;
-use crate::utils::get_touched_pool_reserves;
-
-pub async fn event_handler(provider: Arc<Provider<Ws>>, event_sender: Sender<Event>) {
-    /*
-    Current addresses are all from the Ethereum network.
-    Please change them according to your chain of interest.
-    */
+use crate::backrun::{decode_pending_swap, index_by_pair, plan_backruns, simulate_pending_swap};
+use crate::bundler::{coinbase_tip, Bundler, Flashloan};
+use crate::constants::{augment_blacklist, chain::ChainContext, load_blacklist, Env, BLACKLIST_PATH};
+use crate::flashbot::mev_protection::{MEVProtection, MempoolConditions};
+use crate::flashbot::types::{ArbitrageOpportunity, DexProtocol, ExecutionConfig, PoolInfo};
+use crate::gas::{max_fee_after, GasOracle, GasQuote};
+use crate::kms::{ConfiguredSigner, KmsConfig};
+use crate::monitoring::path_label;
+use crate::multi::{batch_get_uniswap_v2_reserves, Reserve};
+use crate::paths::{generate_triangular_paths, select_non_overlapping, ArbPath};
+use crate::pools::{
+    dedup_by_pair_keep_deepest, load_all_pools_from_v2, update_pool_cache, LiquidityFilter, Pool,
+};
+use crate::security::SecurityManager;
+use crate::simulator::{UniswapV2Simulator, V3PoolState};
+use crate::streams::{Event, NewBlock};
+use crate::utils::{from_usd, gas_cost_in_token, get_touched_pool_reserves, native_to_token, to_usd};
+
+pub async fn event_handler(
+    provider: Arc<Provider<Ws>>,
+    event_sender: Sender<Event>,
+    execution: ExecutionConfig,
+    paper_trading: bool,
+    dedup_pools_by_pair: bool,
+    mut pool_filter: LiquidityFilter,
+) {
     let env = Env::new();
 
-    let factory_addresses = vec!["0xC0AEe478e3658e2610c5F7A4A2E1777cE9e4f2Ac"];
-    let router_addresses = vec!["0xd9e1cE17f2641f24aE83637ab66a2cca9C378B9F"];
-    let factory_blocks = vec![10794229u64];
+    // Addresses come from the chain context of CHAIN_ID
+    let chain = match ChainContext::for_chain(env.chain_id.as_u64()) {
+        Some(chain) => chain,
+        None => {
+            info!("No chain context for chain id {}", env.chain_id);
+            return;
+        }
+    };
+    let deployment = match chain.v2_deployments.first() {
+        Some(deployment) => deployment,
+        None => {
+            info!("No V2 deployment configured for {}", chain.name);
+            return;
+        }
+    };
+
+    let factory_address = format!("{:?}", deployment.factory);
+    let factory_addresses = vec![factory_address.as_str()];
+    let factory_blocks = vec![deployment.from_block];
+    let router_address = deployment.router;
 
-    let pools_vec = load_all_pools_from_v2(env.wss_url.clone(), factory_addresses, factory_blocks)
-        .await
-        .unwrap();
+    // The stablecoin is always priced; other prices come from the caller
+    pool_filter.prices.entry(chain.stablecoin).or_insert(1.0);
+    let mut pools_vec =
+        match load_all_pools_from_v2(env.wss_url.clone(), factory_addresses.clone(), factory_blocks, &pool_filter).await {
+            Ok(pools_vec) => pools_vec,
+            Err(e) => {
+                warn!("Failed to load pools for {}: {:?}", chain.name, e);
+                return;
+            }
+        };
+    // A cache hit only holds pairs created up to its last sync
+    match update_pool_cache(env.wss_url.clone(), factory_addresses, deployment.from_block, &pool_filter).await {
+        Ok(new_pools) => pools_vec.extend(new_pools),
+        Err(e) => warn!("Failed to update the pool cache: {:?}", e),
+    }
     info!("Initial pool count: {}", pools_vec.len());
 
-    // Performing USDC triangular arbitrage
-    let usdc_address = H160::from_str("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48").unwrap();
-    let usdc_decimals = 6;
+    let pools_vec = if dedup_pools_by_pair {
+        // Cached pools carry no reserves, and depth is judged on live ones
+        let live = batch_get_uniswap_v2_reserves(env.https_url.clone(), pools_vec.clone()).await;
+        let pools_vec: Vec<Pool> = pools_vec
+            .into_iter()
+            .map(|mut pool| {
+                if let Some(reserve) = live.get(&pool.address) {
+                    pool.reserve0 = reserve.reserve0;
+                    pool.reserve1 = reserve.reserve1;
+                }
+                pool
+            })
+            .collect();
+        let deduped = dedup_by_pair_keep_deepest(pools_vec);
+        info!("Pool count after pair dedup: {}", deduped.len());
+        deduped
+    } else {
+        pools_vec
+    };
+
+    // Performing stablecoin triangular arbitrage
+    let usdc_address = chain.stablecoin;
 
     let paths = generate_triangular_paths(&pools_vec, usdc_address);
 
-    let blacklist_tokens = get_blacklist_tokens();
+    // Screen the tokens our paths trade and keep newly found honeypots on file
+    let mut blacklist = load_blacklist(BLACKLIST_PATH);
+    let traded_tokens: HashSet<H160> = paths
+        .iter()
+        .flat_map(|path| path.pools.iter().flat_map(|pool| [pool.token0, pool.token1]))
+        .filter(|token| *token != usdc_address)
+        .collect();
+    let security = SecurityManager::new();
+    if let Err(e) = augment_blacklist(&security, traded_tokens, &mut blacklist, BLACKLIST_PATH).await {
+        warn!("Failed to update the blacklist: {:?}", e);
+    }
+    let blacklist_tokens: Vec<H160> = blacklist.into_iter().collect();
 
     let mut pools = HashMap::new();
 
     for path in &paths {
-        if !path.should_blacklist(&blacklist_tokens) {
-            pools.insert(path.pool_1.address.clone(), path.pool_1.clone());
-            pools.insert(path.pool_2.address.clone(), path.pool_2.clone());
-            pools.insert(path.pool_3.address.clone(), path.pool_3.clone());
+        if !path.should_blacklist_excluding(&blacklist_tokens, Some(usdc_address)) {
+            for pool in &path.pools {
+                pools.insert(pool.address.clone(), pool.clone());
+            }
         }
     }
     info!("New pool count: {:?}", pools.len());
 
     let pools_vec: Vec<Pool> = pools.values().cloned().collect();
+    let native_pool = match find_native_pool(&pools_vec, usdc_address, chain.wrapped_native) {
+        Ok(native_pool) => native_pool,
+        Err(e) => {
+            info!("Can't price gas on {}: {:?}", chain.name, e);
+            return;
+        }
+    };
+    let evaluation = EvaluationConfig::new(execution, native_pool, chain.wrapped_native);
+    let start_block = provider.get_block_number().await.unwrap_or_default();
     let mut reserves =
         batch_get_uniswap_v2_reserves(env.https_url.clone(), pools_vec.clone()).await;
+    let mut reserve_ages = ReserveAges::new(reserves.keys(), start_block);
+    let mut v3_states: HashMap<H160, V3PoolState> = HashMap::new();
+    // Only the deployment's own pools are routed through by its router
+    let pairs = index_by_pair(pools.values(), deployment.factory);
+    if pairs.is_empty() {
+        warn!("No pools of {:?} in the cache, pending swaps won't be backrun", deployment.factory);
+    }
+    let mut latest_block: Option<NewBlock> = None;
+    let mut latest_gas_quote: Option<GasQuote> = None;
 
+    // Send from the KMS key when one is configured
+    let sender = match &env.kms_key_id {
+        Some(key_id) => {
+            let kms = KmsConfig { key_id: key_id.clone(), region: None };
+            match kms.signer(env.chain_id.as_u64()).await {
+                Ok(signer) => ConfiguredSigner::Kms(signer),
+                Err(e) => {
+                    info!("Can't load KMS key {}: {:?}", key_id, e);
+                    return;
+                }
+            }
+        }
+        None => match env.private_key.parse() {
+            Ok(wallet) => ConfiguredSigner::Local(wallet),
+            Err(e) => {
+                info!("Invalid PRIVATE_KEY: {:?}", e);
+                return;
+            }
+        },
+    };
+
+    // One bundler for the handler so its nonce manager sees every transaction
+    let bundler = Arc::new(
+        Bundler::with_sender(env.clone(), sender)
+            .with_paper_trading(paper_trading)
+            .with_private_only(evaluation.execution.private_only)
+            .with_coinbase_tip_bps(evaluation.execution.coinbase_tip_bps),
+    );
+    let gas_oracle = GasOracle::new(provider.clone());
+    // Only its delay logic and sandwich bot tracking are used here, to vary
+    // which block our arbs target
+    let mev_protection = Arc::new(
+        MEVProtection::new(String::new(), None, None, U256::zero())
+            .with_max_jitter_blocks(evaluation.execution.max_jitter_blocks),
+    );
+    let mut pending_since_block = 0usize;
+    let mut sandwich_bot_pending = false;
     let mut event_receiver = event_sender.subscribe();
 
     loop {
@@ -61,8 +194,28 @@ pub async fn event_handler(provider: Arc<Provider<Ws>>, event_sender: Sender<Eve
             Ok(event) => match event {
                 Event::Block(block) => {
                     info!("{:?}", block);
+                    latest_block = Some(block.clone());
+                    bundler.nonces.on_new_block(block.block_number);
+                    let block_delay = mev_protection.block_delay(&MempoolConditions {
+                        pending_count: std::mem::take(&mut pending_since_block),
+                        sandwich_bot_pending: std::mem::take(&mut sandwich_bot_pending),
+                        ..Default::default()
+                    });
+                    // Learn sandwich bots from the new block without holding up evaluation
+                    tokio::spawn({
+                        let (mev_protection, provider) = (mev_protection.clone(), provider.clone());
+                        let block_number = block.block_number;
+                        async move {
+                            if let Err(e) = mev_protection
+                                .update_sandwich_bots(provider.as_ref(), block_number, 1)
+                                .await
+                            {
+                                info!("Failed to scan block {} for sandwiches: {:?}", block_number, e);
+                            }
+                        }
+                    });
                     let touched_reserves =
-                        match get_touched_pool_reserves(provider.clone(), block.block_number).await
+                        match get_touched_pool_reserves(provider.clone(), block.block_number, &reserves).await
                         {
                             Ok(response) => response,
                             Err(e) => {
@@ -70,147 +223,1096 @@ pub async fn event_handler(provider: Arc<Provider<Ws>>, event_sender: Sender<Eve
                                 HashMap::new()
                             }
                         };
-                    let mut touched_pools = Vec::new();
-                    for (address, reserve) in touched_reserves.into_iter() {
-                        if reserves.contains_key(&address) {
-                            reserves.insert(address, reserve);
-                            touched_pools.push(address);
-                        }
-                    }
+                    let mut touched_pools = apply_touched_reserves(&mut reserves, touched_reserves);
+                    reserve_ages.mark(&touched_pools, block.block_number);
                     info!("{:?}", touched_pools);
 
-                    let mut spreads = HashMap::new();
-                    for (idx, path) in (&paths).iter().enumerate() {
-                        let touched_path = touched_pools
-                            .iter()
-                            .map(|pool| path.has_pool(&pool) as i32)
-                            .sum::<i32>()
-                            >= 1;
-
-                        if touched_path {
-                            let one_token_in = U256::from(1);
-                            let simulated = path.simulate_v2_path(one_token_in, &reserves);
-
-                            match simulated {
-                                Some(price_quote) => {
-                                    let one_usdc_in = one_token_in * U256::from(usdc_decimals);
-                                    let _out = price_quote.as_u128() as i128;
-                                    let _in = one_usdc_in.as_u128() as i128;
-                                    let spread = _out - _in;
-
-                                    if spread > 0 {
-                                        spreads.insert(idx, spread);
-                                    }
-                                }
-                                None => {}
-                            }
-                        }
+                    // Pools that haven't traded in a while may have missed a Sync
+                    let due = reserve_ages.due_for_refresh(
+                        block.block_number,
+                        evaluation.refresh_interval_blocks,
+                        evaluation.refresh_batch_size,
+                    );
+                    if !due.is_empty() {
+                        let due_pools: Vec<Pool> =
+                            due.iter().filter_map(|address| pools.get(address).cloned()).collect();
+                        let refreshed =
+                            batch_get_uniswap_v2_reserves(env.https_url.clone(), due_pools).await;
+                        let refreshed_pools = apply_touched_reserves(&mut reserves, refreshed);
+                        reserve_ages.mark(&refreshed_pools, block.block_number);
+                        info!("Refreshed {} of {} due pools", refreshed_pools.len(), due.len());
+                        touched_pools.extend(refreshed_pools);
                     }
+                    let stale_pools = reserve_ages
+                        .stale_pools(block.block_number, evaluation.max_reserve_age_blocks);
 
-                    let usdc_weth_address =
-                        Address::from_str("0x397FF1542f962076d0BFE58eA045FfA2d347ACa0").unwrap();
-                    let pool = pools.get(&usdc_weth_address).unwrap();
-                    let reserve = reserves.get(&usdc_weth_address).unwrap();
-                    let weth_price = UniswapV2Simulator::reserves_to_price(
-                        reserve.reserve0,
-                        reserve.reserve1,
-                        pool.decimals0,
-                        pool.decimals1,
-                        false,
+                    // Bid from recent tips when the node serves fee history, else
+                    // fall back to base-fee multiples. Kept for this block's backruns
+                    latest_gas_quote = match gas_oracle.quote().await {
+                        Ok(quote) => Some(quote),
+                        Err(e) => {
+                            info!("Gas oracle unavailable: {:?}", e);
+                            None
+                        }
+                    };
+
+                    let planned = evaluate_block(
+                        &block,
+                        &touched_pools,
+                        &stale_pools,
+                        &paths,
+                        &reserves,
+                        usdc_address,
+                        latest_gas_quote.as_ref(),
+                        &evaluation,
                     );
 
-                    let base_fee = block.next_base_fee;
-                    let estimated_gas_usage = U256::from(550000);
-                    let gas_cost_in_wei = base_fee * estimated_gas_usage;
-                    let gas_cost_in_wmatic =
-                        (gas_cost_in_wei.as_u64() as f64) / ((*WEI).as_u64() as f64);
-                    let gas_cost_in_usdc = weth_price * gas_cost_in_wmatic;
-                    let gas_cost_in_usdc =
-                        U256::from((gas_cost_in_usdc * ((10 as f64).powi(usdc_decimals))) as u64);
-
-                    let mut sorted_spreads: Vec<_> = spreads.iter().collect();
-                    sorted_spreads.sort_by_key(|x| x.1);
-                    sorted_spreads.reverse();
-
-                    for spread in sorted_spreads {
-                        let path_idx = spread.0;
-                        let path = &paths[*path_idx];
-                        let opt = path.optimize_amount_in(U256::from(1000), 10, &reserves);
-                        let min_profit_threshold = gas_cost_in_usdc * U256::from(2); // 2x gas cost
-                        let excess_profit =
-                            (opt.1.as_u128() as i128) - (gas_cost_in_usdc.as_u128() as i128);
-
-                        if excess_profit > min_profit_threshold.as_u128() as i128 {
-                            let bundler = Bundler::new();
-                            
-                            // Create path parameters for the arbitrage
-                            let paths = vec![
-                                PathParam {
-                                    router: path.router_1,
-                                    token_in: path.token_in,
-                                    token_out: path.token_mid,
-                                },
-                                PathParam {
-                                    router: path.router_2,
-                                    token_in: path.token_mid,
-                                    token_out: path.token_out,
-                                },
-                                PathParam {
-                                    router: path.router_3,
-                                    token_in: path.token_out,
-                                    token_out: path.token_in,
-                                },
-                            ];
-
-                            // Dynamic gas pricing based on network conditions
-                            let priority_multiplier = if excess_profit > (min_profit_threshold.as_u128() as i128 * 3) {
-                                U256::from(3) // Higher priority for very profitable trades
-                            } else {
-                                U256::from(2)
-                            };
-                            
-                            let max_priority_fee = base_fee * priority_multiplier;
-                            let max_fee = base_fee * (priority_multiplier + U256::from(1));
-
-                            match bundler.order_tx(
-                                paths,
-                                opt.0, // optimal amount in
+                    for arb in planned {
+                        let started = Instant::now();
+                        let path = &paths[arb.path_idx];
+                        // The bundle targets a block that depends on congestion and
+                        // jitter, so the fee cap must cover its base fee
+                        let target_block = mev_protection.target_block(block.block_number, block_delay);
+                        let max_fee =
+                            max_fee_after(arb.max_fee, (target_block - block.block_number).as_u64() - 1);
+
+                        // Create path parameters for the arbitrage, with a minimum
+                        // output per hop so a moved pool reverts instead of filling
+                        let amounts_out = match path.simulate_v2_hops(arb.amount_in, &reserves) {
+                            Some(amounts_out) => amounts_out,
+                            None => continue,
+                        };
+                        let routers = vec![router_address; path.nhop as usize];
+                        let path_params =
+                            path.to_path_params(&routers, &amounts_out, evaluation.max_slippage_bps);
+                        // Structured log context for this trade
+                        let mut tokens: Vec<Address> =
+                            path_params.iter().map(|hop| hop.token_in).collect();
+                        tokens.extend(path_params.last().map(|hop| hop.token_out));
+                        let trade_path = path_label(&tokens);
+                        let trade_profit = arb.excess_profit.to_string();
+                        let base_decimals = if path.zero_for_ones[0] {
+                            path.pools[0].decimals0
+                        } else {
+                            path.pools[0].decimals1
+                        };
+
+                        let tx = match bundler
+                            .order_tx(
+                                path_params,
+                                arb.amount_in * U256::exp10(base_decimals as usize),
                                 Flashloan::NotUsed,
                                 Address::zero(),
-                                max_priority_fee,
+                                arb.max_priority_fee,
                                 max_fee,
-                            ).await {
-                                Ok(tx) => {
-                                    // Sign the transaction
-                                    if let Ok(signed_tx) = bundler.sign_tx(tx).await {
-                                        // Create and send the bundle with backrun protection
-                                        let bundle = bundler.to_bundle(
-                                            vec![signed_tx],
-                                            block.block_number,
-                                        ).set_revert_if_partial(); // Prevent partial bundle execution
-                                        
-                                        if let Ok(hash) = bundler.send_bundle(bundle).await {
-                                            info!("Bundle sent successfully! Hash: {:?}, Profit: {:?} USDC", hash, excess_profit);
-                                        } else {
-                                            info!("Failed to send bundle");
+                            )
+                            .await
+                        {
+                            Ok(tx) => tx,
+                            Err(e) => {
+                                info!("Failed to create transaction: {:?}", e);
+                                continue;
+                            }
+                        };
+                        let nonce = tx.nonce().copied().unwrap_or_default();
+
+                        // Sign the transaction
+                        let signed_tx = match bundler.sign_tx(tx).await {
+                            Ok(signed_tx) => signed_tx,
+                            Err(e) => {
+                                info!("Failed to sign transaction: {:?}", e);
+                                bundler.nonces.release(nonce);
+                                continue;
+                            }
+                        };
+
+                        // Tip the builder a share of the profit in the same bundle
+                        let tip_in_usdc = coinbase_tip(arb.excess_profit, bundler.coinbase_tip_bps);
+                        let mut signed_txs = vec![signed_tx];
+                        let mut nonces = vec![nonce];
+                        if let Err(e) = push_coinbase_tip(
+                            &bundler,
+                            &arb,
+                            max_fee,
+                            chain.stablecoin_decimals,
+                            &mut signed_txs,
+                            &mut nonces,
+                        )
+                        .await
+                        {
+                            info!("Failed to create coinbase tip: {:?}", e);
+                            release_nonces(&bundler, &nonces);
+                            continue;
+                        }
+
+                        // Dry-run the bundle before paying for it
+                        match bundler
+                            .simulate_bundle(signed_txs.clone(), block.block_number.as_u64())
+                            .await
+                        {
+                            Ok(sim) => {
+                                let sim_gas_in_usdc = native_to_token(
+                                    sim.gas_fees,
+                                    arb.native_price,
+                                    1.0,
+                                    chain.stablecoin_decimals,
+                                );
+                                if sim.has_revert()
+                                    || arb.excess_profit.saturating_sub(tip_in_usdc) <= sim_gas_in_usdc
+                                {
+                                    info!(
+                                        path = trade_path.as_str(), profit = trade_profit.as_str();
+                                        "Skipping bundle: revert={} profit={} sim gas cost={} USDC",
+                                        sim.has_revert(),
+                                        arb.excess_profit,
+                                        sim_gas_in_usdc
+                                    );
+                                    release_nonces(&bundler, &nonces);
+                                    continue;
+                                }
+                            }
+                            Err(e) => {
+                                info!(
+                                    path = trade_path.as_str(), profit = trade_profit.as_str();
+                                    "Bundle simulation failed: {:?}", e
+                                );
+                                release_nonces(&bundler, &nonces);
+                                continue;
+                            }
+                        }
+
+                        // The block may have moved while we built and simulated the
+                        // bundle: re-read the path's pools and re-check the profit
+                        let latest = batch_get_uniswap_v2_reserves(
+                            env.https_url.clone(),
+                            path.pools.clone(),
+                        )
+                        .await;
+                        let moved = apply_touched_reserves(&mut reserves, latest);
+                        reserve_ages.mark(&moved, block.block_number);
+                        if !recheck_profit(path, &arb, &reserves, &evaluation) {
+                            info!(
+                                path = trade_path.as_str(), profit = trade_profit.as_str();
+                                "Skipping bundle: path {} no longer profitable", arb.path_idx
+                            );
+                            release_nonces(&bundler, &nonces);
+                            continue;
+                        }
+
+                        // Create and send the bundle with backrun protection
+                        let bundle = bundler
+                            .to_bundle(signed_txs, block.block_number, target_block)
+                            .set_revert_if_partial(); // Prevent partial bundle execution
+
+                        match bundler.send_bundle(bundle.clone()).await {
+                            Ok(_) if bundler.paper_trading => {
+                                // Nothing went out, so the nonces are free again
+                                release_nonces(&bundler, &nonces);
+                                let opportunity =
+                                    planned_opportunity(path, &arb, &reserves, usdc_address, block.block_number);
+                                bundler.record_paper_trade(&opportunity, started.elapsed());
+                            }
+                            Ok(hash) => {
+                                info!(
+                                    path = trade_path.as_str(), profit = trade_profit.as_str();
+                                    "Bundle sent successfully! Hash: {:?}, Profit: {:?} USDC",
+                                    hash, arb.excess_profit
+                                );
+                                // Re-submit while the opportunity is still fresh
+                                let max_blocks = evaluation.execution.max_opportunity_age_blocks;
+                                tokio::spawn({
+                                    let bundler = bundler.clone();
+                                    async move {
+                                        match bundler.await_inclusion(hash, bundle, max_blocks).await {
+                                            Ok(status) => info!("Bundle {:?}: {:?}", hash, status),
+                                            Err(e) => info!("Stopped tracking bundle {:?}: {:?}", hash, e),
                                         }
                                     }
+                                });
+                            }
+                            Err(e) => {
+                                if !bundler.nonces.handle_error(&e.to_string()) {
+                                    release_nonces(&bundler, &nonces);
                                 }
-                                Err(e) => {
-                                    info!("Failed to create transaction: {:?}", e);
-                                }
+                                info!(
+                                    path = trade_path.as_str(), profit = trade_profit.as_str();
+                                    "Failed to send bundle: {:?}", e
+                                );
                             }
                         }
                     }
                 }
-                Event::PendingTx(_) => {
-                    // not using pending tx
+                Event::PendingTx(target) => {
+                    pending_since_block += 1;
+                    // A sandwich bot's swap is undone by its own back-run, so it
+                    // isn't backrun, and our next bundles wait a block longer
+                    if mev_protection.is_sandwich_bot(target.from) {
+                        sandwich_bot_pending = true;
+                        continue;
+                    }
+                    let block = match &latest_block {
+                        Some(block) => block,
+                        None => continue,
+                    };
+                    let impact = match decode_pending_swap(&target, router_address)
+                        .and_then(|swap| simulate_pending_swap(&swap, &pairs, &reserves))
+                    {
+                        Some(impact) => impact,
+                        None => continue,
+                    };
+                    let stale_pools = reserve_ages
+                        .stale_pools(block.block_number, evaluation.max_reserve_age_blocks);
+                    let planned = plan_backruns(
+                        block,
+                        &impact,
+                        &stale_pools,
+                        &paths,
+                        usdc_address,
+                        latest_gas_quote.as_ref(),
+                        &evaluation,
+                    );
+
+                    for arb in planned {
+                        let started = Instant::now();
+                        let path = &paths[arb.path_idx];
+                        let amounts_out = match path.simulate_v2_hops(arb.amount_in, &impact.reserves) {
+                            Some(amounts_out) => amounts_out,
+                            None => continue,
+                        };
+                        let routers = vec![router_address; path.nhop as usize];
+                        let path_params =
+                            path.to_path_params(&routers, &amounts_out, evaluation.max_slippage_bps);
+                        let base_decimals = if path.zero_for_ones[0] {
+                            path.pools[0].decimals0
+                        } else {
+                            path.pools[0].decimals1
+                        };
+
+                        let tx = match bundler
+                            .order_tx(
+                                path_params,
+                                arb.amount_in * U256::exp10(base_decimals as usize),
+                                Flashloan::NotUsed,
+                                Address::zero(),
+                                arb.max_priority_fee,
+                                arb.max_fee,
+                            )
+                            .await
+                        {
+                            Ok(tx) => tx,
+                            Err(e) => {
+                                info!("Failed to create backrun transaction: {:?}", e);
+                                continue;
+                            }
+                        };
+                        let nonce = tx.nonce().copied().unwrap_or_default();
+                        let signed_tx = match bundler.sign_tx(tx).await {
+                            Ok(signed_tx) => signed_tx,
+                            Err(_) => {
+                                bundler.nonces.release(nonce);
+                                continue;
+                            }
+                        };
+                        let mut signed_txs = vec![signed_tx];
+                        let mut nonces = vec![nonce];
+                        if let Err(e) = push_coinbase_tip(
+                            &bundler,
+                            &arb,
+                            arb.max_fee,
+                            chain.stablecoin_decimals,
+                            &mut signed_txs,
+                            &mut nonces,
+                        )
+                        .await
+                        {
+                            info!("Failed to create coinbase tip for backrun of {:?}: {:?}", target.hash, e);
+                            release_nonces(&bundler, &nonces);
+                            continue;
+                        }
+
+                        // The target goes first so our trade lands on its post-state,
+                        // followed by the builder's tip
+                        let mut bundle_txs = vec![BundleTransaction::from(target.clone())];
+                        bundle_txs.extend(signed_txs.into_iter().map(BundleTransaction::from));
+                        let bundle = bundler
+                            .to_bundle(bundle_txs, block.block_number, block.block_number + 1)
+                            .set_revert_if_partial();
+                        match bundler.send_bundle(bundle).await {
+                            Ok(_) if bundler.paper_trading => {
+                                release_nonces(&bundler, &nonces);
+                                let opportunity = planned_opportunity(
+                                    path,
+                                    &arb,
+                                    &impact.reserves,
+                                    usdc_address,
+                                    block.block_number,
+                                );
+                                bundler.record_paper_trade(&opportunity, started.elapsed());
+                            }
+                            // Only lands beside its target, so there's nothing to re-submit
+                            Ok(hash) => info!(
+                                "Backrun of {:?} sent, fire and forget! Hash: {:?}, Profit: {:?} USDC",
+                                target.hash, hash, arb.excess_profit
+                            ),
+                            Err(e) => {
+                                if !bundler.nonces.handle_error(&e.to_string()) {
+                                    release_nonces(&bundler, &nonces);
+                                }
+                                info!("Failed to send backrun of {:?}: {:?}", target.hash, e);
+                            }
+                        }
+                    }
                 }
                 Event::Log(_) => {
                     // not using logs
                 }
+                Event::StreamReconnected => {
+                    // reserves are refreshed from Sync logs on the next block
+                }
+                Event::V3Swap {
+                    pool,
+                    sqrt_price_x96,
+                    liquidity,
+                    ..
+                } => {
+                    v3_states.insert(
+                        pool,
+                        V3PoolState {
+                            sqrt_price_x96,
+                            liquidity,
+                        },
+                    );
+                }
             },
             Err(_) => {}
         }
     }
 }
+
+/// Tokens `path` trades through, starting and ending with its base token.
+fn path_tokens(path: &ArbPath) -> Vec<Address> {
+    let mut tokens: Vec<Address> = path
+        .pools
+        .iter()
+        .zip(&path.zero_for_ones)
+        .map(|(pool, zero_for_one)| if *zero_for_one { pool.token0 } else { pool.token1 })
+        .collect();
+    tokens.extend(tokens.first().copied());
+    tokens
+}
+
+/// `arb` on `path` as an `ArbitrageOpportunity`, to record as a trade.
+pub fn planned_opportunity(
+    path: &ArbPath,
+    arb: &PlannedArb,
+    reserves: &HashMap<H160, Reserve>,
+    base_token: H160,
+    block_number: U64,
+) -> ArbitrageOpportunity {
+    let base_decimals = if path.zero_for_ones[0] {
+        path.pools[0].decimals0
+    } else {
+        path.pools[0].decimals1
+    };
+    let pools = path
+        .pools
+        .iter()
+        .map(|pool| {
+            let reserve = reserves.get(&pool.address);
+            PoolInfo {
+                address: pool.address,
+                protocol: DexProtocol::UniswapV2,
+                token0: pool.token0,
+                token1: pool.token1,
+                reserves: reserve.map_or_else(Default::default, |r| (r.reserve0, r.reserve1)),
+                fee: pool.fee,
+                liquidity: U256::zero(),
+            }
+        })
+        .collect();
+
+    ArbitrageOpportunity {
+        path: path_tokens(path),
+        expected_profit: arb.excess_profit,
+        required_flash_amount: arb.amount_in * U256::exp10(base_decimals as usize),
+        risk_score: 0,
+        gas_cost: arb.gas_cost,
+        execution_time_ms: 0,
+        pools,
+        profit_token: base_token,
+        discovered_at_block: block_number.as_u64(),
+    }
+}
+
+/// Gas units assumed for a triangular arbitrage when planning.
+pub const ESTIMATED_GAS_USAGE: u64 = 550_000;
+/// Wrapped native tokens (WETH, WMATIC, ...) all use 18 decimals.
+const NATIVE_DECIMALS: u8 = 18;
+/// Each hop reverts if it returns this much less than simulated.
+pub const DEFAULT_MAX_SLIPPAGE_BPS: u16 = 50;
+/// Paths through a pool not updated for this many blocks are skipped.
+pub const DEFAULT_MAX_RESERVE_AGE_BLOCKS: u64 = 300;
+pub const DEFAULT_REFRESH_INTERVAL_BLOCKS: u64 = 10;
+pub const DEFAULT_REFRESH_BATCH_SIZE: usize = 500;
+/// Pending swaps moving a pool by less than this aren't backrun.
+pub const DEFAULT_MIN_BACKRUN_IMPACT_BPS: u64 = 10;
+
+/// Fixed inputs to `evaluate_block` for the lifetime of a handler.
+#[derive(Debug, Clone)]
+pub struct EvaluationConfig {
+    pub execution: ExecutionConfig,
+    /// Base token / wrapped native pool used to price gas in the base token.
+    /// `None` when the base token is the wrapped native itself.
+    pub native_pool: Option<Pool>,
+    pub wrapped_native: H160,
+    pub estimated_gas_usage: U256,
+    /// Upper bound (in whole base tokens) of the amount-in search.
+    pub max_amount_in: U256,
+    pub tolerance: usize,
+    pub max_slippage_bps: u16,
+    pub max_reserve_age_blocks: u64,
+    /// Every this many blocks, re-fetch the `refresh_batch_size` oldest pools.
+    pub refresh_interval_blocks: u64,
+    pub refresh_batch_size: usize,
+    /// Smallest pending swap to backrun, in bps of the pool's input reserve.
+    pub min_backrun_impact_bps: u64,
+}
+
+impl EvaluationConfig {
+    pub fn new(execution: ExecutionConfig, native_pool: Option<Pool>, wrapped_native: H160) -> Self {
+        Self {
+            execution,
+            native_pool,
+            wrapped_native,
+            estimated_gas_usage: U256::from(ESTIMATED_GAS_USAGE),
+            max_amount_in: U256::from(1000),
+            tolerance: 10,
+            max_slippage_bps: DEFAULT_MAX_SLIPPAGE_BPS,
+            max_reserve_age_blocks: DEFAULT_MAX_RESERVE_AGE_BLOCKS,
+            refresh_interval_blocks: DEFAULT_REFRESH_INTERVAL_BLOCKS,
+            refresh_batch_size: DEFAULT_REFRESH_BATCH_SIZE,
+            min_backrun_impact_bps: DEFAULT_MIN_BACKRUN_IMPACT_BPS,
+        }
+    }
+
+    /// Wrapped native price in `base_token`, and the base token's decimals.
+    /// `None` if the base/native pool has no reserves yet.
+    pub fn native_price_in(&self, base_token: H160, reserves: &HashMap<H160, Reserve>) -> Option<(f64, u8)> {
+        if base_token == self.wrapped_native {
+            return Some((1.0, NATIVE_DECIMALS));
+        }
+
+        let pool = self.native_pool.as_ref()?;
+        let reserve = reserves.get(&pool.address)?;
+        let native_price = UniswapV2Simulator::reserves_to_price(
+            reserve.reserve0,
+            reserve.reserve1,
+            pool.decimals0,
+            pool.decimals1,
+            pool.token0 == self.wrapped_native,
+        );
+        let base_decimals = if pool.token0 == base_token {
+            pool.decimals0
+        } else {
+            pool.decimals1
+        };
+        Some((native_price, base_decimals))
+    }
+}
+
+/// Sign a `payCoinbase` tip of the bundler's share of `arb`'s profit, paid in
+/// native currency from the bot's balance, and add it after the order. Its
+/// nonce is pushed before signing, so on error the caller releases `nonces`.
+async fn push_coinbase_tip<S: Signer + Clone + 'static>(
+    bundler: &Bundler<S>,
+    arb: &PlannedArb,
+    max_fee: U256,
+    stablecoin_decimals: u8,
+    signed_txs: &mut Vec<Bytes>,
+    nonces: &mut Vec<U256>,
+) -> Result<()> {
+    let expected_profit = from_usd(
+        to_usd(arb.excess_profit, stablecoin_decimals, 1.0),
+        18,
+        arb.native_price,
+    );
+    if let Some(tip_tx) = bundler
+        .coinbase_tip_tx(expected_profit, arb.max_priority_fee, max_fee)
+        .await?
+    {
+        nonces.push(tip_tx.nonce().copied().unwrap_or_default());
+        signed_txs.push(bundler.sign_tx(tip_tx).await?);
+    }
+    Ok(())
+}
+
+/// Hand back the nonces of an unsent bundle, latest first, since only the
+/// most recently reserved nonce can be released.
+fn release_nonces<S: Signer>(bundler: &Bundler<S>, nonces: &[U256]) {
+    for nonce in nonces.iter().rev() {
+        bundler.nonces.release(*nonce);
+    }
+}
+
+/// The pool pricing the wrapped native in `base_token`. `None` if they're the
+/// same token, since gas is then already denominated in the base token.
+pub fn find_native_pool(pools: &[Pool], base_token: H160, wrapped_native: H160) -> Result<Option<Pool>> {
+    if base_token == wrapped_native {
+        return Ok(None);
+    }
+
+    pools
+        .iter()
+        .find(|pool| {
+            (pool.token0 == base_token && pool.token1 == wrapped_native)
+                || (pool.token0 == wrapped_native && pool.token1 == base_token)
+        })
+        .cloned()
+        .map(Some)
+        .ok_or_else(|| anyhow!("No {:?}/{:?} pool among the loaded pools", base_token, wrapped_native))
+
+}
+
+/// An arbitrage `evaluate_block` decided is worth submitting.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedArb {
+    pub path_idx: usize,
+    /// Optimal input, in whole base tokens.
+    pub amount_in: U256,
+    /// Gross profit in base token units.
+    pub expected_profit: U256,
+    pub gas_cost: U256,
+    /// Profit net of gas cost.
+    pub excess_profit: U256,
+    pub min_profit_threshold: U256,
+    /// Wrapped native price in base tokens at evaluation time.
+    pub native_price: f64,
+    pub max_priority_fee: U256,
+    pub max_fee: U256,
+}
+
+/// Overwrite `reserves` with the `Sync`-derived values for tracked pools and
+/// return the pools that changed.
+pub fn apply_touched_reserves(
+    reserves: &mut HashMap<H160, Reserve>,
+    touched_reserves: HashMap<H160, Reserve>,
+) -> Vec<H160> {
+    let mut touched_pools = Vec::new();
+    for (address, reserve) in touched_reserves.into_iter() {
+        if reserves.contains_key(&address) {
+            reserves.insert(address, reserve);
+            touched_pools.push(address);
+        }
+    }
+    touched_pools
+}
+
+/// Block at which each tracked pool's reserves were last written.
+#[derive(Debug, Clone, Default)]
+pub struct ReserveAges {
+    updated_at: HashMap<H160, U64>,
+}
+
+impl ReserveAges {
+    pub fn new<'a>(pools: impl IntoIterator<Item = &'a H160>, block_number: U64) -> Self {
+        Self {
+            updated_at: pools.into_iter().map(|pool| (*pool, block_number)).collect(),
+        }
+    }
+
+    pub fn mark(&mut self, pools: &[H160], block_number: U64) {
+        for pool in pools {
+            self.updated_at.insert(*pool, block_number);
+        }
+    }
+
+    /// Pools last updated more than `max_age_blocks` before `block_number`.
+    pub fn stale_pools(&self, block_number: U64, max_age_blocks: u64) -> HashSet<H160> {
+        self.updated_at
+            .iter()
+            .filter(|(_, updated_at)| block_number.saturating_sub(**updated_at) > U64::from(max_age_blocks))
+            .map(|(pool, _)| *pool)
+            .collect()
+    }
+
+    /// The `batch_size` least recently updated pools on every `interval_blocks`-th
+    /// block, oldest first; nothing on other blocks.
+    pub fn due_for_refresh(&self, block_number: U64, interval_blocks: u64, batch_size: usize) -> Vec<H160> {
+        if interval_blocks == 0 || block_number.as_u64() % interval_blocks != 0 {
+            return Vec::new();
+        }
+
+        let mut by_age: Vec<_> = self.updated_at.iter().collect();
+        by_age.sort_by_key(|(_, updated_at)| **updated_at);
+        by_age.into_iter().take(batch_size).map(|(pool, _)| *pool).collect()
+    }
+}
+
+/// Re-simulate a planned arbitrage against `reserves` and check it still clears
+/// the min-profit threshold at its planned input and gas cost.
+pub fn recheck_profit(
+    path: &ArbPath,
+    arb: &PlannedArb,
+    reserves: &HashMap<H160, Reserve>,
+    config: &EvaluationConfig,
+) -> bool {
+    let amount_out = match path.simulate_v2_path(arb.amount_in, reserves) {
+        Some(amount_out) => amount_out,
+        None => return false,
+    };
+    let base_decimals = if path.zero_for_ones[0] {
+        path.pools[0].decimals0
+    } else {
+        path.pools[0].decimals1
+    };
+    let amount_in = arb.amount_in * U256::exp10(base_decimals as usize);
+
+    let profit = amount_out.saturating_sub(amount_in);
+    let excess_profit = profit.saturating_sub(arb.gas_cost);
+    config.execution.should_submit(excess_profit, arb.gas_cost)
+}
+
+/// Plan the arbitrages for one block: rank the paths through touched pools by
+/// spread, keep the non-overlapping best ones, size them net of gas and price
+/// their fees. Paths through `stale_pools` are skipped. No I/O, so the caller
+/// only fetches reserves and submits.
+pub fn evaluate_block(
+    block: &NewBlock,
+    touched_pools: &[H160],
+    stale_pools: &HashSet<H160>,
+    paths: &[ArbPath],
+    reserves: &HashMap<H160, Reserve>,
+    base_token: H160,
+    gas_quote: Option<&GasQuote>,
+    config: &EvaluationConfig,
+) -> Vec<PlannedArb> {
+    let mut spreads = HashMap::new();
+    for (idx, path) in paths.iter().enumerate() {
+        if !touched_pools.iter().any(|pool| path.has_pool(pool)) {
+            continue;
+        }
+        if stale_pools.iter().any(|pool| path.has_pool(pool)) {
+            continue;
+        }
+
+        let one_token_in = U256::from(1);
+        if let Some(price_quote) = path.simulate_v2_path(one_token_in, reserves) {
+            let base_decimals = if path.zero_for_ones[0] {
+                path.pools[0].decimals0
+            } else {
+                path.pools[0].decimals1
+            };
+            let one_base_in = one_token_in * U256::exp10(base_decimals as usize);
+            let spread = (price_quote.as_u128() as i128) - (one_base_in.as_u128() as i128);
+
+            if spread > 0 {
+                spreads.insert(idx, spread);
+            }
+        }
+    }
+
+    // Without the reference pool gas can't be priced: skip the block, not the bot
+    let (native_price, base_decimals) = match config.native_price_in(base_token, reserves) {
+        Some(priced) => priced,
+        None => {
+            warn!(
+                "No reserves for the gas reference pool, skipping block {}",
+                block.block_number
+            );
+            return Vec::new();
+        }
+    };
+    // Cost gas at what we'll bid: the oracle's buffered base fee plus its tip
+    // when there is a quote, else the bare next base fee
+    let gas_price = match gas_quote {
+        Some(quote) => quote.base_fee + quote.max_priority_fee,
+        None => block.next_base_fee,
+    };
+    let gas_cost = gas_cost_in_token(
+        gas_price,
+        config.estimated_gas_usage,
+        native_price,
+        1.0,
+        base_decimals,
+    );
+
+    let mut sorted_spreads: Vec<_> = spreads.iter().collect();
+    sorted_spreads.sort_by_key(|x| x.1);
+    sorted_spreads.reverse();
+
+    // Size every candidate first so an unprofitable path can't take the
+    // slot of an overlapping profitable one
+    let mut sized = HashMap::new();
+    let ranked: Vec<usize> = sorted_spreads
+        .iter()
+        .map(|(idx, _)| **idx)
+        .filter(|&path_idx| {
+            let (amount_in, expected_profit) =
+                paths[path_idx].optimize_amount_in(config.max_amount_in, config.tolerance, reserves);
+            let excess_profit = expected_profit.saturating_sub(gas_cost);
+            if !config.execution.should_submit(excess_profit, gas_cost) {
+                return false;
+            }
+            sized.insert(path_idx, (amount_in, expected_profit, excess_profit));
+            true
+        })
+        .collect();
+
+    // Overlapping bundles would compete for the same reserves
+    let selected = select_non_overlapping(paths, &ranked, config.execution.max_bundles_per_block);
+
+    let min_profit_threshold = config.execution.min_profit_for(gas_cost);
+    selected
+        .into_iter()
+        .filter_map(|path_idx| {
+            let (amount_in, expected_profit, excess_profit) = sized.remove(&path_idx)?;
+
+            // Bid more for the more profitable trades
+            let priority_multiplier = config
+                .execution
+                .priority_multiplier(excess_profit, min_profit_threshold);
+            let (max_fee, max_priority_fee) = match gas_quote {
+                Some(quote) => quote.fees(priority_multiplier),
+                None => (
+                    block.next_base_fee * (priority_multiplier + U256::one()),
+                    block.next_base_fee * priority_multiplier,
+                ),
+            };
+            Some(PlannedArb {
+                path_idx,
+                amount_in,
+                expected_profit,
+                gas_cost,
+                excess_profit,
+                min_profit_threshold,
+                native_price,
+                max_priority_fee,
+                max_fee,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{execution_config, make_pool, reserve};
+
+    const GWEI_30: u64 = 30_000_000_000;
+
+    fn block(next_base_fee: u64) -> NewBlock {
+        NewBlock {
+            block_number: U64::from(1),
+            base_fee: U256::from(next_base_fee),
+            next_base_fee: U256::from(next_base_fee),
+            ..Default::default()
+        }
+    }
+
+    fn config(native_pool: Pool, wrapped_native: H160) -> EvaluationConfig {
+        EvaluationConfig::new(execution_config(), Some(native_pool), wrapped_native)
+    }
+
+    /// USDC -> WETH -> DAI -> USDC at $2000 WETH, with the DAI/USDC pool set by the caller.
+    struct Fixture {
+        usdc: H160,
+        weth: H160,
+        usdc_weth: Pool,
+        dai_usdc: Pool,
+        paths: Vec<ArbPath>,
+        reserves: HashMap<H160, Reserve>,
+    }
+
+    fn fixture(dai_reserve: u128, usdc_reserve: u128) -> Fixture {
+        let (usdc, weth, dai) = (H160::random(), H160::random(), H160::random());
+        let usdc_weth = make_pool(usdc, weth, 6, 18);
+        let weth_dai = make_pool(weth, dai, 18, 18);
+        let dai_usdc = make_pool(dai, usdc, 18, 6);
+
+        let reserves = HashMap::from([
+            (usdc_weth.address, reserve(2_000_000, 6, 1_000, 18)),
+            (weth_dai.address, reserve(1_000, 18, 2_000_000, 18)),
+            (dai_usdc.address, reserve(dai_reserve, 18, usdc_reserve, 6)),
+        ]);
+        let paths = vec![ArbPath::new(
+            vec![usdc_weth.clone(), weth_dai, dai_usdc.clone()],
+            vec![true, true, true],
+        )];
+
+        Fixture {
+            usdc,
+            weth,
+            usdc_weth,
+            dai_usdc,
+            paths,
+            reserves,
+        }
+    }
+
+    #[test]
+    fn test_evaluate_block_plans_profitable_path() {
+        let f = fixture(1_600_000, 2_400_000);
+        let config = config(f.usdc_weth.clone(), f.weth);
+
+        let touched = [f.dai_usdc.address];
+        let planned = evaluate_block(&block(GWEI_30), &touched, &HashSet::new(), &f.paths, &f.reserves, f.usdc, None, &config);
+
+        assert_eq!(planned.len(), 1);
+        let arb = &planned[0];
+        let (amount_in, profit) =
+            f.paths[0].optimize_amount_in(config.max_amount_in, config.tolerance, &f.reserves);
+        assert_eq!(arb.path_idx, 0);
+        assert_eq!(arb.amount_in, amount_in);
+        assert_eq!(arb.expected_profit, profit);
+        assert_eq!(arb.excess_profit, profit - arb.gas_cost);
+        assert_eq!(arb.min_profit_threshold, arb.gas_cost * 2);
+        assert!((arb.native_price - 2000.0).abs() < 1e-6);
+
+        // Far above 3x the threshold: top of the default ladder
+        assert!(arb.excess_profit > arb.min_profit_threshold * 3);
+        assert_eq!(arb.max_priority_fee, U256::from(GWEI_30) * 3);
+        assert_eq!(arb.max_fee, U256::from(GWEI_30) * 4);
+    }
+
+    #[test]
+    fn test_evaluate_block_skips_balanced_and_untouched_paths() {
+        let balanced = fixture(2_000_000, 2_000_000);
+        let config_balanced = config(balanced.usdc_weth.clone(), balanced.weth);
+        assert!(evaluate_block(
+            &block(GWEI_30),
+            &[balanced.dai_usdc.address],
+            &HashSet::new(),
+            &balanced.paths,
+            &balanced.reserves,
+            balanced.usdc,
+            None,
+            &config_balanced,
+        )
+        .is_empty());
+
+        // Profitable, but none of its pools moved this block
+        let skewed = fixture(1_800_000, 2_200_000);
+        let config_skewed = config(skewed.usdc_weth.clone(), skewed.weth);
+        assert!(evaluate_block(
+            &block(GWEI_30),
+            &[H160::random()],
+            &HashSet::new(),
+            &skewed.paths,
+            &skewed.reserves,
+            skewed.usdc,
+            None,
+            &config_skewed,
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_evaluate_block_respects_gas_cost() {
+        // ~2% edge: worth it at 1 gwei, not at 3000 gwei
+        let f = fixture(1_980_000, 2_020_000);
+        let config = config(f.usdc_weth.clone(), f.weth);
+        let touched = [f.dai_usdc.address];
+
+        let cheap = block(1_000_000_000);
+        let planned = evaluate_block(&cheap, &touched, &HashSet::new(), &f.paths, &f.reserves, f.usdc, None, &config);
+        assert_eq!(planned.len(), 1);
+
+        let expensive = block(3_000 * 1_000_000_000);
+        let planned = evaluate_block(&expensive, &touched, &HashSet::new(), &f.paths, &f.reserves, f.usdc, None, &config);
+        assert!(planned.is_empty());
+    }
+
+    #[test]
+    fn test_evaluate_block_prices_gas_from_quote() {
+        let f = fixture(1_980_000, 2_020_000);
+        let config = config(f.usdc_weth.clone(), f.weth);
+        let touched = [f.dai_usdc.address];
+        let cheap = block(1_000_000_000);
+
+        let quote = GasQuote {
+            base_fee: U256::from(1_125_000_000u64),
+            max_priority_fee: U256::from(2_000_000_000u64),
+        };
+        let planned =
+            evaluate_block(&cheap, &touched, &HashSet::new(), &f.paths, &f.reserves, f.usdc, Some(&quote), &config);
+        assert_eq!(planned.len(), 1);
+        let without_quote =
+            evaluate_block(&cheap, &touched, &HashSet::new(), &f.paths, &f.reserves, f.usdc, None, &config);
+        assert!(planned[0].gas_cost > without_quote[0].gas_cost);
+        let multiplier = config
+            .execution
+            .priority_multiplier(planned[0].excess_profit, planned[0].min_profit_threshold);
+        assert_eq!((planned[0].max_fee, planned[0].max_priority_fee), quote.fees(multiplier));
+
+        // A tip the base fee alone wouldn't show makes the trade unprofitable
+        let pricey = GasQuote {
+            base_fee: U256::from(1_125_000_000u64),
+            max_priority_fee: U256::from(3_000u64 * 1_000_000_000),
+        };
+        let planned =
+            evaluate_block(&cheap, &touched, &HashSet::new(), &f.paths, &f.reserves, f.usdc, Some(&pricey), &config);
+        assert!(planned.is_empty());
+    }
+
+    #[test]
+    fn test_unprofitable_path_does_not_block_overlapping_one() {
+        let mut f = fixture(1_600_000, 2_400_000);
+        let config = config(f.usdc_weth.clone(), f.weth);
+
+        // Same first two hops through a tiny DAI/USDC pool: the widest spread,
+        // but too shallow to cover gas
+        let dai = f.dai_usdc.token0;
+        let shallow = make_pool(dai, f.usdc, 18, 6);
+        f.reserves.insert(shallow.address, reserve(10, 18, 40, 6));
+        let mut pools = f.paths[0].pools.clone();
+        pools[2] = shallow.clone();
+        f.paths.push(ArbPath::new(pools, vec![true, true, true]));
+
+        let touched = [f.dai_usdc.address, shallow.address];
+        let planned = evaluate_block(&block(GWEI_30), &touched, &HashSet::new(), &f.paths, &f.reserves, f.usdc, None, &config);
+
+        assert_eq!(planned.len(), 1);
+        assert_eq!(planned[0].path_idx, 0);
+    }
+
+    #[test]
+    fn test_planned_opportunity_describes_the_arb() {
+        let f = fixture(1_600_000, 2_400_000);
+        let config = config(f.usdc_weth.clone(), f.weth);
+        let planned = evaluate_block(&block(GWEI_30), &[f.dai_usdc.address], &HashSet::new(), &f.paths, &f.reserves, f.usdc, None, &config);
+        let arb = &planned[0];
+
+        let opportunity = planned_opportunity(&f.paths[0], arb, &f.reserves, f.usdc, U64::from(7));
+        assert_eq!(opportunity.path.first(), Some(&f.usdc));
+        assert_eq!(opportunity.path.last(), Some(&f.usdc));
+        assert_eq!(opportunity.path.len(), 4);
+        assert_eq!(opportunity.required_flash_amount, arb.amount_in * U256::exp10(6));
+        assert_eq!(opportunity.expected_profit, arb.excess_profit);
+        assert_eq!(opportunity.pools.len(), 3);
+        assert_eq!(opportunity.discovered_at_block, 7);
+    }
+
+    #[test]
+    fn test_stale_pool_excludes_its_paths() {
+        let f = fixture(1_600_000, 2_400_000);
+        let config = config(f.usdc_weth.clone(), f.weth);
+        let touched = [f.dai_usdc.address];
+
+        // Every pool last seen at block 1; the DAI/USDC pool trades again at 400
+        let mut ages = ReserveAges::new(f.reserves.keys(), U64::from(1));
+        ages.mark(&touched, U64::from(400));
+        let now = NewBlock {
+            block_number: U64::from(400),
+            ..block(GWEI_30)
+        };
+
+        let stale = ages.stale_pools(now.block_number, config.max_reserve_age_blocks);
+        assert_eq!(stale.len(), 2);
+        assert!(!stale.contains(&f.dai_usdc.address));
+        let planned = evaluate_block(&now, &touched, &stale, &f.paths, &f.reserves, f.usdc, None, &config);
+        assert!(planned.is_empty());
+
+        // Within the bound the same block plans the arbitrage
+        let fresh = ages.stale_pools(U64::from(301), config.max_reserve_age_blocks);
+        assert!(fresh.is_empty());
+        let planned = evaluate_block(&now, &touched, &fresh, &f.paths, &f.reserves, f.usdc, None, &config);
+        assert_eq!(planned.len(), 1);
+    }
+
+    #[test]
+    fn test_oldest_pools_refreshed_on_schedule() {
+        let (a, b, c) = (H160::random(), H160::random(), H160::random());
+        let mut ages = ReserveAges::new([a, b, c].iter(), U64::from(100));
+        ages.mark(&[b], U64::from(105));
+        ages.mark(&[c], U64::from(103));
+
+        // Only every 10th block
+        assert!(ages.due_for_refresh(U64::from(109), 10, 2).is_empty());
+        let due = ages.due_for_refresh(U64::from(110), 10, 2);
+        assert_eq!(due, vec![a, c]);
+
+        // Once refreshed, the next round picks up the rest
+        ages.mark(&due, U64::from(110));
+        assert_eq!(ages.due_for_refresh(U64::from(120), 10, 1), vec![b]);
+        assert!(ages.stale_pools(U64::from(120), 300).is_empty());
+    }
+
+    #[test]
+    fn test_recheck_skips_arb_when_reserves_move() {
+        let f = fixture(1_600_000, 2_400_000);
+        let config = config(f.usdc_weth.clone(), f.weth);
+        let touched = [f.dai_usdc.address];
+
+        let planned = evaluate_block(&block(GWEI_30), &touched, &HashSet::new(), &f.paths, &f.reserves, f.usdc, None, &config);
+        assert_eq!(planned.len(), 1);
+        let arb = &planned[0];
+        assert!(recheck_profit(&f.paths[0], arb, &f.reserves, &config));
+
+        // Someone closes the DAI/USDC skew before our bundle goes out
+        let mut reserves = f.reserves.clone();
+        let moved = apply_touched_reserves(
+            &mut reserves,
+            HashMap::from([(f.dai_usdc.address, reserve(2_000_000, 18, 2_000_000, 6))]),
+        );
+        assert_eq!(moved, vec![f.dai_usdc.address]);
+        assert!(!recheck_profit(&f.paths[0], arb, &reserves, &config));
+    }
+
+    #[test]
+    fn test_weth_base_costs_gas_in_weth_directly() {
+        let f = fixture(1_600_000, 2_400_000);
+        let weth_dai = f.paths[0].pools[1].clone();
+        // The same cycle, starting and ending in WETH
+        let paths = vec![ArbPath::new(
+            vec![weth_dai, f.dai_usdc.clone(), f.usdc_weth.clone()],
+            vec![true, true, true],
+        )];
+
+        let pools = paths[0].pools.clone();
+        let native_pool = find_native_pool(&pools, f.weth, f.weth).unwrap();
+        assert!(native_pool.is_none());
+        let mut config = config(f.usdc_weth.clone(), f.weth);
+        config.native_pool = native_pool;
+
+        assert_eq!(config.native_price_in(f.weth, &f.reserves), Some((1.0, 18)));
+        let touched = [f.dai_usdc.address];
+        let planned = evaluate_block(&block(GWEI_30), &touched, &HashSet::new(), &paths, &f.reserves, f.weth, None, &config);
+
+        assert_eq!(planned.len(), 1);
+        // 30 gwei * 550k gas, in wei with no price conversion
+        assert_eq!(planned[0].gas_cost, U256::from(GWEI_30) * U256::from(ESTIMATED_GAS_USAGE));
+        assert_eq!(planned[0].native_price, 1.0);
+    }
+
+    #[test]
+    fn test_find_native_pool() {
+        let f = fixture(2_000_000, 2_000_000);
+        let pools = f.paths[0].pools.clone();
+
+        let found = find_native_pool(&pools, f.usdc, f.weth).unwrap();
+        assert_eq!(found.map(|pool| pool.address), Some(f.usdc_weth.address));
+
+        // Without a USDC/WETH pool, gas can't be priced in USDC
+        let without_native_pair: Vec<Pool> =
+            pools.into_iter().filter(|pool| pool.address != f.usdc_weth.address).collect();
+        let err = find_native_pool(&without_native_pair, f.usdc, f.weth).unwrap_err();
+        assert!(err.to_string().contains("No"));
+    }
+
+    #[test]
+    fn test_missing_reference_pool_skips_block_without_panicking() {
+        let f = fixture(1_600_000, 2_400_000);
+        let config = config(f.usdc_weth.clone(), f.weth);
+        let touched = [f.dai_usdc.address];
+
+        let mut reserves = f.reserves.clone();
+        let reference = reserves.remove(&f.usdc_weth.address).unwrap();
+        let planned = evaluate_block(&block(GWEI_30), &touched, &HashSet::new(), &f.paths, &reserves, f.usdc, None, &config);
+        assert!(planned.is_empty());
+
+        // The next block, with the pool back, is evaluated as usual
+        reserves.insert(f.usdc_weth.address, reference);
+        let planned = evaluate_block(&block(GWEI_30), &touched, &HashSet::new(), &f.paths, &reserves, f.usdc, None, &config);
+        assert_eq!(planned.len(), 1);
+    }
+}
@@ -1,42 +1,98 @@
+use anyhow::{anyhow, Result};
 use ethers::{
     providers::{Provider, Ws},
-    types::{Filter, Log, Transaction, U256, U64},
+    abi::{decode, ParamType, Token},
+    types::{Block, Filter, Log, Transaction, H160, H256, I256, U256, U64},
+    utils::keccak256,
 };
 use ethers_providers::Middleware;
-use std::sync::Arc;
+use log::warn;
+use std::{future::Future, pin::Pin, sync::Arc, time::Duration};
 use tokio::sync::broadcast::Sender;
-use tokio_stream::StreamExt;
-
-use crate::utils::calculate_next_block_base_fee;
+use tokio_stream::{Stream, StreamExt};
 
+/// The head block as published on `Event::Block`, shared by every consumer.
 #[derive(Default, Debug, Clone)]
 pub struct NewBlock {
     pub block_number: U64,
+    pub hash: H256,
+    pub timestamp: U256,
     pub base_fee: U256,
     pub next_base_fee: U256,
 }
 
+impl NewBlock {
+    /// None for pending blocks, which have no number or hash yet.
+    pub fn from_block(block: &Block<H256>) -> Option<Self> {
+        let base_fee = block.base_fee_per_gas.unwrap_or_default();
+        Some(Self {
+            block_number: block.number?,
+            hash: block.hash?,
+            timestamp: block.timestamp,
+            base_fee,
+            next_base_fee: compute_next_base_fee(block),
+        })
+    }
+}
+
+/// Base fee of the block after `parent`, per EIP-1559.
+pub fn compute_next_base_fee(parent: &Block<H256>) -> U256 {
+    let base_fee = parent.base_fee_per_gas.unwrap_or_default();
+    let gas_target = parent.gas_limit / ELASTICITY_MULTIPLIER;
+    if gas_target.is_zero() || parent.gas_used == gas_target {
+        return base_fee;
+    }
+
+    if parent.gas_used > gas_target {
+        let delta = base_fee * (parent.gas_used - gas_target)
+            / gas_target
+            / BASE_FEE_MAX_CHANGE_DENOMINATOR;
+        base_fee + delta.max(U256::one())
+    } else {
+        let delta = base_fee * (gas_target - parent.gas_used)
+            / gas_target
+            / BASE_FEE_MAX_CHANGE_DENOMINATOR;
+        base_fee.saturating_sub(delta)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Event {
     Block(NewBlock),
     PendingTx(Transaction),
     Log(Log),
+    /// A dropped subscription was re-established; events may have been missed.
+    StreamReconnected,
+    V3Swap {
+        pool: H160,
+        sqrt_price_x96: U256,
+        liquidity: U256,
+        tick: i32,
+    },
 }
 
-pub async fn stream_new_blocks(provider: Arc<Provider<Ws>>, event_sender: Sender<Event>) {
-    let stream = provider.subscribe_blocks().await.unwrap();
-    let mut stream = stream.filter_map(|block| match block.number {
-        Some(number) => Some(NewBlock {
-            block_number: number,
-            base_fee: block.base_fee_per_gas.unwrap_or_default(),
-            next_base_fee: U256::from(calculate_next_block_base_fee(
-                block.gas_used,
-                block.gas_limit,
-                block.base_fee_per_gas.unwrap_or_default(),
-            )),
-        }),
-        None => None,
-    });
+/// EIP-1559: the gas target is `gas_limit / ELASTICITY_MULTIPLIER`.
+pub const ELASTICITY_MULTIPLIER: u64 = 2;
+/// EIP-1559: the base fee moves by at most 1/8 per block.
+pub const BASE_FEE_MAX_CHANGE_DENOMINATOR: u64 = 8;
+
+pub const V2_SWAP_EVENT: &str = "Swap(address,uint256,uint256,uint256,uint256,address)";
+pub const V3_SWAP_EVENT: &str = "Swap(address,address,int256,int256,uint160,uint128,int24)";
+pub const V3_MINT_EVENT: &str = "Mint(address,address,int24,int24,uint128,uint256,uint256)";
+pub const V3_BURN_EVENT: &str = "Burn(address,int24,int24,uint128,uint256,uint256)";
+
+/// Consecutive failed subscribe attempts before a stream gives up.
+pub const MAX_RESUBSCRIBE_ATTEMPTS: u32 = 5;
+pub const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+
+type PendingTxStream<'a> = Pin<Box<dyn Stream<Item = Transaction> + Send + 'a>>;
+
+pub async fn stream_new_blocks(
+    provider: Arc<Provider<Ws>>,
+    event_sender: Sender<Event>,
+) -> Result<()> {
+    let stream = provider.subscribe_blocks().await?;
+    let mut stream = stream.filter_map(|block| NewBlock::from_block(&block));
 
     while let Some(block) = stream.next().await {
         match event_sender.send(Event::Block(block)) {
@@ -44,20 +100,79 @@ pub async fn stream_new_blocks(provider: Arc<Provider<Ws>>, event_sender: Sender
             Err(_) => {}
         }
     }
+
+    Err(anyhow!("Block subscription closed"))
 }
 
-pub async fn stream_pending_transactions(provider: Arc<Provider<Ws>>, event_sender: Sender<Event>) {
-    let stream = provider.subscribe_pending_txs().await.unwrap();
-    let mut stream = stream.transactions_unordered(256).fuse();
+pub async fn stream_pending_transactions(
+    provider: Arc<Provider<Ws>>,
+    event_sender: Sender<Event>,
+) -> Result<()> {
+    let provider = provider.as_ref();
+    let subscribe = || async move {
+        let stream = provider.subscribe_pending_txs().await?;
+        let stream = stream
+            .transactions_unordered(256)
+            .filter_map(|result| result.ok());
+        Ok(Box::pin(stream) as PendingTxStream)
+    };
 
-    while let Some(result) = stream.next().await {
-        match result {
-            Ok(tx) => match event_sender.send(Event::PendingTx(tx)) {
-                Ok(_) => {}
-                Err(_) => {}
-            },
-            Err(_) => {}
-        };
+    forward_with_resubscribe(
+        subscribe,
+        &event_sender,
+        MAX_RESUBSCRIBE_ATTEMPTS,
+        RESUBSCRIBE_DELAY,
+    )
+    .await
+}
+
+/// Publish transactions from `subscribe()` to `event_sender`, re-subscribing whenever
+/// the stream ends. Every successful re-subscription is announced with
+/// `Event::StreamReconnected` on the same channel, so existing receivers stay valid.
+pub async fn forward_with_resubscribe<'a, F, Fut>(
+    mut subscribe: F,
+    event_sender: &Sender<Event>,
+    max_attempts: u32,
+    delay: Duration,
+) -> Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<PendingTxStream<'a>>>,
+{
+    let mut connected_once = false;
+    let mut failed_attempts = 0;
+
+    loop {
+        match subscribe().await {
+            Ok(mut stream) => {
+                failed_attempts = 0;
+                if connected_once {
+                    let _ = event_sender.send(Event::StreamReconnected);
+                }
+                connected_once = true;
+
+                while let Some(tx) = stream.next().await {
+                    match event_sender.send(Event::PendingTx(tx)) {
+                        Ok(_) => {}
+                        Err(_) => {}
+                    }
+                }
+                warn!("Pending transaction subscription dropped, re-subscribing");
+            }
+            Err(e) => {
+                failed_attempts += 1;
+                if failed_attempts >= max_attempts {
+                    return Err(anyhow!(
+                        "Pending transaction subscription failed {} times: {}",
+                        failed_attempts,
+                        e
+                    ));
+                }
+                warn!("Failed to subscribe to pending transactions: {:?}", e);
+            }
+        }
+
+        tokio::time::sleep(delay).await;
     }
 }
 
@@ -72,3 +187,219 @@ pub async fn stream_uniswap_v2_events(provider: Arc<Provider<Ws>>, event_sender:
         };
     }
 }
+
+/// Subscribe to Swap/Mint/Burn logs of the given V3 pools. Swaps carry the post-trade
+/// price and active liquidity and are published as `Event::V3Swap`; Mint/Burn are
+/// forwarded as raw `Event::Log` since they do not include the resulting pool state.
+pub async fn stream_uniswap_v3_events(
+    provider: Arc<Provider<Ws>>,
+    pools: Vec<H160>,
+    event_sender: Sender<Event>,
+) -> Result<()> {
+    let topics: Vec<H256> = [V3_SWAP_EVENT, V3_MINT_EVENT, V3_BURN_EVENT]
+        .iter()
+        .map(|event| H256::from(keccak256(event)))
+        .collect();
+    let filter = Filter::new().address(pools).topic0(topics);
+    let mut stream = provider.subscribe_logs(&filter).await?;
+
+    while let Some(log) = stream.next().await {
+        let event = decode_v3_swap_log(&log).unwrap_or(Event::Log(log));
+        match event_sender.send(event) {
+            Ok(_) => {}
+            Err(_) => {}
+        };
+    }
+
+    Err(anyhow!("Uniswap V3 log subscription closed"))
+}
+
+pub fn decode_v3_swap_log(log: &Log) -> Option<Event> {
+    if log.topics.first() != Some(&H256::from(keccak256(V3_SWAP_EVENT))) {
+        return None;
+    }
+
+    let data = decode(
+        &[
+            ParamType::Int(256),
+            ParamType::Int(256),
+            ParamType::Uint(160),
+            ParamType::Uint(128),
+            ParamType::Int(24),
+        ],
+        &log.data,
+    )
+    .ok()?;
+
+    let sqrt_price_x96 = match data[2] {
+        Token::Uint(value) => value,
+        _ => return None,
+    };
+    let liquidity = match data[3] {
+        Token::Uint(value) => value,
+        _ => return None,
+    };
+    let tick = match data[4] {
+        Token::Int(value) => I256::from_raw(value).as_i32(),
+        _ => return None,
+    };
+
+    Some(Event::V3Swap {
+        pool: log.address,
+        sqrt_price_x96,
+        liquidity,
+        tick,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::encode;
+    use tokio::sync::broadcast;
+
+    fn tx(hash: H256) -> Transaction {
+        Transaction {
+            hash,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pending_tx_stream_resumes_after_drop() {
+        let (event_sender, mut receiver) = broadcast::channel(16);
+        let (a, b, c) = (H256::random(), H256::random(), H256::random());
+
+        // First subscription drops after two txs, the second after one, then the node is gone
+        let mut batches = vec![vec![tx(a), tx(b)], vec![tx(c)]].into_iter();
+        let subscribe = || {
+            let batch = batches.next();
+            async move {
+                match batch {
+                    Some(txs) => Ok(Box::pin(tokio_stream::iter(txs)) as PendingTxStream),
+                    None => Err(anyhow!("connection refused")),
+                }
+            }
+        };
+
+        let result =
+            forward_with_resubscribe(subscribe, &event_sender, 1, Duration::from_millis(1)).await;
+        assert!(result.is_err());
+
+        let mut received = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            received.push(event);
+        }
+
+        assert_eq!(received.len(), 4);
+        assert!(matches!(&received[0], Event::PendingTx(t) if t.hash == a));
+        assert!(matches!(&received[1], Event::PendingTx(t) if t.hash == b));
+        assert!(matches!(&received[2], Event::StreamReconnected));
+        assert!(matches!(&received[3], Event::PendingTx(t) if t.hash == c));
+    }
+
+    #[test]
+    fn test_new_block_from_streamed_block() {
+        let hash = H256::random();
+        let block = Block {
+            number: Some(U64::from(19_000_000)),
+            hash: Some(hash),
+            timestamp: U256::from(1_705_000_000),
+            base_fee_per_gas: Some(U256::from(20_000_000_000u64)),
+            gas_used: U256::from(15_000_000),
+            gas_limit: U256::from(30_000_000),
+            ..Default::default()
+        };
+
+        let new_block = NewBlock::from_block(&block).unwrap();
+        assert_eq!(new_block.block_number, U64::from(19_000_000));
+        assert_eq!(new_block.hash, hash);
+        assert_eq!(new_block.timestamp, U256::from(1_705_000_000));
+        // Exactly at the gas target, so the base fee carries over
+        assert_eq!(new_block.next_base_fee, U256::from(20_000_000_000u64));
+
+        let pending = Block::<H256> { number: None, hash: None, ..block };
+        assert!(NewBlock::from_block(&pending).is_none());
+    }
+
+    fn parent(base_fee: u64, gas_used: u64) -> Block<H256> {
+        Block {
+            base_fee_per_gas: Some(U256::from(base_fee)),
+            gas_used: U256::from(gas_used),
+            gas_limit: U256::from(30_000_000),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_next_base_fee_at_gas_target() {
+        assert_eq!(compute_next_base_fee(&parent(10_000_000_000, 15_000_000)), U256::from(10_000_000_000u64));
+    }
+
+    #[test]
+    fn test_next_base_fee_above_gas_target() {
+        // A full block raises the base fee by the maximum 12.5%
+        assert_eq!(compute_next_base_fee(&parent(10_000_000_000, 30_000_000)), U256::from(11_250_000_000u64));
+        // 20M of a 15M target: 10 gwei * 5/15 / 8
+        assert_eq!(compute_next_base_fee(&parent(10_000_000_000, 20_000_000)), U256::from(10_416_666_666u64));
+        // The increase is at least 1 wei
+        assert_eq!(compute_next_base_fee(&parent(7, 15_000_001)), U256::from(8));
+    }
+
+    #[test]
+    fn test_next_base_fee_below_gas_target() {
+        // An empty block lowers the base fee by the maximum 12.5%
+        assert_eq!(compute_next_base_fee(&parent(10_000_000_000, 0)), U256::from(8_750_000_000u64));
+        // 10M of a 15M target: 10 gwei * 5/15 / 8
+        assert_eq!(compute_next_base_fee(&parent(10_000_000_000, 10_000_000)), U256::from(9_583_333_334u64));
+    }
+
+    #[test]
+    fn test_decode_v3_swap_log() {
+        let pool = H160::random();
+        let sqrt_price_x96 = U256::from_dec_str("1771595571142957166518320255467520").unwrap();
+        let liquidity = U256::from_dec_str("25000000000000000000").unwrap();
+
+        let log = Log {
+            address: pool,
+            topics: vec![
+                H256::from(keccak256(V3_SWAP_EVENT)),
+                H256::from(H160::random()),
+                H256::from(H160::random()),
+            ],
+            data: encode(&[
+                Token::Int(I256::from(-1_000_000_000i64).into_raw()),
+                Token::Int(I256::from(500_000_000_000_000_000i64).into_raw()),
+                Token::Uint(sqrt_price_x96),
+                Token::Uint(liquidity),
+                Token::Int(I256::from(-201_240).into_raw()),
+            ])
+            .into(),
+            ..Default::default()
+        };
+
+        match decode_v3_swap_log(&log) {
+            Some(Event::V3Swap {
+                pool: decoded_pool,
+                sqrt_price_x96: decoded_price,
+                liquidity: decoded_liquidity,
+                tick,
+            }) => {
+                assert_eq!(decoded_pool, pool);
+                assert_eq!(decoded_price, sqrt_price_x96);
+                assert_eq!(decoded_liquidity, liquidity);
+                assert_eq!(tick, -201_240);
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_v3_swap_log_ignores_other_events() {
+        let log = Log {
+            topics: vec![H256::from(keccak256(V3_MINT_EVENT))],
+            ..Default::default()
+        };
+        assert!(decode_v3_swap_log(&log).is_none());
+    }
+}
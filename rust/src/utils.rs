@@ -3,75 +3,184 @@ use ethers::{
     self,
     abi::{decode, ParamType, Token},
     providers::{Middleware, Provider, Ws},
-    types::{Filter, H160, U256, U64},
+    types::{Filter, Log, H160, H256, U256, U64},
+    utils::keccak256,
 };
 use fern::colors::{Color, ColoredLevelConfig};
-use log::LevelFilter;
-use rand::Rng;
-use std::{collections::HashMap, sync::Arc};
+use log::{
+    kv::{Key, Value, VisitSource},
+    LevelFilter, Record,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, str::FromStr, sync::Arc};
 
 use crate::multi::Reserve;
 
+/// Output format of `setup_logger_with`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Plain,
+    /// One JSON object per line, for Loki/ELK style shippers.
+    Json,
+}
+
 pub fn setup_logger() -> Result<()> {
-    let colors = ColoredLevelConfig {
-        trace: Color::Cyan,
-        debug: Color::Magenta,
-        info: Color::Green,
-        warn: Color::Red,
-        error: Color::BrightRed,
-        ..ColoredLevelConfig::new()
-    };
+    setup_logger_with(LogFormat::Plain, "info")
+}
 
-    fern::Dispatch::new()
+/// Log this crate at `level` (e.g. "debug") and dependencies at errors only.
+pub fn setup_logger_with(format: LogFormat, level: &str) -> Result<()> {
+    logger_dispatch(format, level)?
+        .chain(std::io::stdout())
+        .apply()?;
+
+    Ok(())
+}
+
+fn logger_dispatch(format: LogFormat, level: &str) -> Result<fern::Dispatch> {
+    let level = LevelFilter::from_str(level)
+        .map_err(|_| anyhow::anyhow!("Invalid log level: {}", level))?;
+
+    Ok(fern::Dispatch::new()
         .format(move |out, message, record| {
-            out.finish(format_args!(
+            let line = format_log_line(format, record, message, chrono::Local::now());
+            out.finish(format_args!("{}", line))
+        })
+        .level(LevelFilter::Error)
+        .level_for("rust", level))
+}
+
+/// Collects a record's key-values, e.g. a trade's `path` and `profit`.
+struct LogFields(Vec<(String, String)>);
+
+impl<'kvs> VisitSource<'kvs> for LogFields {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), log::kv::Error> {
+        self.0.push((key.to_string(), value.to_string()));
+        Ok(())
+    }
+}
+
+fn format_log_line(
+    format: LogFormat,
+    record: &Record,
+    message: &std::fmt::Arguments,
+    now: chrono::DateTime<chrono::Local>,
+) -> String {
+    let mut fields = LogFields(Vec::new());
+    let _ = record.key_values().visit(&mut fields);
+
+    match format {
+        LogFormat::Plain => {
+            let colors = ColoredLevelConfig {
+                trace: Color::Cyan,
+                debug: Color::Magenta,
+                info: Color::Green,
+                warn: Color::Red,
+                error: Color::BrightRed,
+                ..ColoredLevelConfig::new()
+            };
+            let mut line = format!(
                 "{}[{}] {}",
-                chrono::Local::now().format("[%H:%M:%S]"),
+                now.format("[%H:%M:%S]"),
                 colors.color(record.level()),
                 message
-            ))
-        })
-        .chain(std::io::stdout())
-        .level(log::LevelFilter::Error)
-        .level_for("rust", LevelFilter::Info)
-        .apply()?;
+            );
+            for (key, value) in &fields.0 {
+                line.push_str(&format!(" {}={}", key, value));
+            }
+            line
+        }
+        LogFormat::Json => {
+            let mut object = serde_json::Map::new();
+            object.insert("timestamp".into(), now.to_rfc3339().into());
+            object.insert("level".into(), record.level().as_str().into());
+            object.insert(
+                "module".into(),
+                record.module_path().unwrap_or(record.target()).into(),
+            );
+            object.insert("message".into(), message.to_string().into());
+            for (key, value) in fields.0 {
+                object.insert(key, value.into());
+            }
+            serde_json::Value::Object(object).to_string()
+        }
+    }
+}
 
-    Ok(())
+/// Decimals used for normalized USD values.
+pub const USD_DECIMALS: u8 = 18;
+
+fn price_to_wad(price_usd: f64) -> U256 {
+    if !price_usd.is_finite() || price_usd <= 0.0 {
+        return U256::zero();
+    }
+    U256::from((price_usd * 1e18) as u128)
+}
+
+fn saturate(value: ethers::types::U512) -> U256 {
+    U256::try_from(value).unwrap_or(U256::MAX)
+}
+
+/// `value` as the nearest `f64`. Unlike `as_u128`, never panics: every U256
+/// fits in an `f64`'s range, losing only precision.
+pub fn u256_to_f64(value: U256) -> f64 {
+    value.0.iter().rev().fold(0.0, |acc, limb| acc * 2f64.powi(64) + *limb as f64)
+}
+
+/// USD value of `amount` token units, as an 18-decimal fixed-point number.
+pub fn to_usd(amount: U256, token_decimals: u8, token_price_usd: f64) -> U256 {
+    let value = amount.full_mul(price_to_wad(token_price_usd)) / U256::exp10(token_decimals as usize);
+    saturate(value)
+}
+
+/// Token units worth `usd` (18-decimal fixed-point). Zero if the price is unusable.
+pub fn from_usd(usd: U256, token_decimals: u8, token_price_usd: f64) -> U256 {
+    let price = price_to_wad(token_price_usd);
+    if price.is_zero() {
+        return U256::zero();
+    }
+    saturate(usd.full_mul(U256::exp10(token_decimals as usize)) / price)
 }
 
-pub fn calculate_next_block_base_fee(
-    gas_used: U256,
-    gas_limit: U256,
-    base_fee_per_gas: U256,
+/// Convert a native-currency amount in wei into units of another token.
+pub fn native_to_token(
+    amount_wei: U256,
+    native_price_usd: f64,
+    token_price_usd: f64,
+    token_decimals: u8,
 ) -> U256 {
-    let gas_used = gas_used;
+    let usd = to_usd(amount_wei, 18, native_price_usd);
+    from_usd(usd, token_decimals, token_price_usd)
+}
 
-    let mut target_gas_used = gas_limit / 2;
-    target_gas_used = if target_gas_used == U256::zero() {
-        U256::one()
-    } else {
-        target_gas_used
-    };
+/// Cost of `gas_units` at `base_fee`, expressed in units of a token.
+pub fn gas_cost_in_token(
+    base_fee: U256,
+    gas_units: U256,
+    native_price_usd: f64,
+    token_price_usd: f64,
+    token_decimals: u8,
+) -> U256 {
+    let gas_cost_wei = saturate(base_fee.full_mul(gas_units));
+    native_to_token(gas_cost_wei, native_price_usd, token_price_usd, token_decimals)
+}
 
-    let new_base_fee = {
-        if gas_used > target_gas_used {
-            base_fee_per_gas
-                + ((base_fee_per_gas * (gas_used - target_gas_used)) / target_gas_used)
-                    / U256::from(8u64)
-        } else {
-            base_fee_per_gas
-                - ((base_fee_per_gas * (target_gas_used - gas_used)) / target_gas_used)
-                    / U256::from(8u64)
-        }
-    };
+const BPS_DENOMINATOR: u64 = 10_000;
 
-    let seed = rand::thread_rng().gen_range(0..9);
-    new_base_fee + seed
+/// `amount` less `slippage_bps` basis points, in integer math.
+pub fn min_amount_after_slippage(amount: U256, slippage_bps: u16) -> U256 {
+    let bps = U256::from(slippage_bps.min(BPS_DENOMINATOR as u16));
+    amount - amount.saturating_mul(bps) / U256::from(BPS_DENOMINATOR)
 }
 
-pub async fn get_touched_pool_reserves(
+/// Reserves of every `known` pool that emitted a `Sync` in `block_number`, taken
+/// straight from the event data so no `getReserves` round-trip is needed.
+pub async fn get_touched_pool_reserves<V>(
     provider: Arc<Provider<Ws>>,
     block_number: U64,
+    known: &HashMap<H160, V>,
 ) -> Result<HashMap<H160, Reserve>> {
     let sync_event = "Sync(uint112,uint112)";
     let event_filter = Filter::new()
@@ -81,35 +190,269 @@ pub async fn get_touched_pool_reserves(
 
     let logs = provider.get_logs(&event_filter).await?;
 
-    let mut tx_idx = HashMap::new();
+    Ok(reserves_from_sync_logs(&logs, known))
+}
+
+/// Fold `Sync` logs of `known` pools into the latest reserve per pool. A pool's
+/// final `Sync` within the block wins, ordered by transaction index and then log index.
+pub fn reserves_from_sync_logs<V>(logs: &[Log], known: &HashMap<H160, V>) -> HashMap<H160, Reserve> {
+    let sync_topic = H256::from(keccak256("Sync(uint112,uint112)"));
+
+    let mut log_idx = HashMap::new();
     let mut reserves = HashMap::new();
 
-    for log in &logs {
-        let decoded = decode(&[ParamType::Uint(256), ParamType::Uint(256)], &log.data);
-        match decoded {
-            Ok(data) => {
-                let idx = log.transaction_index.unwrap_or_default();
-                let prev_tx_idx = tx_idx.get(&log.address);
-                let update = (*prev_tx_idx.unwrap_or(&U64::zero())) <= idx;
-
-                if update {
-                    let reserve0 = match data[0] {
-                        Token::Uint(rs) => rs,
-                        _ => U256::zero(),
-                    };
-                    let reserve1 = match data[1] {
-                        Token::Uint(rs) => rs,
-                        _ => U256::zero(),
-                    };
-                    let reserve = Reserve { reserve0, reserve1 };
-
-                    reserves.insert(log.address, reserve);
-                    tx_idx.insert(log.address, idx);
-                }
-            }
-            Err(_) => {}
+    for log in logs {
+        if log.removed.unwrap_or(false)
+            || log.topics.first() != Some(&sync_topic)
+            || !known.contains_key(&log.address)
+        {
+            continue;
+        }
+
+        let reserve = match decode_sync_log(log) {
+            Some(reserve) => reserve,
+            None => continue,
+        };
+
+        let idx = (
+            log.transaction_index.unwrap_or_default(),
+            log.log_index.unwrap_or_default(),
+        );
+        let update = match log_idx.get(&log.address) {
+            Some(prev_idx) => *prev_idx <= idx,
+            None => true,
+        };
+
+        if update {
+            reserves.insert(log.address, reserve);
+            log_idx.insert(log.address, idx);
+        }
+    }
+
+    reserves
+}
+
+pub fn decode_sync_log(log: &Log) -> Option<Reserve> {
+    let data = decode(&[ParamType::Uint(112), ParamType::Uint(112)], &log.data).ok()?;
+    let reserve0 = match data[0] {
+        Token::Uint(rs) => rs,
+        _ => return None,
+    };
+    let reserve1 = match data[1] {
+        Token::Uint(rs) => rs,
+        _ => return None,
+    };
+    Some(Reserve { reserve0, reserve1 })
+}
+
+const INV_PHI: f64 = 0.618_033_988_749_895;
+
+/// Maximize a unimodal `f` over the integers in `[lo, hi]`. The bracket shrinks by the
+/// golden ratio per evaluation until it is `tolerance` wide, then is scanned exhaustively.
+pub(crate) fn golden_section_max(
+    mut lo: u64,
+    mut hi: u64,
+    tolerance: u64,
+    mut f: impl FnMut(u64) -> i128,
+) -> (u64, i128) {
+    // Below 4 points the two probes collide, so always finish with a scan
+    let tolerance = tolerance.max(3);
+    let probe = |lo: u64, hi: u64| ((hi - lo) as f64 * INV_PHI) as u64;
+
+    let mut x1 = hi - probe(lo, hi);
+    let mut x2 = lo + probe(lo, hi);
+    let mut f1 = f(x1);
+    let mut f2 = f(x2);
+
+    while hi - lo > tolerance {
+        if f1 < f2 {
+            lo = x1;
+            x1 = x2;
+            f1 = f2;
+            x2 = lo + probe(lo, hi);
+            f2 = f(x2);
+        } else {
+            hi = x2;
+            x2 = x1;
+            f2 = f1;
+            x1 = hi - probe(lo, hi);
+            f1 = f(x1);
+        }
+
+        // Integer rounding can leave the reused probe on the wrong side
+        if x1 > x2 {
+            std::mem::swap(&mut x1, &mut x2);
+            std::mem::swap(&mut f1, &mut f2);
         }
     }
 
-    Ok(reserves)
+    (lo..=hi)
+        .map(|x| (x, f(x)))
+        .max_by_key(|(_, value)| *value)
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::encode;
+
+    fn sync_log(pool: H160, reserve0: u128, reserve1: u128, tx_index: u64, log_index: u64) -> Log {
+        Log {
+            address: pool,
+            topics: vec![H256::from(keccak256("Sync(uint112,uint112)"))],
+            data: encode(&[
+                Token::Uint(U256::from(reserve0)),
+                Token::Uint(U256::from(reserve1)),
+            ])
+            .into(),
+            transaction_index: Some(U64::from(tx_index)),
+            log_index: Some(U256::from(log_index)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_decode_sync_log() {
+        let pool = H160::random();
+        let log = sync_log(pool, 1_000_000, 2_500_000_000_000_000_000, 0, 0);
+
+        let reserve = decode_sync_log(&log).unwrap();
+        assert_eq!(reserve.reserve0, U256::from(1_000_000u64));
+        assert_eq!(reserve.reserve1, U256::from(2_500_000_000_000_000_000u128));
+    }
+
+    #[test]
+    fn test_reserves_from_sync_logs_keeps_last_update() {
+        let pool_a = H160::random();
+        let pool_b = H160::random();
+        let mut other = sync_log(H160::random(), 1, 1, 0, 0);
+        other.topics = vec![H256::random()];
+
+        let logs = vec![
+            sync_log(pool_a, 100, 200, 3, 10),
+            sync_log(pool_a, 300, 400, 3, 12),
+            sync_log(pool_b, 5, 6, 1, 2),
+            sync_log(pool_a, 50, 60, 1, 1),
+            sync_log(H160::random(), 7, 8, 2, 0),
+            other,
+        ];
+        let known: HashMap<H160, ()> = [(pool_a, ()), (pool_b, ())].into();
+
+        let reserves = reserves_from_sync_logs(&logs, &known);
+        assert_eq!(reserves.len(), 2);
+        assert_eq!(reserves[&pool_a].reserve0, U256::from(300));
+        assert_eq!(reserves[&pool_a].reserve1, U256::from(400));
+        assert_eq!(reserves[&pool_b].reserve0, U256::from(5));
+    }
+
+    #[test]
+    fn test_u256_to_f64() {
+        assert_eq!(u256_to_f64(U256::zero()), 0.0);
+        assert_eq!(u256_to_f64(U256::from(1_500_000u64)), 1_500_000.0);
+        assert_eq!(u256_to_f64(U256::one() << 100), 2f64.powi(100));
+        assert_eq!(u256_to_f64(U256::MAX), 2f64.powi(256));
+    }
+
+    #[test]
+    fn test_to_usd_usdc() {
+        // 1.5 USDC at $1 -> $1.5
+        let usd = to_usd(U256::from(1_500_000u64), 6, 1.0);
+        assert_eq!(usd, U256::from(1_500_000_000_000_000_000u128));
+        assert_eq!(from_usd(usd, 6, 1.0), U256::from(1_500_000u64));
+    }
+
+    #[test]
+    fn test_to_usd_weth() {
+        // 2 WETH at $2000 -> $4000
+        let two_weth = U256::from(2_000_000_000_000_000_000u128);
+        let usd = to_usd(two_weth, 18, 2000.0);
+        assert_eq!(usd, U256::from(4000u64) * U256::exp10(18));
+        assert_eq!(from_usd(usd, 18, 2000.0), two_weth);
+    }
+
+    #[test]
+    fn test_gas_cost_in_token() {
+        // 30 gwei * 200k gas = 0.006 ETH = $12 at $2000
+        let base_fee = U256::from(30_000_000_000u64);
+        let gas = U256::from(200_000u64);
+
+        assert_eq!(gas_cost_in_token(base_fee, gas, 2000.0, 1.0, 6), U256::from(12_000_000u64));
+        assert_eq!(
+            gas_cost_in_token(base_fee, gas, 2000.0, 2000.0, 18),
+            U256::from(6_000_000_000_000_000u64)
+        );
+        assert_eq!(gas_cost_in_token(base_fee, gas, 2000.0, 0.0, 6), U256::zero());
+    }
+
+    #[test]
+    fn test_json_log_lines_are_valid_json() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let (_, logger) = logger_dispatch(LogFormat::Json, "info")
+            .unwrap()
+            .chain(sender)
+            .into_log();
+
+        let context: &[(&str, &str)] = &[("path", "USDC->WETH->DAI"), ("profit", "1500000")];
+        logger.log(
+            &Record::builder()
+                .args(format_args!("Bundle sent"))
+                .level(log::Level::Info)
+                .target("rust::strategy")
+                .module_path_static(Some("rust::strategy"))
+                .key_values(&context)
+                .build(),
+        );
+        logger.log(
+            &Record::builder()
+                .args(format_args!("Reference pool missing"))
+                .level(log::Level::Warn)
+                .target("rust::strategy")
+                .build(),
+        );
+        // Below the configured level
+        logger.log(
+            &Record::builder()
+                .args(format_args!("noisy"))
+                .level(log::Level::Debug)
+                .target("rust::strategy")
+                .build(),
+        );
+
+        let lines: Vec<String> = receiver.try_iter().collect();
+        assert_eq!(lines.len(), 2);
+        let parsed: Vec<serde_json::Value> = lines
+            .iter()
+            .map(|line| serde_json::from_str(line.trim_end()).unwrap())
+            .collect();
+
+        assert_eq!(parsed[0]["level"], "INFO");
+        assert_eq!(parsed[0]["module"], "rust::strategy");
+        assert_eq!(parsed[0]["message"], "Bundle sent");
+        assert_eq!(parsed[0]["path"], "USDC->WETH->DAI");
+        assert_eq!(parsed[0]["profit"], "1500000");
+        assert!(parsed[0]["timestamp"].is_string());
+
+        assert_eq!(parsed[1]["level"], "WARN");
+        assert!(parsed[1].get("path").is_none());
+    }
+
+    #[test]
+    fn test_invalid_log_level_is_rejected() {
+        assert!(logger_dispatch(LogFormat::Plain, "loud").is_err());
+    }
+
+    #[test]
+    fn test_golden_section_finds_peak_in_few_evaluations() {
+        let peak = 12_345i128;
+        let mut evaluations = 0;
+        let (x, value) = golden_section_max(0, 100_000, 10, |x| {
+            evaluations += 1;
+            -(x as i128 - peak).pow(2)
+        });
+
+        assert_eq!(x as i128, peak);
+        assert_eq!(value, 0);
+        assert!(evaluations < 50, "{} evaluations", evaluations);
+    }
 }
@@ -0,0 +1,199 @@
+use anyhow::{anyhow, Result};
+use ethers::{
+    signers::{LocalWallet, Signer},
+    types::U64,
+};
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc,
+};
+use tokio::sync::Notify;
+
+use crate::nonce::NonceManager;
+
+struct PooledWallet {
+    wallet: LocalWallet,
+    nonces: Arc<NonceManager>,
+    busy: AtomicBool,
+}
+
+/// Several funded wallets that submissions rotate across, so concurrent
+/// opportunities don't queue behind one nonce sequence. Each wallet has its
+/// own `NonceManager` and is lent to one execution at a time.
+pub struct WalletPool {
+    wallets: Vec<PooledWallet>,
+    next: AtomicUsize,
+    released: Notify,
+}
+
+/// A wallet checked out of the pool, returned when dropped.
+pub struct WalletLease {
+    pool: Arc<WalletPool>,
+    index: usize,
+}
+
+impl WalletLease {
+    pub fn wallet(&self) -> &LocalWallet {
+        &self.pool.wallets[self.index].wallet
+    }
+
+    pub fn nonces(&self) -> &Arc<NonceManager> {
+        &self.pool.wallets[self.index].nonces
+    }
+}
+
+impl Drop for WalletLease {
+    fn drop(&mut self) {
+        self.pool.wallets[self.index].busy.store(false, Ordering::Release);
+        self.pool.released.notify_waiters();
+    }
+}
+
+impl WalletPool {
+    pub fn new(wallets: Vec<LocalWallet>) -> Result<Self> {
+        if wallets.is_empty() {
+            return Err(anyhow!("Wallet pool needs at least one wallet"));
+        }
+        Ok(Self {
+            wallets: wallets
+                .into_iter()
+                .map(|wallet| PooledWallet {
+                    nonces: Arc::new(NonceManager::new(wallet.address())),
+                    wallet,
+                    busy: AtomicBool::new(false),
+                })
+                .collect(),
+            next: AtomicUsize::new(0),
+            released: Notify::new(),
+        })
+    }
+
+    /// `primary` followed by the wallets for hex private `keys`, with or
+    /// without a `0x` prefix, all signing for `chain_id`.
+    pub fn from_keys<S: AsRef<str>>(primary: LocalWallet, keys: &[S], chain_id: u64) -> Result<Self> {
+        let additional = keys.iter().map(|key| {
+            key.as_ref()
+                .parse::<LocalWallet>()
+                .map_err(|e| anyhow!("Invalid wallet key: {}", e))
+        });
+        let wallets = std::iter::once(Ok(primary))
+            .chain(additional)
+            .map(|wallet| wallet.map(|wallet| wallet.with_chain_id(chain_id)))
+            .collect::<Result<Vec<_>>>()?;
+        Self::new(wallets)
+    }
+
+    pub fn len(&self) -> usize {
+        self.wallets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.wallets.is_empty()
+    }
+
+    pub fn wallets(&self) -> impl Iterator<Item = &LocalWallet> {
+        self.wallets.iter().map(|pooled| &pooled.wallet)
+    }
+
+    /// Check out the next idle wallet in round-robin order, if any is idle.
+    pub fn try_acquire(self: &Arc<Self>) -> Option<WalletLease> {
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        (0..self.wallets.len())
+            .map(|offset| (start + offset) % self.wallets.len())
+            .find(|index| {
+                self.wallets[*index]
+                    .busy
+                    .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+                    .is_ok()
+            })
+            .map(|index| WalletLease {
+                pool: self.clone(),
+                index,
+            })
+    }
+
+    /// Check out an idle wallet, waiting for one to be returned if all are busy.
+    pub async fn acquire(self: &Arc<Self>) -> WalletLease {
+        loop {
+            let released = self.released.notified();
+            if let Some(lease) = self.try_acquire() {
+                return lease;
+            }
+            released.await;
+        }
+    }
+
+    /// Forward a new block to every wallet's nonce manager.
+    pub fn on_new_block(&self, block_number: U64) {
+        for pooled in &self.wallets {
+            pooled.nonces.on_new_block(block_number);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::Address;
+    use std::{collections::HashMap, time::Duration};
+    use tokio::task::JoinSet;
+
+    fn pool(size: usize) -> Arc<WalletPool> {
+        let wallets = (0..size)
+            .map(|_| LocalWallet::new(&mut rand::thread_rng()))
+            .collect();
+        Arc::new(WalletPool::new(wallets).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_opportunities_spread_across_wallets() {
+        let pool = pool(3);
+
+        let mut executions = JoinSet::new();
+        for _ in 0..9 {
+            let pool = pool.clone();
+            executions.spawn(async move {
+                let lease = pool.acquire().await;
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                lease.wallet().address()
+            });
+        }
+
+        let mut uses: HashMap<Address, usize> = HashMap::new();
+        while let Some(sender) = executions.join_next().await {
+            *uses.entry(sender.unwrap()).or_default() += 1;
+        }
+        assert_eq!(uses.len(), 3);
+        assert_eq!(uses.values().sum::<usize>(), 9);
+    }
+
+    #[tokio::test]
+    async fn test_busy_wallets_are_skipped() {
+        let pool = pool(2);
+
+        let first = pool.try_acquire().unwrap();
+        let second = pool.try_acquire().unwrap();
+        assert_ne!(first.wallet().address(), second.wallet().address());
+        assert!(pool.try_acquire().is_none());
+
+        let freed = first.wallet().address();
+        drop(first);
+        assert_eq!(pool.try_acquire().unwrap().wallet().address(), freed);
+    }
+
+    #[test]
+    fn test_from_keys_gives_each_wallet_its_own_nonces() {
+        let primary = LocalWallet::new(&mut rand::thread_rng());
+        let keys = [format!("0x{}", "11".repeat(32)), "22".repeat(32)];
+        let pool = Arc::new(WalletPool::from_keys(primary.clone(), &keys, 137).unwrap());
+        assert_eq!(pool.len(), 3);
+        assert_eq!(pool.wallets().next().unwrap().address(), primary.address());
+        assert!(pool.wallets().all(|wallet| wallet.chain_id() == 137));
+
+        let leases: Vec<_> = (0..3).map(|_| pool.try_acquire().unwrap()).collect();
+        for lease in &leases {
+            assert_eq!(lease.nonces().address(), lease.wallet().address());
+        }
+        assert!(WalletPool::from_keys(primary, &["not a key"], 1).is_err());
+    }
+}
@@ -0,0 +1,53 @@
+use anyhow::Result;
+use ethers::utils::id;
+use rust::abi::ABI;
+
+#[test]
+fn test_abi_loads_outside_crate_dir() -> Result<()> {
+    // Runs in its own test binary, so changing the working directory is safe
+    let original = std::env::current_dir()?;
+    std::env::set_current_dir(std::env::temp_dir())?;
+    let result = ABI::new();
+    std::env::set_current_dir(original)?;
+
+    let abi = result?;
+    assert!(abi.erc20.function("transfer").is_ok());
+    assert!(abi.flashloan_executor.function("executeFlashloan").is_ok());
+    assert!(abi.get_dex_abi("uniswap_v2").is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn test_shared_abi_is_parsed_once() -> Result<()> {
+    let first = ABI::shared()?;
+    let second = ABI::shared()?;
+    assert!(std::ptr::eq(first, second));
+
+    Ok(())
+}
+
+#[test]
+fn test_execute_flashloan_selector() -> Result<()> {
+    let abi = ABI::shared()?;
+
+    let selector = abi.function_selector("flashloan_executor", "executeFlashloan")?;
+    assert_eq!(selector, id("executeFlashloan(address,uint256,address[],bytes)"));
+
+    let executor = abi.get_bot_abi("flashloan_executor")?;
+    assert_eq!(executor.function("executeFlashloan")?.short_signature(), selector);
+
+    Ok(())
+}
+
+#[test]
+fn test_selector_lookup_errors() -> Result<()> {
+    let abi = ABI::shared()?;
+
+    assert_eq!(abi.function_selector("erc20", "transfer")?, id("transfer(address,uint256)"));
+    assert!(abi.function_selector("flashloan_executor", "missingFunction").is_err());
+    assert!(abi.function_selector("unknown", "transfer").is_err());
+    assert!(abi.get_bot_abi("unknown").is_err());
+
+    Ok(())
+}
@@ -58,10 +58,18 @@ async fn test_flashloan_execution() -> Result<()> {
 async fn test_state_monitoring() -> Result<()> {
     common::setup();
     
-    let monitor = StateMonitor::new(Duration::from_secs(1));
+    let (provider, mock) = ethers::providers::Provider::mocked();
+    let monitor = StateMonitor::new(std::sync::Arc::new(provider), Duration::from_secs(1));
     let pool = Address::random();
     
     // Test pool state updates
+    let reserves: ethers::types::Bytes = ethers::abi::encode(&[
+        ethers::abi::Token::Uint(U256::from(1_000)),
+        ethers::abi::Token::Uint(U256::from(2_000)),
+        ethers::abi::Token::Uint(U256::zero()),
+    ])
+    .into();
+    mock.push(reserves)?;
     monitor.update_pool_state(pool).await?;
     
     // Test monitoring multiple pools
@@ -1,21 +1,46 @@
 use anyhow::Result;
-use rust::metrics::StateMonitor;
-use ethers::types::{Address, U256};
-use std::time::Duration;
+use rust::{
+    metrics::StateMonitor,
+    monitoring::{install_metrics_recorder, render_metrics, Metrics},
+};
+use ethers::{
+    abi::{encode, Token},
+    providers::{MockProvider, Provider},
+    types::{Address, Bytes, U256},
+};
+use std::{sync::Arc, time::Duration};
 use test_log::test;
 
+fn mocked_monitor() -> (StateMonitor<Provider<MockProvider>>, MockProvider) {
+    let (provider, mock) = Provider::mocked();
+    (StateMonitor::new(Arc::new(provider), Duration::from_secs(1)), mock)
+}
+
+fn push_reserves(mock: &MockProvider, reserve0: u64, reserve1: u64) {
+    let response: Bytes = encode(&[
+        Token::Uint(U256::from(reserve0)),
+        Token::Uint(U256::from(reserve1)),
+        Token::Uint(U256::zero()),
+    ])
+    .into();
+    mock.push(response).unwrap();
+}
+
 #[test]
 async fn test_pool_state_updates() -> Result<()> {
-    let monitor = StateMonitor::new(Duration::from_secs(1));
+    let (monitor, mock) = mocked_monitor();
     let pool = Address::random();
     
     // Test initial state
+    push_reserves(&mock, 1_000, 2_000);
     monitor.update_pool_state(pool).await?;
     let states = monitor.pool_states.read().await;
     let state = states.get(&pool).unwrap();
     assert_eq!(state.update_count, 1);
+    drop(states);
     
     // Test multiple updates
+    push_reserves(&mock, 1_000, 2_000);
     monitor.update_pool_state(pool).await?;
     let states = monitor.pool_states.read().await;
     let state = states.get(&pool).unwrap();
@@ -26,14 +51,13 @@ async fn test_pool_state_updates() -> Result<()> {
 
 #[test]
 async fn test_price_thresholds() -> Result<()> {
-    let monitor = StateMonitor::new(Duration::from_secs(1));
+    let (monitor, _mock) = mocked_monitor();
     let pool = Address::random();
     
     // Add price thresholds (±10% from base price)
-    monitor.price_thresholds.insert(
-        pool,
-        (U256::from(900), U256::from(1100))
-    );
+    monitor
+        .set_price_threshold(pool, U256::from(900), U256::from(1100))
+        .await;
     
     // Test price within threshold
     let changes = monitor.check_significant_changes(pool).await;
@@ -48,11 +72,12 @@ async fn test_price_thresholds() -> Result<()> {
 
 #[test]
 async fn test_monitoring_multiple_pools() -> Result<()> {
-    let monitor = StateMonitor::new(Duration::from_secs(1));
+    let (monitor, mock) = mocked_monitor();
     let pools = vec![Address::random(), Address::random(), Address::random()];
     
     // Update all pools
     for pool in &pools {
+        push_reserves(&mock, 1_000, 2_000);
         monitor.update_pool_state(*pool).await?;
     }
     
@@ -67,3 +92,21 @@ async fn test_monitoring_multiple_pools() -> Result<()> {
     
     Ok(())
 }
+
+#[test]
+async fn test_metrics_endpoint_exposes_both_families() -> Result<()> {
+    let recorder = install_metrics_recorder()?;
+    let metrics = Metrics::new()?;
+    metrics.record_trade("WETH->USDC");
+
+    let (monitor, mock) = mocked_monitor();
+    push_reserves(&mock, 1_000, 2_000);
+    monitor.update_pools(&[Address::random()]).await;
+
+    let scraped = render_metrics(&recorder)?;
+    assert!(scraped.contains("flashbot_trades_total"));
+    assert!(scraped.contains("pool_updates_total"));
+    assert!(scraped.contains("execution_time_seconds"));
+    
+    Ok(())
+}
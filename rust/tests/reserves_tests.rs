@@ -0,0 +1,55 @@
+use anyhow::Result;
+use ethers::types::{Address, U256};
+use rust::{
+    multi::{batch_get_uniswap_v2_reserves, batch_get_uniswap_v2_reserves_multicall},
+    pools::{DexVariant, Pool},
+};
+use std::str::FromStr;
+use test_log::test;
+
+fn mainnet_pools() -> Vec<Pool> {
+    // USDC/WETH, WETH/USDT, DAI/WETH, WBTC/WETH on Uniswap V2
+    [
+        "0xB4e16d0168e52d35CaCD2c6185b44281Ec28C9Dc",
+        "0x0d4a11d5EEaaC28EC3F61d100daF4d40471f1852",
+        "0xA478c2975Ab1Ea89e8196811F51A7B7Ade33eB11",
+        "0xBb2b8038a1640196FbE3e38816F3e67Cba72D940",
+    ]
+    .iter()
+    .map(|address| Pool {
+        address: Address::from_str(address).unwrap(),
+        version: DexVariant::UniswapV2,
+        token0: Address::zero(),
+        token1: Address::zero(),
+        decimals0: 18,
+        decimals1: 18,
+        fee: 300,
+        reserve0: U256::zero(),
+        reserve1: U256::zero(),
+        sqrt_price_x96: None,
+        liquidity: None,
+        tick: None,
+        factory: None,
+    })
+    .collect()
+}
+
+#[test]
+#[ignore = "requires HTTPS_URL pointing at an Ethereum mainnet node"]
+async fn test_multicall_reserves_match_serial() -> Result<()> {
+    let https_url = std::env::var("HTTPS_URL")?;
+    let pools = mainnet_pools();
+
+    let serial = batch_get_uniswap_v2_reserves(https_url.clone(), pools.clone()).await;
+    let multicall = batch_get_uniswap_v2_reserves_multicall(https_url, pools.clone(), 2).await?;
+
+    assert_eq!(serial.len(), pools.len());
+    assert_eq!(multicall.len(), pools.len());
+    for pool in &pools {
+        let a = &serial[&pool.address];
+        let b = &multicall[&pool.address];
+        assert_eq!((a.reserve0, a.reserve1), (b.reserve0, b.reserve1));
+    }
+
+    Ok(())
+}